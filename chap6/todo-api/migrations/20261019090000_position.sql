@@ -0,0 +1,7 @@
+ALTER TABLE todos ADD COLUMN position BIGINT NOT NULL DEFAULT 0;
+
+UPDATE todos SET position = ranked.rn * 1024
+FROM (SELECT id, row_number() OVER (ORDER BY id DESC) AS rn FROM todos) AS ranked
+WHERE todos.id = ranked.id;
+
+CREATE INDEX todos_position_idx ON todos (position);
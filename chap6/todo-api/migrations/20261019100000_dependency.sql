@@ -0,0 +1,9 @@
+CREATE TABLE todo_dependencies
+(
+    todo_id    INTEGER NOT NULL REFERENCES todos(id) DEFERRABLE INITIALLY DEFERRED,
+    blocker_id INTEGER NOT NULL REFERENCES todos(id) DEFERRABLE INITIALLY DEFERRED,
+    PRIMARY KEY (todo_id, blocker_id),
+    CHECK (todo_id <> blocker_id)
+);
+
+CREATE INDEX todo_dependencies_blocker_id_idx ON todo_dependencies (blocker_id);
@@ -0,0 +1,11 @@
+CREATE TABLE todo_comments
+(
+    id         SERIAL PRIMARY KEY,
+    todo_id    INTEGER     NOT NULL REFERENCES todos(id) DEFERRABLE INITIALLY DEFERRED,
+    author     TEXT        NOT NULL,
+    body       TEXT        NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX todo_comments_todo_id_idx ON todo_comments (todo_id);
@@ -0,0 +1,12 @@
+CREATE TABLE todo_attachments
+(
+    id           SERIAL PRIMARY KEY,
+    todo_id      INTEGER     NOT NULL REFERENCES todos(id) DEFERRABLE INITIALLY DEFERRED,
+    filename     TEXT        NOT NULL,
+    content_type TEXT        NOT NULL,
+    size         BIGINT      NOT NULL,
+    storage_key  TEXT        NOT NULL UNIQUE,
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX todo_attachments_todo_id_idx ON todo_attachments (todo_id);
@@ -0,0 +1,6 @@
+ALTER TABLE todos ADD COLUMN priority TEXT CHECK (priority ~ '^[A-Z]$');
+ALTER TABLE todos ADD COLUMN due DATE;
+ALTER TABLE todos ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT now();
+ALTER TABLE todos ADD COLUMN completed_at TIMESTAMPTZ;
+
+UPDATE todos SET completed_at = now() WHERE completed;
@@ -0,0 +1,7 @@
+CREATE TABLE calendar_tokens
+(
+    id         SERIAL PRIMARY KEY,
+    token_hash TEXT        NOT NULL UNIQUE,
+    prefix     TEXT        NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,28 @@
+CREATE TABLE users
+(
+    id            SERIAL PRIMARY KEY,
+    issuer        TEXT        NOT NULL,
+    subject       TEXT        NOT NULL,
+    email         TEXT,
+    name          TEXT,
+    created_at    TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_login_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (issuer, subject)
+);
+
+CREATE TABLE sessions
+(
+    token_hash TEXT PRIMARY KEY,
+    user_id    INTEGER     NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE oidc_logins
+(
+    state         TEXT PRIMARY KEY,
+    code_verifier TEXT        NOT NULL,
+    nonce         TEXT        NOT NULL,
+    redirect_to   TEXT        NOT NULL,
+    expires_at    TIMESTAMPTZ NOT NULL
+);
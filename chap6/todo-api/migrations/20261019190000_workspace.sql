@@ -0,0 +1,35 @@
+CREATE TABLE workspaces
+(
+    id         SERIAL PRIMARY KEY,
+    name       TEXT        NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE workspace_members
+(
+    workspace_id INTEGER     NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    user_id      INTEGER     NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    role         TEXT        NOT NULL CHECK (role IN ('owner', 'editor', 'viewer')),
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (workspace_id, user_id)
+);
+
+CREATE INDEX workspace_members_user_id_idx ON workspace_members (user_id);
+
+CREATE TABLE workspace_invites
+(
+    id           SERIAL PRIMARY KEY,
+    workspace_id INTEGER     NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    token_hash   TEXT        NOT NULL UNIQUE,
+    role         TEXT        NOT NULL CHECK (role IN ('owner', 'editor', 'viewer')),
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT now(),
+    expires_at   TIMESTAMPTZ NOT NULL,
+    accepted_by  INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    accepted_at  TIMESTAMPTZ
+);
+
+ALTER TABLE todos ADD COLUMN workspace_id INTEGER REFERENCES workspaces(id);
+ALTER TABLE labels ADD COLUMN workspace_id INTEGER REFERENCES workspaces(id);
+
+CREATE INDEX todos_workspace_id_idx ON todos (workspace_id);
+CREATE INDEX labels_workspace_id_idx ON labels (workspace_id);
@@ -0,0 +1,13 @@
+CREATE TABLE shares
+(
+    id            SERIAL PRIMARY KEY,
+    token_hash    TEXT        NOT NULL UNIQUE,
+    prefix        TEXT        NOT NULL,
+    todo_id       INTEGER REFERENCES todos(id) ON DELETE CASCADE,
+    label_id      INTEGER REFERENCES labels(id) ON DELETE CASCADE,
+    workspace_id  INTEGER REFERENCES workspaces(id) ON DELETE CASCADE,
+    password_hash TEXT,
+    created_at    TIMESTAMPTZ NOT NULL DEFAULT now(),
+    expires_at    TIMESTAMPTZ,
+    CHECK ((todo_id IS NULL) <> (label_id IS NULL))
+);
@@ -0,0 +1,3 @@
+ALTER TABLE api_tokens ADD COLUMN user_id INTEGER REFERENCES users(id) ON DELETE CASCADE;
+
+CREATE INDEX api_tokens_user_id_idx ON api_tokens (user_id);
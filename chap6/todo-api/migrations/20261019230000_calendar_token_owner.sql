@@ -0,0 +1,3 @@
+ALTER TABLE calendar_tokens ADD COLUMN user_id INTEGER REFERENCES users(id) ON DELETE CASCADE;
+
+CREATE INDEX calendar_tokens_user_id_idx ON calendar_tokens (user_id);
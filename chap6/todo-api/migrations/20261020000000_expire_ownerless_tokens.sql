@@ -0,0 +1,3 @@
+-- Tokens from before api_tokens.user_id may have been minted without any authentication, and
+-- those without an owner no longer reach every workspace, so they are expired rather than kept.
+UPDATE api_tokens SET expires_at = now() WHERE user_id IS NULL AND (expires_at IS NULL OR expires_at > now());
@@ -0,0 +1 @@
+ALTER TABLE todo_comments ADD COLUMN author_user_id INTEGER REFERENCES users(id) ON DELETE SET NULL;
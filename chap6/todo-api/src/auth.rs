@@ -0,0 +1,407 @@
+//! API tokens and the scopes they grant.
+//!
+//! [`authenticate`] resolves the `Authorization: Bearer` token of every request, or else its
+//! session cookie from an OpenID Connect login (see `crate::oidc`), into a [`Caller`], and [`RequireScope`] is applied to the routes in `create_app` to turn away callers
+//! without the scope the route needs: reads (`GET`/`HEAD`) need `<resource>:read`, everything
+//! else `<resource>:write`. Requests without a token are let through unless `AUTH_REQUIRED=true`;
+//! a token that is sent has to be valid either way. `ADMIN_TOKEN` is a secret with every scope,
+//! for creating the first tokens.
+//!
+//! Logged in users hold [`USER_SCOPES`] and open access [`OPEN_SCOPES`]; managing webhooks is
+//! left to the admin token and the tokens it creates. A token made by a user is tied to them: it
+//! acts as that user and never holds a scope they do not have.
+
+pub const TODOS_READ: &str = "todos:read";
+pub const TODOS_WRITE: &str = "todos:write";
+pub const LABELS_READ: &str = "labels:read";
+pub const LABELS_WRITE: &str = "labels:write";
+pub const WEBHOOKS_READ: &str = "webhooks:read";
+pub const WEBHOOKS_WRITE: &str = "webhooks:write";
+pub const TOKENS_READ: &str = "tokens:read";
+pub const TOKENS_WRITE: &str = "tokens:write";
+pub const WORKSPACES_READ: &str = "workspaces:read";
+pub const WORKSPACES_WRITE: &str = "workspaces:write";
+
+pub const SCOPES: [&str; 10] = [
+    TODOS_READ,
+    TODOS_WRITE,
+    LABELS_READ,
+    LABELS_WRITE,
+    WEBHOOKS_READ,
+    WEBHOOKS_WRITE,
+    TOKENS_READ,
+    TOKENS_WRITE,
+    WORKSPACES_READ,
+    WORKSPACES_WRITE,
+];
+
+/// What a user logged in through the browser may do.
+pub const USER_SCOPES: [&str; 8] = [
+    TODOS_READ,
+    TODOS_WRITE,
+    LABELS_READ,
+    LABELS_WRITE,
+    TOKENS_READ,
+    TOKENS_WRITE,
+    WORKSPACES_READ,
+    WORKSPACES_WRITE,
+];
+
+/// What requests without credentials may do while `AUTH_REQUIRED` is off.
+pub const OPEN_SCOPES: [&str; 4] = [TODOS_READ, TODOS_WRITE, LABELS_READ, LABELS_WRITE];
+
+#[derive(Debug, Clone, Default)]
+pub struct AuthConfig {
+    required: bool,
+    admin_token: Option<String>,
+}
+
+impl AuthConfig {
+    pub fn new(required: bool, admin_token: Option<String>) -> Self {
+        Self { required, admin_token }
+    }
+
+    pub fn from_env() -> Self {
+        let required = std::env::var("AUTH_REQUIRED").map(|value| value == "true").unwrap_or(false);
+        Self::new(required, std::env::var("ADMIN_TOKEN").ok())
+    }
+
+    pub fn required(&self) -> bool {
+        self.required
+    }
+
+    fn is_admin(&self, token: &str) -> bool {
+        use sha2::Digest;
+        // compare digests so the comparison time does not depend on the secret
+        self.admin_token
+            .as_deref()
+            .is_some_and(|expected| sha2::Sha256::digest(expected.as_bytes()) == sha2::Sha256::digest(token.as_bytes()))
+    }
+}
+
+/// Who sent a request, as far as authorization is concerned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Caller {
+    /// No token while `AUTH_REQUIRED` is off; allowed [`OPEN_SCOPES`].
+    Open,
+    Admin,
+    /// `user_id` is the user who created the token, none for tokens made with the admin token,
+    /// which reach no workspace.
+    Token { id: i32, user_id: Option<i32>, scopes: Vec<String> },
+    /// A user logged in through the browser; allowed [`USER_SCOPES`].
+    User { id: i32 },
+    /// No token while one is required, or a token that is unknown or expired.
+    Unauthenticated,
+}
+
+impl Caller {
+    pub fn check(&self, scope: &str) -> Result<(), AuthError> {
+        let granted = match self {
+            Caller::Admin => true,
+            Caller::Open => OPEN_SCOPES.contains(&scope),
+            Caller::User { .. } => USER_SCOPES.contains(&scope),
+            Caller::Token { user_id, scopes, .. } => {
+                scopes.iter().any(|granted| granted == scope) && (user_id.is_none() || USER_SCOPES.contains(&scope))
+            }
+            Caller::Unauthenticated => return Err(AuthError::Unauthenticated),
+        };
+        if granted {
+            Ok(())
+        } else {
+            Err(AuthError::MissingScope(scope.to_string()))
+        }
+    }
+
+    /// The user acting, whether logged in or through one of their tokens.
+    pub fn user_id(&self) -> Option<i32> {
+        match self {
+            Caller::User { id } => Some(*id),
+            Caller::Token { user_id, .. } => *user_id,
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum AuthError {
+    #[error("missing, unknown or expired API token")]
+    Unauthenticated,
+    #[error("caller lacks the {0} scope")]
+    MissingScope(String),
+}
+
+impl axum::response::IntoResponse for AuthError {
+    fn into_response(self) -> axum::response::Response {
+        match self {
+            AuthError::Unauthenticated => {
+                let mut response = (axum::http::StatusCode::UNAUTHORIZED, self.to_string()).into_response();
+                response
+                    .headers_mut()
+                    .insert(axum::http::header::WWW_AUTHENTICATE, axum::http::HeaderValue::from_static("Bearer"));
+                response
+            }
+            AuthError::MissingScope(_) => (axum::http::StatusCode::FORBIDDEN, self.to_string()).into_response(),
+        }
+    }
+}
+
+pub fn bearer_token(headers: &axum::http::HeaderMap) -> Option<&str> {
+    let value = headers.get(axum::http::header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    scheme.eq_ignore_ascii_case("bearer").then(|| token.trim())
+}
+
+/// Name of the cookie holding the session secret of a logged in user.
+pub const SESSION_COOKIE: &str = "session";
+
+/// The value of the cookie `name` sent with a request.
+pub fn cookie<'a>(headers: &'a axum::http::HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(axum::http::header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+/// Resolves the credentials a request came with into its [`Caller`]: an API token (or the admin
+/// token) sent as a bearer token, or else a login session. Shared by the HTTP middleware and the
+/// gRPC server (see `crate::grpc`), so both accept the same credentials.
+pub async fn resolve_caller<T: crate::repositories::token::ApiTokenRepository, U: crate::repositories::user::UserRepository>(
+    config: &AuthConfig,
+    tokens: &T,
+    users: &U,
+    bearer: Option<&str>,
+    session: Option<&str>,
+) -> anyhow::Result<Caller> {
+    if let Some(token) = bearer {
+        if config.is_admin(token) {
+            return Ok(Caller::Admin);
+        }
+        return Ok(match tokens.verify(token).await? {
+            Some(api_token) => Caller::Token { id: api_token.id, user_id: api_token.user_id, scopes: api_token.scopes },
+            None => Caller::Unauthenticated,
+        });
+    }
+    let user = match session {
+        Some(session) => users.session_user(session).await?,
+        None => None,
+    };
+    Ok(match user {
+        Some(user) => Caller::User { id: user.id },
+        None if config.required => Caller::Unauthenticated,
+        None => Caller::Open,
+    })
+}
+
+/// The bearer token and the session secret a request came with.
+fn credentials(headers: &axum::http::HeaderMap) -> (Option<&str>, Option<&str>) {
+    (bearer_token(headers), cookie(headers, SESSION_COOKIE))
+}
+
+pub async fn authenticate<T: crate::repositories::token::ApiTokenRepository, U: crate::repositories::user::UserRepository>(
+    mut request: axum::http::Request<axum::body::Body>,
+    next: axum::middleware::Next<axum::body::Body>,
+) -> axum::response::Response {
+    let config = request
+        .extensions()
+        .get::<AuthConfig>()
+        .cloned()
+        .expect("auth config extension is missing");
+    let tokens = request
+        .extensions()
+        .get::<std::sync::Arc<T>>()
+        .cloned()
+        .expect("api token repository extension is missing");
+    let users = request
+        .extensions()
+        .get::<std::sync::Arc<U>>()
+        .cloned()
+        .expect("user repository extension is missing");
+    let (bearer, session) = credentials(request.headers());
+    let caller = match resolve_caller(&config, tokens.as_ref(), users.as_ref(), bearer, session).await {
+        Ok(caller) => caller,
+        Err(e) => {
+            tracing::error!("failed to authenticate: {}", e);
+            return axum::response::IntoResponse::into_response(axum::http::StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    // a write by a logged in session needs its CSRF token (see `crate::security`); going on
+    // without the session instead would let another site act with open access
+    if let (Caller::User { .. }, Some(session)) = (&caller, session) {
+        if !crate::security::csrf_verified(request.method(), request.headers(), session) {
+            return axum::response::IntoResponse::into_response((
+                axum::http::StatusCode::FORBIDDEN,
+                "missing or invalid csrf token".to_string(),
+            ));
+        }
+    }
+    request.extensions_mut().insert(caller);
+    next.run(request).await
+}
+
+/// What a group of routes deals in, and so which scopes they need.
+pub trait Resource: Send + 'static {
+    fn scope(method: &axum::http::Method) -> &'static str;
+}
+
+fn read_or_write(method: &axum::http::Method, read: &'static str, write: &'static str) -> &'static str {
+    match *method {
+        axum::http::Method::GET | axum::http::Method::HEAD => read,
+        _ => write,
+    }
+}
+
+pub struct Todos;
+
+impl Resource for Todos {
+    fn scope(method: &axum::http::Method) -> &'static str {
+        read_or_write(method, TODOS_READ, TODOS_WRITE)
+    }
+}
+
+pub struct Labels;
+
+impl Resource for Labels {
+    fn scope(method: &axum::http::Method) -> &'static str {
+        read_or_write(method, LABELS_READ, LABELS_WRITE)
+    }
+}
+
+pub struct Webhooks;
+
+impl Resource for Webhooks {
+    fn scope(method: &axum::http::Method) -> &'static str {
+        read_or_write(method, WEBHOOKS_READ, WEBHOOKS_WRITE)
+    }
+}
+
+/// API and calendar feed tokens.
+pub struct Tokens;
+
+impl Resource for Tokens {
+    fn scope(method: &axum::http::Method) -> &'static str {
+        read_or_write(method, TOKENS_READ, TOKENS_WRITE)
+    }
+}
+
+/// Workspaces, their members and invites.
+pub struct Workspaces;
+
+impl Resource for Workspaces {
+    fn scope(method: &axum::http::Method) -> &'static str {
+        read_or_write(method, WORKSPACES_READ, WORKSPACES_WRITE)
+    }
+}
+
+/// Every GraphQL operation is a `POST`, so the route only needs `todos:read`; mutations check
+/// their own scopes.
+pub struct GraphQl;
+
+impl Resource for GraphQl {
+    fn scope(_: &axum::http::Method) -> &'static str {
+        TODOS_READ
+    }
+}
+
+/// Extractor rejecting callers without the scope `R` needs for the request method; applied
+/// to routes with [`require`].
+pub struct RequireScope<R>(std::marker::PhantomData<fn() -> R>);
+
+#[axum::async_trait]
+impl<R: Resource, B: Send> axum::extract::FromRequest<B> for RequireScope<R> {
+    type Rejection = AuthError;
+
+    async fn from_request(req: &mut axum::extract::RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let caller = req.extensions().and_then(|extensions| extensions.get::<Caller>());
+        caller.unwrap_or(&Caller::Unauthenticated).check(R::scope(req.method()))?;
+        Ok(RequireScope(std::marker::PhantomData))
+    }
+}
+
+/// Route layer running [`RequireScope`], e.g. `.route_layer(require::<Todos>())`.
+pub fn require<R: Resource>() -> axum::extract::extractor_middleware::ExtractorMiddlewareLayer<RequireScope<R>> {
+    axum::extract::extractor_middleware::<RequireScope<R>>()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::token::ApiTokenRepository;
+    use tower::ServiceExt;
+
+    fn app(config: AuthConfig, repository: crate::repositories::token::test_utils::ApiTokenRepositoryForMemory) -> axum::Router {
+        axum::Router::new()
+            .route(
+                "/todos",
+                axum::routing::get(|| async { "todos" })
+                    .post(|| async { "created" })
+                    .route_layer(require::<Todos>()),
+            )
+            .layer(axum::middleware::from_fn(
+                authenticate::<
+                    crate::repositories::token::test_utils::ApiTokenRepositoryForMemory,
+                    crate::repositories::user::test_utils::UserRepositoryForMemory,
+                >,
+            ))
+            .layer(axum::extract::Extension(std::sync::Arc::new(repository)))
+            .layer(axum::extract::Extension(std::sync::Arc::new(
+                crate::repositories::user::test_utils::UserRepositoryForMemory::new(),
+            )))
+            .layer(axum::extract::Extension(config))
+    }
+
+    async fn status(app: &axum::Router, method: &str, token: Option<&str>) -> axum::http::StatusCode {
+        let mut request = axum::http::Request::builder().method(method).uri("/todos");
+        if let Some(token) = token {
+            request = request.header("authorization", format!("Bearer {}", token));
+        }
+        app.clone().oneshot(request.body(axum::body::Body::empty()).unwrap()).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn scopes_are_checked_per_method() {
+        let repository = crate::repositories::token::test_utils::ApiTokenRepositoryForMemory::new();
+        let reader = repository
+            .create(crate::repositories::token::CreateApiToken::new("reader".to_string(), vec![TODOS_READ.to_string()], None), None)
+            .await
+            .unwrap();
+        let app = app(AuthConfig::new(false, None), repository.clone());
+
+        assert_eq!(axum::http::StatusCode::OK, status(&app, "GET", Some(&reader.token)).await);
+        assert_eq!(axum::http::StatusCode::FORBIDDEN, status(&app, "POST", Some(&reader.token)).await);
+        assert_eq!(axum::http::StatusCode::UNAUTHORIZED, status(&app, "GET", Some("todo_unknown")).await);
+        assert_eq!(axum::http::StatusCode::OK, status(&app, "POST", None).await);
+        assert!(repository.all().await.unwrap()[0].last_used_at.is_some());
+
+        repository.delete(reader.api_token.id).await.unwrap();
+        assert_eq!(axum::http::StatusCode::UNAUTHORIZED, status(&app, "GET", Some(&reader.token)).await);
+    }
+
+    #[tokio::test]
+    async fn tokens_can_be_required() {
+        let repository = crate::repositories::token::test_utils::ApiTokenRepositoryForMemory::new();
+        let app = app(AuthConfig::new(true, Some("admin-secret".to_string())), repository);
+
+        assert_eq!(axum::http::StatusCode::UNAUTHORIZED, status(&app, "GET", None).await);
+        assert_eq!(axum::http::StatusCode::OK, status(&app, "POST", Some("admin-secret")).await);
+    }
+
+    #[test]
+    fn webhooks_are_left_to_the_admin() {
+        let user = Caller::User { id: 1 };
+        let users_token = Caller::Token { id: 1, user_id: Some(1), scopes: vec![WEBHOOKS_READ.to_string(), TODOS_READ.to_string()] };
+        let admins_token = Caller::Token { id: 2, user_id: None, scopes: vec![WEBHOOKS_READ.to_string()] };
+
+        assert_eq!(Ok(()), user.check(TOKENS_WRITE));
+        assert_eq!(Err(AuthError::MissingScope(WEBHOOKS_READ.to_string())), user.check(WEBHOOKS_READ));
+        assert_eq!(Ok(()), users_token.check(TODOS_READ));
+        assert!(users_token.check(WEBHOOKS_READ).is_err());
+        assert_eq!(Ok(()), admins_token.check(WEBHOOKS_READ));
+        assert_eq!(Ok(()), Caller::Open.check(TODOS_WRITE));
+        assert!(Caller::Open.check(TOKENS_WRITE).is_err());
+        assert!(Caller::Open.check(WEBHOOKS_WRITE).is_err());
+    }
+}
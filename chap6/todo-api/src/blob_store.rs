@@ -0,0 +1,144 @@
+pub type BlobStream = std::pin::Pin<Box<dyn futures::Stream<Item = std::io::Result<bytes::Bytes>> + Send>>;
+
+#[axum::async_trait]
+pub trait BlobStore: std::fmt::Debug + std::marker::Send + std::marker::Sync + 'static {
+    async fn put(&self, key: &str, data: bytes::Bytes) -> anyhow::Result<()>;
+    async fn get(&self, key: &str) -> anyhow::Result<BlobStream>;
+    async fn delete(&self, key: &str) -> anyhow::Result<()>;
+}
+
+#[derive(Debug, thiserror::Error)]
+enum BlobStoreError {
+    #[error("NotFound, {0}")]
+    NotFound(String),
+    #[error("Invalid key {0}")]
+    InvalidKey(String),
+}
+
+/// Builds a fresh key for a blob belonging to `todo_id`.
+pub fn new_blob_key(todo_id: i32) -> String {
+    format!("todos/{}/{:032x}", todo_id, rand::random::<u128>())
+}
+
+fn validate_key(key: &str) -> anyhow::Result<()> {
+    let valid = !key.is_empty()
+        && key.split('/').all(|segment| !segment.is_empty() && segment != "." && segment != "..")
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '/' | '-' | '_' | '.'));
+    if !valid {
+        return Err(BlobStoreError::InvalidKey(key.to_string()).into());
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct BlobStoreForFs {
+    root: std::path::PathBuf,
+}
+
+impl BlobStoreForFs {
+    pub fn new(root: impl Into<std::path::PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> anyhow::Result<std::path::PathBuf> {
+        validate_key(key)?;
+        Ok(self.root.join(key))
+    }
+}
+
+#[axum::async_trait]
+impl BlobStore for BlobStoreForFs {
+    async fn put(&self, key: &str, data: bytes::Bytes) -> anyhow::Result<()> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(path, data).await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> anyhow::Result<BlobStream> {
+        let file = tokio::fs::File::open(self.path(key)?).await.map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => anyhow::Error::new(BlobStoreError::NotFound(key.to_string())),
+            _ => e.into(),
+        })?;
+        Ok(Box::pin(tokio_util::io::ReaderStream::new(file)))
+    }
+
+    async fn delete(&self, key: &str) -> anyhow::Result<()> {
+        match tokio::fs::remove_file(self.path(key)?).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+type BlobDatas = std::collections::HashMap<String, bytes::Bytes>;
+
+#[derive(Debug, Clone, Default)]
+pub struct BlobStoreForMemory {
+    store: std::sync::Arc<std::sync::RwLock<BlobDatas>>,
+}
+
+impl BlobStoreForMemory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[axum::async_trait]
+impl BlobStore for BlobStoreForMemory {
+    async fn put(&self, key: &str, data: bytes::Bytes) -> anyhow::Result<()> {
+        validate_key(key)?;
+        self.store.write().unwrap().insert(key.to_string(), data);
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> anyhow::Result<BlobStream> {
+        let data = self
+            .store
+            .read()
+            .unwrap()
+            .get(key)
+            .cloned()
+            .ok_or_else(|| BlobStoreError::NotFound(key.to_string()))?;
+        Ok(Box::pin(futures::stream::once(async { Ok(data) })))
+    }
+
+    async fn delete(&self, key: &str) -> anyhow::Result<()> {
+        self.store.write().unwrap().remove(key);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::TryStreamExt;
+
+    async fn blob_store_scenario(store: impl BlobStore) {
+        let key = new_blob_key(1);
+        let data = bytes::Bytes::from_static(b"%PDF-1.4 attachment");
+        store.put(&key, data.clone()).await.expect("failed put blob");
+
+        let chunks: Vec<bytes::Bytes> = store.get(&key).await.unwrap().try_collect().await.unwrap();
+        assert_eq!(data, chunks.concat());
+
+        store.delete(&key).await.expect("failed delete blob");
+        assert!(store.get(&key).await.is_err());
+        assert!(store.delete(&key).await.is_ok());
+        assert!(store.put("../escape", data).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn blob_store_for_memory_scenario() {
+        blob_store_scenario(BlobStoreForMemory::new()).await;
+    }
+
+    #[tokio::test]
+    async fn blob_store_for_fs_scenario() {
+        let root = std::env::temp_dir().join(format!("todo-api-blobs-{:x}", rand::random::<u64>()));
+        blob_store_scenario(BlobStoreForFs::new(&root)).await;
+        tokio::fs::remove_dir_all(root).await.ok();
+    }
+}
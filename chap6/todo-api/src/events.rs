@@ -0,0 +1,194 @@
+//! Change feed for todos, consumed by streaming clients such as gRPC `WatchTodos` and `/ws`.
+//!
+//! Every event gets a sequence number, and the most recent ones are kept so a client that
+//! reconnects or falls behind can replay what it missed instead of reloading everything.
+//! [`TodoRepositoryWithEvents`] wraps any `TodoRepository` and publishes after each successful
+//! write, so every backend gets the feed without knowing about it.
+
+/// Events a subscriber may fall behind by before it is told it lagged.
+pub const EVENT_CAPACITY: usize = 256;
+
+/// Events kept for replay by [`TodoEvents::since`].
+pub const HISTORY_CAPACITY: usize = 1024;
+
+pub type SequencedEvent = (u64, TodoEvent);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TodoEvent {
+    Created(crate::repositories::todo::TodoEntity),
+    Updated(crate::repositories::todo::TodoEntity),
+    /// `workspace_id` is the workspace the todo was in, so deletions reach only its members.
+    Deleted { id: i32, workspace_id: Option<i32> },
+}
+
+impl TodoEvent {
+    /// Deletions carry no todo to filter on, so they always match.
+    pub fn matches(&self, filter: &crate::repositories::todo::TodoFilter) -> bool {
+        match self {
+            TodoEvent::Created(todo) | TodoEvent::Updated(todo) => filter.matches(todo),
+            TodoEvent::Deleted { .. } => true,
+        }
+    }
+
+    /// Whether a subscriber with `memberships` may see the event at all.
+    pub fn visible_to(&self, memberships: &crate::workspaces::Memberships) -> bool {
+        match self {
+            TodoEvent::Created(todo) | TodoEvent::Updated(todo) => memberships.can_view(todo.workspace_id),
+            TodoEvent::Deleted { workspace_id, .. } => memberships.can_view(*workspace_id),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct History {
+    last_seq: u64,
+    events: std::collections::VecDeque<SequencedEvent>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TodoEvents {
+    sender: tokio::sync::broadcast::Sender<SequencedEvent>,
+    history: std::sync::Arc<std::sync::Mutex<History>>,
+}
+
+impl TodoEvents {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = tokio::sync::broadcast::channel(capacity);
+        Self { sender, history: std::sync::Arc::default() }
+    }
+
+    /// Subscribes to live events, returning the sequence number of the last event it will not see.
+    pub fn subscribe(&self) -> (u64, tokio::sync::broadcast::Receiver<SequencedEvent>) {
+        let history = self.history.lock().unwrap();
+        (history.last_seq, self.sender.subscribe())
+    }
+
+    pub fn publish(&self, event: TodoEvent) {
+        // hold the lock while sending so live and replayed events share one order
+        let mut history = self.history.lock().unwrap();
+        history.last_seq += 1;
+        let sequenced = (history.last_seq, event);
+        history.events.push_back(sequenced.clone());
+        if history.events.len() > HISTORY_CAPACITY {
+            history.events.pop_front();
+        }
+        // sending only fails when nobody is subscribed
+        let _ = self.sender.send(sequenced);
+    }
+
+    pub fn last_seq(&self) -> u64 {
+        self.history.lock().unwrap().last_seq
+    }
+
+    /// Events after `seq`, or `None` when some of them were already dropped from the history
+    /// (or `seq` comes from before a restart) and the caller has to reload instead.
+    pub fn since(&self, seq: u64) -> Option<Vec<SequencedEvent>> {
+        let history = self.history.lock().unwrap();
+        let oldest = history.events.front().map_or(history.last_seq + 1, |(oldest, _)| *oldest);
+        if seq > history.last_seq || seq + 1 < oldest {
+            return None;
+        }
+        Some(history.events.iter().filter(|(event_seq, _)| *event_seq > seq).cloned().collect())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TodoRepositoryWithEvents<T> {
+    inner: T,
+    events: TodoEvents,
+}
+
+impl<T: crate::repositories::todo::TodoRepository> TodoRepositoryWithEvents<T> {
+    pub fn new(inner: T, events: TodoEvents) -> Self {
+        Self { inner, events }
+    }
+
+    fn updated(&self, todo: crate::repositories::todo::TodoEntity) -> crate::repositories::todo::TodoEntity {
+        self.events.publish(TodoEvent::Updated(todo.clone()));
+        todo
+    }
+}
+
+#[axum::async_trait]
+impl<T: crate::repositories::todo::TodoRepository> crate::repositories::todo::TodoRepository for TodoRepositoryWithEvents<T> {
+    async fn create(&self, payload: crate::repositories::todo::CreateTodo) -> anyhow::Result<crate::repositories::todo::TodoEntity> {
+        let todo = self.inner.create(payload).await?;
+        self.events.publish(TodoEvent::Created(todo.clone()));
+        Ok(todo)
+    }
+
+    async fn find(&self, id: i32) -> anyhow::Result<crate::repositories::todo::TodoEntity> {
+        self.inner.find(id).await
+    }
+
+    async fn all(&self) -> anyhow::Result<Vec<crate::repositories::todo::TodoEntity>> {
+        self.inner.all().await
+    }
+
+    fn stream(&self) -> crate::repositories::todo::TodoStream {
+        self.inner.stream()
+    }
+
+    async fn update(&self, id: i32, payload: crate::repositories::todo::UpdateTodo) -> anyhow::Result<crate::repositories::todo::TodoEntity> {
+        Ok(self.updated(self.inner.update(id, payload).await?))
+    }
+
+    async fn move_to(&self, id: i32, payload: crate::repositories::todo::MoveTodo) -> anyhow::Result<crate::repositories::todo::TodoEntity> {
+        Ok(self.updated(self.inner.move_to(id, payload).await?))
+    }
+
+    async fn add_blocker(&self, id: i32, blocker_id: i32) -> anyhow::Result<crate::repositories::todo::TodoEntity> {
+        Ok(self.updated(self.inner.add_blocker(id, blocker_id).await?))
+    }
+
+    async fn remove_blocker(&self, id: i32, blocker_id: i32) -> anyhow::Result<crate::repositories::todo::TodoEntity> {
+        Ok(self.updated(self.inner.remove_blocker(id, blocker_id).await?))
+    }
+
+    async fn assign(&self, id: i32, assignee_id: Option<i32>) -> anyhow::Result<crate::repositories::todo::TodoEntity> {
+        Ok(self.updated(self.inner.assign(id, assignee_id).await?))
+    }
+
+    async fn watchers(&self, id: i32) -> anyhow::Result<Vec<i32>> {
+        self.inner.watchers(id).await
+    }
+
+    async fn watch(&self, id: i32, user_id: i32) -> anyhow::Result<()> {
+        self.inner.watch(id, user_id).await
+    }
+
+    async fn unwatch(&self, id: i32, user_id: i32) -> anyhow::Result<()> {
+        self.inner.unwatch(id, user_id).await
+    }
+
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        let workspace_id = self.inner.find(id).await?.workspace_id;
+        self.inner.delete(id).await?;
+        self.events.publish(TodoEvent::Deleted { id, workspace_id });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn replay_from_history() {
+        let events = TodoEvents::new(EVENT_CAPACITY);
+        assert_eq!(Some(vec![]), events.since(0));
+        for id in 1..=(HISTORY_CAPACITY as i32 + 2) {
+            events.publish(TodoEvent::Deleted { id, workspace_id: None });
+        }
+        let last_seq = HISTORY_CAPACITY as u64 + 2;
+        assert_eq!(last_seq, events.last_seq());
+
+        let replayed = events.since(last_seq - 2).unwrap();
+        assert_eq!(vec![last_seq - 1, last_seq], replayed.iter().map(|(seq, _)| *seq).collect::<Vec<_>>());
+        assert_eq!(Some(vec![]), events.since(last_seq));
+        // the first two events have been dropped, and future sequence numbers mean a restart
+        assert_eq!(HISTORY_CAPACITY, events.since(2).unwrap().len());
+        assert_eq!(None, events.since(1));
+        assert_eq!(None, events.since(last_seq + 1));
+    }
+}
@@ -0,0 +1,466 @@
+//! GraphQL schema over the same repository traits as the REST handlers.
+//!
+//! Resolvers read the repositories from the request data, so the schema is generic over any
+//! `TodoRepository`/`LabelRepository` pair. `Label.todos` goes through [`TodosByLabelLoader`],
+//! which answers every label in a query from a single `TodoRepository::all` call.
+//! Queries are limited in depth and complexity, so `Label.todos` and `Todo.labels` cannot be
+//! nested into responses that multiply with every level.
+
+/// Largest page `todos` returns, whatever `limit` asks for.
+const MAX_PAGE_SIZE: usize = 100;
+
+/// Deepest nesting a query may have, leaving room for GraphiQL's introspection query.
+const MAX_DEPTH: usize = 15;
+/// Upper bound of a query's cost, one per field with lists counted as [`LIST_COST`] items.
+const MAX_COMPLEXITY: usize = 1000;
+/// Items a nested list such as `Label.todos` is assumed to hold when pricing a query.
+const LIST_COST: usize = 20;
+
+pub type TodoSchema<T, L> = async_graphql::Schema<QueryRoot<T, L>, MutationRoot<T, L>, async_graphql::EmptySubscription>;
+
+pub fn build_schema<T: crate::repositories::todo::TodoRepository, L: crate::repositories::label::LabelRepository>() -> TodoSchema<T, L> {
+    async_graphql::Schema::build(
+        QueryRoot(std::marker::PhantomData),
+        MutationRoot(std::marker::PhantomData),
+        async_graphql::EmptySubscription,
+    )
+    .limit_depth(MAX_DEPTH)
+    .limit_complexity(MAX_COMPLEXITY)
+    .finish()
+}
+
+/// Adds the repositories and a fresh label loader to a request before it is executed.
+pub fn request_data<T: crate::repositories::todo::TodoRepository, L: crate::repositories::label::LabelRepository>(
+    request: async_graphql::Request,
+    todo_repository: std::sync::Arc<T>,
+    label_repository: std::sync::Arc<L>,
+) -> async_graphql::Request {
+    let loader = TodosByLabelLoader::new(todo_repository.clone());
+    request
+        .data(todo_repository)
+        .data(label_repository)
+        .data(async_graphql::dataloader::DataLoader::new(loader, tokio::spawn))
+}
+
+fn coded_error(message: impl Into<String>, code: &'static str) -> async_graphql::Error {
+    async_graphql::ErrorExtensions::extend_with(async_graphql::Error::new(message), |_, extensions| extensions.set("code", code))
+}
+
+/// Mirrors `repository_error_response`: client errors keep their message and get a `code`.
+fn repository_error(error: anyhow::Error) -> async_graphql::Error {
+    let code = match error.downcast_ref::<crate::repositories::RepositoryError>() {
+        Some(crate::repositories::RepositoryError::NotFound(_)) => "NOT_FOUND",
+        Some(
+            crate::repositories::RepositoryError::Blocked(_)
+            | crate::repositories::RepositoryError::Cycle(_)
+            | crate::repositories::RepositoryError::LastOwner(_),
+        ) => "CONFLICT",
+        _ => {
+            tracing::error!("graphql resolver failed: {}", error);
+            return coded_error("Internal Server Error", "INTERNAL_SERVER_ERROR");
+        }
+    };
+    coded_error(error.to_string(), code)
+}
+
+fn validated<P: validator::Validate>(payload: P) -> async_graphql::Result<P> {
+    payload
+        .validate()
+        .map_err(|e| coded_error(format!("validation error: {}", e).replace('\n', ", "), "BAD_USER_INPUT"))?;
+    Ok(payload)
+}
+
+fn workspace_error(error: crate::workspaces::WorkspaceError) -> async_graphql::Error {
+    match error {
+        // same as for a todo or label that does not exist
+        crate::workspaces::WorkspaceError::NotFound(_) => coded_error("Not Found", "NOT_FOUND"),
+        e @ crate::workspaces::WorkspaceError::ForeignLabel(_) => coded_error(e.to_string(), "BAD_USER_INPUT"),
+        e => coded_error(e.to_string(), "FORBIDDEN"),
+    }
+}
+
+/// The caller's workspace roles the handler put in the request data; requests without them
+/// are refused.
+fn memberships<'a>(ctx: &async_graphql::Context<'a>) -> async_graphql::Result<&'a crate::workspaces::Memberships> {
+    ctx.data_opt::<crate::workspaces::Memberships>()
+        .ok_or_else(|| coded_error(crate::auth::AuthError::Unauthenticated.to_string(), "UNAUTHENTICATED"))
+}
+
+/// Loads a todo the caller may act on with `permission`.
+async fn authorized_todo<T: crate::repositories::todo::TodoRepository>(
+    ctx: &async_graphql::Context<'_>,
+    id: i32,
+    permission: crate::workspaces::Permission,
+) -> async_graphql::Result<crate::repositories::todo::TodoEntity> {
+    let todo = ctx.data::<std::sync::Arc<T>>()?.find(id).await.map_err(repository_error)?;
+    memberships(ctx)?.check(todo.workspace_id, permission).map_err(workspace_error)?;
+    Ok(todo)
+}
+
+/// Checks a field against the scopes of the caller the handler put in the request data;
+/// requests without a caller are refused.
+pub(crate) struct ScopeGuard(pub(crate) &'static str);
+
+impl async_graphql::Guard for ScopeGuard {
+    async fn check(&self, ctx: &async_graphql::Context<'_>) -> async_graphql::Result<()> {
+        match ctx.data_opt::<crate::auth::Caller>().map(|caller| caller.check(self.0)) {
+            Some(Ok(())) => Ok(()),
+            Some(Err(e @ crate::auth::AuthError::MissingScope(_))) => Err(coded_error(e.to_string(), "FORBIDDEN")),
+            _ => Err(coded_error(crate::auth::AuthError::Unauthenticated.to_string(), "UNAUTHENTICATED")),
+        }
+    }
+}
+
+type LoadAll = dyn Fn() -> futures::future::BoxFuture<'static, anyhow::Result<Vec<crate::repositories::todo::TodoEntity>>> + Send + Sync;
+
+/// Groups todos by label id; the todo repository is captured in a closure so `Label`'s
+/// resolvers do not need to be generic over it.
+pub struct TodosByLabelLoader {
+    load_all: Box<LoadAll>,
+}
+
+impl TodosByLabelLoader {
+    pub fn new<T: crate::repositories::todo::TodoRepository>(repository: std::sync::Arc<T>) -> Self {
+        Self {
+            load_all: Box::new(move || {
+                let repository = repository.clone();
+                Box::pin(async move { repository.all().await })
+            }),
+        }
+    }
+}
+
+impl async_graphql::dataloader::Loader<i32> for TodosByLabelLoader {
+    type Value = Vec<crate::repositories::todo::TodoEntity>;
+    type Error = std::sync::Arc<anyhow::Error>;
+
+    async fn load(&self, keys: &[i32]) -> Result<std::collections::HashMap<i32, Self::Value>, Self::Error> {
+        let todos = (self.load_all)().await.map_err(std::sync::Arc::new)?;
+        let mut grouped: std::collections::HashMap<i32, Self::Value> = keys.iter().map(|key| (*key, vec![])).collect();
+        for todo in todos {
+            for label in todo.labels.iter() {
+                if let Some(todos) = grouped.get_mut(&label.id) {
+                    todos.push(todo.clone());
+                }
+            }
+        }
+        Ok(grouped)
+    }
+}
+
+#[async_graphql::ComplexObject]
+impl crate::repositories::label::Label {
+    /// Todos carrying this label, in list order.
+    #[graphql(complexity = "LIST_COST * child_complexity")]
+    async fn todos(&self, ctx: &async_graphql::Context<'_>) -> async_graphql::Result<Vec<crate::repositories::todo::TodoEntity>> {
+        let loader = ctx.data::<async_graphql::dataloader::DataLoader<TodosByLabelLoader>>()?;
+        let memberships = memberships(ctx)?;
+        let todos = loader
+            .load_one(self.id)
+            .await
+            .map_err(|e| repository_error(anyhow::anyhow!(e.to_string())))?;
+        Ok(todos.unwrap_or_default().into_iter().filter(|todo| memberships.can_view(todo.workspace_id)).collect())
+    }
+
+    async fn todo_count(&self, ctx: &async_graphql::Context<'_>) -> async_graphql::Result<usize> {
+        Ok(self.todos(ctx).await?.len())
+    }
+}
+
+/// `UpdateTodo` for GraphQL, where `priority: null` or `due: null` clears the value.
+#[derive(Debug, Clone, async_graphql::InputObject)]
+pub struct UpdateTodoInput {
+    text: Option<String>,
+    completed: Option<bool>,
+    labels: Option<Vec<i32>>,
+    priority: async_graphql::MaybeUndefined<String>,
+    due: async_graphql::MaybeUndefined<chrono::NaiveDate>,
+}
+
+impl From<UpdateTodoInput> for crate::repositories::todo::UpdateTodo {
+    fn from(input: UpdateTodoInput) -> Self {
+        crate::repositories::todo::UpdateTodo::new(input.text, input.completed, input.labels)
+            .schedule(input.priority.into(), input.due.into())
+    }
+}
+
+#[derive(Debug, Clone, async_graphql::SimpleObject)]
+pub struct TodoPage {
+    pub nodes: Vec<crate::repositories::todo::TodoEntity>,
+    /// Number of todos matching the filter, across all pages.
+    pub total_count: usize,
+    pub has_next_page: bool,
+}
+
+fn paginate(todos: Vec<crate::repositories::todo::TodoEntity>, offset: usize, limit: usize) -> TodoPage {
+    let total_count = todos.len();
+    let nodes: Vec<_> = todos.into_iter().skip(offset).take(limit.min(MAX_PAGE_SIZE)).collect();
+    TodoPage {
+        has_next_page: offset + nodes.len() < total_count,
+        nodes,
+        total_count,
+    }
+}
+
+pub struct QueryRoot<T, L>(std::marker::PhantomData<(T, L)>);
+
+#[async_graphql::Object(name = "Query")]
+impl<T: crate::repositories::todo::TodoRepository, L: crate::repositories::label::LabelRepository> QueryRoot<T, L> {
+    #[allow(clippy::too_many_arguments)]
+    async fn todos(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        label: Option<i32>,
+        completed: Option<bool>,
+        workspace: Option<i32>,
+        assignee: Option<i32>,
+        #[graphql(default = 0, validator(minimum = 0))] offset: i32,
+        #[graphql(default = 100, validator(minimum = 0, maximum = 100))] limit: i32,
+    ) -> async_graphql::Result<TodoPage> {
+        let filter = crate::repositories::todo::TodoFilter {
+            label,
+            completed,
+            workspace,
+            assignee: assignee.map(crate::repositories::todo::AssigneeFilter::User),
+        };
+        let memberships = memberships(ctx)?;
+        let todos: Vec<_> = ctx
+            .data::<std::sync::Arc<T>>()?
+            .all()
+            .await
+            .map_err(repository_error)?
+            .into_iter()
+            .filter(|todo| filter.matches(todo) && memberships.can_view(todo.workspace_id))
+            .collect();
+        Ok(paginate(todos, offset as usize, limit as usize))
+    }
+
+    async fn todo(&self, ctx: &async_graphql::Context<'_>, id: i32) -> async_graphql::Result<crate::repositories::todo::TodoEntity> {
+        authorized_todo::<T>(ctx, id, crate::workspaces::Permission::View).await
+    }
+
+    #[graphql(guard = "ScopeGuard(crate::auth::LABELS_READ)")]
+    async fn labels(&self, ctx: &async_graphql::Context<'_>) -> async_graphql::Result<Vec<crate::repositories::label::Label>> {
+        let memberships = memberships(ctx)?;
+        let labels = ctx.data::<std::sync::Arc<L>>()?.all().await.map_err(repository_error)?;
+        Ok(labels.into_iter().filter(|label| memberships.can_view(label.workspace_id)).collect())
+    }
+}
+
+pub struct MutationRoot<T, L>(std::marker::PhantomData<(T, L)>);
+
+#[async_graphql::Object(name = "Mutation")]
+impl<T: crate::repositories::todo::TodoRepository, L: crate::repositories::label::LabelRepository> MutationRoot<T, L> {
+    #[graphql(guard = "ScopeGuard(crate::auth::TODOS_WRITE)")]
+    async fn create_todo(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        input: crate::repositories::todo::CreateTodo,
+    ) -> async_graphql::Result<crate::repositories::todo::TodoEntity> {
+        let input = validated(input)?;
+        memberships(ctx)?
+            .check(input.workspace_id(), crate::workspaces::Permission::Edit)
+            .map_err(workspace_error)?;
+        let labels = ctx.data::<std::sync::Arc<L>>()?.all().await.map_err(repository_error)?;
+        crate::workspaces::check_labels(&labels, input.labels(), input.workspace_id()).map_err(workspace_error)?;
+        ctx.data::<std::sync::Arc<T>>()?.create(input).await.map_err(repository_error)
+    }
+
+    #[graphql(guard = "ScopeGuard(crate::auth::TODOS_WRITE)")]
+    async fn update_todo(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        id: i32,
+        input: UpdateTodoInput,
+    ) -> async_graphql::Result<crate::repositories::todo::TodoEntity> {
+        let input = validated(crate::repositories::todo::UpdateTodo::from(input))?;
+        let todo = authorized_todo::<T>(ctx, id, crate::workspaces::Permission::Edit).await?;
+        if let Some(ids) = input.labels() {
+            let labels = ctx.data::<std::sync::Arc<L>>()?.all().await.map_err(repository_error)?;
+            crate::workspaces::check_labels(&labels, ids, todo.workspace_id).map_err(workspace_error)?;
+        }
+        ctx.data::<std::sync::Arc<T>>()?.update(id, input).await.map_err(repository_error)
+    }
+
+    #[graphql(guard = "ScopeGuard(crate::auth::TODOS_WRITE)")]
+    async fn move_todo(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        id: i32,
+        input: crate::repositories::todo::MoveTodo,
+    ) -> async_graphql::Result<crate::repositories::todo::TodoEntity> {
+        let input = validated(input)?;
+        authorized_todo::<T>(ctx, id, crate::workspaces::Permission::Edit).await?;
+        if let Some(target_id) = input.target() {
+            authorized_todo::<T>(ctx, target_id, crate::workspaces::Permission::View).await?;
+        }
+        ctx.data::<std::sync::Arc<T>>()?.move_to(id, input).await.map_err(repository_error)
+    }
+
+    #[graphql(guard = "ScopeGuard(crate::auth::TODOS_WRITE)")]
+    async fn add_blocker(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        id: i32,
+        blocker_id: i32,
+    ) -> async_graphql::Result<crate::repositories::todo::TodoEntity> {
+        authorized_todo::<T>(ctx, id, crate::workspaces::Permission::Edit).await?;
+        authorized_todo::<T>(ctx, blocker_id, crate::workspaces::Permission::View).await?;
+        ctx.data::<std::sync::Arc<T>>()?.add_blocker(id, blocker_id).await.map_err(repository_error)
+    }
+
+    #[graphql(guard = "ScopeGuard(crate::auth::TODOS_WRITE)")]
+    async fn remove_blocker(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        id: i32,
+        blocker_id: i32,
+    ) -> async_graphql::Result<crate::repositories::todo::TodoEntity> {
+        authorized_todo::<T>(ctx, id, crate::workspaces::Permission::Edit).await?;
+        ctx.data::<std::sync::Arc<T>>()?.remove_blocker(id, blocker_id).await.map_err(repository_error)
+    }
+
+    #[graphql(guard = "ScopeGuard(crate::auth::TODOS_WRITE)")]
+    async fn delete_todo(&self, ctx: &async_graphql::Context<'_>, id: i32) -> async_graphql::Result<bool> {
+        authorized_todo::<T>(ctx, id, crate::workspaces::Permission::Edit).await?;
+        ctx.data::<std::sync::Arc<T>>()?.delete(id).await.map_err(repository_error)?;
+        Ok(true)
+    }
+
+    #[graphql(guard = "ScopeGuard(crate::auth::LABELS_WRITE)")]
+    async fn create_label(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        input: crate::handlers::label::CreateLabel,
+    ) -> async_graphql::Result<crate::repositories::label::Label> {
+        let input = validated(input)?;
+        memberships(ctx)?
+            .check(input.workspace_id, crate::workspaces::Permission::Edit)
+            .map_err(workspace_error)?;
+        ctx.data::<std::sync::Arc<L>>()?.create(input.name, input.workspace_id).await.map_err(repository_error)
+    }
+
+    #[graphql(guard = "ScopeGuard(crate::auth::LABELS_WRITE)")]
+    async fn delete_label(&self, ctx: &async_graphql::Context<'_>, id: i32) -> async_graphql::Result<bool> {
+        let label = ctx.data::<std::sync::Arc<L>>()?.find(id).await.map_err(repository_error)?;
+        memberships(ctx)?
+            .check(label.workspace_id, crate::workspaces::Permission::Edit)
+            .map_err(workspace_error)?;
+        ctx.data::<std::sync::Arc<L>>()?.delete(id).await.map_err(repository_error)?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn todo(id: i32) -> crate::repositories::todo::TodoEntity {
+        crate::repositories::todo::TodoEntity {
+            id,
+            text: format!("todo {}", id),
+            completed: false,
+            position: id as i64,
+            priority: None,
+            due: None,
+            created_at: chrono::Utc::now(),
+            completed_at: None,
+            workspace_id: None,
+            assignee_id: None,
+            blocked: false,
+            comment_count: 0,
+            labels: vec![],
+        }
+    }
+
+    #[test]
+    fn paginate_todos() {
+        let todos: Vec<_> = (1..=5).map(todo).collect();
+        let page = paginate(todos.clone(), 1, 2);
+        assert_eq!(vec![2, 3], page.nodes.iter().map(|todo| todo.id).collect::<Vec<_>>());
+        assert_eq!(5, page.total_count);
+        assert!(page.has_next_page);
+
+        let page = paginate(todos, 3, 10);
+        assert_eq!(vec![4, 5], page.nodes.iter().map(|todo| todo.id).collect::<Vec<_>>());
+        assert!(!page.has_next_page);
+    }
+
+    #[test]
+    fn schema_mirrors_repositories() {
+        let sdl = build_schema::<crate::repositories::todo::TodoRepositoryForDb, crate::repositories::label::LabelRepositoryForDb>().sdl();
+        for field in ["createTodo(input: CreateTodoInput!)", "moveTodo(", "addBlocker(", "deleteLabel(", "todoCount: Int!"] {
+            assert!(sdl.contains(field), "schema is missing {}", field);
+        }
+    }
+
+    #[tokio::test]
+    async fn nested_queries_are_limited() {
+        let schema = build_schema::<crate::repositories::todo::TodoRepositoryForDb, crate::repositories::label::LabelRepositoryForDb>();
+        let errors = |response: async_graphql::Response| response.errors.into_iter().map(|e| e.message).collect::<Vec<_>>();
+        let nested = |levels: usize| format!("{{ labels {}{{ id }}{} }}", "{ todos { labels ".repeat(levels), " } }".repeat(levels));
+
+        // one round trip is fine, it only fails for lack of repositories
+        assert!(!errors(schema.execute(nested(1)).await).iter().any(|message| message.starts_with("Query is")));
+        assert_eq!(vec!["Query is too complex."], errors(schema.execute(nested(3)).await));
+        let deep = format!("{{ __schema {{ queryType {{ fields {{ type {}{{ name }}{} }} }} }} }}", "{ ofType ".repeat(12), " }".repeat(12));
+        assert_eq!(vec!["Query is nested too deep."], errors(schema.execute(deep).await));
+    }
+
+    #[tokio::test]
+    async fn nested_labels_need_their_scope() {
+        use crate::repositories::label::LabelRepository;
+        use crate::repositories::todo::TodoRepository;
+        let label_repository = std::sync::Arc::new(crate::repositories::label::test_utils::LabelRepositoryForMemory::new());
+        let work = label_repository.create("work".to_string(), None).await.unwrap();
+        let todo_repository = std::sync::Arc::new(crate::repositories::todo::test_utils::TodoRepositoryForMemory::new(vec![work.clone()]));
+        todo_repository
+            .create(crate::repositories::todo::CreateTodo::new("ship it".to_string(), vec![work.id], None, None))
+            .await
+            .unwrap();
+        let schema = build_schema::<crate::repositories::todo::test_utils::TodoRepositoryForMemory, crate::repositories::label::test_utils::LabelRepositoryForMemory>();
+        let query = |scopes: &[&str]| {
+            let caller = crate::auth::Caller::Token { id: 1, user_id: None, scopes: scopes.iter().map(|scope| scope.to_string()).collect() };
+            request_data(
+                async_graphql::Request::new("{ todos { nodes { text labels { name } } } }"),
+                todo_repository.clone(),
+                label_repository.clone(),
+            )
+            .data(caller)
+            .data(crate::workspaces::Memberships::unrestricted())
+        };
+
+        let response = schema.execute(query(&[crate::auth::TODOS_READ])).await;
+        let codes: Vec<_> = response.errors.iter().map(|e| e.extensions.as_ref().and_then(|ext| ext.get("code")).cloned()).collect();
+        assert_eq!(vec![Some(async_graphql::Value::from("FORBIDDEN"))], codes);
+
+        let response = schema.execute(query(&[crate::auth::TODOS_READ, crate::auth::LABELS_READ])).await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            async_graphql::value!({ "todos": { "nodes": [{ "text": "ship it", "labels": [{ "name": "work" }] }] } }),
+            response.data
+        );
+    }
+
+    #[test]
+    fn last_owner_is_a_conflict() {
+        let error = repository_error(crate::repositories::RepositoryError::LastOwner(1).into());
+        let code = error.extensions.as_ref().and_then(|extensions| extensions.get("code")).cloned();
+        assert_eq!(Some(async_graphql::Value::from("CONFLICT")), code);
+    }
+
+    #[test]
+    fn null_clears_the_schedule() {
+        let input = UpdateTodoInput {
+            text: None,
+            completed: None,
+            labels: None,
+            priority: async_graphql::MaybeUndefined::Null,
+            due: async_graphql::MaybeUndefined::Undefined,
+        };
+        assert_eq!(
+            crate::repositories::todo::UpdateTodo::new(None, None, None).schedule(Some(None), None),
+            crate::repositories::todo::UpdateTodo::from(input)
+        );
+    }
+}
@@ -0,0 +1,787 @@
+//! tonic services for `proto/todo.proto`, served on their own port next to the HTTP API.
+//!
+//! Both services are generic over the repository traits, like the REST handlers. `WatchTodos`
+//! streams from [`crate::events::TodoEvents`], so it sees writes made over any transport.
+//!
+//! [`Authenticate`] resolves the `authorization` metadata (or the session cookie) of every call
+//! into a [`Caller`] the way `crate::auth::authenticate` does for HTTP, and every RPC checks the
+//! scope it needs and the caller's workspace roles, as the REST routes do.
+
+// tonic's service traits return `Status` by value, so the helpers feeding them do too
+#![allow(clippy::result_large_err)]
+
+pub mod proto {
+    tonic::include_proto!("todo.v1");
+}
+
+type WatchTodosStream = std::pin::Pin<Box<dyn futures::Stream<Item = Result<proto::TodoEvent, tonic::Status>> + Send>>;
+
+use crate::auth::Caller;
+use crate::workspaces::{Memberships, Permission};
+
+pub type Router<A, U, W> = tonic::transport::server::Router<tower::layer::util::Stack<Authenticate<A, U, W>, tower::layer::util::Identity>>;
+
+/// Builds the gRPC server; callers pick the listener with `serve` or `serve_with_incoming`.
+pub fn router<
+    T: crate::repositories::todo::TodoRepository,
+    L: crate::repositories::label::LabelRepository,
+    A: crate::repositories::token::ApiTokenRepository,
+    U: crate::repositories::user::UserRepository,
+    W: crate::repositories::workspace::WorkspaceRepository,
+>(
+    todo_repository: T,
+    label_repository: L,
+    events: crate::events::TodoEvents,
+    authenticate: Authenticate<A, U, W>,
+) -> Router<A, U, W> {
+    let label_repository = std::sync::Arc::new(label_repository);
+    tonic::transport::Server::builder()
+        .layer(authenticate)
+        .add_service(proto::todo_service_server::TodoServiceServer::new(TodoGrpcService {
+            repository: std::sync::Arc::new(todo_repository),
+            label_repository: label_repository.clone(),
+            events,
+        }))
+        .add_service(proto::label_service_server::LabelServiceServer::new(LabelGrpcService {
+            repository: label_repository,
+        }))
+}
+
+/// Layer putting the [`Caller`] of every call and its [`Memberships`] into the request
+/// extensions, where [`authorize`] finds them.
+pub struct Authenticate<A, U, W> {
+    config: crate::auth::AuthConfig,
+    tokens: std::sync::Arc<A>,
+    users: std::sync::Arc<U>,
+    workspaces: std::sync::Arc<W>,
+}
+
+impl<A, U, W> Authenticate<A, U, W> {
+    pub fn new(config: crate::auth::AuthConfig, tokens: A, users: U, workspaces: W) -> Self {
+        Self {
+            config,
+            tokens: std::sync::Arc::new(tokens),
+            users: std::sync::Arc::new(users),
+            workspaces: std::sync::Arc::new(workspaces),
+        }
+    }
+}
+
+impl<A, U, W> Clone for Authenticate<A, U, W> {
+    fn clone(&self) -> Self {
+        Self {
+            config: self.config.clone(),
+            tokens: self.tokens.clone(),
+            users: self.users.clone(),
+            workspaces: self.workspaces.clone(),
+        }
+    }
+}
+
+impl<
+        A: crate::repositories::token::ApiTokenRepository,
+        U: crate::repositories::user::UserRepository,
+        W: crate::repositories::workspace::WorkspaceRepository,
+    > Authenticate<A, U, W>
+{
+    async fn identify(&self, headers: &axum::http::HeaderMap) -> anyhow::Result<(Caller, Memberships)> {
+        // no browser can make a gRPC call on another site's behalf, so the session cookie needs
+        // no CSRF token here
+        let session = crate::auth::cookie(headers, crate::auth::SESSION_COOKIE);
+        let bearer = crate::auth::bearer_token(headers);
+        let caller = crate::auth::resolve_caller(&self.config, self.tokens.as_ref(), self.users.as_ref(), bearer, session).await?;
+        let memberships = crate::workspaces::memberships(self.workspaces.as_ref(), &caller).await?;
+        Ok((caller, memberships))
+    }
+}
+
+impl<S, A, U, W> tower::Layer<S> for Authenticate<A, U, W> {
+    type Service = AuthenticateService<S, A, U, W>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AuthenticateService { inner, authenticate: self.clone() }
+    }
+}
+
+#[derive(Clone)]
+pub struct AuthenticateService<S, A, U, W> {
+    inner: S,
+    authenticate: Authenticate<A, U, W>,
+}
+
+impl<S, A, U, W> tower::Service<axum::http::Request<tonic::transport::Body>> for AuthenticateService<S, A, U, W>
+where
+    S: tower::Service<axum::http::Request<tonic::transport::Body>, Response = axum::http::Response<tonic::body::BoxBody>>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send,
+    A: crate::repositories::token::ApiTokenRepository,
+    U: crate::repositories::user::UserRepository,
+    W: crate::repositories::workspace::WorkspaceRepository,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = futures::future::BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut std::task::Context<'_>) -> std::task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: axum::http::Request<tonic::transport::Body>) -> Self::Future {
+        // call the instance that was polled ready, leaving a fresh clone for the next call
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let authenticate = self.authenticate.clone();
+        Box::pin(async move {
+            match authenticate.identify(request.headers()).await {
+                Ok((caller, memberships)) => {
+                    request.extensions_mut().insert(caller);
+                    request.extensions_mut().insert(memberships);
+                    inner.call(request).await
+                }
+                Err(e) => {
+                    tracing::error!("failed to authenticate grpc call: {}", e);
+                    Ok(tonic::Status::internal("Internal Server Error").to_http())
+                }
+            }
+        })
+    }
+}
+
+/// Checks that the caller holds `scope`, returning its workspace roles.
+fn authorize<M>(request: &tonic::Request<M>, scope: &str) -> Result<Memberships, tonic::Status> {
+    let caller = request.extensions().get::<Caller>().unwrap_or(&Caller::Unauthenticated);
+    caller.check(scope).map_err(|e| match e {
+        crate::auth::AuthError::Unauthenticated => tonic::Status::unauthenticated(e.to_string()),
+        crate::auth::AuthError::MissingScope(_) => tonic::Status::permission_denied(e.to_string()),
+    })?;
+    Ok(request.extensions().get::<Memberships>().cloned().unwrap_or_else(|| Memberships::new(Default::default())))
+}
+
+/// Same mapping as `crate::workspaces::concealed_response`: non-members are told the todo or
+/// label does not exist.
+fn workspace_status(error: crate::workspaces::WorkspaceError) -> tonic::Status {
+    match error {
+        crate::workspaces::WorkspaceError::NotFound(_) => tonic::Status::not_found("Not Found"),
+        e @ crate::workspaces::WorkspaceError::ForeignLabel(_) => tonic::Status::invalid_argument(e.to_string()),
+        e => tonic::Status::permission_denied(e.to_string()),
+    }
+}
+
+/// Same mapping as `repository_error_response`, in gRPC status codes.
+fn status(error: anyhow::Error) -> tonic::Status {
+    match error.downcast_ref::<crate::repositories::RepositoryError>() {
+        Some(e @ crate::repositories::RepositoryError::NotFound(_)) => tonic::Status::not_found(e.to_string()),
+        Some(e @ crate::repositories::RepositoryError::Duplicate(_)) => tonic::Status::already_exists(e.to_string()),
+        Some(e @ (crate::repositories::RepositoryError::Blocked(_) | crate::repositories::RepositoryError::Cycle(_))) => {
+            tonic::Status::failed_precondition(e.to_string())
+        }
+        _ => {
+            tracing::error!("grpc call failed: {}", error);
+            tonic::Status::internal("Internal Server Error")
+        }
+    }
+}
+
+fn validated<P: validator::Validate>(payload: P) -> Result<P, tonic::Status> {
+    payload
+        .validate()
+        .map_err(|e| tonic::Status::invalid_argument(format!("validation error: {}", e).replace('\n', ", ")))?;
+    Ok(payload)
+}
+
+/// Optional strings of an update: absent keeps the todo's value, empty clears it.
+fn cleared(value: Option<String>) -> Option<Option<String>> {
+    value.map(|value| Some(value).filter(|value| !value.is_empty()))
+}
+
+fn parse_due(due: Option<String>) -> Result<Option<chrono::NaiveDate>, tonic::Status> {
+    due.map(|due| {
+        chrono::NaiveDate::parse_from_str(&due, "%Y-%m-%d")
+            .map_err(|_| tonic::Status::invalid_argument(format!("due is not a YYYY-MM-DD date: {}", due)))
+    })
+    .transpose()
+}
+
+fn timestamp(time: chrono::DateTime<chrono::Utc>) -> prost_types::Timestamp {
+    prost_types::Timestamp {
+        seconds: time.timestamp(),
+        nanos: time.timestamp_subsec_nanos() as i32,
+    }
+}
+
+fn todo_filter(filter: Option<proto::TodoFilter>) -> crate::repositories::todo::TodoFilter {
+    let filter = filter.unwrap_or_default();
+    crate::repositories::todo::TodoFilter {
+        label: filter.label,
+        completed: filter.completed,
+        workspace: None,
+        assignee: None,
+    }
+}
+
+impl From<crate::repositories::label::Label> for proto::Label {
+    fn from(label: crate::repositories::label::Label) -> Self {
+        Self { id: label.id, name: label.name, workspace_id: label.workspace_id }
+    }
+}
+
+impl From<crate::repositories::todo::TodoEntity> for proto::Todo {
+    fn from(todo: crate::repositories::todo::TodoEntity) -> Self {
+        Self {
+            id: todo.id,
+            text: todo.text,
+            completed: todo.completed,
+            position: todo.position,
+            priority: todo.priority,
+            due: todo.due.map(|due| due.format("%Y-%m-%d").to_string()),
+            created_at: Some(timestamp(todo.created_at)),
+            completed_at: todo.completed_at.map(timestamp),
+            blocked: todo.blocked,
+            comment_count: todo.comment_count,
+            labels: todo.labels.into_iter().map(proto::Label::from).collect(),
+            workspace_id: todo.workspace_id,
+        }
+    }
+}
+
+impl From<crate::events::TodoEvent> for proto::TodoEvent {
+    fn from(event: crate::events::TodoEvent) -> Self {
+        let event = match event {
+            crate::events::TodoEvent::Created(todo) => proto::todo_event::Event::Created(todo.into()),
+            crate::events::TodoEvent::Updated(todo) => proto::todo_event::Event::Updated(todo.into()),
+            crate::events::TodoEvent::Deleted { id, .. } => proto::todo_event::Event::Deleted(id),
+        };
+        Self { event: Some(event) }
+    }
+}
+
+pub struct TodoGrpcService<T, L> {
+    repository: std::sync::Arc<T>,
+    label_repository: std::sync::Arc<L>,
+    events: crate::events::TodoEvents,
+}
+
+impl<T: crate::repositories::todo::TodoRepository, L> TodoGrpcService<T, L> {
+    /// Checks the caller's role in the workspace of the todo `id`, like `require_todo` does.
+    async fn check_todo(&self, memberships: &Memberships, id: i32, permission: Permission) -> Result<(), tonic::Status> {
+        let todo = self.repository.find(id).await.map_err(status)?;
+        memberships.check(todo.workspace_id, permission).map_err(workspace_status)
+    }
+}
+
+#[tonic::async_trait]
+impl<T: crate::repositories::todo::TodoRepository, L: crate::repositories::label::LabelRepository> proto::todo_service_server::TodoService
+    for TodoGrpcService<T, L>
+{
+    async fn create_todo(&self, request: tonic::Request<proto::CreateTodoRequest>) -> Result<tonic::Response<proto::Todo>, tonic::Status> {
+        let memberships = authorize(&request, crate::auth::TODOS_WRITE)?;
+        let request = request.into_inner();
+        let payload = validated(
+            crate::repositories::todo::CreateTodo::new(request.text, request.labels, request.priority, parse_due(request.due)?)
+                .in_workspace(request.workspace_id),
+        )?;
+        memberships.check(payload.workspace_id(), Permission::Edit).map_err(workspace_status)?;
+        let labels = self.label_repository.all().await.map_err(status)?;
+        crate::workspaces::check_labels(&labels, payload.labels(), payload.workspace_id()).map_err(workspace_status)?;
+        let todo = self.repository.create(payload).await.map_err(status)?;
+        Ok(tonic::Response::new(todo.into()))
+    }
+
+    async fn get_todo(&self, request: tonic::Request<proto::GetTodoRequest>) -> Result<tonic::Response<proto::Todo>, tonic::Status> {
+        let memberships = authorize(&request, crate::auth::TODOS_READ)?;
+        let todo = self.repository.find(request.into_inner().id).await.map_err(status)?;
+        memberships.check(todo.workspace_id, Permission::View).map_err(workspace_status)?;
+        Ok(tonic::Response::new(todo.into()))
+    }
+
+    async fn list_todos(
+        &self,
+        request: tonic::Request<proto::ListTodosRequest>,
+    ) -> Result<tonic::Response<proto::ListTodosResponse>, tonic::Status> {
+        let memberships = authorize(&request, crate::auth::TODOS_READ)?;
+        let filter = todo_filter(request.into_inner().filter);
+        let todos = self
+            .repository
+            .all()
+            .await
+            .map_err(status)?
+            .into_iter()
+            .filter(|todo| filter.matches(todo) && memberships.can_view(todo.workspace_id))
+            .map(proto::Todo::from)
+            .collect();
+        Ok(tonic::Response::new(proto::ListTodosResponse { todos }))
+    }
+
+    async fn update_todo(&self, request: tonic::Request<proto::UpdateTodoRequest>) -> Result<tonic::Response<proto::Todo>, tonic::Status> {
+        let memberships = authorize(&request, crate::auth::TODOS_WRITE)?;
+        let request = request.into_inner();
+        let payload = validated(
+            crate::repositories::todo::UpdateTodo::new(request.text, request.completed, request.labels.map(|labels| labels.ids))
+                .schedule(cleared(request.priority), cleared(request.due).map(parse_due).transpose()?),
+        )?;
+        let todo = self.repository.find(request.id).await.map_err(status)?;
+        memberships.check(todo.workspace_id, Permission::Edit).map_err(workspace_status)?;
+        if let Some(ids) = payload.labels() {
+            let labels = self.label_repository.all().await.map_err(status)?;
+            crate::workspaces::check_labels(&labels, ids, todo.workspace_id).map_err(workspace_status)?;
+        }
+        let todo = self.repository.update(request.id, payload).await.map_err(status)?;
+        Ok(tonic::Response::new(todo.into()))
+    }
+
+    async fn move_todo(&self, request: tonic::Request<proto::MoveTodoRequest>) -> Result<tonic::Response<proto::Todo>, tonic::Status> {
+        let memberships = authorize(&request, crate::auth::TODOS_WRITE)?;
+        let request = request.into_inner();
+        let payload = match request.target {
+            Some(proto::move_todo_request::Target::Before(before)) => crate::repositories::todo::MoveTodo::new(Some(before), None),
+            Some(proto::move_todo_request::Target::After(after)) => crate::repositories::todo::MoveTodo::new(None, Some(after)),
+            None => return Err(tonic::Status::invalid_argument("specify exactly one of before or after")),
+        };
+        self.check_todo(&memberships, request.id, Permission::Edit).await?;
+        // the target only has to be visible
+        if let Some(target_id) = payload.target() {
+            self.check_todo(&memberships, target_id, Permission::View).await?;
+        }
+        let todo = self.repository.move_to(request.id, payload).await.map_err(status)?;
+        Ok(tonic::Response::new(todo.into()))
+    }
+
+    async fn add_blocker(&self, request: tonic::Request<proto::BlockerRequest>) -> Result<tonic::Response<proto::Todo>, tonic::Status> {
+        let memberships = authorize(&request, crate::auth::TODOS_WRITE)?;
+        let request = request.into_inner();
+        self.check_todo(&memberships, request.id, Permission::Edit).await?;
+        // the blocker only has to be visible
+        self.check_todo(&memberships, request.blocker_id, Permission::View).await?;
+        let todo = self.repository.add_blocker(request.id, request.blocker_id).await.map_err(status)?;
+        Ok(tonic::Response::new(todo.into()))
+    }
+
+    async fn remove_blocker(&self, request: tonic::Request<proto::BlockerRequest>) -> Result<tonic::Response<proto::Todo>, tonic::Status> {
+        let memberships = authorize(&request, crate::auth::TODOS_WRITE)?;
+        let request = request.into_inner();
+        self.check_todo(&memberships, request.id, Permission::Edit).await?;
+        let todo = self.repository.remove_blocker(request.id, request.blocker_id).await.map_err(status)?;
+        Ok(tonic::Response::new(todo.into()))
+    }
+
+    async fn delete_todo(
+        &self,
+        request: tonic::Request<proto::DeleteTodoRequest>,
+    ) -> Result<tonic::Response<proto::DeleteTodoResponse>, tonic::Status> {
+        let memberships = authorize(&request, crate::auth::TODOS_WRITE)?;
+        let id = request.into_inner().id;
+        self.check_todo(&memberships, id, Permission::Edit).await?;
+        self.repository.delete(id).await.map_err(status)?;
+        Ok(tonic::Response::new(proto::DeleteTodoResponse {}))
+    }
+
+    type WatchTodosStream = WatchTodosStream;
+
+    async fn watch_todos(&self, request: tonic::Request<proto::WatchTodosRequest>) -> Result<tonic::Response<Self::WatchTodosStream>, tonic::Status> {
+        let memberships = authorize(&request, crate::auth::TODOS_READ)?;
+        let filter = todo_filter(request.into_inner().filter);
+        let (_, receiver) = self.events.subscribe();
+        let events = tokio_stream::wrappers::BroadcastStream::new(receiver);
+        let stream = futures::StreamExt::filter_map(events, move |event| {
+            let item = match event {
+                Ok((_, event)) if event.visible_to(&memberships) && event.matches(&filter) => Some(Ok(event.into())),
+                Ok(_) => None,
+                Err(tokio_stream::wrappers::errors::BroadcastStreamRecvError::Lagged(skipped)) => Some(Err(
+                    tonic::Status::resource_exhausted(format!("watcher fell behind by {} events; list todos again", skipped)),
+                )),
+            };
+            futures::future::ready(item)
+        });
+        Ok(tonic::Response::new(Box::pin(stream)))
+    }
+}
+
+pub struct LabelGrpcService<L> {
+    repository: std::sync::Arc<L>,
+}
+
+#[tonic::async_trait]
+impl<L: crate::repositories::label::LabelRepository> proto::label_service_server::LabelService for LabelGrpcService<L> {
+    async fn create_label(&self, request: tonic::Request<proto::CreateLabelRequest>) -> Result<tonic::Response<proto::Label>, tonic::Status> {
+        let memberships = authorize(&request, crate::auth::LABELS_WRITE)?;
+        let request = request.into_inner();
+        let payload = validated(crate::handlers::label::CreateLabel {
+            workspace_id: request.workspace_id,
+            ..crate::handlers::label::CreateLabel::new(request.name)
+        })?;
+        memberships.check(payload.workspace_id, Permission::Edit).map_err(workspace_status)?;
+        let label = self.repository.create(payload.name, payload.workspace_id).await.map_err(status)?;
+        Ok(tonic::Response::new(label.into()))
+    }
+
+    async fn list_labels(
+        &self,
+        request: tonic::Request<proto::ListLabelsRequest>,
+    ) -> Result<tonic::Response<proto::ListLabelsResponse>, tonic::Status> {
+        let memberships = authorize(&request, crate::auth::LABELS_READ)?;
+        let labels = self.repository.all().await.map_err(status)?;
+        Ok(tonic::Response::new(proto::ListLabelsResponse {
+            labels: labels
+                .into_iter()
+                .filter(|label| memberships.can_view(label.workspace_id))
+                .map(proto::Label::from)
+                .collect(),
+        }))
+    }
+
+    async fn delete_label(
+        &self,
+        request: tonic::Request<proto::DeleteLabelRequest>,
+    ) -> Result<tonic::Response<proto::DeleteLabelResponse>, tonic::Status> {
+        let memberships = authorize(&request, crate::auth::LABELS_WRITE)?;
+        let id = request.into_inner().id;
+        let label = self.repository.find(id).await.map_err(status)?;
+        memberships.check(label.workspace_id, Permission::Edit).map_err(workspace_status)?;
+        self.repository.delete(id).await.map_err(status)?;
+        Ok(tonic::Response::new(proto::DeleteLabelResponse {}))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::label::LabelRepository;
+
+    struct Clients {
+        todos: proto::todo_service_client::TodoServiceClient<tonic::transport::Channel>,
+        labels: proto::label_service_client::LabelServiceClient<tonic::transport::Channel>,
+    }
+
+    struct Server {
+        addr: std::net::SocketAddr,
+        todos: crate::events::TodoRepositoryWithEvents<crate::repositories::todo::test_utils::TodoRepositoryForMemory>,
+        tokens: crate::repositories::token::test_utils::ApiTokenRepositoryForMemory,
+        workspaces: crate::repositories::workspace::test_utils::WorkspaceRepositoryForMemory,
+    }
+
+    /// Serves in-memory repositories on an ephemeral port.
+    async fn serve(config: crate::auth::AuthConfig) -> Server {
+        let label_repository = crate::repositories::label::test_utils::LabelRepositoryForMemory::new();
+        let work = label_repository.create("work".to_string(), None).await.unwrap();
+        let events = crate::events::TodoEvents::new(crate::events::EVENT_CAPACITY);
+        let todo_repository = crate::events::TodoRepositoryWithEvents::new(
+            crate::repositories::todo::test_utils::TodoRepositoryForMemory::new(vec![work]),
+            events.clone(),
+        );
+        let tokens = crate::repositories::token::test_utils::ApiTokenRepositoryForMemory::new();
+        let workspaces = crate::repositories::workspace::test_utils::WorkspaceRepositoryForMemory::new();
+        let authenticate = Authenticate::new(
+            config,
+            tokens.clone(),
+            crate::repositories::user::test_utils::UserRepositoryForMemory::new(),
+            workspaces.clone(),
+        );
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            router(todo_repository.clone(), label_repository, events, authenticate)
+                .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener)),
+        );
+        Server { addr, todos: todo_repository, tokens, workspaces }
+    }
+
+    async fn connect(addr: std::net::SocketAddr) -> Clients {
+        let channel = tonic::transport::Endpoint::from_shared(format!("http://{}", addr))
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        Clients {
+            todos: proto::todo_service_client::TodoServiceClient::new(channel.clone()),
+            labels: proto::label_service_client::LabelServiceClient::new(channel),
+        }
+    }
+
+    /// Open access, as when `AUTH_REQUIRED` is off.
+    async fn start() -> Clients {
+        connect(serve(crate::auth::AuthConfig::default()).await.addr).await
+    }
+
+    fn authorized<M>(message: M, token: &str) -> tonic::Request<M> {
+        let mut request = tonic::Request::new(message);
+        request
+            .metadata_mut()
+            .insert("authorization", format!("Bearer {}", token).parse().unwrap());
+        request
+    }
+
+    fn create_request(text: &str) -> proto::CreateTodoRequest {
+        proto::CreateTodoRequest {
+            text: text.to_string(),
+            labels: vec![1],
+            priority: Some("A".to_string()),
+            due: Some("2026-10-20".to_string()),
+            workspace_id: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn todo_crud_scenario() {
+        let mut clients = start().await;
+
+        let created = clients.todos.create_todo(create_request("write report")).await.unwrap().into_inner();
+        assert_eq!("write report", created.text);
+        assert_eq!(vec![proto::Label { id: 1, name: "work".to_string(), workspace_id: None }], created.labels);
+        assert_eq!(Some("2026-10-20".to_string()), created.due);
+
+        let second = clients.todos.create_todo(create_request("review report")).await.unwrap().into_inner();
+        let moved = clients
+            .todos
+            .move_todo(proto::MoveTodoRequest {
+                id: created.id,
+                target: Some(proto::move_todo_request::Target::Before(second.id)),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(moved.position < second.position);
+
+        let blocked = clients
+            .todos
+            .add_blocker(proto::BlockerRequest { id: created.id, blocker_id: second.id })
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(blocked.blocked);
+        let refused = clients
+            .todos
+            .update_todo(proto::UpdateTodoRequest { id: created.id, completed: Some(true), ..Default::default() })
+            .await
+            .unwrap_err();
+        assert_eq!(tonic::Code::FailedPrecondition, refused.code());
+        let cycle = clients
+            .todos
+            .add_blocker(proto::BlockerRequest { id: second.id, blocker_id: created.id })
+            .await
+            .unwrap_err();
+        assert_eq!(tonic::Code::FailedPrecondition, cycle.code());
+
+        let updated = clients
+            .todos
+            .update_todo(proto::UpdateTodoRequest {
+                id: second.id,
+                completed: Some(true),
+                labels: Some(proto::LabelIds { ids: vec![] }),
+                priority: Some(String::new()),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(updated.completed);
+        assert_eq!(None, updated.priority);
+        assert_eq!(Some("2026-10-20".to_string()), updated.due);
+        assert!(updated.completed_at.is_some());
+        assert!(updated.labels.is_empty());
+
+        let open = clients
+            .todos
+            .list_todos(proto::ListTodosRequest {
+                filter: Some(proto::TodoFilter { label: None, completed: Some(false) }),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(vec![created.id], open.todos.iter().map(|todo| todo.id).collect::<Vec<_>>());
+        assert!(!open.todos[0].blocked);
+
+        clients.todos.delete_todo(proto::DeleteTodoRequest { id: created.id }).await.unwrap();
+        let missing = clients.todos.get_todo(proto::GetTodoRequest { id: created.id }).await.unwrap_err();
+        assert_eq!(tonic::Code::NotFound, missing.code());
+    }
+
+    #[tokio::test]
+    async fn invalid_payloads_are_rejected() {
+        let mut clients = start().await;
+        let empty = clients.todos.create_todo(create_request("")).await.unwrap_err();
+        assert_eq!(tonic::Code::InvalidArgument, empty.code());
+        let bad_due = clients
+            .todos
+            .create_todo(proto::CreateTodoRequest { due: Some("tomorrow".to_string()), ..create_request("x") })
+            .await
+            .unwrap_err();
+        assert_eq!(tonic::Code::InvalidArgument, bad_due.code());
+        let no_target = clients
+            .todos
+            .move_todo(proto::MoveTodoRequest { id: 1, target: None })
+            .await
+            .unwrap_err();
+        assert_eq!(tonic::Code::InvalidArgument, no_target.code());
+    }
+
+    #[tokio::test]
+    async fn label_scenario() {
+        let mut clients = start().await;
+        let label = clients
+            .labels
+            .create_label(proto::CreateLabelRequest { name: "home".to_string(), workspace_id: None })
+            .await
+            .unwrap()
+            .into_inner();
+        let duplicate = clients
+            .labels
+            .create_label(proto::CreateLabelRequest { name: "home".to_string(), workspace_id: None })
+            .await
+            .unwrap_err();
+        assert_eq!(tonic::Code::AlreadyExists, duplicate.code());
+
+        clients.labels.delete_label(proto::DeleteLabelRequest { id: label.id }).await.unwrap();
+        let labels = clients.labels.list_labels(proto::ListLabelsRequest {}).await.unwrap().into_inner();
+        assert_eq!(vec!["work".to_string()], labels.labels.into_iter().map(|label| label.name).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn watch_todos_streams_matching_changes() {
+        let mut clients = start().await;
+        let mut stream = clients
+            .todos
+            .watch_todos(proto::WatchTodosRequest {
+                filter: Some(proto::TodoFilter { label: Some(1), completed: None }),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+
+        let unlabelled = proto::CreateTodoRequest { labels: vec![], ..create_request("not watched") };
+        clients.todos.create_todo(unlabelled).await.unwrap();
+        let created = clients.todos.create_todo(create_request("watched")).await.unwrap().into_inner();
+        clients
+            .todos
+            .update_todo(proto::UpdateTodoRequest { id: created.id, text: Some("renamed".to_string()), ..Default::default() })
+            .await
+            .unwrap();
+        clients.todos.delete_todo(proto::DeleteTodoRequest { id: created.id }).await.unwrap();
+
+        let mut events = vec![];
+        for _ in 0..3 {
+            let event = tokio::time::timeout(std::time::Duration::from_secs(5), stream.message())
+                .await
+                .unwrap()
+                .unwrap()
+                .unwrap();
+            events.push(event.event.unwrap());
+        }
+        match &events[..] {
+            [proto::todo_event::Event::Created(first), proto::todo_event::Event::Updated(second), proto::todo_event::Event::Deleted(id)] => {
+                assert_eq!("watched", first.text);
+                assert_eq!("renamed", second.text);
+                assert_eq!(created.id, *id);
+            }
+            events => panic!("unexpected events: {:?}", events),
+        }
+    }
+
+    #[tokio::test]
+    async fn calls_are_authenticated_and_kept_to_workspaces() {
+        use crate::repositories::token::ApiTokenRepository;
+        use crate::repositories::todo::TodoRepository;
+        use crate::repositories::workspace::WorkspaceRepository;
+
+        let server = serve(crate::auth::AuthConfig::new(true, None)).await;
+        let mut clients = connect(server.addr).await;
+        let anonymous = clients.todos.list_todos(proto::ListTodosRequest::default()).await.unwrap_err();
+        assert_eq!(tonic::Code::Unauthenticated, anonymous.code());
+
+        let token = |scopes: &[&str], user_id| {
+            let payload = crate::repositories::token::CreateApiToken::new(
+                "grpc".to_string(),
+                scopes.iter().map(|scope| scope.to_string()).collect(),
+                None,
+            );
+            let tokens = server.tokens.clone();
+            async move { tokens.create(payload, Some(user_id)).await.unwrap().token }
+        };
+        let member = token(&[crate::auth::TODOS_READ, crate::auth::TODOS_WRITE], 1).await;
+        let outsider = token(&[crate::auth::TODOS_READ, crate::auth::TODOS_WRITE], 2).await;
+        let reader = token(&[crate::auth::TODOS_READ], 2).await;
+
+        let refused = clients
+            .todos
+            .create_todo(authorized(create_request("read only"), &reader))
+            .await
+            .unwrap_err();
+        assert_eq!(tonic::Code::PermissionDenied, refused.code());
+
+        let mut stream = clients
+            .todos
+            .watch_todos(authorized(proto::WatchTodosRequest::default(), &outsider))
+            .await
+            .unwrap()
+            .into_inner();
+        let workspace = server.workspaces.create("team".to_string(), 1).await.unwrap();
+        let secret = server
+            .todos
+            .create(crate::repositories::todo::CreateTodo::new("secret".to_string(), vec![], None, None).in_workspace(Some(workspace.id)))
+            .await
+            .unwrap();
+
+        let hidden = clients
+            .todos
+            .get_todo(authorized(proto::GetTodoRequest { id: secret.id }, &outsider))
+            .await
+            .unwrap_err();
+        assert_eq!(tonic::Code::NotFound, hidden.code());
+        let undeletable = clients
+            .todos
+            .delete_todo(authorized(proto::DeleteTodoRequest { id: secret.id }, &outsider))
+            .await
+            .unwrap_err();
+        assert_eq!(tonic::Code::NotFound, undeletable.code());
+        let listed = clients
+            .todos
+            .list_todos(authorized(proto::ListTodosRequest::default(), &outsider))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(listed.todos.is_empty());
+
+        let found = clients
+            .todos
+            .get_todo(authorized(proto::GetTodoRequest { id: secret.id }, &member))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!("secret", found.text);
+        clients
+            .todos
+            .delete_todo(authorized(proto::DeleteTodoRequest { id: secret.id }, &member))
+            .await
+            .unwrap();
+
+        // the watcher saw neither the creation nor the deletion of the workspace todo
+        let visible = proto::CreateTodoRequest { labels: vec![], ..create_request("visible") };
+        let visible = clients.todos.create_todo(authorized(visible, &outsider)).await.unwrap().into_inner();
+        let event = tokio::time::timeout(std::time::Duration::from_secs(5), stream.message())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(Some(proto::todo_event::Event::Created(visible)), event.event);
+
+        // callers choose the workspace of what they create, among those they can edit
+        let shared = proto::CreateTodoRequest { labels: vec![], workspace_id: Some(workspace.id), ..create_request("shared") };
+        let refused = clients.todos.create_todo(authorized(shared.clone(), &outsider)).await.unwrap_err();
+        assert_eq!(tonic::Code::NotFound, refused.code());
+        let created = clients.todos.create_todo(authorized(shared, &member)).await.unwrap().into_inner();
+        assert_eq!(Some(workspace.id), created.workspace_id);
+        let foreign_label = proto::CreateTodoRequest { workspace_id: Some(workspace.id), ..create_request("labelled") };
+        let refused = clients.todos.create_todo(authorized(foreign_label, &member)).await.unwrap_err();
+        assert_eq!(tonic::Code::InvalidArgument, refused.code());
+
+        let label = proto::CreateLabelRequest { name: "team".to_string(), workspace_id: Some(workspace.id) };
+        let outsider = token(&[crate::auth::LABELS_WRITE], 2).await;
+        let refused = clients.labels.create_label(authorized(label.clone(), &outsider)).await.unwrap_err();
+        assert_eq!(tonic::Code::NotFound, refused.code());
+        let member = token(&[crate::auth::LABELS_WRITE], 1).await;
+        let created = clients.labels.create_label(authorized(label, &member)).await.unwrap().into_inner();
+        assert_eq!(Some(workspace.id), created.workspace_id);
+    }
+}
@@ -1,5 +1,33 @@
+pub mod attachment;
+pub mod auth;
+pub mod calendar;
+pub mod comment;
+pub mod graphql;
 pub mod label;
+pub mod metrics;
+pub mod openapi;
+pub mod share;
 pub mod todo;
+pub mod token;
+pub mod transfer;
+pub mod webhook;
+pub mod workspace;
+pub mod ws;
+
+/// Maps the repository errors a client can act on to their status, falling back to `status`.
+fn repository_error_response(error: anyhow::Error, status: axum::http::StatusCode) -> (axum::http::StatusCode, String) {
+    match error.downcast_ref::<crate::repositories::RepositoryError>() {
+        Some(e @ crate::repositories::RepositoryError::NotFound(_)) => (axum::http::StatusCode::NOT_FOUND, e.to_string()),
+        Some(
+            e @ (crate::repositories::RepositoryError::Blocked(_)
+            | crate::repositories::RepositoryError::Cycle(_)
+            | crate::repositories::RepositoryError::LastOwner(_)),
+        ) => {
+            (axum::http::StatusCode::CONFLICT, e.to_string())
+        }
+        _ => (status, status.canonical_reason().unwrap_or_default().to_string()),
+    }
+}
 
 #[derive(Debug)]
 pub struct ValidatedJson<T>(T);
@@ -20,7 +48,7 @@ where
         })?;
         value.validate().map_err(|rejection| {
             (axum::http::StatusCode::BAD_REQUEST, format!("validation error: {}", rejection).replace("\n", ", "))
-        });
+        })?;
         Ok(ValidatedJson(value))
     }
 }
@@ -0,0 +1,175 @@
+use super::*;
+
+/// Largest file accepted by `create_attachment`.
+const MAX_ATTACHMENT_BYTES: usize = 10 * 1024 * 1024;
+
+/// Body limit for uploads: the largest attachment plus room for the multipart framing.
+pub const UPLOAD_LIMIT_BYTES: usize = MAX_ATTACHMENT_BYTES + 64 * 1024;
+
+/// Content types safe to render in the browser rather than download.
+const INLINE_CONTENT_TYPES: [&str; 5] = ["image/png", "image/jpeg", "image/gif", "image/webp", "application/pdf"];
+
+#[utoipa::path(
+    post,
+    path = "/todos/{id}/attachments",
+    tag = "attachments",
+    params(("id" = i32, Path, description = "Todo id")),
+    request_body(content = Vec<u8>, description = "Multipart form with a `file` field", content_type = "multipart/form-data"),
+    responses(
+        (status = 201, description = "Attachment stored", body = crate::repositories::attachment::Attachment),
+        (status = 400, description = "Missing or malformed file field"),
+        (status = 404, description = "Todo not found"),
+        (status = 413, description = "File too large"),
+    ),
+)]
+pub async fn create_attachment<T: crate::repositories::attachment::AttachmentRepository>(
+    axum::extract::Path(todo_id): axum::extract::Path<i32>,
+    mut multipart: axum::extract::Multipart,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<T>>,
+    axum::extract::Extension(blob_store): axum::extract::Extension<std::sync::Arc<dyn crate::blob_store::BlobStore>>,
+) -> Result<impl axum::response::IntoResponse, (axum::http::StatusCode, String)> {
+    let multipart_error = |rejection: axum::extract::multipart::MultipartError| {
+        (axum::http::StatusCode::BAD_REQUEST, format!("Multipart error: {}", rejection))
+    };
+
+    while let Some(mut field) = multipart.next_field().await.map_err(multipart_error)? {
+        if field.name() != Some("file") {
+            continue;
+        }
+        let filename = field.file_name().unwrap_or("attachment").to_string();
+        let content_type = field
+            .content_type()
+            .map(|content_type| content_type.to_string())
+            .unwrap_or_else(|| mime::APPLICATION_OCTET_STREAM.to_string());
+
+        let mut data = bytes::BytesMut::new();
+        while let Some(chunk) = futures::StreamExt::next(&mut field).await {
+            let chunk = chunk.map_err(multipart_error)?;
+            if data.len() + chunk.len() > MAX_ATTACHMENT_BYTES {
+                return Err((
+                    axum::http::StatusCode::PAYLOAD_TOO_LARGE,
+                    format!("attachment exceeds {} bytes", MAX_ATTACHMENT_BYTES),
+                ));
+            }
+            data.extend_from_slice(&chunk);
+        }
+
+        let storage_key = crate::blob_store::new_blob_key(todo_id);
+        let size = data.len() as i64;
+        blob_store
+            .put(&storage_key, data.freeze())
+            .await
+            .map_err(|e| repository_error_response(e, axum::http::StatusCode::INTERNAL_SERVER_ERROR))?;
+        let attachment = repository
+            .create(
+                todo_id,
+                crate::repositories::attachment::CreateAttachment {
+                    filename,
+                    content_type,
+                    size,
+                    storage_key: storage_key.clone(),
+                },
+            )
+            .await;
+        return match attachment {
+            Ok(attachment) => Ok((axum::http::StatusCode::CREATED, axum::Json(attachment))),
+            Err(e) => {
+                if let Err(e) = blob_store.delete(&storage_key).await {
+                    tracing::warn!("failed to remove orphaned blob {}: {}", storage_key, e);
+                }
+                Err(repository_error_response(e, axum::http::StatusCode::INTERNAL_SERVER_ERROR))
+            }
+        };
+    }
+
+    Err((axum::http::StatusCode::BAD_REQUEST, "missing file field".to_string()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/todos/{id}/attachments",
+    tag = "attachments",
+    params(("id" = i32, Path, description = "Todo id")),
+    responses(
+        (status = 200, description = "Attachments on the todo", body = [crate::repositories::attachment::Attachment]),
+    ),
+)]
+pub async fn all_attachment<T: crate::repositories::attachment::AttachmentRepository>(
+    axum::extract::Path(todo_id): axum::extract::Path<i32>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<T>>,
+) -> Result<impl axum::response::IntoResponse, axum::http::StatusCode> {
+    let attachments = repository
+        .all(todo_id)
+        .await
+        .or(Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok((axum::http::StatusCode::OK, axum::Json(attachments)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/todos/{id}/attachments/{attachment_id}",
+    tag = "attachments",
+    params(("id" = i32, Path, description = "Todo id"), ("attachment_id" = i32, Path, description = "Attachment id")),
+    responses(
+        (status = 200, description = "Attachment content", body = Vec<u8>, content_type = "application/octet-stream"),
+        (status = 404, description = "Attachment not found"),
+    ),
+)]
+pub async fn find_attachment<T: crate::repositories::attachment::AttachmentRepository>(
+    axum::extract::Path((todo_id, id)): axum::extract::Path<(i32, i32)>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<T>>,
+    axum::extract::Extension(blob_store): axum::extract::Extension<std::sync::Arc<dyn crate::blob_store::BlobStore>>,
+) -> Result<impl axum::response::IntoResponse, axum::http::StatusCode> {
+    let attachment = repository
+        .find(todo_id, id)
+        .await
+        .or(Err(axum::http::StatusCode::NOT_FOUND))?;
+    let stream = blob_store
+        .get(&attachment.storage_key)
+        .await
+        .or(Err(axum::http::StatusCode::NOT_FOUND))?;
+
+    let disposition = if INLINE_CONTENT_TYPES.contains(&attachment.content_type.as_str()) {
+        "inline"
+    } else {
+        "attachment"
+    };
+    let filename: String = attachment
+        .filename
+        .chars()
+        .map(|c| if c == '"' || c == '\\' || c.is_control() { '_' } else { c })
+        .collect();
+    let headers = axum::response::Headers(vec![
+        (axum::http::header::CONTENT_TYPE, attachment.content_type),
+        (axum::http::header::CONTENT_LENGTH, attachment.size.to_string()),
+        (axum::http::header::CONTENT_DISPOSITION, format!("{}; filename=\"{}\"", disposition, filename)),
+        (axum::http::header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+    ]);
+    Ok((headers, axum::body::StreamBody::new(stream)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/todos/{id}/attachments/{attachment_id}",
+    tag = "attachments",
+    params(("id" = i32, Path, description = "Todo id"), ("attachment_id" = i32, Path, description = "Attachment id")),
+    responses(
+        (status = 204, description = "Attachment deleted"),
+        (status = 404, description = "Attachment not found"),
+    ),
+)]
+pub async fn delete_attachment<T: crate::repositories::attachment::AttachmentRepository>(
+    axum::extract::Path((todo_id, id)): axum::extract::Path<(i32, i32)>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<T>>,
+    axum::extract::Extension(blob_store): axum::extract::Extension<std::sync::Arc<dyn crate::blob_store::BlobStore>>,
+) -> axum::http::StatusCode {
+    match repository.delete(todo_id, id).await {
+        Ok(attachment) => {
+            if let Err(e) = blob_store.delete(&attachment.storage_key).await {
+                tracing::warn!("failed to remove blob {}: {}", attachment.storage_key, e);
+            }
+            axum::http::StatusCode::NO_CONTENT
+        }
+        Err(_) => axum::http::StatusCode::NOT_FOUND,
+    }
+}
@@ -0,0 +1,210 @@
+/// Remembers which browser started a login, so a callback can not be completed in another one.
+const STATE_COOKIE: &str = "oidc_state";
+
+#[derive(Debug, serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LoginQuery {
+    /// Path to return to once logged in, `/` when omitted.
+    redirect_to: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+/// Only paths on this server, so the login can not be used to send users elsewhere.
+fn local_path(redirect_to: Option<String>) -> String {
+    redirect_to
+        .filter(|path| path.starts_with('/') && !path.starts_with("//") && !path.starts_with("/\\"))
+        .unwrap_or("/".to_string())
+}
+
+fn set_cookie(name: &str, value: &str, path: &str, max_age: std::time::Duration, secure: bool) -> (axum::http::HeaderName, String) {
+    let cookie = format!("{}; HttpOnly", cookie_attributes(name, value, path, max_age, secure));
+    (axum::http::header::SET_COOKIE, cookie)
+}
+
+/// Unlike the session cookie, readable by scripts, which have to send its token back on writes.
+fn set_csrf_cookie(value: &str, max_age: std::time::Duration, secure: bool) -> (axum::http::HeaderName, String) {
+    let cookie = cookie_attributes(crate::security::CSRF_COOKIE, value, "/", max_age, secure);
+    (axum::http::header::SET_COOKIE, cookie)
+}
+
+fn cookie_attributes(name: &str, value: &str, path: &str, max_age: std::time::Duration, secure: bool) -> String {
+    let secure = if secure { "; Secure" } else { "" };
+    format!("{}={}; Path={}; Max-Age={}; SameSite=Lax{}", name, value, path, max_age.as_secs(), secure)
+}
+
+fn configured(client: Option<crate::oidc::OidcClient>) -> Result<crate::oidc::OidcClient, (axum::http::StatusCode, String)> {
+    client.ok_or((axum::http::StatusCode::NOT_FOUND, "login is not configured".to_string()))
+}
+
+fn oidc_error_response(error: crate::oidc::OidcError) -> (axum::http::StatusCode, String) {
+    tracing::warn!("login failed: {}", error);
+    match error {
+        crate::oidc::OidcError::Provider(_) => (axum::http::StatusCode::BAD_GATEWAY, "identity provider is unavailable".to_string()),
+        crate::oidc::OidcError::InvalidIdToken(_) => (axum::http::StatusCode::UNAUTHORIZED, error.to_string()),
+    }
+}
+
+fn internal_error(error: anyhow::Error) -> (axum::http::StatusCode, String) {
+    tracing::error!("login failed: {}", error);
+    (axum::http::StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error".to_string())
+}
+
+#[utoipa::path(
+    get,
+    path = "/auth/login",
+    tag = "auth",
+    params(LoginQuery),
+    responses(
+        (status = 303, description = "Redirect to the identity provider"),
+        (status = 404, description = "OpenID Connect is not configured"),
+        (status = 502, description = "The identity provider could not be reached"),
+    ),
+)]
+pub async fn login<U: crate::repositories::user::UserRepository>(
+    axum::extract::Query(query): axum::extract::Query<LoginQuery>,
+    axum::extract::Extension(client): axum::extract::Extension<Option<crate::oidc::OidcClient>>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<U>>,
+) -> Result<impl axum::response::IntoResponse, (axum::http::StatusCode, String)> {
+    let client = configured(client)?;
+    let state = crate::oidc::random_secret();
+    let login = crate::repositories::user::PendingLogin {
+        code_verifier: crate::oidc::random_secret(),
+        nonce: crate::oidc::random_secret(),
+        redirect_to: local_path(query.redirect_to),
+    };
+    let url = client
+        .authorization_url(&state, &login.nonce, &crate::oidc::pkce_challenge(&login.code_verifier))
+        .await
+        .map_err(oidc_error_response)?;
+    let ttl = chrono::Duration::from_std(crate::oidc::LOGIN_TTL).unwrap();
+    repository.begin_login(&state, login, ttl).await.map_err(internal_error)?;
+
+    let secure = client.config().secure_cookies();
+    let headers = axum::response::Headers(vec![
+        (axum::http::header::LOCATION, url),
+        set_cookie(STATE_COOKIE, &state, "/auth", crate::oidc::LOGIN_TTL, secure),
+    ]);
+    Ok((axum::http::StatusCode::SEE_OTHER, headers))
+}
+
+#[utoipa::path(
+    get,
+    path = "/auth/callback",
+    tag = "auth",
+    params(CallbackQuery),
+    responses(
+        (status = 303, description = "Logged in; redirect back with a session cookie"),
+        (status = 400, description = "Login was refused, expired, already completed or started in another browser"),
+        (status = 401, description = "The ID token did not validate"),
+        (status = 404, description = "OpenID Connect is not configured"),
+        (status = 502, description = "The identity provider could not be reached or refused the code"),
+    ),
+)]
+pub async fn callback<U: crate::repositories::user::UserRepository>(
+    axum::extract::Query(query): axum::extract::Query<CallbackQuery>,
+    headers: axum::http::HeaderMap,
+    axum::extract::Extension(client): axum::extract::Extension<Option<crate::oidc::OidcClient>>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<U>>,
+) -> Result<impl axum::response::IntoResponse, (axum::http::StatusCode, String)> {
+    let client = configured(client)?;
+    if let Some(error) = query.error {
+        let description = query.error_description.map(|description| format!(": {}", description)).unwrap_or_default();
+        return Err((axum::http::StatusCode::BAD_REQUEST, format!("login refused: {}{}", error, description)));
+    }
+    let (code, state) = match (query.code, query.state) {
+        (Some(code), Some(state)) => (code, state),
+        _ => return Err((axum::http::StatusCode::BAD_REQUEST, "missing code or state".to_string())),
+    };
+    if crate::auth::cookie(&headers, STATE_COOKIE) != Some(state.as_str()) {
+        return Err((axum::http::StatusCode::BAD_REQUEST, "login was started in another browser".to_string()));
+    }
+    let login = repository
+        .take_login(&state)
+        .await
+        .map_err(internal_error)?
+        .ok_or((axum::http::StatusCode::BAD_REQUEST, "login expired or already completed".to_string()))?;
+
+    let id_token = client.exchange(&code, &login.code_verifier).await.map_err(oidc_error_response)?;
+    let claims = client.validate(&id_token, &login.nonce).await.map_err(oidc_error_response)?;
+    let user = repository.upsert(client.identity(claims)).await.map_err(internal_error)?;
+    let ttl = chrono::Duration::from_std(crate::oidc::SESSION_TTL).unwrap();
+    let session = repository.create_session(user.id, ttl).await.map_err(internal_error)?;
+
+    let secure = client.config().secure_cookies();
+    let headers = axum::response::Headers(vec![
+        (axum::http::header::LOCATION, login.redirect_to),
+        set_cookie(crate::auth::SESSION_COOKIE, &session, "/", crate::oidc::SESSION_TTL, secure),
+        set_csrf_cookie(&crate::security::csrf_token(&session), crate::oidc::SESSION_TTL, secure),
+        set_cookie(STATE_COOKIE, "", "/auth", std::time::Duration::ZERO, secure),
+    ]);
+    Ok((axum::http::StatusCode::SEE_OTHER, headers))
+}
+
+#[utoipa::path(
+    get,
+    path = "/auth/me",
+    tag = "auth",
+    responses(
+        (status = 200, description = "The logged in user, with the CSRF token of the session in `x-csrf-token`", body = crate::repositories::user::User),
+        (status = 401, description = "Not logged in"),
+    ),
+)]
+pub async fn me<U: crate::repositories::user::UserRepository>(
+    headers: axum::http::HeaderMap,
+    axum::extract::Extension(caller): axum::extract::Extension<crate::auth::Caller>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<U>>,
+) -> Result<impl axum::response::IntoResponse, axum::response::Response> {
+    let id = match caller {
+        crate::auth::Caller::User { id } => id,
+        _ => return Err(axum::response::IntoResponse::into_response(crate::auth::AuthError::Unauthenticated)),
+    };
+    let user = repository
+        .find(id)
+        .await
+        .map_err(|_| axum::response::IntoResponse::into_response(axum::http::StatusCode::INTERNAL_SERVER_ERROR))?;
+    let csrf_token = crate::auth::cookie(&headers, crate::auth::SESSION_COOKIE)
+        .map(crate::security::csrf_token)
+        .unwrap_or_default();
+    let headers = axum::response::Headers(vec![(axum::http::HeaderName::from_static(crate::security::CSRF_HEADER), csrf_token)]);
+    Ok((axum::http::StatusCode::OK, headers, axum::Json(user)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/logout",
+    tag = "auth",
+    responses(
+        (status = 204, description = "Session ended and its cookie cleared"),
+        (status = 403, description = "Missing or invalid CSRF token"),
+    ),
+)]
+pub async fn logout<U: crate::repositories::user::UserRepository>(
+    headers: axum::http::HeaderMap,
+    axum::extract::Extension(client): axum::extract::Extension<Option<crate::oidc::OidcClient>>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<U>>,
+) -> Result<impl axum::response::IntoResponse, axum::http::StatusCode> {
+    if let Some(session) = crate::auth::cookie(&headers, crate::auth::SESSION_COOKIE) {
+        // another site could otherwise log the user out
+        if !crate::security::csrf_verified(&axum::http::Method::POST, &headers, session) {
+            return Err(axum::http::StatusCode::FORBIDDEN);
+        }
+        repository
+            .delete_session(session)
+            .await
+            .or(Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR))?;
+    }
+    let secure = client.is_some_and(|client| client.config().secure_cookies());
+    let headers = axum::response::Headers(vec![
+        set_cookie(crate::auth::SESSION_COOKIE, "", "/", std::time::Duration::ZERO, secure),
+        set_csrf_cookie("", std::time::Duration::ZERO, secure),
+    ]);
+    Ok((axum::http::StatusCode::NO_CONTENT, headers))
+}
@@ -0,0 +1,127 @@
+/// A user only sees and revokes their own calendar tokens; the admin token and the tokens it
+/// made see all.
+fn visible(caller: &crate::auth::Caller, calendar_token: &crate::repositories::calendar::CalendarToken) -> bool {
+    match caller.user_id() {
+        Some(user_id) => calendar_token.user_id == Some(user_id),
+        None => true,
+    }
+}
+
+#[derive(Debug, serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct FeedQuery {
+    token: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/calendar.ics",
+    tag = "calendar",
+    params(FeedQuery),
+    responses(
+        (status = 200, description = "Todos the token's owner can see, as an iCalendar feed", body = String, content_type = "text/calendar"),
+        (status = 404, description = "Unknown token"),
+    ),
+)]
+pub async fn calendar_feed<
+    T: crate::repositories::todo::TodoRepository,
+    C: crate::repositories::calendar::CalendarTokenRepository,
+    W: crate::repositories::workspace::WorkspaceRepository,
+>(
+    axum::extract::Query(query): axum::extract::Query<FeedQuery>,
+    axum::extract::Extension(todo_repository): axum::extract::Extension<std::sync::Arc<T>>,
+    axum::extract::Extension(calendar_repository): axum::extract::Extension<std::sync::Arc<C>>,
+    axum::extract::Extension(workspace_repository): axum::extract::Extension<std::sync::Arc<W>>,
+) -> Result<impl axum::response::IntoResponse, axum::http::StatusCode> {
+    let calendar_token = calendar_repository
+        .verify(&query.token)
+        .await
+        .or(Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR))?
+        .ok_or(axum::http::StatusCode::NOT_FOUND)?;
+    // the feed shows what its owner can see; tokens without one only get todos in no workspace
+    let memberships = match calendar_token.user_id {
+        Some(user_id) => crate::workspaces::Memberships::new(
+            workspace_repository
+                .roles(user_id)
+                .await
+                .or(Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR))?,
+        ),
+        None => crate::workspaces::Memberships::new(Default::default()),
+    };
+
+    let todos = futures::TryStreamExt::try_filter(todo_repository.stream(), move |todo| {
+        futures::future::ready(memberships.can_view(todo.workspace_id))
+    });
+    let records = futures::TryStreamExt::map_ok(todos, crate::transfer::TodoRecord::from);
+    let format = crate::transfer::TransferFormat::Ics;
+    let chunks = crate::transfer::encode(format, records);
+    let headers = axum::response::Headers(vec![(axum::http::header::CONTENT_TYPE, format.content_type())]);
+    Ok((headers, axum::body::StreamBody::new(chunks)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/calendar/tokens",
+    tag = "calendar",
+    responses(
+        (status = 201, description = "Token created; the secret is only returned here", body = crate::repositories::calendar::NewCalendarToken),
+    ),
+)]
+pub async fn create_calendar_token<C: crate::repositories::calendar::CalendarTokenRepository>(
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<C>>,
+    axum::extract::Extension(caller): axum::extract::Extension<crate::auth::Caller>,
+) -> Result<impl axum::response::IntoResponse, axum::http::StatusCode> {
+    let token = repository
+        .create(caller.user_id())
+        .await
+        .or(Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok((axum::http::StatusCode::CREATED, axum::Json(token)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/calendar/tokens",
+    tag = "calendar",
+    responses(
+        (status = 200, description = "All tokens", body = [crate::repositories::calendar::CalendarToken]),
+    ),
+)]
+pub async fn all_calendar_token<C: crate::repositories::calendar::CalendarTokenRepository>(
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<C>>,
+    axum::extract::Extension(caller): axum::extract::Extension<crate::auth::Caller>,
+) -> Result<impl axum::response::IntoResponse, axum::http::StatusCode> {
+    let tokens: Vec<_> = repository
+        .all()
+        .await
+        .or(Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR))?
+        .into_iter()
+        .filter(|calendar_token| visible(&caller, calendar_token))
+        .collect();
+    Ok((axum::http::StatusCode::OK, axum::Json(tokens)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/calendar/tokens/{id}",
+    tag = "calendar",
+    params(("id" = i32, Path, description = "Token id")),
+    responses(
+        (status = 204, description = "Token revoked"),
+        (status = 404, description = "Token not found"),
+    ),
+)]
+pub async fn delete_calendar_token<C: crate::repositories::calendar::CalendarTokenRepository>(
+    axum::extract::Path(id): axum::extract::Path<i32>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<C>>,
+    axum::extract::Extension(caller): axum::extract::Extension<crate::auth::Caller>,
+) -> axum::http::StatusCode {
+    match repository.find(id).await {
+        Ok(calendar_token) if visible(&caller, &calendar_token) => {}
+        _ => return axum::http::StatusCode::NOT_FOUND,
+    }
+    repository
+        .delete(id)
+        .await
+        .map(|_| axum::http::StatusCode::NO_CONTENT)
+        .unwrap_or(axum::http::StatusCode::NOT_FOUND)
+}
@@ -0,0 +1,210 @@
+use super::*;
+
+/// Who a new comment is by: the user behind the caller, by name or email, or else what the caller is.
+async fn author<U: crate::repositories::user::UserRepository>(caller: &crate::auth::Caller, user_repository: &U) -> anyhow::Result<String> {
+    if let Some(user_id) = caller.user_id() {
+        let user = user_repository.find(user_id).await?;
+        return Ok(user.name.or(user.email).unwrap_or_else(|| format!("user {}", user.id)));
+    }
+    Ok(match caller {
+        crate::auth::Caller::Admin => "admin".to_string(),
+        crate::auth::Caller::Token { id, .. } => format!("token {}", id),
+        _ => "anonymous".to_string(),
+    })
+}
+
+/// Whether `caller` may change or delete `comment` on a todo in `workspace_id`: its author, the
+/// workspace's owners and the admin token may.
+fn may_change(
+    comment: &crate::repositories::comment::Comment,
+    caller: &crate::auth::Caller,
+    memberships: &crate::workspaces::Memberships,
+    workspace_id: Option<i32>,
+) -> bool {
+    let owner = workspace_id.is_some() && memberships.check(workspace_id, crate::workspaces::Permission::Manage).is_ok();
+    *caller == crate::auth::Caller::Admin || (caller.user_id().is_some() && caller.user_id() == comment.author_user_id) || owner
+}
+
+/// Finds comment `id` and checks that `caller` may change it.
+async fn changeable<T: crate::repositories::comment::CommentRepository, Todo: crate::repositories::todo::TodoRepository>(
+    repository: &T,
+    todo_repository: &Todo,
+    caller: &crate::auth::Caller,
+    memberships: &crate::workspaces::Memberships,
+    todo_id: i32,
+    id: i32,
+) -> Result<(), (axum::http::StatusCode, String)> {
+    let comment = repository
+        .find(todo_id, id)
+        .await
+        .map_err(|e| repository_error_response(e, axum::http::StatusCode::INTERNAL_SERVER_ERROR))?;
+    let todo = todo_repository
+        .find(todo_id)
+        .await
+        .map_err(|e| repository_error_response(e, axum::http::StatusCode::INTERNAL_SERVER_ERROR))?;
+    if !may_change(&comment, caller, memberships, todo.workspace_id) {
+        return Err((axum::http::StatusCode::FORBIDDEN, "only the author or a workspace owner can change a comment".to_string()));
+    }
+    Ok(())
+}
+
+#[utoipa::path(
+    post,
+    path = "/todos/{id}/comments",
+    tag = "comments",
+    params(("id" = i32, Path, description = "Todo id")),
+    request_body = crate::repositories::comment::CreateComment,
+    responses(
+        (status = 201, description = "Comment created, by the caller", body = crate::repositories::comment::Comment),
+        (status = 400, description = "Invalid payload"),
+        (status = 404, description = "Todo not found"),
+    ),
+)]
+pub async fn create_comment<T: crate::repositories::comment::CommentRepository, U: crate::repositories::user::UserRepository>(
+    axum::extract::Path(todo_id): axum::extract::Path<i32>,
+    ValidatedJson(payload): ValidatedJson<crate::repositories::comment::CreateComment>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<T>>,
+    axum::extract::Extension(user_repository): axum::extract::Extension<std::sync::Arc<U>>,
+    axum::extract::Extension(caller): axum::extract::Extension<crate::auth::Caller>,
+) -> Result<impl axum::response::IntoResponse, (axum::http::StatusCode, String)> {
+    let author = author(&caller, user_repository.as_ref())
+        .await
+        .map_err(|e| repository_error_response(e, axum::http::StatusCode::INTERNAL_SERVER_ERROR))?;
+    let comment = repository
+        .create(todo_id, payload, author, caller.user_id())
+        .await
+        .map_err(|e| repository_error_response(e, axum::http::StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok((axum::http::StatusCode::CREATED, axum::Json(comment)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/todos/{id}/comments",
+    tag = "comments",
+    params(("id" = i32, Path, description = "Todo id")),
+    responses(
+        (status = 200, description = "Comments on the todo", body = [crate::repositories::comment::Comment]),
+    ),
+)]
+pub async fn all_comment<T: crate::repositories::comment::CommentRepository>(
+    axum::extract::Path(todo_id): axum::extract::Path<i32>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<T>>,
+) -> Result<impl axum::response::IntoResponse, axum::http::StatusCode> {
+    let comments = repository
+        .all(todo_id)
+        .await
+        .or(Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok((axum::http::StatusCode::OK, axum::Json(comments)))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/todos/{id}/comments/{comment_id}",
+    tag = "comments",
+    params(("id" = i32, Path, description = "Todo id"), ("comment_id" = i32, Path, description = "Comment id")),
+    request_body = crate::repositories::comment::UpdateComment,
+    responses(
+        (status = 200, description = "Comment updated", body = crate::repositories::comment::Comment),
+        (status = 400, description = "Invalid payload"),
+        (status = 403, description = "Neither the author nor a workspace owner"),
+        (status = 404, description = "Comment not found"),
+    ),
+)]
+pub async fn update_comment<T: crate::repositories::comment::CommentRepository, Todo: crate::repositories::todo::TodoRepository>(
+    axum::extract::Path((todo_id, id)): axum::extract::Path<(i32, i32)>,
+    ValidatedJson(payload): ValidatedJson<crate::repositories::comment::UpdateComment>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<T>>,
+    axum::extract::Extension(todo_repository): axum::extract::Extension<std::sync::Arc<Todo>>,
+    axum::extract::Extension(caller): axum::extract::Extension<crate::auth::Caller>,
+    axum::extract::Extension(memberships): axum::extract::Extension<crate::workspaces::Memberships>,
+) -> Result<impl axum::response::IntoResponse, (axum::http::StatusCode, String)> {
+    changeable(repository.as_ref(), todo_repository.as_ref(), &caller, &memberships, todo_id, id).await?;
+    let comment = repository
+        .update(todo_id, id, payload)
+        .await
+        .map_err(|e| repository_error_response(e, axum::http::StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok((axum::http::StatusCode::OK, axum::Json(comment)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/todos/{id}/comments/{comment_id}",
+    tag = "comments",
+    params(("id" = i32, Path, description = "Todo id"), ("comment_id" = i32, Path, description = "Comment id")),
+    responses(
+        (status = 204, description = "Comment deleted"),
+        (status = 403, description = "Neither the author nor a workspace owner"),
+        (status = 404, description = "Comment not found"),
+    ),
+)]
+pub async fn delete_comment<T: crate::repositories::comment::CommentRepository, Todo: crate::repositories::todo::TodoRepository>(
+    axum::extract::Path((todo_id, id)): axum::extract::Path<(i32, i32)>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<T>>,
+    axum::extract::Extension(todo_repository): axum::extract::Extension<std::sync::Arc<Todo>>,
+    axum::extract::Extension(caller): axum::extract::Extension<crate::auth::Caller>,
+    axum::extract::Extension(memberships): axum::extract::Extension<crate::workspaces::Memberships>,
+) -> Result<axum::http::StatusCode, (axum::http::StatusCode, String)> {
+    changeable(repository.as_ref(), todo_repository.as_ref(), &caller, &memberships, todo_id, id).await?;
+    repository
+        .delete(todo_id, id)
+        .await
+        .map(|_| axum::http::StatusCode::NO_CONTENT)
+        .map_err(|e| repository_error_response(e, axum::http::StatusCode::INTERNAL_SERVER_ERROR))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::user::UserRepository;
+
+    #[tokio::test]
+    async fn comments_are_by_the_caller() {
+        let users = crate::repositories::user::test_utils::UserRepositoryForMemory::new();
+        let identity = |subject: &str, email: Option<&str>, name: Option<&str>| crate::repositories::user::Identity {
+            issuer: "https://id.example".to_string(),
+            subject: subject.to_string(),
+            email: email.map(str::to_string),
+            name: name.map(str::to_string),
+        };
+        let alice = users.upsert(identity("alice", Some("alice@example.com"), Some("Alice"))).await.unwrap();
+        let bob = users.upsert(identity("bob", Some("bob@example.com"), None)).await.unwrap();
+
+        assert_eq!("Alice", author(&crate::auth::Caller::User { id: alice.id }, &users).await.unwrap());
+        let token = crate::auth::Caller::Token { id: 3, user_id: Some(bob.id), scopes: vec![] };
+        assert_eq!("bob@example.com", author(&token, &users).await.unwrap());
+        let token = crate::auth::Caller::Token { id: 3, user_id: None, scopes: vec![] };
+        assert_eq!("token 3", author(&token, &users).await.unwrap());
+        assert_eq!("admin", author(&crate::auth::Caller::Admin, &users).await.unwrap());
+        assert_eq!("anonymous", author(&crate::auth::Caller::Open, &users).await.unwrap());
+    }
+
+    #[test]
+    fn only_authors_and_owners_change_comments() {
+        let (author_id, owner_id, editor_id) = (1, 2, 3);
+        let comment = crate::repositories::comment::Comment {
+            id: 1,
+            todo_id: 1,
+            author: "Alice".to_string(),
+            author_user_id: Some(author_id),
+            body: "mine".to_string(),
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+        };
+        let workspace_id = 7;
+        let roles = |role| crate::workspaces::Memberships::new([(workspace_id, role)].into_iter().collect());
+        let editor = roles(crate::repositories::workspace::Role::Editor);
+        let owner = roles(crate::repositories::workspace::Role::Owner);
+
+        assert!(may_change(&comment, &crate::auth::Caller::User { id: author_id }, &editor, Some(workspace_id)));
+        let token = crate::auth::Caller::Token { id: 1, user_id: Some(author_id), scopes: vec![] };
+        assert!(may_change(&comment, &token, &editor, Some(workspace_id)));
+        assert!(!may_change(&comment, &crate::auth::Caller::User { id: editor_id }, &editor, Some(workspace_id)));
+        assert!(may_change(&comment, &crate::auth::Caller::User { id: owner_id }, &owner, Some(workspace_id)));
+        assert!(may_change(&comment, &crate::auth::Caller::Admin, &crate::workspaces::Memberships::unrestricted(), Some(workspace_id)));
+        // without a workspace there is no owner, and open access is nobody's
+        let open = crate::workspaces::Memberships::new(Default::default());
+        assert!(!may_change(&comment, &crate::auth::Caller::Open, &open, None));
+        let anonymous = crate::repositories::comment::Comment { author_user_id: None, ..comment };
+        assert!(!may_change(&anonymous, &crate::auth::Caller::Open, &open, None));
+    }
+}
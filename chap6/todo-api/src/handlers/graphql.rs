@@ -0,0 +1,43 @@
+#[utoipa::path(
+    post,
+    path = "/graphql",
+    tag = "graphql",
+    request_body(content = Object, description = "GraphQL request with `query`, `variables` and `operationName`"),
+    responses(
+        (status = 200, description = "GraphQL response; errors are reported in its `errors` field", body = Object),
+    ),
+)]
+pub async fn graphql<T: crate::repositories::todo::TodoRepository, L: crate::repositories::label::LabelRepository>(
+    axum::Json(request): axum::Json<async_graphql::Request>,
+    axum::extract::Extension(schema): axum::extract::Extension<crate::graphql::TodoSchema<T, L>>,
+    axum::extract::Extension(todo_repository): axum::extract::Extension<std::sync::Arc<T>>,
+    axum::extract::Extension(label_repository): axum::extract::Extension<std::sync::Arc<L>>,
+    axum::extract::Extension(caller): axum::extract::Extension<crate::auth::Caller>,
+    axum::extract::Extension(memberships): axum::extract::Extension<crate::workspaces::Memberships>,
+) -> impl axum::response::IntoResponse {
+    let request = crate::graphql::request_data(request, todo_repository, label_repository)
+        .data(caller)
+        .data(memberships);
+    axum::Json(schema.execute(request).await)
+}
+
+/// GraphiQL loads itself from unpkg and runs inline scripts, which the default page policy forbids.
+const GRAPHIQL_CSP: &str = "default-src 'self'; script-src 'self' 'unsafe-inline' https://unpkg.com; style-src 'self' 'unsafe-inline' https://unpkg.com; img-src 'self' data: https://graphql.org; font-src 'self' data: https://unpkg.com; object-src 'none'; base-uri 'none'; frame-ancestors 'none'";
+
+/// GraphiQL is only served by debug builds.
+#[utoipa::path(
+    get,
+    path = "/graphql",
+    tag = "graphql",
+    responses(
+        (status = 200, description = "GraphiQL page (debug builds only)", content_type = "text/html"),
+        (status = 404, description = "Release build"),
+    ),
+)]
+pub async fn graphiql() -> Result<impl axum::response::IntoResponse, axum::http::StatusCode> {
+    if !cfg!(debug_assertions) {
+        return Err(axum::http::StatusCode::NOT_FOUND);
+    }
+    let headers = axum::response::Headers(vec![(axum::http::header::CONTENT_SECURITY_POLICY, GRAPHIQL_CSP)]);
+    Ok((headers, axum::response::Html(async_graphql::http::GraphiQLSource::build().endpoint("/graphql").finish())))
+}
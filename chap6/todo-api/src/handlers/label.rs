@@ -1,27 +1,81 @@
 use super::*;
 
+#[utoipa::path(
+    post,
+    path = "/labels",
+    tag = "labels",
+    params(("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key replay the first response instead of running again")),
+    request_body = CreateLabel,
+    responses(
+        (status = 201, description = "Label created", body = crate::repositories::label::Label),
+        (status = 400, description = "Invalid payload"),
+        (status = 403, description = "Caller can not edit the workspace"),
+        (status = 409, description = "A request with the same Idempotency-Key is still in progress"),
+        (status = 422, description = "Idempotency-Key was already used for a different request"),
+    ),
+)]
 pub async fn create_label<T: crate::repositories::label::LabelRepository>(
     ValidatedJson(payload): ValidatedJson<CreateLabel>,
     axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<T>>,
-) -> Result<impl axum::response::IntoResponse, axum::http::StatusCode> {
+    axum::extract::Extension(memberships): axum::extract::Extension<crate::workspaces::Memberships>,
+) -> Result<impl axum::response::IntoResponse, axum::response::Response> {
+    memberships
+        .check(payload.workspace_id, crate::workspaces::Permission::Edit)
+        .map_err(axum::response::IntoResponse::into_response)?;
     let label = repository
-        .create(payload.name)
+        .create(payload.name, payload.workspace_id)
         .await
-        .or(Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR))?;
+        .map_err(|_| axum::response::IntoResponse::into_response(axum::http::StatusCode::INTERNAL_SERVER_ERROR))?;
     Ok((axum::http::StatusCode::CREATED, axum::Json(label)))
 }
 
+#[utoipa::path(
+    get,
+    path = "/labels",
+    tag = "labels",
+    responses(
+        (status = 200, description = "All labels, leaving out workspaces the caller is not a member of", body = [crate::repositories::label::Label]),
+    ),
+)]
 pub async fn all_label<T: crate::repositories::label::LabelRepository>(
     axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<T>>,
+    axum::extract::Extension(memberships): axum::extract::Extension<crate::workspaces::Memberships>,
 ) -> Result<impl axum::response::IntoResponse, axum::http::StatusCode> {
-    let labels = repository.all().await.unwrap();
+    let labels: Vec<_> = repository
+        .all()
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|label| memberships.can_view(label.workspace_id))
+        .collect();
     Ok((axum::http::StatusCode::OK, axum::Json(labels)))
 }
 
+#[utoipa::path(
+    delete,
+    path = "/labels/{id}",
+    tag = "labels",
+    params(("id" = i32, Path, description = "Label id")),
+    responses(
+        (status = 204, description = "Label deleted"),
+        (status = 403, description = "Caller can not edit the label's workspace"),
+        (status = 404, description = "Label not found"),
+    ),
+)]
 pub async fn delete_label<T: crate::repositories::label::LabelRepository>(
     axum::extract::Path(id): axum::extract::Path<i32>,
     axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<T>>,
+    axum::extract::Extension(memberships): axum::extract::Extension<crate::workspaces::Memberships>,
 ) -> axum::http::StatusCode {
+    let label = match repository.find(id).await {
+        Ok(label) => label,
+        Err(_) => return axum::http::StatusCode::NOT_FOUND,
+    };
+    match memberships.check(label.workspace_id, crate::workspaces::Permission::Edit) {
+        Ok(()) => {}
+        Err(crate::workspaces::WorkspaceError::NotFound(_)) => return axum::http::StatusCode::NOT_FOUND,
+        Err(_) => return axum::http::StatusCode::FORBIDDEN,
+    }
     repository
         .delete(id)
         .await
@@ -29,9 +83,20 @@ pub async fn delete_label<T: crate::repositories::label::LabelRepository>(
         .unwrap_or(axum::http::StatusCode::INTERNAL_SERVER_ERROR)
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq, validator::Validate)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Eq, validator::Validate, utoipa::ToSchema, ts_rs::TS, async_graphql::InputObject)]
+#[ts(rename = "NewLabelPayload")]
+#[graphql(name = "CreateLabelInput")]
 pub struct CreateLabel {
     #[validate(length(min=1, message="cannot be empty"))]
     #[validate(length(max=100, message="over text length"))]
-    name: String,
+    pub name: String,
+    // shares the label with the members of a workspace
+    #[ts(optional)]
+    pub workspace_id: Option<i32>,
+}
+
+impl CreateLabel {
+    pub fn new(name: String) -> Self {
+        Self { name, workspace_id: None }
+    }
 }
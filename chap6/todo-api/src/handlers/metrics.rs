@@ -0,0 +1,14 @@
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "metrics",
+    responses(
+        (status = 200, description = "Metrics in the Prometheus text format", body = String, content_type = "text/plain; version=0.0.4"),
+    ),
+)]
+pub async fn metrics(
+    axum::extract::Extension(metrics): axum::extract::Extension<crate::metrics::Metrics>,
+) -> impl axum::response::IntoResponse {
+    let headers = axum::response::Headers(vec![(axum::http::header::CONTENT_TYPE, prometheus::TEXT_FORMAT)]);
+    (headers, metrics.encode())
+}
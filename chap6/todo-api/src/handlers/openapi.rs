@@ -0,0 +1,27 @@
+#[utoipa::path(
+    get,
+    path = "/openapi.json",
+    tag = "docs",
+    responses(
+        (status = 200, description = "This OpenAPI document", content_type = "application/json"),
+    ),
+)]
+pub async fn openapi_json() -> impl axum::response::IntoResponse {
+    axum::Json(<crate::openapi::ApiDoc as utoipa::OpenApi>::openapi())
+}
+
+pub async fn swagger_ui_redirect() -> axum::response::Redirect {
+    axum::response::Redirect::permanent("/swagger-ui/".parse().unwrap())
+}
+
+/// Serves the Swagger UI assets embedded at build time, pointed at `/openapi.json`.
+pub async fn swagger_ui(
+    axum::extract::Path(tail): axum::extract::Path<String>,
+) -> Result<impl axum::response::IntoResponse, axum::http::StatusCode> {
+    let config = std::sync::Arc::new(utoipa_swagger_ui::Config::from("/openapi.json"));
+    let file = utoipa_swagger_ui::serve(tail.trim_start_matches('/'), config)
+        .or(Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR))?
+        .ok_or(axum::http::StatusCode::NOT_FOUND)?;
+    let headers = axum::response::Headers(vec![(axum::http::header::CONTENT_TYPE, file.content_type)]);
+    Ok((headers, file.bytes.into_owned()))
+}
@@ -0,0 +1,402 @@
+use super::*;
+
+/// Header carrying the password of a protected share to `GET /shared/{token}`.
+pub const SHARE_PASSWORD: &str = "share-password";
+
+/// What a share link shows: one todo, or the todos carrying a label.
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, utoipa::ToSchema)]
+pub struct SharedTodos {
+    /// The label of a shared list.
+    pub label: Option<crate::repositories::label::Label>,
+    pub todos: Vec<crate::repositories::todo::TodoEntity>,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct SharePasswordForm {
+    password: String,
+}
+
+/// Only the shared todo, or the todos carrying the shared label in the label's own workspace;
+/// nothing else is ever read for a share.
+async fn shared_todos<T: crate::repositories::todo::TodoRepository, L: crate::repositories::label::LabelRepository>(
+    share: crate::repositories::share::Share,
+    todo_repository: &T,
+    label_repository: &L,
+) -> anyhow::Result<SharedTodos> {
+    let (label, todos) = match (share.todo_id, share.label_id) {
+        (Some(todo_id), _) => (None, vec![todo_repository.find(todo_id).await?]),
+        (None, Some(label_id)) => {
+            let label = label_repository.find(label_id).await?;
+            let todos = todo_repository
+                .all()
+                .await?
+                .into_iter()
+                .filter(|todo| todo.workspace_id == share.workspace_id && todo.labels.iter().any(|label| label.id == label_id))
+                .collect();
+            (Some(label), todos)
+        }
+        (None, None) => (None, vec![]),
+    };
+    Ok(SharedTodos { label, todos, expires_at: share.expires_at })
+}
+
+/// Shared pages carry the token in their URL, so they are neither cached nor passed on as a referrer.
+fn private_headers() -> axum::response::Headers<Vec<(axum::http::header::HeaderName, &'static str)>> {
+    axum::response::Headers(vec![
+        (axum::http::header::CACHE_CONTROL, "no-store"),
+        (axum::http::header::REFERRER_POLICY, "no-referrer"),
+        (axum::http::header::HeaderName::from_static("x-robots-tag"), "noindex"),
+    ])
+}
+
+fn escape_html(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '&' => "&amp;".to_string(),
+            '<' => "&lt;".to_string(),
+            '>' => "&gt;".to_string(),
+            '"' => "&quot;".to_string(),
+            '\'' => "&#39;".to_string(),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+fn html_page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"robots\" content=\"noindex\">\n<title>{title}</title>\n</head>\n<body>\n<h1>{title}</h1>\n{body}</body>\n</html>\n",
+        title = escape_html(title),
+        body = body,
+    )
+}
+
+fn render_todos(shared: &SharedTodos) -> String {
+    let title = match &shared.label {
+        Some(label) => label.name.as_str(),
+        None => "Shared todo",
+    };
+    let mut body = String::from("<ul>\n");
+    for todo in &shared.todos {
+        let mut details: Vec<String> = todo.labels.iter().map(|label| escape_html(&label.name)).collect();
+        if let Some(due) = todo.due {
+            details.push(format!("due {}", due));
+        }
+        body.push_str(&format!(
+            "<li><input type=\"checkbox\" disabled{}> {}{}</li>\n",
+            if todo.completed { " checked" } else { "" },
+            escape_html(&todo.text),
+            if details.is_empty() { String::new() } else { format!(" <small>({})</small>", details.join(", ")) },
+        ));
+    }
+    body.push_str("</ul>\n");
+    html_page(title, &body)
+}
+
+fn render_password_form(wrong: bool) -> String {
+    let mut body = String::new();
+    if wrong {
+        body.push_str("<p>Wrong password.</p>\n");
+    }
+    body.push_str("<form method=\"post\">\n<input type=\"password\" name=\"password\" autofocus required>\n<button type=\"submit\">Open</button>\n</form>\n");
+    html_page("Password required", &body)
+}
+
+#[utoipa::path(
+    post,
+    path = "/shares",
+    tag = "shares",
+    request_body = crate::repositories::share::CreateShare,
+    responses(
+        (status = 201, description = "Share created; the token is only returned here", body = crate::repositories::share::NewShare),
+        (status = 400, description = "Invalid payload"),
+        (status = 403, description = "Caller can not edit the workspace of the todo or label"),
+        (status = 404, description = "Todo or label not found"),
+    ),
+)]
+pub async fn create_share<S: crate::repositories::share::ShareRepository, T: crate::repositories::todo::TodoRepository, L: crate::repositories::label::LabelRepository>(
+    ValidatedJson(payload): ValidatedJson<crate::repositories::share::CreateShare>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<S>>,
+    axum::extract::Extension(todo_repository): axum::extract::Extension<std::sync::Arc<T>>,
+    axum::extract::Extension(label_repository): axum::extract::Extension<std::sync::Arc<L>>,
+    axum::extract::Extension(memberships): axum::extract::Extension<crate::workspaces::Memberships>,
+) -> Result<impl axum::response::IntoResponse, axum::response::Response> {
+    let not_found = |_| axum::response::IntoResponse::into_response(axum::http::StatusCode::NOT_FOUND);
+    let workspace_id = match (payload.todo_id, payload.label_id) {
+        (Some(todo_id), _) => todo_repository.find(todo_id).await.map_err(not_found)?.workspace_id,
+        (None, Some(label_id)) => label_repository.find(label_id).await.map_err(not_found)?.workspace_id,
+        (None, None) => None,
+    };
+    // publishing is a change to the workspace, so viewers may not
+    memberships
+        .check(workspace_id, crate::workspaces::Permission::Edit)
+        .map_err(crate::workspaces::concealed_response)?;
+    let share = repository
+        .create(payload, workspace_id)
+        .await
+        .map_err(|_| axum::response::IntoResponse::into_response(axum::http::StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok((axum::http::StatusCode::CREATED, axum::Json(share)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/shares",
+    tag = "shares",
+    responses(
+        (status = 200, description = "Shares, leaving out workspaces the caller is not a member of", body = [crate::repositories::share::Share]),
+    ),
+)]
+pub async fn all_share<S: crate::repositories::share::ShareRepository>(
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<S>>,
+    axum::extract::Extension(memberships): axum::extract::Extension<crate::workspaces::Memberships>,
+) -> Result<impl axum::response::IntoResponse, axum::http::StatusCode> {
+    let shares: Vec<_> = repository
+        .all()
+        .await
+        .or(Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR))?
+        .into_iter()
+        .filter(|share| memberships.can_view(share.workspace_id))
+        .collect();
+    Ok((axum::http::StatusCode::OK, axum::Json(shares)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/shares/{id}",
+    tag = "shares",
+    params(("id" = i32, Path, description = "Share id")),
+    responses(
+        (status = 204, description = "Share revoked; its link stops working at once"),
+        (status = 403, description = "Caller can not edit the share's workspace"),
+        (status = 404, description = "Share not found"),
+    ),
+)]
+pub async fn delete_share<S: crate::repositories::share::ShareRepository>(
+    axum::extract::Path(id): axum::extract::Path<i32>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<S>>,
+    axum::extract::Extension(memberships): axum::extract::Extension<crate::workspaces::Memberships>,
+) -> Result<impl axum::response::IntoResponse, axum::response::Response> {
+    let share = repository
+        .find(id)
+        .await
+        .map_err(|_| axum::response::IntoResponse::into_response(axum::http::StatusCode::NOT_FOUND))?;
+    memberships
+        .check(share.workspace_id, crate::workspaces::Permission::Edit)
+        .map_err(crate::workspaces::concealed_response)?;
+    repository
+        .delete(id)
+        .await
+        .map_err(|_| axum::response::IntoResponse::into_response(axum::http::StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok(axum::http::StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/shared/{token}",
+    tag = "shares",
+    params(
+        ("token" = String, Path, description = "Share token"),
+        ("Share-Password" = Option<String>, Header, description = "Password of a protected share"),
+    ),
+    responses(
+        (status = 200, description = "The shared todos, read only", body = SharedTodos),
+        (status = 401, description = "The share has a password and it was missing or wrong"),
+        (status = 404, description = "Unknown, revoked or expired share"),
+    ),
+)]
+pub async fn shared_json<S: crate::repositories::share::ShareRepository, T: crate::repositories::todo::TodoRepository, L: crate::repositories::label::LabelRepository>(
+    axum::extract::Path(token): axum::extract::Path<String>,
+    headers: axum::http::HeaderMap,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<S>>,
+    axum::extract::Extension(todo_repository): axum::extract::Extension<std::sync::Arc<T>>,
+    axum::extract::Extension(label_repository): axum::extract::Extension<std::sync::Arc<L>>,
+) -> Result<impl axum::response::IntoResponse, axum::http::StatusCode> {
+    let password = headers.get(SHARE_PASSWORD).and_then(|value| value.to_str().ok());
+    let share = match repository.open(&token, password).await {
+        Ok(crate::repositories::share::ShareAccess::Open(share)) => share,
+        Ok(crate::repositories::share::ShareAccess::Locked) => return Err(axum::http::StatusCode::UNAUTHORIZED),
+        Ok(crate::repositories::share::ShareAccess::Missing) => return Err(axum::http::StatusCode::NOT_FOUND),
+        Err(_) => return Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR),
+    };
+    let shared = shared_todos(share, todo_repository.as_ref(), label_repository.as_ref())
+        .await
+        .or(Err(axum::http::StatusCode::NOT_FOUND))?;
+    Ok((private_headers(), axum::Json(shared)))
+}
+
+async fn shared_html<S: crate::repositories::share::ShareRepository, T: crate::repositories::todo::TodoRepository, L: crate::repositories::label::LabelRepository>(
+    token: &str,
+    password: Option<&str>,
+    repository: &S,
+    todo_repository: &T,
+    label_repository: &L,
+) -> axum::response::Response {
+    let (status, page) = match repository.open(token, password).await {
+        Ok(crate::repositories::share::ShareAccess::Open(share)) => match shared_todos(share, todo_repository, label_repository).await {
+            Ok(shared) => (axum::http::StatusCode::OK, render_todos(&shared)),
+            Err(_) => (axum::http::StatusCode::NOT_FOUND, html_page("Not found", "")),
+        },
+        Ok(crate::repositories::share::ShareAccess::Locked) => {
+            (axum::http::StatusCode::UNAUTHORIZED, render_password_form(password.is_some()))
+        }
+        Ok(crate::repositories::share::ShareAccess::Missing) => (axum::http::StatusCode::NOT_FOUND, html_page("Not found", "")),
+        Err(_) => (axum::http::StatusCode::INTERNAL_SERVER_ERROR, html_page("Something went wrong", "")),
+    };
+    axum::response::IntoResponse::into_response((status, private_headers(), axum::response::Html(page)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/shared/{token}/page",
+    tag = "shares",
+    params(("token" = String, Path, description = "Share token")),
+    responses(
+        (status = 200, description = "The shared todos as a read only page", content_type = "text/html"),
+        (status = 401, description = "Password form for a protected share", content_type = "text/html"),
+        (status = 404, description = "Unknown, revoked or expired share"),
+    ),
+)]
+pub async fn shared_page<S: crate::repositories::share::ShareRepository, T: crate::repositories::todo::TodoRepository, L: crate::repositories::label::LabelRepository>(
+    axum::extract::Path(token): axum::extract::Path<String>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<S>>,
+    axum::extract::Extension(todo_repository): axum::extract::Extension<std::sync::Arc<T>>,
+    axum::extract::Extension(label_repository): axum::extract::Extension<std::sync::Arc<L>>,
+) -> axum::response::Response {
+    shared_html(&token, None, repository.as_ref(), todo_repository.as_ref(), label_repository.as_ref()).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/shared/{token}/page",
+    tag = "shares",
+    params(("token" = String, Path, description = "Share token")),
+    request_body(content = String, description = "Form with a `password` field", content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "The shared todos as a read only page", content_type = "text/html"),
+        (status = 401, description = "Password form again after a wrong password", content_type = "text/html"),
+        (status = 404, description = "Unknown, revoked or expired share"),
+    ),
+)]
+pub async fn unlock_shared_page<S: crate::repositories::share::ShareRepository, T: crate::repositories::todo::TodoRepository, L: crate::repositories::label::LabelRepository>(
+    axum::extract::Path(token): axum::extract::Path<String>,
+    axum::extract::Form(form): axum::extract::Form<SharePasswordForm>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<S>>,
+    axum::extract::Extension(todo_repository): axum::extract::Extension<std::sync::Arc<T>>,
+    axum::extract::Extension(label_repository): axum::extract::Extension<std::sync::Arc<L>>,
+) -> axum::response::Response {
+    shared_html(&token, Some(&form.password), repository.as_ref(), todo_repository.as_ref(), label_repository.as_ref()).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::label::LabelRepository;
+    use crate::repositories::share::ShareRepository;
+    use crate::repositories::todo::TodoRepository;
+    use tower::ServiceExt;
+
+    type Shares = crate::repositories::share::test_utils::ShareRepositoryForMemory;
+    type Todos = crate::repositories::todo::test_utils::TodoRepositoryForMemory;
+    type Labels = crate::repositories::label::test_utils::LabelRepositoryForMemory;
+
+    fn app(shares: Shares, todos: Todos, labels: Labels) -> axum::Router {
+        axum::Router::new()
+            .route("/shared/:token", axum::routing::get(shared_json::<Shares, Todos, Labels>))
+            .route(
+                "/shared/:token/page",
+                axum::routing::get(shared_page::<Shares, Todos, Labels>).post(unlock_shared_page::<Shares, Todos, Labels>),
+            )
+            .layer(axum::extract::Extension(std::sync::Arc::new(shares)))
+            .layer(axum::extract::Extension(std::sync::Arc::new(todos)))
+            .layer(axum::extract::Extension(std::sync::Arc::new(labels)))
+    }
+
+    async fn send(app: &axum::Router, request: axum::http::Request<axum::body::Body>) -> (axum::http::StatusCode, String) {
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, String::from_utf8(bytes.to_vec()).unwrap())
+    }
+
+    fn get(uri: &str) -> axum::http::Request<axum::body::Body> {
+        axum::http::Request::builder().uri(uri).body(axum::body::Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn a_shared_list_shows_only_its_todos() {
+        let labels = Labels::new();
+        let groceries = labels.create("groceries".to_string(), None).await.unwrap();
+        let todos = Todos::new(vec![groceries.clone()]);
+        let milk = todos
+            .create(crate::repositories::todo::CreateTodo::new("<milk>".to_string(), vec![groceries.id], None, None))
+            .await
+            .unwrap();
+        todos
+            .create(crate::repositories::todo::CreateTodo::new("private".to_string(), vec![], None, None))
+            .await
+            .unwrap();
+        let shares = Shares::new();
+        let share = shares
+            .create(crate::repositories::share::CreateShare::new(None, Some(groceries.id), None, None), None)
+            .await
+            .unwrap();
+        let app = app(shares.clone(), todos, labels);
+
+        let (status, body) = send(&app, get(&format!("/shared/{}", share.token))).await;
+        assert_eq!(axum::http::StatusCode::OK, status);
+        let shared: SharedTodos = serde_json::from_str(&body).unwrap();
+        assert_eq!(vec![milk.id], shared.todos.iter().map(|todo| todo.id).collect::<Vec<_>>());
+        assert_eq!(Some(groceries), shared.label);
+
+        let (status, body) = send(&app, get(&share.url)).await;
+        assert_eq!(axum::http::StatusCode::OK, status);
+        assert!(body.contains("&lt;milk&gt;"));
+        assert!(!body.contains("private"));
+
+        shares.delete(share.share.id).await.unwrap();
+        assert_eq!(axum::http::StatusCode::NOT_FOUND, send(&app, get(&format!("/shared/{}", share.token))).await.0);
+        assert_eq!(axum::http::StatusCode::NOT_FOUND, send(&app, get(&share.url)).await.0);
+    }
+
+    #[tokio::test]
+    async fn protected_shares_ask_for_the_password() {
+        let labels = Labels::new();
+        let todos = Todos::new(vec![]);
+        let todo = todos
+            .create(crate::repositories::todo::CreateTodo::new("secret plan".to_string(), vec![], None, None))
+            .await
+            .unwrap();
+        let shares = Shares::new();
+        let share = shares
+            .create(crate::repositories::share::CreateShare::new(Some(todo.id), None, Some("open sesame".to_string()), Some(1)), None)
+            .await
+            .unwrap();
+        let app = app(shares, todos, labels);
+
+        let (status, body) = send(&app, get(&share.url)).await;
+        assert_eq!(axum::http::StatusCode::UNAUTHORIZED, status);
+        assert!(!body.contains("secret plan"));
+
+        let unlock = |password: &str| {
+            axum::http::Request::builder()
+                .method("POST")
+                .uri(&share.url)
+                .header(axum::http::header::CONTENT_TYPE, mime::APPLICATION_WWW_FORM_URLENCODED.as_ref())
+                .body(axum::body::Body::from(format!("password={}", password)))
+                .unwrap()
+        };
+        let (status, body) = send(&app, unlock("guess")).await;
+        assert_eq!(axum::http::StatusCode::UNAUTHORIZED, status);
+        assert!(body.contains("Wrong password"));
+        let (status, body) = send(&app, unlock("open+sesame")).await;
+        assert_eq!(axum::http::StatusCode::OK, status);
+        assert!(body.contains("secret plan"));
+
+        let request = axum::http::Request::builder()
+            .uri(format!("/shared/{}", share.token))
+            .header(SHARE_PASSWORD, "open sesame")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        assert_eq!(axum::http::StatusCode::OK, send(&app, request).await.0);
+    }
+}
@@ -1,16 +1,51 @@
 use super::*;
-pub async fn create_todo<T: crate::repositories::todo::TodoRepository>(
+#[utoipa::path(
+    post,
+    path = "/todos",
+    tag = "todos",
+    params(("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key replay the first response instead of running again")),
+    request_body = crate::repositories::todo::CreateTodo,
+    responses(
+        (status = 201, description = "Todo created", body = crate::repositories::todo::TodoEntity),
+        (status = 400, description = "Invalid payload, or a label outside the todo's workspace"),
+        (status = 403, description = "Caller can not edit the workspace"),
+        (status = 409, description = "A request with the same Idempotency-Key is still in progress"),
+        (status = 422, description = "Idempotency-Key was already used for a different request"),
+    ),
+)]
+pub async fn create_todo<T: crate::repositories::todo::TodoRepository, L: crate::repositories::label::LabelRepository>(
     ValidatedJson(payload): ValidatedJson<crate::repositories::todo::CreateTodo>,
     axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<T>>,
-) -> Result<impl axum::response::IntoResponse, axum::http::StatusCode> {
+    axum::extract::Extension(label_repository): axum::extract::Extension<std::sync::Arc<L>>,
+    axum::extract::Extension(memberships): axum::extract::Extension<crate::workspaces::Memberships>,
+) -> Result<impl axum::response::IntoResponse, axum::response::Response> {
+    memberships
+        .check(payload.workspace_id(), crate::workspaces::Permission::Edit)
+        .map_err(axum::response::IntoResponse::into_response)?;
+    let labels = label_repository
+        .all()
+        .await
+        .map_err(|_| axum::response::IntoResponse::into_response(axum::http::StatusCode::INTERNAL_SERVER_ERROR))?;
+    crate::workspaces::check_labels(&labels, payload.labels(), payload.workspace_id())
+        .map_err(axum::response::IntoResponse::into_response)?;
     let todo = repository
         .create(payload)
         .await
-        .or(Err(axum::http::StatusCode::NOT_FOUND))?;
+        .map_err(|_| axum::response::IntoResponse::into_response(axum::http::StatusCode::NOT_FOUND))?;
 
     Ok((axum::http::StatusCode::CREATED, axum::Json(todo)))
 }
 
+#[utoipa::path(
+    get,
+    path = "/todos/{id}",
+    tag = "todos",
+    params(("id" = i32, Path, description = "Todo id")),
+    responses(
+        (status = 200, description = "Todo found", body = crate::repositories::todo::TodoEntity),
+        (status = 404, description = "Todo not found"),
+    ),
+)]
 pub async fn find_todo<T: crate::repositories::todo::TodoRepository>(
     axum::extract::Path(id): axum::extract::Path<i32>,
     axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<T>>,
@@ -19,25 +54,318 @@ pub async fn find_todo<T: crate::repositories::todo::TodoRepository>(
     Ok((axum::http::StatusCode::OK, axum::Json(todo)))
 }
 
+#[utoipa::path(
+    get,
+    path = "/todos",
+    tag = "todos",
+    params(crate::repositories::todo::TodoFilter),
+    responses(
+        (status = 200, description = "Todos in list order, leaving out workspaces the caller is not a member of", body = [crate::repositories::todo::TodoEntity]),
+        (status = 400, description = "`assignee=me` from a caller that is not a user or one of their tokens"),
+    ),
+)]
 pub async fn all_todo<T: crate::repositories::todo::TodoRepository>(
+    axum::extract::Query(filter): axum::extract::Query<crate::repositories::todo::TodoFilter>,
     axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<T>>,
-) -> Result<impl axum::response::IntoResponse, axum::http::StatusCode> {
-    let todo = repository.all().await.unwrap();
+    axum::extract::Extension(caller): axum::extract::Extension<crate::auth::Caller>,
+    axum::extract::Extension(memberships): axum::extract::Extension<crate::workspaces::Memberships>,
+) -> Result<impl axum::response::IntoResponse, (axum::http::StatusCode, String)> {
+    let filter = filter
+        .for_user(caller.user_id())
+        .ok_or_else(|| (axum::http::StatusCode::BAD_REQUEST, "assignee=me needs a user or one of their tokens".to_string()))?;
+    let todo: Vec<_> = repository
+        .all()
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|todo| filter.matches(todo) && memberships.can_view(todo.workspace_id))
+        .collect();
     Ok((axum::http::StatusCode::OK, axum::Json(todo)))
 }
 
-pub async fn update_todo<T: crate::repositories::todo::TodoRepository>(
+#[utoipa::path(
+    patch,
+    path = "/todos/{id}",
+    tag = "todos",
+    params(("id" = i32, Path, description = "Todo id")),
+    request_body = crate::repositories::todo::UpdateTodo,
+    responses(
+        (status = 201, description = "Todo updated", body = crate::repositories::todo::TodoEntity),
+        (status = 400, description = "Invalid payload, or a label outside the todo's workspace"),
+        (status = 403, description = "Caller can not edit the todo's workspace"),
+        (status = 404, description = "Todo not found"),
+        (status = 409, description = "Todo is still blocked"),
+    ),
+)]
+pub async fn update_todo<T: crate::repositories::todo::TodoRepository, L: crate::repositories::label::LabelRepository>(
     axum::extract::Path(id): axum::extract::Path<i32>,
     ValidatedJson(payload): ValidatedJson<crate::repositories::todo::UpdateTodo>,
     axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<T>>,
-) -> Result<impl axum::response::IntoResponse, axum::http::StatusCode> {
+    axum::extract::Extension(label_repository): axum::extract::Extension<std::sync::Arc<L>>,
+) -> Result<impl axum::response::IntoResponse, (axum::http::StatusCode, String)> {
+    if let Some(ids) = payload.labels() {
+        let todo = repository
+            .find(id)
+            .await
+            .map_err(|e| repository_error_response(e, axum::http::StatusCode::NOT_FOUND))?;
+        let labels = label_repository
+            .all()
+            .await
+            .map_err(|e| repository_error_response(e, axum::http::StatusCode::INTERNAL_SERVER_ERROR))?;
+        crate::workspaces::check_labels(&labels, ids, todo.workspace_id)
+            .map_err(|e| (axum::http::StatusCode::BAD_REQUEST, e.to_string()))?;
+    }
     let todo = repository
         .update(id, payload)
         .await
-        .or(Err(axum::http::StatusCode::NOT_FOUND))?;
+        .map_err(|e| repository_error_response(e, axum::http::StatusCode::NOT_FOUND))?;
     Ok((axum::http::StatusCode::CREATED, axum::Json(todo)))
 }
 
+#[utoipa::path(
+    post,
+    path = "/todos/{id}/move",
+    tag = "todos",
+    params(("id" = i32, Path, description = "Todo id")),
+    request_body = crate::repositories::todo::MoveTodo,
+    responses(
+        (status = 200, description = "Todo moved", body = crate::repositories::todo::TodoEntity),
+        (status = 400, description = "Invalid payload"),
+        (status = 403, description = "Caller can not edit the todo's workspace"),
+        (status = 404, description = "Todo or target not found"),
+    ),
+)]
+pub async fn move_todo<T: crate::repositories::todo::TodoRepository>(
+    axum::extract::Path(id): axum::extract::Path<i32>,
+    ValidatedJson(payload): ValidatedJson<crate::repositories::todo::MoveTodo>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<T>>,
+    axum::extract::Extension(memberships): axum::extract::Extension<crate::workspaces::Memberships>,
+) -> Result<impl axum::response::IntoResponse, (axum::http::StatusCode, String)> {
+    // the todo itself was checked by `require_todo`; the target only has to be visible
+    if let Some(target_id) = payload.target() {
+        let target = repository
+            .find(target_id)
+            .await
+            .map_err(|e| repository_error_response(e, axum::http::StatusCode::INTERNAL_SERVER_ERROR))?;
+        if !memberships.can_view(target.workspace_id) {
+            return Err(repository_error_response(
+                crate::repositories::RepositoryError::NotFound(target_id).into(),
+                axum::http::StatusCode::NOT_FOUND,
+            ));
+        }
+    }
+    let todo = repository
+        .move_to(id, payload)
+        .await
+        .map_err(|e| repository_error_response(e, axum::http::StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok((axum::http::StatusCode::OK, axum::Json(todo)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/todos/{id}/blockers",
+    tag = "todos",
+    params(("id" = i32, Path, description = "Todo id")),
+    request_body = crate::repositories::todo::AddBlocker,
+    responses(
+        (status = 201, description = "Blocker added", body = crate::repositories::todo::TodoEntity),
+        (status = 403, description = "Caller can not edit the todo's workspace"),
+        (status = 404, description = "Todo not found"),
+        (status = 409, description = "Blocker would form a cycle"),
+    ),
+)]
+pub async fn add_blocker<T: crate::repositories::todo::TodoRepository>(
+    axum::extract::Path(id): axum::extract::Path<i32>,
+    ValidatedJson(payload): ValidatedJson<crate::repositories::todo::AddBlocker>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<T>>,
+    axum::extract::Extension(memberships): axum::extract::Extension<crate::workspaces::Memberships>,
+) -> Result<impl axum::response::IntoResponse, (axum::http::StatusCode, String)> {
+    // the todo itself was checked by `require_todo`; the blocker only has to be visible
+    let blocker = repository
+        .find(payload.blocker_id)
+        .await
+        .map_err(|e| repository_error_response(e, axum::http::StatusCode::INTERNAL_SERVER_ERROR))?;
+    if !memberships.can_view(blocker.workspace_id) {
+        return Err(repository_error_response(
+            crate::repositories::RepositoryError::NotFound(payload.blocker_id).into(),
+            axum::http::StatusCode::NOT_FOUND,
+        ));
+    }
+    let todo = repository
+        .add_blocker(id, payload.blocker_id)
+        .await
+        .map_err(|e| repository_error_response(e, axum::http::StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok((axum::http::StatusCode::CREATED, axum::Json(todo)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/todos/{id}/blockers/{blocker_id}",
+    tag = "todos",
+    params(("id" = i32, Path, description = "Todo id"), ("blocker_id" = i32, Path, description = "Blocking todo id")),
+    responses(
+        (status = 200, description = "Blocker removed", body = crate::repositories::todo::TodoEntity),
+        (status = 403, description = "Caller can not edit the todo's workspace"),
+        (status = 404, description = "Todo not found"),
+    ),
+)]
+pub async fn remove_blocker<T: crate::repositories::todo::TodoRepository>(
+    axum::extract::Path((id, blocker_id)): axum::extract::Path<(i32, i32)>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<T>>,
+) -> Result<impl axum::response::IntoResponse, (axum::http::StatusCode, String)> {
+    let todo = repository
+        .remove_blocker(id, blocker_id)
+        .await
+        .map_err(|e| repository_error_response(e, axum::http::StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok((axum::http::StatusCode::OK, axum::Json(todo)))
+}
+
+/// Assignees and watchers have to be members of the todo's workspace, or any user for todos
+/// outside workspaces.
+async fn check_member<W: crate::repositories::workspace::WorkspaceRepository, U: crate::repositories::user::UserRepository>(
+    workspace_id: Option<i32>,
+    user_id: i32,
+    workspace_repository: &W,
+    user_repository: &U,
+) -> Result<(), axum::response::Response> {
+    let internal_error = |_| axum::response::IntoResponse::into_response(axum::http::StatusCode::INTERNAL_SERVER_ERROR);
+    let error = match workspace_id {
+        Some(workspace_id) => {
+            let roles = workspace_repository.roles(user_id).await.map_err(internal_error)?;
+            if roles.contains_key(&workspace_id) {
+                return Ok(());
+            }
+            format!("user {} is not a member of workspace {}", user_id, workspace_id)
+        }
+        None => match user_repository.find(user_id).await {
+            Ok(_) => return Ok(()),
+            Err(e) if matches!(e.downcast_ref::<crate::repositories::RepositoryError>(), Some(crate::repositories::RepositoryError::NotFound(_))) => {
+                format!("user {} not found", user_id)
+            }
+            Err(e) => return Err(internal_error(e)),
+        },
+    };
+    Err(axum::response::IntoResponse::into_response((axum::http::StatusCode::BAD_REQUEST, error)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/todos/{id}/assign",
+    tag = "todos",
+    params(("id" = i32, Path, description = "Todo id")),
+    request_body = crate::repositories::todo::AssignTodo,
+    responses(
+        (status = 200, description = "Assignee changed; the assignee now watches the todo", body = crate::repositories::todo::TodoEntity),
+        (status = 400, description = "The assignee is not a member of the todo's workspace"),
+        (status = 403, description = "Caller can not edit the todo's workspace"),
+        (status = 404, description = "Todo not found"),
+    ),
+)]
+pub async fn assign_todo<T: crate::repositories::todo::TodoRepository, W: crate::repositories::workspace::WorkspaceRepository, U: crate::repositories::user::UserRepository>(
+    axum::extract::Path(id): axum::extract::Path<i32>,
+    ValidatedJson(payload): ValidatedJson<crate::repositories::todo::AssignTodo>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<T>>,
+    axum::extract::Extension(workspace_repository): axum::extract::Extension<std::sync::Arc<W>>,
+    axum::extract::Extension(user_repository): axum::extract::Extension<std::sync::Arc<U>>,
+) -> Result<impl axum::response::IntoResponse, axum::response::Response> {
+    let todo = repository
+        .find(id)
+        .await
+        .map_err(|_| axum::response::IntoResponse::into_response(axum::http::StatusCode::NOT_FOUND))?;
+    if let Some(assignee_id) = payload.assignee_id {
+        check_member(todo.workspace_id, assignee_id, workspace_repository.as_ref(), user_repository.as_ref()).await?;
+    }
+    let todo = repository
+        .assign(id, payload.assignee_id)
+        .await
+        .map_err(|e| axum::response::IntoResponse::into_response(repository_error_response(e, axum::http::StatusCode::INTERNAL_SERVER_ERROR)))?;
+    Ok((axum::http::StatusCode::OK, axum::Json(todo)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/todos/{id}/watchers",
+    tag = "todos",
+    params(("id" = i32, Path, description = "Todo id")),
+    responses(
+        (status = 200, description = "Ids of the users watching the todo", body = [i32]),
+        (status = 404, description = "Todo not found"),
+    ),
+)]
+pub async fn all_watcher<T: crate::repositories::todo::TodoRepository>(
+    axum::extract::Path(id): axum::extract::Path<i32>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<T>>,
+) -> Result<impl axum::response::IntoResponse, (axum::http::StatusCode, String)> {
+    let watchers = repository
+        .watchers(id)
+        .await
+        .map_err(|e| repository_error_response(e, axum::http::StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok((axum::http::StatusCode::OK, axum::Json(watchers)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/todos/{id}/watchers/{user_id}",
+    tag = "todos",
+    params(("id" = i32, Path, description = "Todo id"), ("user_id" = i32, Path, description = "User id")),
+    responses(
+        (status = 204, description = "The user watches the todo"),
+        (status = 400, description = "The user is not a member of the todo's workspace"),
+        (status = 403, description = "Caller can not edit the todo's workspace and is not adding themselves"),
+        (status = 404, description = "Todo not found"),
+    ),
+)]
+pub async fn add_watcher<T: crate::repositories::todo::TodoRepository, W: crate::repositories::workspace::WorkspaceRepository, U: crate::repositories::user::UserRepository>(
+    axum::extract::Path((id, user_id)): axum::extract::Path<(i32, i32)>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<T>>,
+    axum::extract::Extension(workspace_repository): axum::extract::Extension<std::sync::Arc<W>>,
+    axum::extract::Extension(user_repository): axum::extract::Extension<std::sync::Arc<U>>,
+) -> Result<impl axum::response::IntoResponse, axum::response::Response> {
+    let todo = repository
+        .find(id)
+        .await
+        .map_err(|e| axum::response::IntoResponse::into_response(repository_error_response(e, axum::http::StatusCode::INTERNAL_SERVER_ERROR)))?;
+    check_member(todo.workspace_id, user_id, workspace_repository.as_ref(), user_repository.as_ref()).await?;
+    repository
+        .watch(id, user_id)
+        .await
+        .map_err(|e| axum::response::IntoResponse::into_response(repository_error_response(e, axum::http::StatusCode::INTERNAL_SERVER_ERROR)))?;
+    Ok(axum::http::StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/todos/{id}/watchers/{user_id}",
+    tag = "todos",
+    params(("id" = i32, Path, description = "Todo id"), ("user_id" = i32, Path, description = "User id")),
+    responses(
+        (status = 204, description = "The user no longer watches the todo"),
+        (status = 403, description = "Caller can not edit the todo's workspace and is not removing themselves"),
+        (status = 404, description = "Todo not found, or the user was not watching it"),
+    ),
+)]
+pub async fn delete_watcher<T: crate::repositories::todo::TodoRepository>(
+    axum::extract::Path((id, user_id)): axum::extract::Path<(i32, i32)>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<T>>,
+) -> Result<impl axum::response::IntoResponse, axum::response::Response> {
+    repository
+        .unwatch(id, user_id)
+        .await
+        .map_err(|e| axum::response::IntoResponse::into_response(repository_error_response(e, axum::http::StatusCode::INTERNAL_SERVER_ERROR)))?;
+    Ok(axum::http::StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/todos/{id}",
+    tag = "todos",
+    params(("id" = i32, Path, description = "Todo id")),
+    responses(
+        (status = 204, description = "Todo deleted"),
+        (status = 403, description = "Caller can not edit the todo's workspace"),
+        (status = 404, description = "Todo not found"),
+    ),
+)]
 pub async fn delete_todo<T: crate::repositories::todo::TodoRepository>(
     axum::extract::Path(id): axum::extract::Path<i32>,
     axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<T>>,
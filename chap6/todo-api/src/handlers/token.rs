@@ -0,0 +1,84 @@
+use super::*;
+
+/// A user only sees and revokes their own tokens; the admin token and the tokens it made see all.
+fn visible(caller: &crate::auth::Caller, api_token: &crate::repositories::token::ApiToken) -> bool {
+    match caller.user_id() {
+        Some(user_id) => api_token.user_id == Some(user_id),
+        None => true,
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/tokens",
+    tag = "tokens",
+    request_body = crate::repositories::token::CreateApiToken,
+    responses(
+        (status = 201, description = "Token created, acting as the caller's user; the secret is only returned here", body = crate::repositories::token::NewApiToken),
+        (status = 400, description = "Invalid payload"),
+        (status = 403, description = "Asked for a scope the caller does not have"),
+    ),
+)]
+pub async fn create_token<A: crate::repositories::token::ApiTokenRepository>(
+    ValidatedJson(payload): ValidatedJson<crate::repositories::token::CreateApiToken>,
+    axum::extract::Extension(caller): axum::extract::Extension<crate::auth::Caller>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<A>>,
+) -> Result<impl axum::response::IntoResponse, axum::response::Response> {
+    // a token can only hand out what it has itself
+    for scope in &payload.scopes {
+        caller.check(scope).map_err(axum::response::IntoResponse::into_response)?;
+    }
+    let token = repository
+        .create(payload, caller.user_id())
+        .await
+        .map_err(|_| axum::response::IntoResponse::into_response(axum::http::StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok((axum::http::StatusCode::CREATED, axum::Json(token)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/tokens",
+    tag = "tokens",
+    responses(
+        (status = 200, description = "The caller's tokens, or all of them for the admin token", body = [crate::repositories::token::ApiToken]),
+    ),
+)]
+pub async fn all_token<A: crate::repositories::token::ApiTokenRepository>(
+    axum::extract::Extension(caller): axum::extract::Extension<crate::auth::Caller>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<A>>,
+) -> Result<impl axum::response::IntoResponse, axum::http::StatusCode> {
+    let tokens: Vec<_> = repository
+        .all()
+        .await
+        .or(Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR))?
+        .into_iter()
+        .filter(|api_token| visible(&caller, api_token))
+        .collect();
+    Ok((axum::http::StatusCode::OK, axum::Json(tokens)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/tokens/{id}",
+    tag = "tokens",
+    params(("id" = i32, Path, description = "Token id")),
+    responses(
+        (status = 204, description = "Token revoked"),
+        (status = 404, description = "Token not found or not the caller's"),
+    ),
+)]
+pub async fn delete_token<A: crate::repositories::token::ApiTokenRepository>(
+    axum::extract::Path(id): axum::extract::Path<i32>,
+    axum::extract::Extension(caller): axum::extract::Extension<crate::auth::Caller>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<A>>,
+) -> axum::http::StatusCode {
+    match repository.find(id).await {
+        Ok(api_token) if visible(&caller, &api_token) => {}
+        _ => return axum::http::StatusCode::NOT_FOUND,
+    }
+    repository
+        .delete(id)
+        .await
+        .map(|_| axum::http::StatusCode::NO_CONTENT)
+        .unwrap_or(axum::http::StatusCode::NOT_FOUND)
+}
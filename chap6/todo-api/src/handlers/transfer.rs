@@ -0,0 +1,138 @@
+use super::*;
+
+#[derive(Debug, serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportQuery {
+    #[serde(default)]
+    format: crate::transfer::TransferFormat,
+}
+
+#[derive(Debug, serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ImportQuery {
+    #[serde(default)]
+    format: crate::transfer::TransferFormat,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[derive(Debug, serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct MarkdownImportQuery {
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[utoipa::path(
+    get,
+    path = "/export",
+    tag = "transfer",
+    params(ExportQuery),
+    responses(
+        (status = 200, description = "Todos in the requested format", body = String),
+    ),
+)]
+pub async fn export_todos<T: crate::repositories::todo::TodoRepository>(
+    axum::extract::Query(query): axum::extract::Query<ExportQuery>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<T>>,
+    axum::extract::Extension(memberships): axum::extract::Extension<crate::workspaces::Memberships>,
+) -> Result<impl axum::response::IntoResponse, axum::http::StatusCode> {
+    let todos = futures::TryStreamExt::try_filter(repository.stream(), move |todo| {
+        futures::future::ready(memberships.can_view(todo.workspace_id))
+    });
+    let records = futures::TryStreamExt::map_ok(todos, crate::transfer::TodoRecord::from);
+    let chunks = crate::transfer::encode(query.format, records);
+    let headers = axum::response::Headers(vec![(axum::http::header::CONTENT_TYPE, query.format.content_type())]);
+    Ok((headers, axum::body::StreamBody::new(chunks)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/import",
+    tag = "transfer",
+    params(ImportQuery, ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key replay the first response instead of running again")),
+    request_body(content = String, description = "Todos in the given format"),
+    responses(
+        (status = 200, description = "Import result", body = crate::transfer::ImportReport),
+        (status = 400, description = "Body could not be parsed"),
+        (status = 409, description = "A request with the same Idempotency-Key is still in progress"),
+        (status = 422, description = "Idempotency-Key was already used for a different request"),
+    ),
+)]
+pub async fn import_todos<T: crate::repositories::todo::TodoRepository, L: crate::repositories::label::LabelRepository>(
+    axum::extract::Query(query): axum::extract::Query<ImportQuery>,
+    body: bytes::Bytes,
+    axum::extract::Extension(todo_repository): axum::extract::Extension<std::sync::Arc<T>>,
+    axum::extract::Extension(label_repository): axum::extract::Extension<std::sync::Arc<L>>,
+) -> Result<impl axum::response::IntoResponse, (axum::http::StatusCode, String)> {
+    let rows = crate::transfer::decode(query.format, &body)
+        .map_err(|e| (axum::http::StatusCode::BAD_REQUEST, format!("Import parse error: {}", e)))?;
+    let report = crate::transfer::import_records(todo_repository.as_ref(), label_repository.as_ref(), rows, query.dry_run)
+        .await
+        .map_err(|e| repository_error_response(e, axum::http::StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok((axum::http::StatusCode::OK, axum::Json(report)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/export/markdown",
+    tag = "transfer",
+    params(crate::repositories::todo::TodoFilter),
+    responses(
+        (status = 200, description = "Todos as a markdown checklist", body = String, content_type = "text/markdown"),
+    ),
+)]
+pub async fn export_markdown<T: crate::repositories::todo::TodoRepository, L: crate::repositories::label::LabelRepository>(
+    axum::extract::Query(filter): axum::extract::Query<crate::repositories::todo::TodoFilter>,
+    axum::extract::Extension(todo_repository): axum::extract::Extension<std::sync::Arc<T>>,
+    axum::extract::Extension(label_repository): axum::extract::Extension<std::sync::Arc<L>>,
+    axum::extract::Extension(memberships): axum::extract::Extension<crate::workspaces::Memberships>,
+) -> Result<impl axum::response::IntoResponse, axum::http::StatusCode> {
+    let todos = todo_repository
+        .all()
+        .await
+        .or(Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR))?;
+    let records: Vec<_> = todos
+        .into_iter()
+        .filter(|todo| filter.matches(todo) && memberships.can_view(todo.workspace_id))
+        .map(crate::transfer::TodoRecord::from)
+        .collect();
+
+    let heading = match filter.label {
+        Some(label_id) => label_repository
+            .all()
+            .await
+            .or(Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR))?
+            .into_iter()
+            .find(|label| label.id == label_id && memberships.can_view(label.workspace_id))
+            .map(|label| label.name),
+        None => None,
+    };
+    let headers = axum::response::Headers(vec![(axum::http::header::CONTENT_TYPE, "text/markdown; charset=utf-8")]);
+    Ok((headers, crate::transfer::markdown::render(heading.as_deref(), &records)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/import/markdown",
+    tag = "transfer",
+    params(MarkdownImportQuery, ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key replay the first response instead of running again")),
+    request_body(content = String, description = "Markdown checklist", content_type = "text/markdown"),
+    responses(
+        (status = 200, description = "Import result", body = crate::transfer::ImportReport),
+        (status = 409, description = "A request with the same Idempotency-Key is still in progress"),
+        (status = 422, description = "Idempotency-Key was already used for a different request"),
+    ),
+)]
+pub async fn import_markdown<T: crate::repositories::todo::TodoRepository, L: crate::repositories::label::LabelRepository>(
+    axum::extract::Query(query): axum::extract::Query<MarkdownImportQuery>,
+    body: String,
+    axum::extract::Extension(todo_repository): axum::extract::Extension<std::sync::Arc<T>>,
+    axum::extract::Extension(label_repository): axum::extract::Extension<std::sync::Arc<L>>,
+) -> Result<impl axum::response::IntoResponse, (axum::http::StatusCode, String)> {
+    let rows = crate::transfer::markdown::parse(&body);
+    let report = crate::transfer::import_records(todo_repository.as_ref(), label_repository.as_ref(), rows, query.dry_run)
+        .await
+        .map_err(|e| repository_error_response(e, axum::http::StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok((axum::http::StatusCode::OK, axum::Json(report)))
+}
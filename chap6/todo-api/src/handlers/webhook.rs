@@ -0,0 +1,107 @@
+use super::*;
+
+#[utoipa::path(
+    post,
+    path = "/webhooks",
+    tag = "webhooks",
+    request_body = crate::repositories::webhook::CreateWebhook,
+    responses(
+        (status = 201, description = "Webhook created; the secret is only returned here", body = crate::repositories::webhook::NewWebhook),
+        (status = 400, description = "Invalid payload, or a url that is not public and not allowlisted"),
+    ),
+)]
+pub async fn create_webhook<W: crate::repositories::webhook::WebhookRepository>(
+    ValidatedJson(payload): ValidatedJson<crate::repositories::webhook::CreateWebhook>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<W>>,
+    axum::extract::Extension(destinations): axum::extract::Extension<crate::webhooks::destination::DestinationPolicy>,
+) -> Result<impl axum::response::IntoResponse, (axum::http::StatusCode, String)> {
+    destinations
+        .check_url(payload.url())
+        .await
+        .map_err(|e| (axum::http::StatusCode::BAD_REQUEST, format!("validation error: url: {}", e)))?;
+    let webhook = repository
+        .create(payload)
+        .await
+        .or(Err((axum::http::StatusCode::INTERNAL_SERVER_ERROR, String::new())))?;
+    Ok((axum::http::StatusCode::CREATED, axum::Json(webhook)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/webhooks",
+    tag = "webhooks",
+    responses(
+        (status = 200, description = "All webhooks", body = [crate::repositories::webhook::Webhook]),
+    ),
+)]
+pub async fn all_webhook<W: crate::repositories::webhook::WebhookRepository>(
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<W>>,
+) -> Result<impl axum::response::IntoResponse, axum::http::StatusCode> {
+    let webhooks = repository
+        .all()
+        .await
+        .or(Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok((axum::http::StatusCode::OK, axum::Json(webhooks)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/webhooks/{id}",
+    tag = "webhooks",
+    params(("id" = i32, Path, description = "Webhook id")),
+    responses(
+        (status = 204, description = "Webhook and its deliveries deleted"),
+        (status = 404, description = "Webhook not found"),
+    ),
+)]
+pub async fn delete_webhook<W: crate::repositories::webhook::WebhookRepository>(
+    axum::extract::Path(id): axum::extract::Path<i32>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<W>>,
+) -> axum::http::StatusCode {
+    repository
+        .delete(id)
+        .await
+        .map(|_| axum::http::StatusCode::NO_CONTENT)
+        .unwrap_or(axum::http::StatusCode::NOT_FOUND)
+}
+
+#[utoipa::path(
+    get,
+    path = "/webhook-deliveries",
+    tag = "webhooks",
+    params(crate::repositories::webhook::DeliveryFilter),
+    responses(
+        (status = 200, description = "Most recent deliveries first", body = [crate::repositories::webhook::Delivery]),
+    ),
+)]
+pub async fn all_delivery<W: crate::repositories::webhook::WebhookRepository>(
+    axum::extract::Query(filter): axum::extract::Query<crate::repositories::webhook::DeliveryFilter>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<W>>,
+) -> Result<impl axum::response::IntoResponse, axum::http::StatusCode> {
+    let deliveries = repository
+        .deliveries(filter)
+        .await
+        .or(Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok((axum::http::StatusCode::OK, axum::Json(deliveries)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/webhook-deliveries/{id}/redeliver",
+    tag = "webhooks",
+    params(("id" = i32, Path, description = "Delivery id")),
+    responses(
+        (status = 202, description = "Delivery queued again", body = crate::repositories::webhook::Delivery),
+        (status = 404, description = "Delivery not found"),
+    ),
+)]
+pub async fn redeliver_delivery<W: crate::repositories::webhook::WebhookRepository>(
+    axum::extract::Path(id): axum::extract::Path<i32>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<W>>,
+) -> Result<impl axum::response::IntoResponse, (axum::http::StatusCode, String)> {
+    let delivery = repository
+        .redeliver(id)
+        .await
+        .map_err(|e| repository_error_response(e, axum::http::StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok((axum::http::StatusCode::ACCEPTED, axum::Json(delivery)))
+}
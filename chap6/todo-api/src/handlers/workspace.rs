@@ -0,0 +1,227 @@
+use super::*;
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, validator::Validate, utoipa::ToSchema)]
+pub struct CreateWorkspace {
+    #[validate(length(min=1, message="Can not be empty"))]
+    #[validate(length(max=100, message="over text length"))]
+    name: String,
+}
+
+/// A workspace along with the caller's role in it, when the caller is a user.
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, utoipa::ToSchema)]
+pub struct WorkspaceWithRole {
+    #[serde(flatten)]
+    pub workspace: crate::repositories::workspace::Workspace,
+    pub role: Option<crate::repositories::workspace::Role>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, validator::Validate, utoipa::ToSchema)]
+pub struct UpdateMember {
+    pub role: crate::repositories::workspace::Role,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, validator::Validate, utoipa::ToSchema)]
+pub struct AcceptInvite {
+    #[validate(length(min=1, message="Can not be empty"))]
+    pub token: String,
+}
+
+fn user_id(caller: &crate::auth::Caller, action: &'static str) -> Result<i32, crate::workspaces::WorkspaceError> {
+    caller.user_id().ok_or(crate::workspaces::WorkspaceError::UserRequired(action))
+}
+
+/// Checks the caller's role before loading the workspace, so non-members can not tell it exists.
+async fn authorize<W: crate::repositories::workspace::WorkspaceRepository>(
+    repository: &W,
+    memberships: &crate::workspaces::Memberships,
+    id: i32,
+    permission: crate::workspaces::Permission,
+) -> Result<crate::repositories::workspace::Workspace, axum::response::Response> {
+    memberships
+        .check(Some(id), permission)
+        .map_err(axum::response::IntoResponse::into_response)?;
+    repository.find(id).await.map_err(|e| {
+        axum::response::IntoResponse::into_response(repository_error_response(e, axum::http::StatusCode::INTERNAL_SERVER_ERROR))
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/workspaces",
+    tag = "workspaces",
+    request_body = CreateWorkspace,
+    responses(
+        (status = 201, description = "Workspace created with the caller as its owner", body = WorkspaceWithRole),
+        (status = 400, description = "Invalid payload"),
+        (status = 403, description = "Caller is not a user or one of their tokens"),
+    ),
+)]
+pub async fn create_workspace<W: crate::repositories::workspace::WorkspaceRepository>(
+    ValidatedJson(payload): ValidatedJson<CreateWorkspace>,
+    axum::extract::Extension(caller): axum::extract::Extension<crate::auth::Caller>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<W>>,
+) -> Result<impl axum::response::IntoResponse, axum::response::Response> {
+    let owner_id = user_id(&caller, "own workspaces").map_err(axum::response::IntoResponse::into_response)?;
+    let workspace = repository
+        .create(payload.name, owner_id)
+        .await
+        .map_err(|_| axum::response::IntoResponse::into_response(axum::http::StatusCode::INTERNAL_SERVER_ERROR))?;
+    let workspace = WorkspaceWithRole { workspace, role: Some(crate::repositories::workspace::Role::Owner) };
+    Ok((axum::http::StatusCode::CREATED, axum::Json(workspace)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/workspaces",
+    tag = "workspaces",
+    responses(
+        (status = 200, description = "Workspaces the caller is a member of; every workspace for callers that are not users", body = [WorkspaceWithRole]),
+    ),
+)]
+pub async fn all_workspace<W: crate::repositories::workspace::WorkspaceRepository>(
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<W>>,
+    axum::extract::Extension(memberships): axum::extract::Extension<crate::workspaces::Memberships>,
+) -> Result<impl axum::response::IntoResponse, axum::http::StatusCode> {
+    let workspaces: Vec<_> = repository
+        .all()
+        .await
+        .or(Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR))?
+        .into_iter()
+        .filter(|workspace| memberships.can_view(Some(workspace.id)))
+        .map(|workspace| WorkspaceWithRole { role: memberships.role(workspace.id), workspace })
+        .collect();
+    Ok((axum::http::StatusCode::OK, axum::Json(workspaces)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/workspaces/{id}/members",
+    tag = "workspaces",
+    params(("id" = i32, Path, description = "Workspace id")),
+    responses(
+        (status = 200, description = "Members in the order they joined", body = [crate::repositories::workspace::Member]),
+        (status = 404, description = "Workspace not found"),
+    ),
+)]
+pub async fn all_member<W: crate::repositories::workspace::WorkspaceRepository>(
+    axum::extract::Path(id): axum::extract::Path<i32>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<W>>,
+    axum::extract::Extension(memberships): axum::extract::Extension<crate::workspaces::Memberships>,
+) -> Result<impl axum::response::IntoResponse, axum::response::Response> {
+    authorize(repository.as_ref(), &memberships, id, crate::workspaces::Permission::View).await?;
+    let members = repository
+        .members(id)
+        .await
+        .map_err(|_| axum::response::IntoResponse::into_response(axum::http::StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok((axum::http::StatusCode::OK, axum::Json(members)))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/workspaces/{id}/members/{user_id}",
+    tag = "workspaces",
+    params(("id" = i32, Path, description = "Workspace id"), ("user_id" = i32, Path, description = "User id")),
+    request_body = UpdateMember,
+    responses(
+        (status = 200, description = "Role changed", body = crate::repositories::workspace::Member),
+        (status = 403, description = "Caller is not an owner"),
+        (status = 404, description = "Workspace or member not found"),
+        (status = 409, description = "The workspace would be left without an owner"),
+    ),
+)]
+pub async fn update_member<W: crate::repositories::workspace::WorkspaceRepository>(
+    axum::extract::Path((id, user_id)): axum::extract::Path<(i32, i32)>,
+    ValidatedJson(payload): ValidatedJson<UpdateMember>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<W>>,
+    axum::extract::Extension(memberships): axum::extract::Extension<crate::workspaces::Memberships>,
+) -> Result<impl axum::response::IntoResponse, axum::response::Response> {
+    authorize(repository.as_ref(), &memberships, id, crate::workspaces::Permission::Manage).await?;
+    let member = repository.set_role(id, user_id, payload.role).await.map_err(|e| {
+        axum::response::IntoResponse::into_response(repository_error_response(e, axum::http::StatusCode::INTERNAL_SERVER_ERROR))
+    })?;
+    Ok((axum::http::StatusCode::OK, axum::Json(member)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/workspaces/{id}/members/{user_id}",
+    tag = "workspaces",
+    params(("id" = i32, Path, description = "Workspace id"), ("user_id" = i32, Path, description = "User id")),
+    responses(
+        (status = 204, description = "Member removed, or left the workspace"),
+        (status = 403, description = "Caller is not an owner and not removing themselves"),
+        (status = 404, description = "Workspace or member not found"),
+        (status = 409, description = "The workspace would be left without an owner"),
+    ),
+)]
+pub async fn delete_member<W: crate::repositories::workspace::WorkspaceRepository>(
+    axum::extract::Path((id, user_id)): axum::extract::Path<(i32, i32)>,
+    axum::extract::Extension(caller): axum::extract::Extension<crate::auth::Caller>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<W>>,
+    axum::extract::Extension(memberships): axum::extract::Extension<crate::workspaces::Memberships>,
+) -> Result<impl axum::response::IntoResponse, axum::response::Response> {
+    // every member may leave
+    let permission = match caller.user_id() {
+        Some(id) if id == user_id => crate::workspaces::Permission::View,
+        _ => crate::workspaces::Permission::Manage,
+    };
+    authorize(repository.as_ref(), &memberships, id, permission).await?;
+    repository.remove_member(id, user_id).await.map_err(|e| {
+        axum::response::IntoResponse::into_response(repository_error_response(e, axum::http::StatusCode::INTERNAL_SERVER_ERROR))
+    })?;
+    Ok(axum::http::StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/workspaces/{id}/invites",
+    tag = "workspaces",
+    params(("id" = i32, Path, description = "Workspace id")),
+    request_body = crate::repositories::workspace::CreateInvite,
+    responses(
+        (status = 201, description = "Invite created; the token is only returned here", body = crate::repositories::workspace::NewInvite),
+        (status = 400, description = "Invalid payload"),
+        (status = 403, description = "Caller is not an owner"),
+        (status = 404, description = "Workspace not found"),
+    ),
+)]
+pub async fn create_invite<W: crate::repositories::workspace::WorkspaceRepository>(
+    axum::extract::Path(id): axum::extract::Path<i32>,
+    ValidatedJson(payload): ValidatedJson<crate::repositories::workspace::CreateInvite>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<W>>,
+    axum::extract::Extension(memberships): axum::extract::Extension<crate::workspaces::Memberships>,
+) -> Result<impl axum::response::IntoResponse, axum::response::Response> {
+    authorize(repository.as_ref(), &memberships, id, crate::workspaces::Permission::Manage).await?;
+    let invite = repository
+        .create_invite(id, payload)
+        .await
+        .map_err(|_| axum::response::IntoResponse::into_response(axum::http::StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok((axum::http::StatusCode::CREATED, axum::Json(invite)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/invites/accept",
+    tag = "workspaces",
+    request_body = AcceptInvite,
+    responses(
+        (status = 200, description = "Joined the workspace; members who already were keep their role", body = crate::repositories::workspace::Member),
+        (status = 403, description = "Caller is not a user or one of their tokens"),
+        (status = 404, description = "Invite unknown, expired or already accepted"),
+    ),
+)]
+pub async fn accept_invite<W: crate::repositories::workspace::WorkspaceRepository>(
+    ValidatedJson(payload): ValidatedJson<AcceptInvite>,
+    axum::extract::Extension(caller): axum::extract::Extension<crate::auth::Caller>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<W>>,
+) -> Result<impl axum::response::IntoResponse, axum::response::Response> {
+    let user_id = user_id(&caller, "accept invites").map_err(axum::response::IntoResponse::into_response)?;
+    let member = repository
+        .accept_invite(&payload.token, user_id)
+        .await
+        .map_err(|_| axum::response::IntoResponse::into_response(axum::http::StatusCode::INTERNAL_SERVER_ERROR))?
+        .ok_or_else(|| {
+            axum::response::IntoResponse::into_response((axum::http::StatusCode::NOT_FOUND, "invite unknown, expired or already accepted".to_string()))
+        })?;
+    Ok((axum::http::StatusCode::OK, axum::Json(member)))
+}
@@ -0,0 +1,659 @@
+//! `/ws`: live todo lists over a WebSocket.
+//!
+//! A connection authenticates with its first message, subscribes to lists (a label id, or
+//! `null` for every todo) and then receives sequenced change events. The `auth` message carries
+//! an API token, or no token to act as whoever opened the socket (a logged in user's session
+//! cookie, or open access); a session only counts when the page is from an allowed origin. Each
+//! connection sees and changes only what its caller could over REST: the scopes of its token and
+//! its roles in workspaces are checked on every mutation and every outgoing event.
+//!
+//! Creating, toggling and deleting todos go through the same `TodoRepository` as the REST
+//! handlers, so the resulting events reach every subscriber, including the sender. A client
+//! that reconnects passes the last `seq` it saw as `since` and gets the missed events replayed,
+//! or `resync` when they are no longer available.
+
+use super::*;
+use crate::auth::Caller;
+
+/// How often the server pings; a connection silent for two intervals is closed.
+const HEARTBEAT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Time a new connection has to send its `auth` message.
+const AUTH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Largest client message accepted.
+const MAX_MESSAGE_BYTES: usize = 64 * 1024;
+
+/// Close code for failed or missing authentication (policy violation).
+const CLOSE_UNAUTHORIZED: u16 = 1008;
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    /// Without a token the connection acts as the caller who opened it.
+    Auth {
+        token: Option<String>,
+    },
+    /// Replaces the connection's subscriptions.
+    Subscribe {
+        lists: Vec<Option<i32>>,
+        since: Option<u64>,
+    },
+    Create {
+        request_id: Option<String>,
+        text: String,
+        #[serde(default)]
+        labels: Vec<i32>,
+    },
+    Toggle {
+        request_id: Option<String>,
+        id: i32,
+    },
+    Delete {
+        request_id: Option<String>,
+        id: i32,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum Change {
+    Created { todo: crate::repositories::todo::TodoEntity },
+    Updated { todo: crate::repositories::todo::TodoEntity },
+    Deleted { id: i32 },
+}
+
+impl From<crate::events::TodoEvent> for Change {
+    fn from(event: crate::events::TodoEvent) -> Self {
+        match event {
+            crate::events::TodoEvent::Created(todo) => Change::Created { todo },
+            crate::events::TodoEvent::Updated(todo) => Change::Updated { todo },
+            crate::events::TodoEvent::Deleted { id, .. } => Change::Deleted { id },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    Ready {
+        seq: u64,
+    },
+    Subscribed {
+        lists: Vec<Option<i32>>,
+        seq: u64,
+    },
+    Event {
+        seq: u64,
+        #[serde(flatten)]
+        change: Change,
+    },
+    /// Missed events are gone from the history; reload the lists and continue from `seq`.
+    Resync {
+        seq: u64,
+    },
+    Result {
+        request_id: Option<String>,
+        todo: Option<crate::repositories::todo::TodoEntity>,
+    },
+    Error {
+        request_id: Option<String>,
+        status: u16,
+        message: String,
+    },
+}
+
+impl ServerMessage {
+    fn error(request_id: Option<String>, (status, message): (axum::http::StatusCode, String)) -> Self {
+        ServerMessage::Error { request_id, status: status.as_u16(), message }
+    }
+}
+
+fn auth_error(error: crate::auth::AuthError) -> (axum::http::StatusCode, String) {
+    let status = match error {
+        crate::auth::AuthError::Unauthenticated => axum::http::StatusCode::UNAUTHORIZED,
+        crate::auth::AuthError::MissingScope(_) => axum::http::StatusCode::FORBIDDEN,
+    };
+    (status, error.to_string())
+}
+
+/// Like `crate::workspaces::concealed_response`, non-members are told the todo does not exist.
+fn workspace_error(error: crate::workspaces::WorkspaceError) -> (axum::http::StatusCode, String) {
+    match error {
+        crate::workspaces::WorkspaceError::NotFound(_) => (axum::http::StatusCode::NOT_FOUND, "Not Found".to_string()),
+        e @ crate::workspaces::WorkspaceError::ForeignLabel(_) => (axum::http::StatusCode::BAD_REQUEST, e.to_string()),
+        e => (axum::http::StatusCode::FORBIDDEN, e.to_string()),
+    }
+}
+
+struct Connection<T, L> {
+    repository: std::sync::Arc<T>,
+    label_repository: std::sync::Arc<L>,
+    events: crate::events::TodoEvents,
+    caller: Caller,
+    memberships: crate::workspaces::Memberships,
+    lists: std::collections::HashSet<Option<i32>>,
+    /// Todos this connection has been told about, so it also hears when they leave its lists.
+    seen: std::collections::HashSet<i32>,
+    last_seq: u64,
+}
+
+impl<T: crate::repositories::todo::TodoRepository, L: crate::repositories::label::LabelRepository> Connection<T, L> {
+    fn new(
+        (repository, label_repository): (std::sync::Arc<T>, std::sync::Arc<L>),
+        events: crate::events::TodoEvents,
+        (caller, memberships): (Caller, crate::workspaces::Memberships),
+        last_seq: u64,
+    ) -> Self {
+        Self {
+            repository,
+            label_repository,
+            events,
+            caller,
+            memberships,
+            lists: std::collections::HashSet::new(),
+            seen: std::collections::HashSet::new(),
+            last_seq,
+        }
+    }
+
+    /// The todo `id`, if the caller may change it.
+    async fn editable(&self, id: i32) -> Result<crate::repositories::todo::TodoEntity, (axum::http::StatusCode, String)> {
+        self.caller.check(crate::auth::TODOS_WRITE).map_err(auth_error)?;
+        let todo = self
+            .repository
+            .find(id)
+            .await
+            .map_err(|e| repository_error_response(e, axum::http::StatusCode::NOT_FOUND))?;
+        self.memberships
+            .check(todo.workspace_id, crate::workspaces::Permission::Edit)
+            .map_err(workspace_error)?;
+        Ok(todo)
+    }
+
+    /// Todos created here are in no workspace, so neither may their labels be.
+    async fn create(
+        &self,
+        payload: crate::repositories::todo::CreateTodo,
+    ) -> Result<crate::repositories::todo::TodoEntity, (axum::http::StatusCode, String)> {
+        self.caller.check(crate::auth::TODOS_WRITE).map_err(auth_error)?;
+        if let Err(e) = validator::Validate::validate(&payload) {
+            return Err((axum::http::StatusCode::BAD_REQUEST, format!("validation error: {}", e).replace('\n', ", ")));
+        }
+        let labels = self
+            .label_repository
+            .all()
+            .await
+            .map_err(|e| repository_error_response(e, axum::http::StatusCode::INTERNAL_SERVER_ERROR))?;
+        crate::workspaces::check_labels(&labels, payload.labels(), None).map_err(workspace_error)?;
+        self.repository
+            .create(payload)
+            .await
+            .map_err(|e| repository_error_response(e, axum::http::StatusCode::INTERNAL_SERVER_ERROR))
+    }
+
+    fn visible(&mut self, event: &crate::events::TodoEvent) -> bool {
+        match event {
+            crate::events::TodoEvent::Created(todo) | crate::events::TodoEvent::Updated(todo) => {
+                let listed = self
+                    .lists
+                    .iter()
+                    .any(|list| list.is_none_or(|label_id| todo.labels.iter().any(|label| label.id == label_id)));
+                if listed {
+                    self.seen.insert(todo.id);
+                    true
+                } else {
+                    // tell the client once that the todo left its lists
+                    self.seen.remove(&todo.id)
+                }
+            }
+            crate::events::TodoEvent::Deleted { id, .. } => {
+                self.seen.remove(id);
+                !self.lists.is_empty()
+            }
+        }
+    }
+
+    /// Turns events into messages, skipping those already delivered or outside the subscriptions.
+    fn deliver(&mut self, events: Vec<crate::events::SequencedEvent>) -> Vec<ServerMessage> {
+        let mut messages = vec![];
+        for (seq, event) in events {
+            if seq <= self.last_seq {
+                continue;
+            }
+            self.last_seq = seq;
+            if event.visible_to(&self.memberships) && self.visible(&event) {
+                messages.push(ServerMessage::Event { seq, change: event.into() });
+            }
+        }
+        messages
+    }
+
+    /// Replays everything after `seq`, or asks the client to reload when that is impossible.
+    fn replay(&mut self, seq: u64) -> Vec<ServerMessage> {
+        match self.events.since(seq) {
+            Some(events) => {
+                self.last_seq = self.last_seq.min(seq);
+                self.deliver(events)
+            }
+            None => {
+                self.last_seq = self.events.last_seq();
+                vec![ServerMessage::Resync { seq: self.last_seq }]
+            }
+        }
+    }
+
+    async fn handle(&mut self, message: ClientMessage) -> Vec<ServerMessage> {
+        match message {
+            ClientMessage::Auth { .. } => vec![ServerMessage::Error {
+                request_id: None,
+                status: axum::http::StatusCode::BAD_REQUEST.as_u16(),
+                message: "already authenticated".to_string(),
+            }],
+            ClientMessage::Subscribe { lists, since } => {
+                self.lists = lists.iter().copied().collect();
+                self.seen.clear();
+                let mut messages = vec![ServerMessage::Subscribed { lists, seq: self.last_seq }];
+                if let Some(since) = since {
+                    messages.extend(self.replay(since));
+                }
+                messages
+            }
+            ClientMessage::Create { request_id, text, labels } => {
+                match self.create(crate::repositories::todo::CreateTodo::new(text, labels, None, None)).await {
+                    Ok(todo) => vec![ServerMessage::Result { request_id, todo: Some(todo) }],
+                    Err(e) => vec![ServerMessage::error(request_id, e)],
+                }
+            }
+            ClientMessage::Toggle { request_id, id } => {
+                let toggled = match self.editable(id).await {
+                    Ok(todo) => {
+                        let payload = crate::repositories::todo::UpdateTodo::new(None, Some(!todo.completed), None);
+                        self.repository
+                            .update(id, payload)
+                            .await
+                            .map_err(|e| repository_error_response(e, axum::http::StatusCode::NOT_FOUND))
+                    }
+                    Err(e) => Err(e),
+                };
+                match toggled {
+                    Ok(todo) => vec![ServerMessage::Result { request_id, todo: Some(todo) }],
+                    Err(e) => vec![ServerMessage::error(request_id, e)],
+                }
+            }
+            ClientMessage::Delete { request_id, id } => {
+                let deleted = match self.editable(id).await {
+                    Ok(_) => self
+                        .repository
+                        .delete(id)
+                        .await
+                        .map_err(|e| repository_error_response(e, axum::http::StatusCode::NOT_FOUND)),
+                    Err(e) => Err(e),
+                };
+                match deleted {
+                    Ok(()) => vec![ServerMessage::Result { request_id, todo: None }],
+                    Err(e) => vec![ServerMessage::error(request_id, e)],
+                }
+            }
+        }
+    }
+}
+
+fn parse(text: &str) -> Result<ClientMessage, String> {
+    serde_json::from_str(text).map_err(|e| format!("Json parse error: {}", e))
+}
+
+async fn send(socket: &mut axum::extract::ws::WebSocket, message: &ServerMessage) -> Result<(), axum::Error> {
+    socket
+        .send(axum::extract::ws::Message::Text(serde_json::to_string(message).unwrap()))
+        .await
+}
+
+async fn close(socket: &mut axum::extract::ws::WebSocket, code: u16, reason: &'static str) {
+    let frame = axum::extract::ws::CloseFrame { code, reason: reason.into() };
+    let _ = socket.send(axum::extract::ws::Message::Close(Some(frame))).await;
+}
+
+/// Repositories and settings a connection authenticates against.
+struct Authenticator<A, U, W> {
+    config: crate::auth::AuthConfig,
+    tokens: std::sync::Arc<A>,
+    users: std::sync::Arc<U>,
+    workspaces: std::sync::Arc<W>,
+}
+
+impl<A: crate::repositories::token::ApiTokenRepository, U: crate::repositories::user::UserRepository, W: crate::repositories::workspace::WorkspaceRepository>
+    Authenticator<A, U, W>
+{
+    /// Waits for the `auth` message and resolves its token, or takes `handshake`, the caller who
+    /// opened the socket, without one. Anything else, nothing within `AUTH_TIMEOUT`, or a caller
+    /// that may not read todos closes the socket.
+    async fn authenticate(
+        &self,
+        socket: &mut axum::extract::ws::WebSocket,
+        handshake: Caller,
+    ) -> Option<(Caller, crate::workspaces::Memberships)> {
+        let first = tokio::time::timeout(AUTH_TIMEOUT, async {
+            loop {
+                match socket.recv().await {
+                    Some(Ok(axum::extract::ws::Message::Text(text))) => return parse(&text).ok(),
+                    Some(Ok(axum::extract::ws::Message::Ping(_) | axum::extract::ws::Message::Pong(_))) => continue,
+                    _ => return None,
+                }
+            }
+        })
+        .await;
+        let caller = match first {
+            Ok(Some(ClientMessage::Auth { token: Some(token) })) => {
+                crate::auth::resolve_caller(&self.config, self.tokens.as_ref(), self.users.as_ref(), Some(&token), None).await
+            }
+            Ok(Some(ClientMessage::Auth { token: None })) => Ok(handshake),
+            _ => Ok(Caller::Unauthenticated),
+        };
+        let identity = match caller {
+            Ok(caller) if caller.check(crate::auth::TODOS_READ).is_ok() => crate::workspaces::memberships(self.workspaces.as_ref(), &caller)
+                .await
+                .map(|memberships| Some((caller, memberships))),
+            Ok(_) => Ok(None),
+            Err(e) => Err(e),
+        };
+        match identity {
+            Ok(Some(identity)) => Some(identity),
+            Ok(None) => {
+                close(socket, CLOSE_UNAUTHORIZED, "unauthorized").await;
+                None
+            }
+            Err(e) => {
+                tracing::error!("failed to authenticate websocket: {}", e);
+                close(socket, 1011, "internal error").await;
+                None
+            }
+        }
+    }
+}
+
+/// The caller who opened the socket, not counting a session cookie that a page from an origin
+/// that is not allowed sent along (cross-site WebSocket hijacking).
+fn handshake_caller(
+    caller: Caller,
+    headers: &axum::http::HeaderMap,
+    auth_config: &crate::auth::AuthConfig,
+    security_config: &crate::security::SecurityConfig,
+) -> Caller {
+    let foreign = headers
+        .get(axum::http::header::ORIGIN)
+        .is_some_and(|origin| !security_config.allows_origin(origin));
+    match caller {
+        Caller::User { .. } if foreign && auth_config.required() => Caller::Unauthenticated,
+        Caller::User { .. } if foreign => Caller::Open,
+        caller => caller,
+    }
+}
+
+async fn run<
+    T: crate::repositories::todo::TodoRepository,
+    L: crate::repositories::label::LabelRepository,
+    A: crate::repositories::token::ApiTokenRepository,
+    U: crate::repositories::user::UserRepository,
+    W: crate::repositories::workspace::WorkspaceRepository,
+>(
+    mut socket: axum::extract::ws::WebSocket,
+    repositories: (std::sync::Arc<T>, std::sync::Arc<L>),
+    events: crate::events::TodoEvents,
+    authenticator: Authenticator<A, U, W>,
+    handshake: Caller,
+) {
+    let identity = match authenticator.authenticate(&mut socket, handshake).await {
+        Some(identity) => identity,
+        None => return,
+    };
+    let (seq, mut receiver) = events.subscribe();
+    let mut connection = Connection::new(repositories, events, identity, seq);
+    if send(&mut socket, &ServerMessage::Ready { seq }).await.is_err() {
+        return;
+    }
+
+    let mut heartbeat = tokio::time::interval_at(tokio::time::Instant::now() + HEARTBEAT_INTERVAL, HEARTBEAT_INTERVAL);
+    let mut last_activity = tokio::time::Instant::now();
+    loop {
+        let messages = tokio::select! {
+            message = socket.recv() => {
+                last_activity = tokio::time::Instant::now();
+                match message {
+                    Some(Ok(axum::extract::ws::Message::Text(text))) => match parse(&text) {
+                        Ok(message) => connection.handle(message).await,
+                        Err(message) => vec![ServerMessage::error(None, (axum::http::StatusCode::BAD_REQUEST, message))],
+                    },
+                    Some(Ok(axum::extract::ws::Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => vec![],
+                }
+            }
+            event = receiver.recv() => match event {
+                Ok(event) => connection.deliver(vec![event]),
+                // the client read too slowly; catch up from the history instead of dropping events
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {
+                    let last_seq = connection.last_seq;
+                    connection.replay(last_seq)
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            },
+            _ = heartbeat.tick() => {
+                if last_activity.elapsed() > HEARTBEAT_INTERVAL * 2 {
+                    close(&mut socket, 1001, "heartbeat timeout").await;
+                    break;
+                }
+                if socket.send(axum::extract::ws::Message::Ping(vec![])).await.is_err() {
+                    break;
+                }
+                vec![]
+            }
+        };
+        for message in messages.iter() {
+            if send(&mut socket, message).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/ws",
+    tag = "todos",
+    responses(
+        (status = 101, description = "Upgraded to a WebSocket carrying JSON messages; see handlers::ws"),
+        (status = 400, description = "Not a WebSocket upgrade request"),
+    ),
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn ws<
+    T: crate::repositories::todo::TodoRepository,
+    L: crate::repositories::label::LabelRepository,
+    A: crate::repositories::token::ApiTokenRepository,
+    U: crate::repositories::user::UserRepository,
+    W: crate::repositories::workspace::WorkspaceRepository,
+>(
+    upgrade: axum::extract::ws::WebSocketUpgrade,
+    headers: axum::http::HeaderMap,
+    axum::extract::Extension(caller): axum::extract::Extension<Caller>,
+    axum::extract::Extension(repository): axum::extract::Extension<std::sync::Arc<T>>,
+    axum::extract::Extension(label_repository): axum::extract::Extension<std::sync::Arc<L>>,
+    axum::extract::Extension(events): axum::extract::Extension<crate::events::TodoEvents>,
+    axum::extract::Extension(security_config): axum::extract::Extension<crate::security::SecurityConfig>,
+    axum::extract::Extension(config): axum::extract::Extension<crate::auth::AuthConfig>,
+    axum::extract::Extension(tokens): axum::extract::Extension<std::sync::Arc<A>>,
+    axum::extract::Extension(users): axum::extract::Extension<std::sync::Arc<U>>,
+    axum::extract::Extension(workspaces): axum::extract::Extension<std::sync::Arc<W>>,
+) -> impl axum::response::IntoResponse {
+    let handshake = handshake_caller(caller, &headers, &config, &security_config);
+    let authenticator = Authenticator { config, tokens, users, workspaces };
+    upgrade
+        .max_message_size(MAX_MESSAGE_BYTES)
+        .on_upgrade(move |socket| run(socket, (repository, label_repository), events, authenticator, handshake))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    type TestConnection = Connection<
+        crate::events::TodoRepositoryWithEvents<crate::repositories::todo::test_utils::TodoRepositoryForMemory>,
+        crate::repositories::label::test_utils::LabelRepositoryForMemory,
+    >;
+
+    /// A connection for `caller`, with the labels home (1) and work (2) and team (3) in workspace 1.
+    async fn connection_as(caller: Caller, memberships: crate::workspaces::Memberships) -> TestConnection {
+        use crate::repositories::label::LabelRepository;
+        let events = crate::events::TodoEvents::new(crate::events::EVENT_CAPACITY);
+        let label_repository = crate::repositories::label::test_utils::LabelRepositoryForMemory::new();
+        let mut labels = vec![];
+        for (name, workspace_id) in [("home", None), ("work", None), ("team", Some(1))] {
+            labels.push(label_repository.create(name.to_string(), workspace_id).await.unwrap());
+        }
+        let repository = crate::events::TodoRepositoryWithEvents::new(
+            crate::repositories::todo::test_utils::TodoRepositoryForMemory::new(labels),
+            events.clone(),
+        );
+        let repositories = (std::sync::Arc::new(repository), std::sync::Arc::new(label_repository));
+        Connection::new(repositories, events, (caller, memberships), 0)
+    }
+
+    async fn connection() -> TestConnection {
+        connection_as(Caller::Open, crate::workspaces::Memberships::new(Default::default())).await
+    }
+
+    /// Another connection to the same repositories, acting for `caller`.
+    fn beside(other: &TestConnection, (caller, memberships): (Caller, crate::workspaces::Memberships), seq: u64) -> TestConnection {
+        let repositories = (other.repository.clone(), other.label_repository.clone());
+        Connection::new(repositories, other.events.clone(), (caller, memberships), seq)
+    }
+
+    fn create(text: &str, labels: Vec<i32>) -> ClientMessage {
+        ClientMessage::Create { request_id: Some(text.to_string()), text: text.to_string(), labels }
+    }
+
+    fn changes(messages: &[ServerMessage]) -> Vec<(u64, &Change)> {
+        messages
+            .iter()
+            .filter_map(|message| match message {
+                ServerMessage::Event { seq, change } => Some((*seq, change)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parse_client_messages() {
+        assert_eq!(
+            Ok(ClientMessage::Subscribe { lists: vec![Some(1), None], since: Some(4) }),
+            parse(r#"{"type":"subscribe","lists":[1,null],"since":4}"#)
+        );
+        assert_eq!(Ok(create("milk", vec![])), parse(r#"{"type":"create","request_id":"milk","text":"milk"}"#));
+        assert!(parse(r#"{"type":"rename"}"#).is_err());
+        assert_eq!(Ok(ClientMessage::Auth { token: None }), parse(r#"{"type":"auth"}"#));
+        assert_eq!(
+            Ok(ClientMessage::Auth { token: Some("secret".to_string()) }),
+            parse(r#"{"type":"auth","token":"secret"}"#)
+        );
+    }
+
+    #[test]
+    fn sessions_only_count_from_allowed_origins() {
+        let security = crate::security::SecurityConfig::new(vec!["http://localhost:3001".parse().unwrap()]);
+        let open = crate::auth::AuthConfig::new(false, None);
+        let required = crate::auth::AuthConfig::new(true, None);
+        let user = Caller::User { id: 1 };
+        let mut headers = axum::http::HeaderMap::new();
+        assert_eq!(user, handshake_caller(user.clone(), &headers, &required, &security));
+        headers.insert(axum::http::header::ORIGIN, "http://localhost:3001".parse().unwrap());
+        assert_eq!(user, handshake_caller(user.clone(), &headers, &required, &security));
+        headers.insert(axum::http::header::ORIGIN, "https://evil.example".parse().unwrap());
+        assert_eq!(Caller::Unauthenticated, handshake_caller(user.clone(), &headers, &required, &security));
+        assert_eq!(Caller::Open, handshake_caller(user, &headers, &open, &security));
+    }
+
+    #[tokio::test]
+    async fn mutations_reach_list_subscribers() {
+        let mut sender = connection().await;
+        let (seq, mut receiver) = sender.events.subscribe();
+        let mut watcher = beside(&sender, (sender.caller.clone(), sender.memberships.clone()), seq);
+        watcher.handle(ClientMessage::Subscribe { lists: vec![Some(1)], since: None }).await;
+
+        let created = match &sender.handle(create("milk", vec![1])).await[..] {
+            [ServerMessage::Result { todo: Some(todo), .. }] => todo.clone(),
+            messages => panic!("unexpected messages: {:?}", messages),
+        };
+        sender.handle(create("report", vec![2])).await;
+        sender.handle(ClientMessage::Toggle { request_id: None, id: created.id }).await;
+        let missing = sender.handle(ClientMessage::Toggle { request_id: None, id: 99 }).await;
+        assert!(matches!(missing[..], [ServerMessage::Error { status: 404, .. }]));
+        let empty = sender.handle(create("", vec![])).await;
+        assert!(matches!(empty[..], [ServerMessage::Error { status: 400, .. }]));
+        sender.handle(ClientMessage::Delete { request_id: None, id: created.id }).await;
+
+        let mut live = vec![];
+        while let Ok(event) = receiver.try_recv() {
+            live.extend(watcher.deliver(vec![event]));
+        }
+        let changes = changes(&live);
+        assert_eq!(vec![1, 3, 4], changes.iter().map(|(seq, _)| *seq).collect::<Vec<_>>());
+        assert!(matches!(changes[1].1, Change::Updated { todo } if todo.completed));
+        assert_eq!(&Change::Deleted { id: created.id }, changes[2].1);
+    }
+
+    #[tokio::test]
+    async fn reconnect_replays_missed_events() {
+        let mut writer = connection().await;
+        writer.handle(create("first", vec![1])).await;
+        writer.handle(create("second", vec![2])).await;
+        writer.handle(create("third", vec![1])).await;
+
+        let mut reconnected = beside(&writer, (writer.caller.clone(), writer.memberships.clone()), writer.events.last_seq());
+        let messages = reconnected.handle(ClientMessage::Subscribe { lists: vec![Some(1)], since: Some(1) }).await;
+        assert_eq!(ServerMessage::Subscribed { lists: vec![Some(1)], seq: 3 }, messages[0]);
+        assert_eq!(vec![3], changes(&messages).iter().map(|(seq, _)| *seq).collect::<Vec<_>>());
+
+        let messages = reconnected.handle(ClientMessage::Subscribe { lists: vec![None], since: Some(42) }).await;
+        assert_eq!(ServerMessage::Resync { seq: 3 }, messages[1]);
+    }
+
+    #[tokio::test]
+    async fn todos_leaving_a_list_are_reported_once() {
+        let mut connection = connection().await;
+        connection.lists.insert(Some(1));
+        let payload = crate::repositories::todo::CreateTodo::new("milk".to_string(), vec![1], None, None);
+        let todo = crate::repositories::todo::TodoRepository::create(connection.repository.as_ref(), payload).await.unwrap();
+        assert!(connection.visible(&crate::events::TodoEvent::Created(todo.clone())));
+        let todo = crate::repositories::todo::TodoEntity { labels: vec![], ..todo };
+        assert!(connection.visible(&crate::events::TodoEvent::Updated(todo.clone())));
+        assert!(!connection.visible(&crate::events::TodoEvent::Updated(todo)));
+    }
+
+    #[tokio::test]
+    async fn connections_keep_to_their_caller() {
+        let mut outsider = connection().await;
+        let payload = crate::repositories::todo::CreateTodo::new("secret".to_string(), vec![3], None, None).in_workspace(Some(1));
+        let secret = crate::repositories::todo::TodoRepository::create(outsider.repository.as_ref(), payload).await.unwrap();
+
+        let toggled = outsider.handle(ClientMessage::Toggle { request_id: None, id: secret.id }).await;
+        assert!(matches!(toggled[..], [ServerMessage::Error { status: 404, .. }]));
+        let deleted = outsider.handle(ClientMessage::Delete { request_id: None, id: secret.id }).await;
+        assert!(matches!(deleted[..], [ServerMessage::Error { status: 404, .. }]));
+        let foreign = outsider.handle(create("borrowed", vec![3])).await;
+        assert!(matches!(foreign[..], [ServerMessage::Error { status: 400, .. }]));
+        let replayed = outsider.handle(ClientMessage::Subscribe { lists: vec![None], since: Some(0) }).await;
+        assert!(changes(&replayed).is_empty());
+
+        let roles = std::collections::HashMap::from([(1, crate::repositories::workspace::Role::Viewer)]);
+        let mut viewer = beside(&outsider, (Caller::User { id: 1 }, crate::workspaces::Memberships::new(roles)), 0);
+        let toggled = viewer.handle(ClientMessage::Toggle { request_id: None, id: secret.id }).await;
+        assert!(matches!(toggled[..], [ServerMessage::Error { status: 403, .. }]));
+        let replayed = viewer.handle(ClientMessage::Subscribe { lists: vec![None], since: Some(0) }).await;
+        assert_eq!(1, changes(&replayed).len());
+
+        let reader = Caller::Token { id: 1, user_id: None, scopes: vec![crate::auth::TODOS_READ.to_string()] };
+        let mut reader = beside(&outsider, (reader, crate::workspaces::Memberships::unrestricted()), 0);
+        let created = reader.handle(create("milk", vec![])).await;
+        assert!(matches!(created[..], [ServerMessage::Error { status: 403, .. }]));
+    }
+}
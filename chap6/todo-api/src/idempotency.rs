@@ -0,0 +1,233 @@
+//! `Idempotency-Key` support for endpoints that create things.
+//!
+//! [`idempotent`] is a route layer: the first request carrying a key runs normally and its
+//! response is stored for [`TTL`]; a retry with the same key and the same request gets the
+//! stored response back (marked with `Idempotent-Replayed: true`) instead of creating a
+//! duplicate. Reusing a key for a different request is a 422, and retrying while the first one
+//! is still running is a 409. Server errors are not stored, so those can be retried as is.
+//! Keys are scoped to the caller (see `crate::limits::client_key`), method and path, so one
+//! client can neither replay nor block another's requests; requests without a key are not
+//! affected.
+
+pub const IDEMPOTENCY_KEY: &str = "idempotency-key";
+pub const REPLAYED: &str = "idempotent-replayed";
+
+/// How long a key, and the response stored for it, is kept.
+pub const TTL: std::time::Duration = std::time::Duration::from_secs(24 * 60 * 60);
+
+const MAX_KEY_LENGTH: usize = 255;
+const PURGE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// Identifies a request by what the handler sees: query string, content type and body.
+fn request_hash(parts: &axum::http::request::Parts, body: &[u8]) -> String {
+    use sha2::Digest;
+    let mut hasher = sha2::Sha256::new();
+    hasher.update(parts.uri.query().unwrap_or_default());
+    hasher.update(b"\n");
+    hasher.update(parts.headers.get(axum::http::header::CONTENT_TYPE).map(|value| value.as_bytes()).unwrap_or_default());
+    hasher.update(b"\n");
+    hasher.update(body);
+    hex::encode(hasher.finalize())
+}
+
+fn parse_key(value: &axum::http::HeaderValue) -> Option<String> {
+    let key = value.to_str().ok()?;
+    let valid = !key.is_empty() && key.len() <= MAX_KEY_LENGTH && key.bytes().all(|byte| byte.is_ascii_graphic());
+    valid.then(|| key.to_string())
+}
+
+fn replay(stored: crate::repositories::idempotency::StoredResponse) -> axum::response::Response {
+    let mut response = axum::response::Response::new(axum::body::boxed(axum::body::Full::from(stored.body)));
+    *response.status_mut() = axum::http::StatusCode::from_u16(stored.status).unwrap_or(axum::http::StatusCode::OK);
+    if let Some(content_type) = stored.content_type.and_then(|value| value.parse().ok()) {
+        response.headers_mut().insert(axum::http::header::CONTENT_TYPE, content_type);
+    }
+    response.headers_mut().insert(REPLAYED, axum::http::HeaderValue::from_static("true"));
+    response
+}
+
+fn error(status: axum::http::StatusCode, message: &str) -> axum::response::Response {
+    axum::response::IntoResponse::into_response((status, message.to_string()))
+}
+
+pub async fn idempotent<I: crate::repositories::idempotency::IdempotencyRepository>(
+    request: axum::http::Request<axum::body::Body>,
+    next: axum::middleware::Next<axum::body::Body>,
+) -> axum::response::Response {
+    let key = match request.headers().get(IDEMPOTENCY_KEY) {
+        Some(value) => match parse_key(value) {
+            Some(key) => key,
+            None => return error(axum::http::StatusCode::BAD_REQUEST, "Idempotency-Key must be 1 to 255 visible ASCII characters"),
+        },
+        None => return next.run(request).await,
+    };
+    let repository = request
+        .extensions()
+        .get::<std::sync::Arc<I>>()
+        .cloned()
+        .expect("idempotency repository extension is missing");
+    let scope = format!("{} {} {}", crate::limits::client_key(&request), request.method(), request.uri().path());
+
+    let (parts, body) = request.into_parts();
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(_) => return error(axum::http::StatusCode::BAD_REQUEST, "failed to read request body"),
+    };
+    let hash = request_hash(&parts, &body);
+
+    let ttl = chrono::Duration::from_std(TTL).unwrap();
+    match repository.reserve(&scope, &key, &hash, ttl).await {
+        Ok(crate::repositories::idempotency::Reservation::Reserved) => {}
+        Ok(crate::repositories::idempotency::Reservation::Existing { request_hash, .. }) if request_hash != hash => {
+            return error(axum::http::StatusCode::UNPROCESSABLE_ENTITY, "Idempotency-Key was already used for a different request");
+        }
+        Ok(crate::repositories::idempotency::Reservation::Existing { response: Some(stored), .. }) => return replay(stored),
+        Ok(crate::repositories::idempotency::Reservation::Existing { response: None, .. }) => {
+            return error(axum::http::StatusCode::CONFLICT, "a request with this Idempotency-Key is still in progress");
+        }
+        Err(e) => {
+            tracing::error!("failed to reserve idempotency key: {}", e);
+            return error(axum::http::StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error");
+        }
+    }
+
+    let response = next.run(axum::http::Request::from_parts(parts, axum::body::Body::from(body))).await;
+    if response.status().is_server_error() {
+        if let Err(e) = repository.release(&scope, &key).await {
+            tracing::error!("failed to release idempotency key: {}", e);
+        }
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(_) => {
+            let _ = repository.release(&scope, &key).await;
+            return error(axum::http::StatusCode::INTERNAL_SERVER_ERROR, "Internal Server Error");
+        }
+    };
+    let stored = crate::repositories::idempotency::StoredResponse {
+        status: parts.status.as_u16(),
+        content_type: parts
+            .headers
+            .get(axum::http::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string),
+        body: body.to_vec(),
+    };
+    if let Err(e) = repository.complete(&scope, &key, stored).await {
+        tracing::error!("failed to store idempotent response: {}", e);
+    }
+    axum::response::Response::from_parts(parts, axum::body::boxed(axum::body::Full::from(body)))
+}
+
+pub async fn run_purge<I: crate::repositories::idempotency::IdempotencyRepository>(repository: I) {
+    let mut interval = tokio::time::interval(PURGE_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = repository.purge_expired().await {
+            tracing::error!("failed to purge idempotency keys: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tower::ServiceExt;
+
+    /// A create endpoint that fails with 500 when the body is `fail`, counting the requests it handles.
+    fn app(created: std::sync::Arc<std::sync::atomic::AtomicUsize>) -> axum::Router {
+        let create = move |body: String| {
+            let created = created.clone();
+            async move {
+                let count = created.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+                if body == "fail" {
+                    return Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR);
+                }
+                Ok((axum::http::StatusCode::CREATED, axum::Json(serde_json::json!({ "id": count, "text": body }))))
+            }
+        };
+        let repository = crate::repositories::idempotency::test_utils::IdempotencyRepositoryForMemory::new();
+        axum::Router::new()
+            .route(
+                "/todos",
+                axum::routing::post(create).route_layer(axum::middleware::from_fn(
+                    idempotent::<crate::repositories::idempotency::test_utils::IdempotencyRepositoryForMemory>,
+                )),
+            )
+            .layer(axum::extract::Extension(std::sync::Arc::new(repository)))
+    }
+
+    async fn post(app: &axum::Router, key: Option<&str>, body: &'static str) -> (axum::http::StatusCode, bool, String) {
+        post_as(app, crate::auth::Caller::Open, key, body).await
+    }
+
+    async fn post_as(
+        app: &axum::Router,
+        caller: crate::auth::Caller,
+        key: Option<&str>,
+        body: &'static str,
+    ) -> (axum::http::StatusCode, bool, String) {
+        let mut request = axum::http::Request::post("/todos");
+        if let Some(key) = key {
+            request = request.header(IDEMPOTENCY_KEY, key);
+        }
+        let mut request = request.body(axum::body::Body::from(body)).unwrap();
+        request.extensions_mut().insert(caller);
+        let response = app.clone().oneshot(request).await.unwrap();
+        let replayed = response.headers().contains_key(REPLAYED);
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, replayed, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn retries_replay_the_first_response() {
+        let created = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let app = app(created.clone());
+
+        let (status, replayed, first) = post(&app, Some("key-1"), "milk").await;
+        assert_eq!((axum::http::StatusCode::CREATED, false), (status, replayed));
+        let (status, replayed, retried) = post(&app, Some("key-1"), "milk").await;
+        assert_eq!((axum::http::StatusCode::CREATED, true, first), (status, replayed, retried));
+        assert_eq!(1, created.load(std::sync::atomic::Ordering::SeqCst));
+
+        let (status, _, _) = post(&app, Some("key-1"), "eggs").await;
+        assert_eq!(axum::http::StatusCode::UNPROCESSABLE_ENTITY, status);
+        post(&app, None, "milk").await;
+        post(&app, None, "milk").await;
+        assert_eq!(3, created.load(std::sync::atomic::Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn server_errors_and_bad_keys_are_not_stored() {
+        let created = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let app = app(created.clone());
+
+        assert_eq!(axum::http::StatusCode::INTERNAL_SERVER_ERROR, post(&app, Some("key-2"), "fail").await.0);
+        assert_eq!(axum::http::StatusCode::INTERNAL_SERVER_ERROR, post(&app, Some("key-2"), "fail").await.0);
+        assert_eq!(2, created.load(std::sync::atomic::Ordering::SeqCst));
+
+        assert_eq!(axum::http::StatusCode::BAD_REQUEST, post(&app, Some("with space"), "milk").await.0);
+        assert_eq!(axum::http::StatusCode::BAD_REQUEST, post(&app, Some(""), "milk").await.0);
+        assert_eq!(2, created.load(std::sync::atomic::Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn keys_are_scoped_to_the_caller() {
+        let created = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let app = app(created.clone());
+        let alice = crate::auth::Caller::User { id: 1 };
+        let bob = crate::auth::Caller::User { id: 2 };
+
+        let (_, _, first) = post_as(&app, alice.clone(), Some("key-3"), "milk").await;
+        let (status, replayed, other) = post_as(&app, bob, Some("key-3"), "milk").await;
+        assert_eq!((axum::http::StatusCode::CREATED, false), (status, replayed));
+        assert_ne!(first, other);
+        let (_, replayed, retried) = post_as(&app, alice, Some("key-3"), "milk").await;
+        assert_eq!((true, first), (replayed, retried));
+        assert_eq!(2, created.load(std::sync::atomic::Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,284 @@
+//! Request rate and size limits.
+//!
+//! [`rate_limit`] gives every client a token bucket for reads (`GET`/`HEAD`) and another for
+//! writes; a request finding its bucket empty gets a 429 with `Retry-After`. Clients are told
+//! apart by their API token or logged in user once `crate::auth::authenticate` has verified it, and by their IP
+//! address otherwise. [`limit_body`] rejects bodies above a size with a 413 before any handler
+//! starts parsing them.
+
+/// Bodies accepted by routes that do not say otherwise.
+pub const BODY_LIMIT_BYTES: usize = 1024 * 1024;
+
+const DEFAULT_READS_PER_MINUTE: u32 = 600;
+const DEFAULT_WRITES_PER_MINUTE: u32 = 120;
+const PRUNE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+impl Access {
+    fn of(method: &axum::http::Method) -> Self {
+        match *method {
+            axum::http::Method::GET | axum::http::Method::HEAD | axum::http::Method::OPTIONS => Access::Read,
+            _ => Access::Write,
+        }
+    }
+}
+
+/// Up to `per_minute` requests in a burst, refilled at `per_minute` a minute.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Budget {
+    capacity: f64,
+    per_second: f64,
+}
+
+impl Budget {
+    pub fn per_minute(requests: u32) -> Self {
+        Self { capacity: requests as f64, per_second: requests as f64 / 60.0 }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated_at: std::time::Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, budget: Budget, now: std::time::Instant) {
+        let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * budget.per_second).min(budget.capacity);
+        self.updated_at = now;
+    }
+}
+
+type Buckets = std::collections::HashMap<(String, Access), Bucket>;
+
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    reads: Option<Budget>,
+    writes: Option<Budget>,
+    buckets: std::sync::Arc<std::sync::Mutex<Buckets>>,
+}
+
+impl RateLimiter {
+    /// `None` leaves that kind of request unlimited.
+    pub fn new(reads: Option<Budget>, writes: Option<Budget>) -> Self {
+        Self { reads, writes, buckets: Default::default() }
+    }
+
+    /// Reads `RATE_LIMIT_READS_PER_MINUTE` and `RATE_LIMIT_WRITES_PER_MINUTE`; `0` turns a limit off.
+    pub fn from_env() -> Self {
+        let budget = |name: &str, default: u32| {
+            let requests = std::env::var(name).ok().and_then(|value| value.parse().ok()).unwrap_or(default);
+            (requests > 0).then(|| Budget::per_minute(requests))
+        };
+        Self::new(
+            budget("RATE_LIMIT_READS_PER_MINUTE", DEFAULT_READS_PER_MINUTE),
+            budget("RATE_LIMIT_WRITES_PER_MINUTE", DEFAULT_WRITES_PER_MINUTE),
+        )
+    }
+
+    fn budget(&self, access: Access) -> Option<Budget> {
+        match access {
+            Access::Read => self.reads,
+            Access::Write => self.writes,
+        }
+    }
+
+    /// Takes a token from `client`'s bucket, or says how long until one is available.
+    pub fn acquire(&self, client: &str, access: Access, now: std::time::Instant) -> Result<(), std::time::Duration> {
+        let budget = match self.budget(access) {
+            Some(budget) => budget,
+            None => return Ok(()),
+        };
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets
+            .entry((client.to_string(), access))
+            .or_insert(Bucket { tokens: budget.capacity, updated_at: now });
+        bucket.refill(budget, now);
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(std::time::Duration::from_secs_f64((1.0 - bucket.tokens) / budget.per_second))
+        }
+    }
+
+    /// Forgets buckets that have refilled completely, as they are the same as new ones.
+    fn prune(&self, now: std::time::Instant) {
+        self.buckets.lock().unwrap().retain(|(_, access), bucket| match self.budget(*access) {
+            Some(budget) => {
+                bucket.refill(budget, now);
+                bucket.tokens < budget.capacity
+            }
+            None => false,
+        });
+    }
+}
+
+pub async fn run_prune(limiter: RateLimiter) {
+    let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+    loop {
+        interval.tick().await;
+        limiter.prune(std::time::Instant::now());
+    }
+}
+
+/// Who is behind a request: the authenticated caller, or else the client's address.
+pub fn client_key<B>(request: &axum::http::Request<B>) -> String {
+    match request.extensions().get::<crate::auth::Caller>() {
+        Some(crate::auth::Caller::Token { id, .. }) => return format!("token:{}", id),
+        Some(crate::auth::Caller::User { id }) => return format!("user:{}", id),
+        Some(crate::auth::Caller::Admin) => return "admin".to_string(),
+        _ => {}
+    }
+    match request.extensions().get::<axum::extract::ConnectInfo<std::net::SocketAddr>>() {
+        Some(axum::extract::ConnectInfo(addr)) => format!("ip:{}", addr.ip()),
+        None => "unknown".to_string(),
+    }
+}
+
+pub async fn rate_limit(
+    request: axum::http::Request<axum::body::Body>,
+    next: axum::middleware::Next<axum::body::Body>,
+) -> axum::response::Response {
+    let limiter = request
+        .extensions()
+        .get::<RateLimiter>()
+        .cloned()
+        .expect("rate limiter extension is missing");
+    let access = Access::of(request.method());
+    match limiter.acquire(&client_key(&request), access, std::time::Instant::now()) {
+        Ok(()) => next.run(request).await,
+        Err(wait) => {
+            let retry_after = wait.as_secs_f64().ceil().max(1.0) as u64;
+            let mut response = axum::response::IntoResponse::into_response((
+                axum::http::StatusCode::TOO_MANY_REQUESTS,
+                "Too Many Requests".to_string(),
+            ));
+            response.headers_mut().insert(axum::http::header::RETRY_AFTER, retry_after.into());
+            response
+        }
+    }
+}
+
+fn too_large(limit: usize) -> axum::response::Response {
+    axum::response::IntoResponse::into_response((
+        axum::http::StatusCode::PAYLOAD_TOO_LARGE,
+        format!("request body exceeds {} bytes", limit),
+    ))
+}
+
+/// Route layer rejecting bodies over `limit` bytes, e.g.
+/// `from_fn(|request, next| limit_body(request, next, BODY_LIMIT_BYTES))`.
+///
+/// A declared `Content-Length` is checked up front and enforced by hyper; a body without one
+/// is read up to the limit before the handler sees it.
+pub async fn limit_body(
+    request: axum::http::Request<axum::body::Body>,
+    next: axum::middleware::Next<axum::body::Body>,
+    limit: usize,
+) -> axum::response::Response {
+    let content_length = request
+        .headers()
+        .get(axum::http::header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    match content_length {
+        Some(length) if length > limit as u64 => too_large(limit),
+        Some(_) => next.run(request).await,
+        None => {
+            let (parts, body) = request.into_parts();
+            let body = match hyper::body::to_bytes(http_body::Limited::new(body, limit)).await {
+                Ok(body) => body,
+                Err(e) if e.is::<http_body::LengthLimitError>() => return too_large(limit),
+                Err(_) => {
+                    return axum::response::IntoResponse::into_response((
+                        axum::http::StatusCode::BAD_REQUEST,
+                        "failed to read request body".to_string(),
+                    ))
+                }
+            };
+            next.run(axum::http::Request::from_parts(parts, axum::body::Body::from(body))).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tower::ServiceExt;
+
+    #[test]
+    fn buckets_refill_over_time() {
+        let limiter = RateLimiter::new(Some(Budget::per_minute(60)), None);
+        let start = std::time::Instant::now();
+        for _ in 0..60 {
+            assert!(limiter.acquire("client", Access::Read, start).is_ok());
+        }
+        assert_eq!(Err(std::time::Duration::from_secs(1)), limiter.acquire("client", Access::Read, start));
+        assert!(limiter.acquire("other", Access::Read, start).is_ok());
+        assert!(limiter.acquire("client", Access::Write, start).is_ok());
+
+        let later = start + std::time::Duration::from_secs(2);
+        assert!(limiter.acquire("client", Access::Read, later).is_ok());
+        assert!(limiter.acquire("client", Access::Read, later).is_ok());
+        assert!(limiter.acquire("client", Access::Read, later).is_err());
+
+        limiter.prune(start + std::time::Duration::from_secs(60));
+        assert_eq!(1, limiter.buckets.lock().unwrap().len());
+        limiter.prune(start + std::time::Duration::from_secs(62));
+        assert!(limiter.buckets.lock().unwrap().is_empty());
+    }
+
+    fn app(limiter: RateLimiter) -> axum::Router {
+        axum::Router::new()
+            .route("/todos", axum::routing::get(|| async { "todos" }).post(|body: String| async move { body }))
+            .route_layer(axum::middleware::from_fn(|request, next| limit_body(request, next, 16)))
+            .layer(axum::middleware::from_fn(rate_limit))
+            .layer(axum::extract::Extension(limiter))
+    }
+
+    async fn send(app: &axum::Router, method: &str, client: [u8; 4], body: axum::body::Body) -> axum::response::Response {
+        let mut request = axum::http::Request::builder().method(method).uri("/todos").body(body).unwrap();
+        request
+            .extensions_mut()
+            .insert(axum::extract::ConnectInfo(std::net::SocketAddr::from((client, 40000))));
+        app.clone().oneshot(request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn writes_over_budget_are_rejected_per_client() {
+        let app = app(RateLimiter::new(Some(Budget::per_minute(60)), Some(Budget::per_minute(2))));
+        for _ in 0..2 {
+            assert_eq!(axum::http::StatusCode::OK, send(&app, "POST", [10, 0, 0, 1], axum::body::Body::from("milk")).await.status());
+        }
+        let response = send(&app, "POST", [10, 0, 0, 1], axum::body::Body::from("milk")).await;
+        assert_eq!(axum::http::StatusCode::TOO_MANY_REQUESTS, response.status());
+        assert_eq!("30", response.headers()[axum::http::header::RETRY_AFTER]);
+
+        assert_eq!(axum::http::StatusCode::OK, send(&app, "GET", [10, 0, 0, 1], axum::body::Body::empty()).await.status());
+        assert_eq!(axum::http::StatusCode::OK, send(&app, "POST", [10, 0, 0, 2], axum::body::Body::from("milk")).await.status());
+    }
+
+    #[tokio::test]
+    async fn large_bodies_are_rejected() {
+        let app = app(RateLimiter::new(None, None));
+        let response = send(&app, "POST", [10, 0, 0, 1], axum::body::Body::from("a body that is too long")).await;
+        assert_eq!(axum::http::StatusCode::PAYLOAD_TOO_LARGE, response.status());
+
+        let chunks = |chunks: Vec<&'static str>| {
+            axum::body::Body::wrap_stream(futures::stream::iter(chunks.into_iter().map(Ok::<_, std::io::Error>)))
+        };
+        let response = send(&app, "POST", [10, 0, 0, 1], chunks(vec!["a body ", "that is ", "too long"])).await;
+        assert_eq!(axum::http::StatusCode::PAYLOAD_TOO_LARGE, response.status());
+
+        let response = send(&app, "POST", [10, 0, 0, 1], chunks(vec!["short ", "body"])).await;
+        assert_eq!(axum::http::StatusCode::OK, response.status());
+        assert_eq!(&b"short body"[..], &hyper::body::to_bytes(response.into_body()).await.unwrap()[..]);
+    }
+}
@@ -1,11 +1,28 @@
+mod auth;
+mod blob_store;
+mod events;
+mod graphql;
+mod grpc;
 mod handlers;
+mod idempotency;
+mod limits;
+mod metrics;
+mod oidc;
+mod openapi;
 mod repositories;
+mod security;
+mod telemetry;
+mod transfer;
+mod webhooks;
+mod workspaces;
+#[cfg(test)]
+mod typescript;
 
 #[tokio::main]
 async fn main() {
     let log_level = std::env::var("RUST_LOG").unwrap_or("info".to_string());
     std::env::set_var("RUST_LOG", log_level);
-    tracing_subscriber::fmt::init();
+    crate::telemetry::init();
     dotenv::dotenv().ok();
 
     let database_url = &std::env::var("DATABASE_URL").expect("undefined DATABASE_URL");
@@ -13,43 +30,349 @@ async fn main() {
     let pool = sqlx::PgPool::connect(database_url)
         .await
         .expect("fail connect database");
+    let enforce_blockers = std::env::var("ENFORCE_BLOCKERS")
+        .map(|value| value != "false")
+        .unwrap_or(true);
+    let blob_store: std::sync::Arc<dyn crate::blob_store::BlobStore> = match std::env::var("BLOB_STORE").as_deref() {
+        Ok("memory") => std::sync::Arc::new(crate::blob_store::BlobStoreForMemory::new()),
+        _ => std::sync::Arc::new(crate::blob_store::BlobStoreForFs::new(
+            std::env::var("BLOB_STORE_DIR").unwrap_or("attachments".to_string()),
+        )),
+    };
+    let webhook_repository = crate::repositories::webhook::WebhookRepositoryForDb::new(pool.clone());
+    let webhook_destinations = crate::webhooks::destination::DestinationPolicy::from_env();
+    tokio::spawn(crate::webhooks::run_worker(webhook_repository.clone(), webhook_destinations.clone()));
+    let idempotency_repository = crate::repositories::idempotency::IdempotencyRepositoryForDb::new(pool.clone());
+    tokio::spawn(crate::idempotency::run_purge(idempotency_repository.clone()));
+    let user_repository = crate::repositories::user::UserRepositoryForDb::new(pool.clone());
+    tokio::spawn(crate::oidc::run_purge(user_repository.clone()));
+    let rate_limiter = crate::limits::RateLimiter::from_env();
+    tokio::spawn(crate::limits::run_prune(rate_limiter.clone()));
+    let events = crate::events::TodoEvents::new(crate::events::EVENT_CAPACITY);
+    let metrics = crate::metrics::Metrics::new();
+    let todo_repository = crate::events::TodoRepositoryWithEvents::new(
+        crate::webhooks::TodoRepositoryWithWebhooks::new(
+            crate::metrics::TodoRepositoryWithMetrics::new(
+                crate::repositories::todo::TodoRepositoryForDb::new(pool.clone())
+                    .enforce_blockers(enforce_blockers)
+                    .blob_store(blob_store.clone()),
+                metrics.clone(),
+            ),
+            webhook_repository.clone(),
+        ),
+        events.clone(),
+    );
+    tokio::spawn(crate::metrics::run_collector(metrics.clone(), pool.clone(), todo_repository.clone()));
+    let label_repository = crate::webhooks::LabelRepositoryWithWebhooks::new(
+        crate::repositories::label::LabelRepositoryForDb::new(pool.clone()),
+        webhook_repository.clone(),
+    );
+
+    let auth_config = crate::auth::AuthConfig::from_env();
+    if !auth_config.required() {
+        tracing::warn!("AUTH_REQUIRED is not set, requests without an API token are allowed");
+    }
+    let oidc_client = crate::oidc::OidcConfig::from_env().map(crate::oidc::OidcClient::new);
+    let security_config = crate::security::SecurityConfig::from_env();
+    if oidc_client.is_none() {
+        tracing::info!("OIDC_ISSUER or OIDC_CLIENT_ID is not set, browser login is disabled");
+    }
+
+    let grpc_port = std::env::var("GRPC_PORT")
+        .ok()
+        .and_then(|port| port.parse().ok())
+        .unwrap_or(50051);
+    let grpc_addr = std::net::SocketAddr::from(([0, 0, 0, 0], grpc_port));
+    let grpc = crate::grpc::router(
+        todo_repository.clone(),
+        label_repository.clone(),
+        events.clone(),
+        crate::grpc::Authenticate::new(
+            auth_config.clone(),
+            crate::repositories::token::ApiTokenRepositoryForDb::new(pool.clone()),
+            user_repository.clone(),
+            crate::repositories::workspace::WorkspaceRepositoryForDb::new(pool.clone()),
+        ),
+    );
+    tokio::spawn(async move {
+        tracing::debug!("grpc listening on {}", grpc_addr);
+        grpc.serve(grpc_addr).await.expect("fail serve grpc");
+    });
+
     let app = create_app(
-        crate::repositories::todo::TodoRepositoryForDb::new(pool.clone()),
-        crate::repositories::label::LabelRepositoryForDb::new(pool.clone())
+        todo_repository,
+        label_repository,
+        crate::repositories::comment::CommentRepositoryForDb::new(pool.clone()),
+        crate::repositories::attachment::AttachmentRepositoryForDb::new(pool.clone()),
+        crate::repositories::calendar::CalendarTokenRepositoryForDb::new(pool.clone()),
+        webhook_repository,
+        webhook_destinations,
+        idempotency_repository,
+        crate::repositories::token::ApiTokenRepositoryForDb::new(pool.clone()),
+        user_repository,
+        crate::repositories::workspace::WorkspaceRepositoryForDb::new(pool.clone()),
+        crate::repositories::share::ShareRepositoryForDb::new(pool.clone()),
+        blob_store,
+        events,
+        metrics,
+        rate_limiter,
+        auth_config,
+        oidc_client,
+        security_config,
     );
     let addr = std::net::SocketAddr::from(([0, 0, 0, 0], 3000));
     tracing::debug!("listening on {}", addr);
     axum::Server::bind(&addr)
-        .serve(app.into_make_service())
+        .serve(app.into_make_service_with_connect_info::<std::net::SocketAddr, _>())
+        .with_graceful_shutdown(async {
+            tokio::signal::ctrl_c().await.ok();
+        })
         .await
         .unwrap();
+    crate::telemetry::shutdown();
 }
 
+#[allow(clippy::too_many_arguments)]
 fn create_app<Todo: crate::repositories::todo::TodoRepository,
-   Label: crate::repositories::label::LabelRepository>
-(todo_repository: Todo, label_repository: Label) -> axum::Router {
+   Label: crate::repositories::label::LabelRepository,
+   Comment: crate::repositories::comment::CommentRepository,
+   Attachment: crate::repositories::attachment::AttachmentRepository,
+   Calendar: crate::repositories::calendar::CalendarTokenRepository,
+   Webhook: crate::repositories::webhook::WebhookRepository,
+   Idempotency: crate::repositories::idempotency::IdempotencyRepository,
+   ApiToken: crate::repositories::token::ApiTokenRepository,
+   User: crate::repositories::user::UserRepository,
+   Workspace: crate::repositories::workspace::WorkspaceRepository,
+   Share: crate::repositories::share::ShareRepository>
+(
+    todo_repository: Todo,
+    label_repository: Label,
+    comment_repository: Comment,
+    attachment_repository: Attachment,
+    calendar_repository: Calendar,
+    webhook_repository: Webhook,
+    webhook_destinations: crate::webhooks::destination::DestinationPolicy,
+    idempotency_repository: Idempotency,
+    api_token_repository: ApiToken,
+    user_repository: User,
+    workspace_repository: Workspace,
+    share_repository: Share,
+    blob_store: std::sync::Arc<dyn crate::blob_store::BlobStore>,
+    events: crate::events::TodoEvents,
+    metrics: crate::metrics::Metrics,
+    rate_limiter: crate::limits::RateLimiter,
+    auth_config: crate::auth::AuthConfig,
+    oidc_client: Option<crate::oidc::OidcClient>,
+    security_config: crate::security::SecurityConfig,
+) -> axum::Router {
     axum::Router::new()
         .route("/", axum::routing::get(root))
-        .route("/todos", axum::routing::post(crate::handlers::todo::create_todo::<Todo>)
-               .get(crate::handlers::todo::all_todo::<Todo>))
-        .route("/todos/:id", axum::routing::get(crate::handlers::todo::delete_todo::<Todo>)
+        .route("/openapi.json", axum::routing::get(crate::handlers::openapi::openapi_json))
+        .route("/metrics", axum::routing::get(crate::handlers::metrics::metrics))
+        .route("/swagger-ui", axum::routing::get(crate::handlers::openapi::swagger_ui_redirect))
+        .route("/swagger-ui/*tail", axum::routing::get(crate::handlers::openapi::swagger_ui))
+        .route("/todos", axum::routing::post(crate::handlers::todo::create_todo::<Todo, Label>)
+               .route_layer(axum::middleware::from_fn(crate::idempotency::idempotent::<Idempotency>))
+               .get(crate::handlers::todo::all_todo::<Todo>)
+               .route_layer(crate::auth::require::<crate::auth::Todos>())
+        )
+        .route("/todos/:id", axum::routing::get(crate::handlers::todo::find_todo::<Todo>)
                .delete(crate::handlers::todo::delete_todo::<Todo>)
-               .patch(crate::handlers::todo::update_todo::<Todo>)
+               .patch(crate::handlers::todo::update_todo::<Todo, Label>)
+               .route_layer(crate::workspaces::require_todo::<Todo>())
+               .route_layer(crate::auth::require::<crate::auth::Todos>())
+        )
+        .route("/todos/:id/move", axum::routing::post(crate::handlers::todo::move_todo::<Todo>)
+               .route_layer(crate::workspaces::require_todo::<Todo>())
+               .route_layer(crate::auth::require::<crate::auth::Todos>())
+        )
+        .route("/todos/:id/blockers", axum::routing::post(crate::handlers::todo::add_blocker::<Todo>)
+               .route_layer(crate::workspaces::require_todo::<Todo>())
+               .route_layer(crate::auth::require::<crate::auth::Todos>())
+        )
+        .route("/todos/:id/blockers/:blocker_id", axum::routing::delete(crate::handlers::todo::remove_blocker::<Todo>)
+               .route_layer(crate::workspaces::require_todo::<Todo>())
+               .route_layer(crate::auth::require::<crate::auth::Todos>())
+        )
+        .route("/todos/:id/assign", axum::routing::post(crate::handlers::todo::assign_todo::<Todo, Workspace, User>)
+               .route_layer(crate::workspaces::require_todo::<Todo>())
+               .route_layer(crate::auth::require::<crate::auth::Todos>())
+        )
+        .route("/todos/:id/watchers", axum::routing::get(crate::handlers::todo::all_watcher::<Todo>)
+               .route_layer(crate::workspaces::require_todo::<Todo>())
+               .route_layer(crate::auth::require::<crate::auth::Todos>())
+        )
+        // watching yourself only needs to view the todo, which `require_todo` allows for `:user_id`
+        .route("/todos/:id/watchers/:user_id", axum::routing::put(crate::handlers::todo::add_watcher::<Todo, Workspace, User>)
+               .delete(crate::handlers::todo::delete_watcher::<Todo>)
+               .route_layer(crate::workspaces::require_todo::<Todo>())
+               .route_layer(crate::auth::require::<crate::auth::Todos>())
+        )
+        .route("/todos/:id/comments", axum::routing::post(crate::handlers::comment::create_comment::<Comment, User>)
+               .get(crate::handlers::comment::all_comment::<Comment>)
+               .route_layer(crate::workspaces::require_todo::<Todo>())
+               .route_layer(crate::auth::require::<crate::auth::Todos>())
+        )
+        .route("/todos/:id/comments/:comment_id", axum::routing::patch(crate::handlers::comment::update_comment::<Comment, Todo>)
+               .delete(crate::handlers::comment::delete_comment::<Comment, Todo>)
+               .route_layer(crate::workspaces::require_todo::<Todo>())
+               .route_layer(crate::auth::require::<crate::auth::Todos>())
+        )
+        .route("/todos/:id/attachments/:attachment_id", axum::routing::get(crate::handlers::attachment::find_attachment::<Attachment>)
+               .delete(crate::handlers::attachment::delete_attachment::<Attachment>)
+               .route_layer(crate::workspaces::require_todo::<Todo>())
+               .route_layer(crate::auth::require::<crate::auth::Todos>())
+        )
+        .route("/export", axum::routing::get(crate::handlers::transfer::export_todos::<Todo>)
+               .route_layer(crate::auth::require::<crate::auth::Todos>())
+               .route_layer(crate::auth::require::<crate::auth::Labels>())
+        )
+        .route("/import", axum::routing::post(crate::handlers::transfer::import_todos::<Todo, Label>)
+               .route_layer(axum::middleware::from_fn(crate::idempotency::idempotent::<Idempotency>))
+               .route_layer(crate::auth::require::<crate::auth::Todos>())
+               .route_layer(crate::auth::require::<crate::auth::Labels>())
+        )
+        .route("/export/markdown", axum::routing::get(crate::handlers::transfer::export_markdown::<Todo, Label>)
+               .route_layer(crate::auth::require::<crate::auth::Todos>())
+               .route_layer(crate::auth::require::<crate::auth::Labels>())
+        )
+        .route("/import/markdown", axum::routing::post(crate::handlers::transfer::import_markdown::<Todo, Label>)
+               .route_layer(axum::middleware::from_fn(crate::idempotency::idempotent::<Idempotency>))
+               .route_layer(crate::auth::require::<crate::auth::Todos>())
+               .route_layer(crate::auth::require::<crate::auth::Labels>())
         )
+        .route("/calendar.ics", axum::routing::get(crate::handlers::calendar::calendar_feed::<Todo, Calendar, Workspace>))
+        .route("/calendar/tokens", axum::routing::post(crate::handlers::calendar::create_calendar_token::<Calendar>)
+               .get(crate::handlers::calendar::all_calendar_token::<Calendar>)
+               .route_layer(crate::auth::require::<crate::auth::Tokens>())
+        )
+        .route("/calendar/tokens/:id", axum::routing::delete(crate::handlers::calendar::delete_calendar_token::<Calendar>)
+               .route_layer(crate::auth::require::<crate::auth::Tokens>())
+        )
+        .route("/tokens", axum::routing::post(crate::handlers::token::create_token::<ApiToken>)
+               .get(crate::handlers::token::all_token::<ApiToken>)
+               .route_layer(crate::auth::require::<crate::auth::Tokens>())
+        )
+        .route("/tokens/:id", axum::routing::delete(crate::handlers::token::delete_token::<ApiToken>)
+               .route_layer(crate::auth::require::<crate::auth::Tokens>())
+        )
+        .route("/auth/login", axum::routing::get(crate::handlers::auth::login::<User>))
+        .route("/auth/callback", axum::routing::get(crate::handlers::auth::callback::<User>))
+        .route("/auth/me", axum::routing::get(crate::handlers::auth::me::<User>))
+        .route("/auth/logout", axum::routing::post(crate::handlers::auth::logout::<User>))
+        .route("/graphql", axum::routing::post(crate::handlers::graphql::graphql::<Todo, Label>)
+               .route_layer(crate::auth::require::<crate::auth::GraphQl>())
+               .get(crate::handlers::graphql::graphiql)
+        )
+        .route("/ws", axum::routing::get(crate::handlers::ws::ws::<Todo, Label, ApiToken, User, Workspace>))
         .route("/labels", axum::routing::post(crate::handlers::label::create_label::<Label>)
+               .route_layer(axum::middleware::from_fn(crate::idempotency::idempotent::<Idempotency>))
                .get(crate::handlers::label::all_label::<Label>)
+               .route_layer(crate::auth::require::<crate::auth::Labels>())
+        )
+        .route("/labels/:id", axum::routing::delete(crate::handlers::label::delete_label::<Label>)
+               .route_layer(crate::auth::require::<crate::auth::Labels>())
+        )
+        .route("/webhooks", axum::routing::post(crate::handlers::webhook::create_webhook::<Webhook>)
+               .get(crate::handlers::webhook::all_webhook::<Webhook>)
+               .route_layer(crate::auth::require::<crate::auth::Webhooks>())
+        )
+        .route("/webhooks/:id", axum::routing::delete(crate::handlers::webhook::delete_webhook::<Webhook>)
+               .route_layer(crate::auth::require::<crate::auth::Webhooks>())
+        )
+        .route("/webhook-deliveries", axum::routing::get(crate::handlers::webhook::all_delivery::<Webhook>)
+               .route_layer(crate::auth::require::<crate::auth::Webhooks>())
+        )
+        .route("/webhook-deliveries/:id/redeliver", axum::routing::post(crate::handlers::webhook::redeliver_delivery::<Webhook>)
+               .route_layer(crate::auth::require::<crate::auth::Webhooks>())
+        )
+        .route("/workspaces", axum::routing::post(crate::handlers::workspace::create_workspace::<Workspace>)
+               .get(crate::handlers::workspace::all_workspace::<Workspace>)
+               .route_layer(crate::auth::require::<crate::auth::Workspaces>())
+        )
+        .route("/workspaces/:id/members", axum::routing::get(crate::handlers::workspace::all_member::<Workspace>)
+               .route_layer(crate::auth::require::<crate::auth::Workspaces>())
+        )
+        .route("/workspaces/:id/members/:user_id", axum::routing::patch(crate::handlers::workspace::update_member::<Workspace>)
+               .delete(crate::handlers::workspace::delete_member::<Workspace>)
+               .route_layer(crate::auth::require::<crate::auth::Workspaces>())
+        )
+        .route("/workspaces/:id/invites", axum::routing::post(crate::handlers::workspace::create_invite::<Workspace>)
+               .route_layer(crate::auth::require::<crate::auth::Workspaces>())
+        )
+        .route("/invites/accept", axum::routing::post(crate::handlers::workspace::accept_invite::<Workspace>)
+               .route_layer(crate::auth::require::<crate::auth::Workspaces>())
+        )
+        .route("/shares", axum::routing::post(crate::handlers::share::create_share::<Share, Todo, Label>)
+               .get(crate::handlers::share::all_share::<Share>)
+               .route_layer(crate::auth::require::<crate::auth::Todos>())
+        )
+        .route("/shares/:id", axum::routing::delete(crate::handlers::share::delete_share::<Share>)
+               .route_layer(crate::auth::require::<crate::auth::Todos>())
+        )
+        .route("/shared/:token", axum::routing::get(crate::handlers::share::shared_json::<Share, Todo, Label>))
+        .route("/shared/:token/page", axum::routing::get(crate::handlers::share::shared_page::<Share, Todo, Label>)
+               .post(crate::handlers::share::unlock_shared_page::<Share, Todo, Label>)
+        )
+        .route_layer(axum::middleware::from_fn(|request, next| {
+            crate::limits::limit_body(request, next, crate::limits::BODY_LIMIT_BYTES)
+        }))
+        .merge(
+            axum::Router::new()
+                .route("/todos/:id/attachments", axum::routing::post(crate::handlers::attachment::create_attachment::<Attachment>)
+                       .get(crate::handlers::attachment::all_attachment::<Attachment>)
+                       .route_layer(crate::workspaces::require_todo::<Todo>())
+                       .route_layer(crate::auth::require::<crate::auth::Todos>())
+                )
+                .route_layer(axum::middleware::from_fn(|request, next| {
+                    crate::limits::limit_body(request, next, crate::handlers::attachment::UPLOAD_LIMIT_BYTES)
+                }))
         )
-        .route("/labels/:id", axum::routing::delete(crate::handlers::label::delete_label::<Label>))
+        .route_layer(axum::middleware::from_fn(crate::telemetry::record_route))
+        .route_layer(axum::middleware::from_fn(crate::metrics::track_http))
         .layer(axum::extract::Extension(std::sync::Arc::new(todo_repository)))
         .layer(axum::extract::Extension(std::sync::Arc::new(label_repository)))
+        .layer(axum::extract::Extension(std::sync::Arc::new(comment_repository)))
+        .layer(axum::extract::Extension(std::sync::Arc::new(attachment_repository)))
+        .layer(axum::extract::Extension(std::sync::Arc::new(calendar_repository)))
+        .layer(axum::extract::Extension(std::sync::Arc::new(webhook_repository)))
+        .layer(axum::extract::Extension(webhook_destinations))
+        .layer(axum::extract::Extension(std::sync::Arc::new(idempotency_repository)))
+        .layer(axum::extract::Extension(std::sync::Arc::new(share_repository)))
+        .layer(axum::extract::Extension(blob_store))
+        .layer(axum::extract::Extension(events))
+        .layer(axum::extract::Extension(metrics))
+        .layer(axum::extract::Extension(crate::graphql::build_schema::<Todo, Label>()))
+        .layer(axum::middleware::from_fn(crate::limits::rate_limit))
+        .layer(axum::middleware::from_fn(crate::workspaces::resolve::<Workspace>))
+        .layer(axum::middleware::from_fn(crate::auth::authenticate::<ApiToken, User>))
+        .layer(axum::extract::Extension(std::sync::Arc::new(workspace_repository)))
+        .layer(axum::extract::Extension(std::sync::Arc::new(api_token_repository)))
+        .layer(axum::extract::Extension(std::sync::Arc::new(user_repository)))
+        .layer(axum::extract::Extension(oidc_client))
+        .layer(axum::extract::Extension(auth_config))
+        .layer(axum::extract::Extension(rate_limiter))
+        .layer(axum::middleware::from_fn(crate::security::headers))
+        .layer(axum::extract::Extension(security_config.clone()))
+        .layer(security_config.cors())
         .layer(
-            tower_http::cors::CorsLayer::new()
-                .allow_origin(tower_http::cors::Origin::exact("http://localhost:3001".parse().unwrap()))
-                .allow_methods(tower_http::cors::Any)
-                .allow_headers(vec![hyper::header::CONTENT_TYPE])
+            tower::ServiceBuilder::new()
+                .map_request(crate::telemetry::drop_invalid_request_id)
+                .layer(tower_http::request_id::SetRequestIdLayer::x_request_id(crate::telemetry::MakeRandomRequestId))
+                .layer(tower_http::trace::TraceLayer::new_for_http()
+                    .make_span_with(crate::telemetry::request_span)
+                    .on_response(crate::telemetry::on_response))
+                .layer(tower_http::request_id::PropagateRequestIdLayer::x_request_id())
         )
 }
 
+#[utoipa::path(
+    get,
+    path = "/",
+    responses(
+        (status = 200, description = "Liveness check", body = String),
+    ),
+)]
 async fn root() -> &'static str {
     "hello world"
 }
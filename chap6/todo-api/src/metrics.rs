@@ -0,0 +1,240 @@
+//! Prometheus metrics served on `/metrics`.
+//!
+//! HTTP requests are counted and timed by [`track_http`], a route layer, so they are labelled
+//! with the route template (`/todos/:id`) instead of the raw path; requests that match no route
+//! are not recorded. [`TodoRepositoryWithMetrics`] times every repository operation, and
+//! [`run_collector`] periodically samples the connection pool and the open/completed todo counts.
+
+/// How often pool and todo gauges are refreshed.
+const COLLECT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15);
+
+#[derive(Clone)]
+pub struct Metrics {
+    registry: prometheus::Registry,
+    http_requests: prometheus::IntCounterVec,
+    http_duration: prometheus::HistogramVec,
+    repository_duration: prometheus::HistogramVec,
+    pool_connections: prometheus::IntGaugeVec,
+    pool_acquire: prometheus::Histogram,
+    todos: prometheus::IntGaugeVec,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let http_requests = prometheus::IntCounterVec::new(
+            prometheus::Opts::new("http_requests_total", "HTTP requests handled"),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let http_duration = prometheus::HistogramVec::new(
+            prometheus::HistogramOpts::new("http_request_duration_seconds", "Time to produce an HTTP response"),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let repository_duration = prometheus::HistogramVec::new(
+            prometheus::HistogramOpts::new("todo_repository_operation_duration_seconds", "Time spent in todo repository operations")
+                .buckets(vec![0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5]),
+            &["operation", "outcome"],
+        )
+        .unwrap();
+        let pool_connections = prometheus::IntGaugeVec::new(
+            prometheus::Opts::new("db_pool_connections", "Database connections by state"),
+            &["state"],
+        )
+        .unwrap();
+        let pool_acquire = prometheus::Histogram::with_opts(
+            prometheus::HistogramOpts::new("db_pool_acquire_wait_seconds", "Time to get a pooled connection, sampled by the collector")
+                .buckets(vec![0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0]),
+        )
+        .unwrap();
+        let todos = prometheus::IntGaugeVec::new(prometheus::Opts::new("todos", "Todos by state"), &["state"]).unwrap();
+
+        let registry = prometheus::Registry::new();
+        registry.register(Box::new(http_requests.clone())).unwrap();
+        registry.register(Box::new(http_duration.clone())).unwrap();
+        registry.register(Box::new(repository_duration.clone())).unwrap();
+        registry.register(Box::new(pool_connections.clone())).unwrap();
+        registry.register(Box::new(pool_acquire.clone())).unwrap();
+        registry.register(Box::new(todos.clone())).unwrap();
+        Self { registry, http_requests, http_duration, repository_duration, pool_connections, pool_acquire, todos }
+    }
+
+    /// Everything in the Prometheus text exposition format.
+    pub fn encode(&self) -> String {
+        let mut buffer = vec![];
+        prometheus::Encoder::encode(&prometheus::TextEncoder::new(), &self.registry.gather(), &mut buffer).unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    fn observe_http(&self, method: &str, route: &str, status: u16, elapsed: std::time::Duration) {
+        let status = status.to_string();
+        let labels = [method, route, status.as_str()];
+        self.http_requests.with_label_values(&labels).inc();
+        self.http_duration.with_label_values(&labels).observe(elapsed.as_secs_f64());
+    }
+
+    async fn time_operation<R>(&self, operation: &str, future: impl std::future::Future<Output = anyhow::Result<R>>) -> anyhow::Result<R> {
+        let started = std::time::Instant::now();
+        let result = future.await;
+        let outcome = if result.is_ok() { "ok" } else { "error" };
+        self.repository_duration
+            .with_label_values(&[operation, outcome])
+            .observe(started.elapsed().as_secs_f64());
+        result
+    }
+
+    fn record_todos(&self, todos: &[crate::repositories::todo::TodoEntity]) {
+        let completed = todos.iter().filter(|todo| todo.completed).count() as i64;
+        self.todos.with_label_values(&["completed"]).set(completed);
+        self.todos.with_label_values(&["open"]).set(todos.len() as i64 - completed);
+    }
+}
+
+pub async fn track_http(
+    request: axum::http::Request<axum::body::Body>,
+    next: axum::middleware::Next<axum::body::Body>,
+) -> axum::response::Response {
+    let metrics = request.extensions().get::<Metrics>().cloned();
+    let route = request.extensions().get::<axum::extract::MatchedPath>().map(|path| path.as_str().to_string());
+    let method = request.method().clone();
+    let started = std::time::Instant::now();
+    let response = next.run(request).await;
+    if let (Some(metrics), Some(route)) = (metrics, route) {
+        metrics.observe_http(method.as_str(), &route, response.status().as_u16(), started.elapsed());
+    }
+    response
+}
+
+async fn collect<T: crate::repositories::todo::TodoRepository>(metrics: &Metrics, pool: &sqlx::PgPool, todo_repository: &T) {
+    let idle = pool.num_idle() as i64;
+    metrics.pool_connections.with_label_values(&["idle"]).set(idle);
+    metrics.pool_connections.with_label_values(&["in_use"]).set(pool.size() as i64 - idle);
+
+    let started = std::time::Instant::now();
+    match pool.acquire().await {
+        Ok(_) => metrics.pool_acquire.observe(started.elapsed().as_secs_f64()),
+        Err(e) => tracing::warn!("metrics: failed to acquire a connection: {}", e),
+    }
+
+    match todo_repository.all().await {
+        Ok(todos) => metrics.record_todos(&todos),
+        Err(e) => tracing::warn!("metrics: failed to count todos: {}", e),
+    }
+}
+
+pub async fn run_collector<T: crate::repositories::todo::TodoRepository>(metrics: Metrics, pool: sqlx::PgPool, todo_repository: T) {
+    let mut interval = tokio::time::interval(COLLECT_INTERVAL);
+    loop {
+        interval.tick().await;
+        collect(&metrics, &pool, &todo_repository).await;
+    }
+}
+
+#[derive(Clone)]
+pub struct TodoRepositoryWithMetrics<T> {
+    inner: T,
+    metrics: Metrics,
+}
+
+impl<T: crate::repositories::todo::TodoRepository> TodoRepositoryWithMetrics<T> {
+    pub fn new(inner: T, metrics: Metrics) -> Self {
+        Self { inner, metrics }
+    }
+}
+
+#[axum::async_trait]
+impl<T: crate::repositories::todo::TodoRepository> crate::repositories::todo::TodoRepository for TodoRepositoryWithMetrics<T> {
+    async fn create(&self, payload: crate::repositories::todo::CreateTodo) -> anyhow::Result<crate::repositories::todo::TodoEntity> {
+        self.metrics.time_operation("create", self.inner.create(payload)).await
+    }
+
+    async fn find(&self, id: i32) -> anyhow::Result<crate::repositories::todo::TodoEntity> {
+        self.metrics.time_operation("find", self.inner.find(id)).await
+    }
+
+    async fn all(&self) -> anyhow::Result<Vec<crate::repositories::todo::TodoEntity>> {
+        self.metrics.time_operation("all", self.inner.all()).await
+    }
+
+    fn stream(&self) -> crate::repositories::todo::TodoStream {
+        self.inner.stream()
+    }
+
+    async fn update(&self, id: i32, payload: crate::repositories::todo::UpdateTodo) -> anyhow::Result<crate::repositories::todo::TodoEntity> {
+        self.metrics.time_operation("update", self.inner.update(id, payload)).await
+    }
+
+    async fn move_to(&self, id: i32, payload: crate::repositories::todo::MoveTodo) -> anyhow::Result<crate::repositories::todo::TodoEntity> {
+        self.metrics.time_operation("move_to", self.inner.move_to(id, payload)).await
+    }
+
+    async fn add_blocker(&self, id: i32, blocker_id: i32) -> anyhow::Result<crate::repositories::todo::TodoEntity> {
+        self.metrics.time_operation("add_blocker", self.inner.add_blocker(id, blocker_id)).await
+    }
+
+    async fn remove_blocker(&self, id: i32, blocker_id: i32) -> anyhow::Result<crate::repositories::todo::TodoEntity> {
+        self.metrics.time_operation("remove_blocker", self.inner.remove_blocker(id, blocker_id)).await
+    }
+
+    async fn assign(&self, id: i32, assignee_id: Option<i32>) -> anyhow::Result<crate::repositories::todo::TodoEntity> {
+        self.metrics.time_operation("assign", self.inner.assign(id, assignee_id)).await
+    }
+
+    async fn watchers(&self, id: i32) -> anyhow::Result<Vec<i32>> {
+        self.metrics.time_operation("watchers", self.inner.watchers(id)).await
+    }
+
+    async fn watch(&self, id: i32, user_id: i32) -> anyhow::Result<()> {
+        self.metrics.time_operation("watch", self.inner.watch(id, user_id)).await
+    }
+
+    async fn unwatch(&self, id: i32, user_id: i32) -> anyhow::Result<()> {
+        self.metrics.time_operation("unwatch", self.inner.unwatch(id, user_id)).await
+    }
+
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        self.metrics.time_operation("delete", self.inner.delete(id)).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::todo::TodoRepository;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn requests_are_recorded_by_route_template() {
+        let metrics = Metrics::new();
+        let app = axum::Router::new()
+            .route("/todos/:id", axum::routing::get(|| async { "todo" }))
+            .route_layer(axum::middleware::from_fn(track_http))
+            .layer(axum::extract::Extension(metrics.clone()));
+        for path in ["/todos/1", "/todos/2", "/missing"] {
+            let request = axum::http::Request::get(path).body(axum::body::Body::empty()).unwrap();
+            app.clone().oneshot(request).await.unwrap();
+        }
+
+        let encoded = metrics.encode();
+        assert!(encoded.contains(r#"http_requests_total{method="GET",route="/todos/:id",status="200"} 2"#));
+        assert!(encoded.contains(r#"http_request_duration_seconds_count{method="GET",route="/todos/:id",status="200"} 2"#));
+        assert!(!encoded.contains("/missing"));
+    }
+
+    #[tokio::test]
+    async fn repository_operations_and_todo_counts() {
+        let metrics = Metrics::new();
+        let repository = TodoRepositoryWithMetrics::new(crate::repositories::todo::test_utils::TodoRepositoryForMemory::new(vec![]), metrics.clone());
+        let todo = repository.create(crate::repositories::todo::CreateTodo::new("count me".to_string(), vec![], None, None)).await.unwrap();
+        repository.create(crate::repositories::todo::CreateTodo::new("and me".to_string(), vec![], None, None)).await.unwrap();
+        repository.update(todo.id, crate::repositories::todo::UpdateTodo::new(None, Some(true), None)).await.unwrap();
+        assert!(repository.find(99).await.is_err());
+        metrics.record_todos(&repository.all().await.unwrap());
+
+        let encoded = metrics.encode();
+        assert!(encoded.contains(r#"todo_repository_operation_duration_seconds_count{operation="create",outcome="ok"} 2"#));
+        assert!(encoded.contains(r#"todo_repository_operation_duration_seconds_count{operation="find",outcome="error"} 1"#));
+        assert!(encoded.contains(r#"todos{state="open"} 1"#));
+        assert!(encoded.contains(r#"todos{state="completed"} 1"#));
+    }
+}
@@ -0,0 +1,528 @@
+//! OpenID Connect login: the authorization code flow with PKCE.
+//!
+//! `/auth/login` sends the browser to the provider with a fresh `state`, `nonce` and PKCE
+//! challenge, remembered by `UserRepository::begin_login`. `/auth/callback` trades the code for
+//! tokens, checks the ID token's signature against the provider's JWKS as well as its issuer,
+//! audience, expiry and nonce, maps the claims to a local user and starts a session. The
+//! provider is found through its discovery document, fetched on first use.
+
+/// How long a user has to finish logging in at the provider.
+pub const LOGIN_TTL: std::time::Duration = std::time::Duration::from_secs(10 * 60);
+pub const SESSION_TTL: std::time::Duration = std::time::Duration::from_secs(7 * 24 * 60 * 60);
+
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+const PURGE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+/// Allowed difference between our clock and the provider's when checking `exp` and `iat`.
+const CLOCK_SKEW_SECONDS: u64 = 60;
+
+/// Asymmetric algorithms only: a symmetric one would let anyone holding a public key sign tokens.
+const ID_TOKEN_ALGORITHMS: [jsonwebtoken::Algorithm; 8] = [
+    jsonwebtoken::Algorithm::RS256,
+    jsonwebtoken::Algorithm::RS384,
+    jsonwebtoken::Algorithm::RS512,
+    jsonwebtoken::Algorithm::PS256,
+    jsonwebtoken::Algorithm::PS384,
+    jsonwebtoken::Algorithm::PS512,
+    jsonwebtoken::Algorithm::ES256,
+    jsonwebtoken::Algorithm::ES384,
+];
+
+#[derive(Debug, Clone)]
+pub struct OidcConfig {
+    issuer: String,
+    client_id: String,
+    /// Confidential clients authenticate with it; public clients rely on PKCE alone.
+    client_secret: Option<String>,
+    redirect_url: String,
+    scopes: String,
+}
+
+impl OidcConfig {
+    pub fn new(issuer: String, client_id: String, client_secret: Option<String>, redirect_url: String) -> Self {
+        Self {
+            issuer: issuer.trim_end_matches('/').to_string(),
+            client_id,
+            client_secret,
+            redirect_url,
+            scopes: "openid email profile".to_string(),
+        }
+    }
+
+    /// Login is enabled by `OIDC_ISSUER` and `OIDC_CLIENT_ID`; `OIDC_CLIENT_SECRET`,
+    /// `OIDC_REDIRECT_URL` and `OIDC_SCOPES` are optional.
+    pub fn from_env() -> Option<Self> {
+        let issuer = std::env::var("OIDC_ISSUER").ok()?;
+        let client_id = std::env::var("OIDC_CLIENT_ID").ok()?;
+        let redirect_url = std::env::var("OIDC_REDIRECT_URL").unwrap_or("http://localhost:3000/auth/callback".to_string());
+        let mut config = Self::new(issuer, client_id, std::env::var("OIDC_CLIENT_SECRET").ok(), redirect_url);
+        if let Ok(scopes) = std::env::var("OIDC_SCOPES") {
+            config.scopes = scopes;
+        }
+        Some(config)
+    }
+
+    /// Cookies are only marked `Secure` when we are reached over https.
+    pub fn secure_cookies(&self) -> bool {
+        self.redirect_url.starts_with("https://")
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum OidcError {
+    #[error("identity provider request failed: {0}")]
+    Provider(String),
+    #[error("invalid id token: {0}")]
+    InvalidIdToken(String),
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+pub struct IdClaims {
+    pub sub: String,
+    pub email: Option<String>,
+    pub email_verified: Option<bool>,
+    pub name: Option<String>,
+    pub preferred_username: Option<String>,
+    nonce: Option<String>,
+}
+
+/// A random, url safe secret for `state`, `nonce` and PKCE verifiers.
+pub fn random_secret() -> String {
+    base64::Engine::encode(&base64::engine::general_purpose::URL_SAFE_NO_PAD, rand::random::<[u8; 32]>())
+}
+
+/// The `S256` code challenge for a PKCE verifier.
+pub fn pkce_challenge(verifier: &str) -> String {
+    let digest = <sha2::Sha256 as sha2::Digest>::digest(verifier.as_bytes());
+    base64::Engine::encode(&base64::engine::general_purpose::URL_SAFE_NO_PAD, digest)
+}
+
+#[derive(Clone)]
+pub struct OidcClient {
+    config: OidcConfig,
+    http: crate::webhooks::Client,
+    metadata: std::sync::Arc<tokio::sync::OnceCell<ProviderMetadata>>,
+    jwks: std::sync::Arc<tokio::sync::RwLock<jsonwebtoken::jwk::JwkSet>>,
+}
+
+impl OidcClient {
+    pub fn new(config: OidcConfig) -> Self {
+        Self {
+            config,
+            http: crate::webhooks::client(),
+            metadata: Default::default(),
+            jwks: std::sync::Arc::new(tokio::sync::RwLock::new(jsonwebtoken::jwk::JwkSet { keys: vec![] })),
+        }
+    }
+
+    pub fn config(&self) -> &OidcConfig {
+        &self.config
+    }
+
+    async fn send<T: serde::de::DeserializeOwned>(&self, request: hyper::Request<hyper::Body>) -> Result<T, OidcError> {
+        let uri = request.uri().clone();
+        let response = tokio::time::timeout(REQUEST_TIMEOUT, self.http.request(request))
+            .await
+            .map_err(|_| OidcError::Provider(format!("{} timed out", uri)))?
+            .map_err(|e| OidcError::Provider(format!("{}: {}", uri, e)))?;
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body())
+            .await
+            .map_err(|e| OidcError::Provider(format!("{}: {}", uri, e)))?;
+        if !status.is_success() {
+            return Err(OidcError::Provider(format!("{} answered {}: {}", uri, status, String::from_utf8_lossy(&body))));
+        }
+        serde_json::from_slice(&body).map_err(|e| OidcError::Provider(format!("{}: {}", uri, e)))
+    }
+
+    async fn get<T: serde::de::DeserializeOwned>(&self, url: &str) -> Result<T, OidcError> {
+        let request = hyper::Request::get(url)
+            .body(hyper::Body::empty())
+            .map_err(|e| OidcError::Provider(e.to_string()))?;
+        self.send(request).await
+    }
+
+    async fn metadata(&self) -> Result<&ProviderMetadata, OidcError> {
+        self.metadata
+            .get_or_try_init(|| async {
+                let url = format!("{}/.well-known/openid-configuration", self.config.issuer);
+                let metadata: ProviderMetadata = self.get(&url).await?;
+                if metadata.issuer.trim_end_matches('/') != self.config.issuer {
+                    return Err(OidcError::Provider(format!("discovery document is for issuer {}", metadata.issuer)));
+                }
+                Ok(metadata)
+            })
+            .await
+    }
+
+    pub async fn authorization_url(&self, state: &str, nonce: &str, code_challenge: &str) -> Result<String, OidcError> {
+        let metadata = self.metadata().await?;
+        let query = serde_urlencoded::to_string([
+            ("response_type", "code"),
+            ("client_id", &self.config.client_id),
+            ("redirect_uri", &self.config.redirect_url),
+            ("scope", &self.config.scopes),
+            ("state", state),
+            ("nonce", nonce),
+            ("code_challenge", code_challenge),
+            ("code_challenge_method", "S256"),
+        ])
+        .unwrap();
+        let separator = if metadata.authorization_endpoint.contains('?') { '&' } else { '?' };
+        Ok(format!("{}{}{}", metadata.authorization_endpoint, separator, query))
+    }
+
+    /// Redeems an authorization code, returning the ID token.
+    pub async fn exchange(&self, code: &str, code_verifier: &str) -> Result<String, OidcError> {
+        let metadata = self.metadata().await?;
+        let form = serde_urlencoded::to_string([
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &self.config.redirect_url),
+            ("client_id", &self.config.client_id),
+            ("code_verifier", code_verifier),
+        ])
+        .unwrap();
+        let mut request = hyper::Request::post(&metadata.token_endpoint)
+            .header(hyper::header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .header(hyper::header::ACCEPT, "application/json");
+        if let Some(secret) = &self.config.client_secret {
+            let credentials = format!(
+                "{}:{}",
+                serde_urlencoded::to_string([("", &self.config.client_id)]).unwrap().trim_start_matches('='),
+                serde_urlencoded::to_string([("", secret)]).unwrap().trim_start_matches('='),
+            );
+            let encoded = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, credentials);
+            request = request.header(hyper::header::AUTHORIZATION, format!("Basic {}", encoded));
+        }
+        let request = request.body(hyper::Body::from(form)).map_err(|e| OidcError::Provider(e.to_string()))?;
+        let response: TokenResponse = self.send(request).await?;
+        Ok(response.id_token)
+    }
+
+    /// The provider's key with this id, refreshing the key set once when it is not known yet,
+    /// e.g. after the provider rotated its keys.
+    async fn key(&self, kid: &str) -> Result<jsonwebtoken::DecodingKey, OidcError> {
+        let find = |jwks: &jsonwebtoken::jwk::JwkSet| jwks.find(kid).map(jsonwebtoken::DecodingKey::from_jwk);
+        if let Some(key) = find(&*self.jwks.read().await) {
+            return key.map_err(|e| OidcError::InvalidIdToken(e.to_string()));
+        }
+        let jwks: jsonwebtoken::jwk::JwkSet = self.get(&self.metadata().await?.jwks_uri).await?;
+        let key = find(&jwks);
+        *self.jwks.write().await = jwks;
+        match key {
+            Some(key) => key.map_err(|e| OidcError::InvalidIdToken(e.to_string())),
+            None => Err(OidcError::InvalidIdToken(format!("unknown signing key {}", kid))),
+        }
+    }
+
+    pub async fn validate(&self, id_token: &str, nonce: &str) -> Result<IdClaims, OidcError> {
+        let header = jsonwebtoken::decode_header(id_token).map_err(|e| OidcError::InvalidIdToken(e.to_string()))?;
+        if !ID_TOKEN_ALGORITHMS.contains(&header.alg) {
+            return Err(OidcError::InvalidIdToken(format!("{:?} is not allowed", header.alg)));
+        }
+        let kid = header.kid.ok_or_else(|| OidcError::InvalidIdToken("missing kid".to_string()))?;
+        let key = self.key(&kid).await?;
+
+        let mut validation = jsonwebtoken::Validation::new(header.alg);
+        validation.set_issuer(&[&self.metadata().await?.issuer]);
+        validation.set_audience(&[&self.config.client_id]);
+        validation.set_required_spec_claims(&["exp", "iat", "iss", "aud", "sub"]);
+        validation.leeway = CLOCK_SKEW_SECONDS;
+        let claims = jsonwebtoken::decode::<IdClaims>(id_token, &key, &validation)
+            .map_err(|e| OidcError::InvalidIdToken(e.to_string()))?
+            .claims;
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(OidcError::InvalidIdToken("nonce does not match".to_string()));
+        }
+        Ok(claims)
+    }
+
+    /// Maps ID token claims to the local user they log in as; unverified email addresses are dropped.
+    pub fn identity(&self, claims: IdClaims) -> crate::repositories::user::Identity {
+        crate::repositories::user::Identity {
+            issuer: self.config.issuer.clone(),
+            subject: claims.sub,
+            email: claims.email.filter(|_| claims.email_verified != Some(false)),
+            name: claims.name.or(claims.preferred_username),
+        }
+    }
+}
+
+pub async fn run_purge<U: crate::repositories::user::UserRepository>(repository: U) {
+    let mut interval = tokio::time::interval(PURGE_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = repository.purge_expired().await {
+            tracing::error!("failed to purge sessions: {}", e);
+        }
+    }
+}
+
+/// A stand-in identity provider serving discovery, authorization, token and JWKS endpoints.
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+
+    pub const CLIENT_ID: &str = "todo-api";
+    pub const REDIRECT_URL: &str = "http://localhost:3000/auth/callback";
+    const KEY_ID: &str = "mock-key";
+
+    struct IssuedCode {
+        code_challenge: String,
+        nonce: String,
+        redirect_uri: String,
+    }
+
+    #[derive(Clone)]
+    pub struct MockProvider {
+        pub issuer: String,
+        signing_key: std::sync::Arc<jsonwebtoken::EncodingKey>,
+        codes: std::sync::Arc<std::sync::Mutex<std::collections::HashMap<String, IssuedCode>>>,
+    }
+
+    fn error(status: axum::http::StatusCode, error: &str) -> axum::response::Response {
+        axum::response::IntoResponse::into_response((status, axum::Json(serde_json::json!({ "error": error }))))
+    }
+
+    impl MockProvider {
+        pub async fn start() -> Self {
+            let rng = ring::rand::SystemRandom::new();
+            let pkcs8 = ring::signature::EcdsaKeyPair::generate_pkcs8(&ring::signature::ECDSA_P256_SHA256_FIXED_SIGNING, &rng).unwrap();
+            let key_pair =
+                ring::signature::EcdsaKeyPair::from_pkcs8(&ring::signature::ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref(), &rng).unwrap();
+            // an uncompressed point: 0x04 followed by x and y
+            let point = ring::signature::KeyPair::public_key(&key_pair).as_ref().to_vec();
+            let encode = |bytes: &[u8]| base64::Engine::encode(&base64::engine::general_purpose::URL_SAFE_NO_PAD, bytes);
+            let jwks = serde_json::json!({
+                "keys": [{
+                    "kty": "EC", "crv": "P-256", "use": "sig", "alg": "ES256", "kid": KEY_ID,
+                    "x": encode(&point[1..33]), "y": encode(&point[33..]),
+                }]
+            });
+
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let issuer = format!("http://{}", listener.local_addr().unwrap());
+            let provider = Self {
+                issuer: issuer.clone(),
+                signing_key: std::sync::Arc::new(jsonwebtoken::EncodingKey::from_ec_der(pkcs8.as_ref())),
+                codes: Default::default(),
+            };
+            let discovery = serde_json::json!({
+                "issuer": issuer,
+                "authorization_endpoint": format!("{}/authorize", issuer),
+                "token_endpoint": format!("{}/token", issuer),
+                "jwks_uri": format!("{}/jwks", issuer),
+            });
+            let (authorize, token) = (provider.clone(), provider.clone());
+            let app = axum::Router::new()
+                .route("/.well-known/openid-configuration", axum::routing::get(move || async move { axum::Json(discovery) }))
+                .route("/jwks", axum::routing::get(move || async move { axum::Json(jwks) }))
+                .route(
+                    "/authorize",
+                    axum::routing::get(move |query| async move { authorize.authorize(query) }),
+                )
+                .route("/token", axum::routing::post(move |form| async move { token.token(form) }));
+            tokio::spawn(axum::Server::from_tcp(listener).unwrap().serve(app.into_make_service()));
+            provider
+        }
+
+        /// Logs in straight away as the same user.
+        fn authorize(&self, axum::extract::Query(query): axum::extract::Query<std::collections::HashMap<String, String>>) -> axum::response::Response {
+            let param = |name: &str| query.get(name).cloned().unwrap_or_default();
+            if param("client_id") != CLIENT_ID || param("response_type") != "code" || param("code_challenge_method") != "S256" {
+                return error(axum::http::StatusCode::BAD_REQUEST, "invalid_request");
+            }
+            let code = random_secret();
+            self.codes.lock().unwrap().insert(
+                code.clone(),
+                IssuedCode { code_challenge: param("code_challenge"), nonce: param("nonce"), redirect_uri: param("redirect_uri") },
+            );
+            let query = serde_urlencoded::to_string([("code", code), ("state", param("state"))]).unwrap();
+            let location = format!("{}?{}", param("redirect_uri"), query);
+            axum::response::IntoResponse::into_response(axum::response::Redirect::to(location.parse().unwrap()))
+        }
+
+        fn token(&self, axum::extract::Form(form): axum::extract::Form<std::collections::HashMap<String, String>>) -> axum::response::Response {
+            let param = |name: &str| form.get(name).cloned().unwrap_or_default();
+            let issued = match self.codes.lock().unwrap().remove(&param("code")) {
+                Some(issued) => issued,
+                None => return error(axum::http::StatusCode::BAD_REQUEST, "invalid_grant"),
+            };
+            if param("grant_type") != "authorization_code"
+                || param("redirect_uri") != issued.redirect_uri
+                || pkce_challenge(&param("code_verifier")) != issued.code_challenge
+            {
+                return error(axum::http::StatusCode::BAD_REQUEST, "invalid_grant");
+            }
+            let id_token = self.sign(&self.claims(&issued.nonce));
+            axum::response::IntoResponse::into_response(axum::Json(serde_json::json!({
+                "access_token": random_secret(),
+                "token_type": "Bearer",
+                "id_token": id_token,
+            })))
+        }
+
+        /// Valid ID token claims, for tests to tamper with.
+        pub fn claims(&self, nonce: &str) -> serde_json::Value {
+            let now = chrono::Utc::now().timestamp();
+            serde_json::json!({
+                "iss": self.issuer,
+                "aud": CLIENT_ID,
+                "sub": "248289761001",
+                "email": "jane@example.com",
+                "email_verified": true,
+                "name": "Jane Doe",
+                "nonce": nonce,
+                "iat": now,
+                "exp": now + 300,
+            })
+        }
+
+        pub fn sign(&self, claims: &serde_json::Value) -> String {
+            let mut header = jsonwebtoken::Header::new(jsonwebtoken::Algorithm::ES256);
+            header.kid = Some(KEY_ID.to_string());
+            jsonwebtoken::encode(&header, claims, &self.signing_key).unwrap()
+        }
+
+        pub fn client(&self) -> OidcClient {
+            OidcClient::new(OidcConfig::new(self.issuer.clone(), CLIENT_ID.to_string(), None, REDIRECT_URL.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::user::UserRepository;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn id_tokens_are_validated() {
+        let provider = test_utils::MockProvider::start().await;
+        let client = provider.client();
+        let claims = client.validate(&provider.sign(&provider.claims("n-1")), "n-1").await.unwrap();
+        assert_eq!("248289761001", claims.sub);
+
+        let tampered = |field: &str, value: serde_json::Value| {
+            let mut claims = provider.claims("n-1");
+            claims[field] = value;
+            provider.sign(&claims)
+        };
+        let expired = chrono::Utc::now().timestamp() - 600;
+        for id_token in [
+            tampered("aud", "someone-else".into()),
+            tampered("iss", "https://evil.example.com".into()),
+            tampered("exp", expired.into()),
+            tampered("nonce", "n-2".into()),
+            test_utils::MockProvider::start().await.sign(&provider.claims("n-1")),
+        ] {
+            assert!(matches!(client.validate(&id_token, "n-1").await, Err(OidcError::InvalidIdToken(_))));
+        }
+    }
+
+    fn app(client: OidcClient, users: crate::repositories::user::test_utils::UserRepositoryForMemory) -> axum::Router {
+        type Users = crate::repositories::user::test_utils::UserRepositoryForMemory;
+        type Tokens = crate::repositories::token::test_utils::ApiTokenRepositoryForMemory;
+        axum::Router::new()
+            .route("/auth/login", axum::routing::get(crate::handlers::auth::login::<Users>))
+            .route("/auth/callback", axum::routing::get(crate::handlers::auth::callback::<Users>))
+            // answered for writes too, to see whether a session counts on them
+            .route("/auth/me", axum::routing::get(crate::handlers::auth::me::<Users>).post(crate::handlers::auth::me::<Users>))
+            .route("/auth/logout", axum::routing::post(crate::handlers::auth::logout::<Users>))
+            .layer(axum::middleware::from_fn(crate::auth::authenticate::<Tokens, Users>))
+            .layer(axum::extract::Extension(std::sync::Arc::new(users)))
+            .layer(axum::extract::Extension(std::sync::Arc::new(Tokens::new())))
+            .layer(axum::extract::Extension(crate::auth::AuthConfig::new(true, None)))
+            .layer(axum::extract::Extension(Some(client)))
+    }
+
+    async fn get(app: &axum::Router, uri: &str, cookie: Option<&str>) -> axum::response::Response {
+        let mut request = axum::http::Request::get(uri);
+        if let Some(cookie) = cookie {
+            request = request.header(axum::http::header::COOKIE, cookie);
+        }
+        app.clone().oneshot(request.body(axum::body::Body::empty()).unwrap()).await.unwrap()
+    }
+
+    async fn post(app: &axum::Router, uri: &str, cookie: &str, csrf_token: Option<&str>) -> axum::response::Response {
+        let mut request = axum::http::Request::post(uri).header(axum::http::header::COOKIE, cookie);
+        if let Some(csrf_token) = csrf_token {
+            request = request.header(crate::security::CSRF_HEADER, csrf_token);
+        }
+        app.clone().oneshot(request.body(axum::body::Body::empty()).unwrap()).await.unwrap()
+    }
+
+    fn header(response: &axum::response::Response, name: axum::http::header::HeaderName) -> String {
+        response.headers()[name].to_str().unwrap().to_string()
+    }
+
+    /// The `name=value` part of the `Set-Cookie` header setting `name`.
+    fn set_cookie(response: &axum::response::Response, name: &str) -> String {
+        let cookie = response
+            .headers()
+            .get_all(axum::http::header::SET_COOKIE)
+            .iter()
+            .map(|value| value.to_str().unwrap())
+            .find(|value| value.starts_with(&format!("{}=", name)))
+            .unwrap();
+        cookie.split(';').next().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn login_round_trip() {
+        let provider = test_utils::MockProvider::start().await;
+        let users = crate::repositories::user::test_utils::UserRepositoryForMemory::new();
+        let app = app(provider.client(), users.clone());
+
+        let login = get(&app, "/auth/login?redirect_to=/todos", None).await;
+        assert_eq!(axum::http::StatusCode::SEE_OTHER, login.status());
+        let state_cookie = set_cookie(&login, "oidc_state");
+        let authorize = header(&login, axum::http::header::LOCATION);
+        assert!(authorize.starts_with(&format!("{}/authorize?", provider.issuer)));
+
+        let approved = hyper::Client::new().get(authorize.parse().unwrap()).await.unwrap();
+        let callback = approved.headers()[axum::http::header::LOCATION].to_str().unwrap().to_string();
+        let callback = callback.strip_prefix("http://localhost:3000").unwrap();
+
+        assert_eq!(axum::http::StatusCode::BAD_REQUEST, get(&app, callback, None).await.status());
+        let finished = get(&app, callback, Some(&state_cookie)).await;
+        assert_eq!(axum::http::StatusCode::SEE_OTHER, finished.status());
+        assert_eq!("/todos", header(&finished, axum::http::header::LOCATION));
+        let session_cookie = set_cookie(&finished, crate::auth::SESSION_COOKIE);
+
+        let me = get(&app, "/auth/me", Some(&session_cookie)).await;
+        assert_eq!(axum::http::StatusCode::OK, me.status());
+        let csrf_token = header(&me, axum::http::HeaderName::from_static(crate::security::CSRF_HEADER));
+        assert_eq!(format!("{}={}", crate::security::CSRF_COOKIE, csrf_token), set_cookie(&finished, crate::security::CSRF_COOKIE));
+        let user: crate::repositories::user::User = serde_json::from_slice(&hyper::body::to_bytes(me.into_body()).await.unwrap()).unwrap();
+        assert_eq!((provider.issuer.as_str(), "248289761001"), (user.issuer.as_str(), user.subject.as_str()));
+        assert_eq!(Some("jane@example.com".to_string()), users.find(user.id).await.unwrap().email);
+
+        // a write by the session is refused without its csrf token
+        assert_eq!(axum::http::StatusCode::FORBIDDEN, post(&app, "/auth/me", &session_cookie, None).await.status());
+        assert_eq!(axum::http::StatusCode::FORBIDDEN, post(&app, "/auth/me", &session_cookie, Some("forged")).await.status());
+        assert_eq!(axum::http::StatusCode::OK, post(&app, "/auth/me", &session_cookie, Some(&csrf_token)).await.status());
+        assert_eq!(axum::http::StatusCode::FORBIDDEN, post(&app, "/auth/logout", &session_cookie, None).await.status());
+        assert_eq!(axum::http::StatusCode::OK, get(&app, "/auth/me", Some(&session_cookie)).await.status());
+
+        // the login is used up, and the code with it
+        assert_eq!(axum::http::StatusCode::BAD_REQUEST, get(&app, callback, Some(&state_cookie)).await.status());
+        assert_eq!(axum::http::StatusCode::UNAUTHORIZED, get(&app, "/auth/me", None).await.status());
+
+        assert_eq!(axum::http::StatusCode::NO_CONTENT, post(&app, "/auth/logout", &session_cookie, Some(&csrf_token)).await.status());
+        assert_eq!(axum::http::StatusCode::UNAUTHORIZED, get(&app, "/auth/me", Some(&session_cookie)).await.status());
+    }
+}
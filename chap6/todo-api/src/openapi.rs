@@ -0,0 +1,191 @@
+//! OpenAPI document generated from the `#[utoipa::path]` annotations on the handlers.
+//!
+//! Every route registered in `create_app` must be listed in [`ApiDoc`]; the test below
+//! reads `main.rs` and fails for any route that is not.
+
+#[derive(utoipa::OpenApi)]
+#[openapi(
+    info(title = "todo-api", description = "Todos, labels, comments and attachments"),
+    paths(
+        crate::root,
+        crate::handlers::openapi::openapi_json,
+        crate::handlers::metrics::metrics,
+        crate::handlers::todo::create_todo,
+        crate::handlers::todo::all_todo,
+        crate::handlers::todo::find_todo,
+        crate::handlers::todo::update_todo,
+        crate::handlers::todo::delete_todo,
+        crate::handlers::todo::move_todo,
+        crate::handlers::todo::add_blocker,
+        crate::handlers::todo::remove_blocker,
+        crate::handlers::todo::assign_todo,
+        crate::handlers::todo::all_watcher,
+        crate::handlers::todo::add_watcher,
+        crate::handlers::todo::delete_watcher,
+        crate::handlers::comment::create_comment,
+        crate::handlers::comment::all_comment,
+        crate::handlers::comment::update_comment,
+        crate::handlers::comment::delete_comment,
+        crate::handlers::attachment::create_attachment,
+        crate::handlers::attachment::all_attachment,
+        crate::handlers::attachment::find_attachment,
+        crate::handlers::attachment::delete_attachment,
+        crate::handlers::transfer::export_todos,
+        crate::handlers::transfer::import_todos,
+        crate::handlers::transfer::export_markdown,
+        crate::handlers::transfer::import_markdown,
+        crate::handlers::calendar::calendar_feed,
+        crate::handlers::calendar::create_calendar_token,
+        crate::handlers::calendar::all_calendar_token,
+        crate::handlers::calendar::delete_calendar_token,
+        crate::handlers::token::create_token,
+        crate::handlers::token::all_token,
+        crate::handlers::token::delete_token,
+        crate::handlers::auth::login,
+        crate::handlers::auth::callback,
+        crate::handlers::auth::me,
+        crate::handlers::auth::logout,
+        crate::handlers::graphql::graphql,
+        crate::handlers::graphql::graphiql,
+        crate::handlers::ws::ws,
+        crate::handlers::label::create_label,
+        crate::handlers::label::all_label,
+        crate::handlers::label::delete_label,
+        crate::handlers::webhook::create_webhook,
+        crate::handlers::webhook::all_webhook,
+        crate::handlers::webhook::delete_webhook,
+        crate::handlers::webhook::all_delivery,
+        crate::handlers::webhook::redeliver_delivery,
+        crate::handlers::workspace::create_workspace,
+        crate::handlers::workspace::all_workspace,
+        crate::handlers::workspace::all_member,
+        crate::handlers::workspace::update_member,
+        crate::handlers::workspace::delete_member,
+        crate::handlers::workspace::create_invite,
+        crate::handlers::workspace::accept_invite,
+        crate::handlers::share::create_share,
+        crate::handlers::share::all_share,
+        crate::handlers::share::delete_share,
+        crate::handlers::share::shared_json,
+        crate::handlers::share::shared_page,
+        crate::handlers::share::unlock_shared_page,
+    ),
+    components(schemas(
+        crate::repositories::todo::TodoEntity,
+        crate::repositories::todo::CreateTodo,
+        crate::repositories::todo::UpdateTodo,
+        crate::repositories::todo::MoveTodo,
+        crate::repositories::todo::AddBlocker,
+        crate::repositories::todo::AssignTodo,
+        crate::repositories::label::Label,
+        crate::handlers::label::CreateLabel,
+        crate::repositories::comment::Comment,
+        crate::repositories::comment::CreateComment,
+        crate::repositories::comment::UpdateComment,
+        crate::repositories::attachment::Attachment,
+        crate::repositories::calendar::CalendarToken,
+        crate::repositories::calendar::NewCalendarToken,
+        crate::repositories::token::ApiToken,
+        crate::repositories::token::NewApiToken,
+        crate::repositories::token::CreateApiToken,
+        crate::repositories::user::User,
+        crate::transfer::TransferFormat,
+        crate::transfer::ImportReport,
+        crate::transfer::ImportRowError,
+        crate::repositories::webhook::Webhook,
+        crate::repositories::webhook::NewWebhook,
+        crate::repositories::webhook::CreateWebhook,
+        crate::repositories::webhook::Delivery,
+        crate::repositories::workspace::Workspace,
+        crate::repositories::workspace::Role,
+        crate::repositories::workspace::Member,
+        crate::repositories::workspace::Invite,
+        crate::repositories::workspace::NewInvite,
+        crate::repositories::workspace::CreateInvite,
+        crate::handlers::workspace::CreateWorkspace,
+        crate::handlers::workspace::WorkspaceWithRole,
+        crate::handlers::workspace::UpdateMember,
+        crate::handlers::workspace::AcceptInvite,
+        crate::repositories::share::Share,
+        crate::repositories::share::NewShare,
+        crate::repositories::share::CreateShare,
+        crate::handlers::share::SharedTodos,
+    )),
+    tags(
+        (name = "todos"),
+        (name = "labels"),
+        (name = "comments"),
+        (name = "attachments"),
+        (name = "transfer", description = "Import and export in JSON, CSV, NDJSON, todo.txt, iCalendar and markdown"),
+        (name = "calendar", description = "Token protected iCalendar feed"),
+        (name = "tokens", description = "Personal API tokens, sent as `Authorization: Bearer`, with scopes such as `todos:read` and `todos:write`; a token acts as the user who made it"),
+        (name = "auth", description = "Browser login through an OpenID Connect provider, kept in a `session` cookie"),
+        (name = "graphql", description = "GraphQL view over todos and labels"),
+        (name = "workspaces", description = "Todos and labels shared with other users as owners, editors or viewers"),
+        (name = "shares", description = "Public read only links to a todo or to the todos carrying a label, with optional password and expiry"),
+        (name = "webhooks", description = "Signed change notifications with retries and dead letters; they carry todos of every workspace, so only the admin token manages them"),
+        (name = "metrics", description = "Prometheus metrics"),
+        (name = "docs"),
+    )
+)]
+pub struct ApiDoc;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use utoipa::OpenApi;
+
+    /// Routes serving the documentation itself rather than the API.
+    const UNDOCUMENTED_ROUTES: [&str; 2] = ["/swagger-ui", "/swagger-ui/*tail"];
+
+    /// Collects `(path, method)` pairs from the `.route(...)` calls in `create_app`.
+    fn registered_routes(source: &str) -> Vec<(String, String)> {
+        let mut routes = vec![];
+        for chunk in source.split(".route(\"").skip(1) {
+            let (path, rest) = chunk.split_once('"').unwrap();
+            let rest = rest.split(".layer(").next().unwrap();
+            for method in ["get", "post", "put", "patch", "delete"] {
+                if rest.contains(&format!("routing::{}(", method)) || rest.contains(&format!(".{}(crate::", method)) {
+                    routes.push((path.to_string(), method.to_string()));
+                }
+            }
+        }
+        routes
+    }
+
+    fn openapi_path(route: &str) -> String {
+        route
+            .split('/')
+            .map(|segment| match segment.strip_prefix(':') {
+                Some(param) => format!("{{{}}}", param),
+                None => segment.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    #[test]
+    fn every_route_is_documented() {
+        let routes = registered_routes(include_str!("main.rs"));
+        assert!(routes.len() > 20);
+
+        let doc = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let missing: Vec<String> = routes
+            .into_iter()
+            .filter(|(path, _)| !UNDOCUMENTED_ROUTES.contains(&path.as_str()))
+            .filter(|(path, method)| doc["paths"][openapi_path(path)][method].is_null())
+            .map(|(path, method)| format!("{} {}", method.to_uppercase(), path))
+            .collect();
+        assert!(missing.is_empty(), "routes without OpenAPI documentation: {:?}", missing);
+    }
+
+    #[test]
+    fn payload_schemas_are_published() {
+        let doc = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        for schema in ["TodoEntity", "CreateTodo", "UpdateTodo", "Label", "CreateLabel"] {
+            assert!(doc["components"]["schemas"][schema].is_object(), "missing schema {}", schema);
+        }
+        let update = &doc["components"]["schemas"]["UpdateTodo"];
+        assert!(update["required"].as_array().is_none_or(|required| required.is_empty()));
+    }
+}
@@ -1,12 +1,117 @@
+pub mod attachment;
+pub mod calendar;
+pub mod comment;
+pub mod idempotency;
 pub mod label;
+pub mod share;
 pub mod todo;
+pub mod token;
+pub mod user;
+pub mod webhook;
+pub mod workspace;
 
 #[derive(Debug, thiserror::Error)]
-enum RepositoryError {
+pub enum RepositoryError {
     #[error("Unexpected Error: {0}")]
     Unexpected(String),
     #[error("NotFound, {0}")]
     NotFound(i32),
     #[error("Duplicate data {0}")]
     Duplicate(i32),
+    #[error("Blocked, {0} has open blockers")]
+    Blocked(i32),
+    #[error("Dependency cycle {}", .0.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(" -> "))]
+    Cycle(Vec<i32>),
+    #[error("Workspace {0} needs at least one owner")]
+    LastOwner(i32),
+}
+
+/// Adds `.traced()` to sqlx queries: running the query then opens a `db.query` span carrying
+/// the statement, so traces show the SQL behind each repository call and how long it took.
+pub trait TraceQuery: Sized {
+    fn traced(self) -> Traced<Self>;
+}
+
+impl<'q, Q: sqlx::Execute<'q, sqlx::Postgres>> TraceQuery for Q {
+    fn traced(self) -> Traced<Self> {
+        let span = tracing::info_span!(
+            "db.query",
+            otel.kind = "client",
+            db.system = "postgresql",
+            db.statement = self.sql().trim(),
+        );
+        Traced { query: self, span }
+    }
+}
+
+pub struct Traced<Q> {
+    query: Q,
+    span: tracing::Span,
+}
+
+impl<'q> Traced<sqlx::query::Query<'q, sqlx::Postgres, sqlx::postgres::PgArguments>> {
+    pub async fn execute<'e, 'c: 'e, E>(self, executor: E) -> Result<sqlx::postgres::PgQueryResult, sqlx::Error>
+    where
+        'q: 'e,
+        E: sqlx::Executor<'c, Database = sqlx::Postgres>,
+    {
+        tracing::Instrument::instrument(self.query.execute(executor), self.span).await
+    }
+
+    pub async fn fetch_optional<'e, 'c: 'e, E>(self, executor: E) -> Result<Option<sqlx::postgres::PgRow>, sqlx::Error>
+    where
+        'q: 'e,
+        E: sqlx::Executor<'c, Database = sqlx::Postgres>,
+    {
+        tracing::Instrument::instrument(self.query.fetch_optional(executor), self.span).await
+    }
+}
+
+impl<'q, O> Traced<sqlx::query::QueryAs<'q, sqlx::Postgres, O, sqlx::postgres::PgArguments>>
+where
+    O: Send + Unpin + for<'r> sqlx::FromRow<'r, sqlx::postgres::PgRow>,
+{
+    pub async fn fetch_one<'e, 'c: 'e, E>(self, executor: E) -> Result<O, sqlx::Error>
+    where
+        'q: 'e,
+        E: 'e + sqlx::Executor<'c, Database = sqlx::Postgres>,
+        O: 'e,
+    {
+        tracing::Instrument::instrument(self.query.fetch_one(executor), self.span).await
+    }
+
+    /// Rows as they arrive; the span is entered while the stream is polled.
+    pub fn fetch<'e, 'c: 'e, E>(self, executor: E) -> futures::stream::BoxStream<'e, Result<O, sqlx::Error>>
+    where
+        'q: 'e,
+        E: 'e + sqlx::Executor<'c, Database = sqlx::Postgres>,
+        O: 'e,
+    {
+        let span = self.span;
+        let rows = self.query.fetch(executor);
+        Box::pin(futures::stream::unfold(rows, move |mut rows| {
+            tracing::Instrument::instrument(
+                async move { futures::StreamExt::next(&mut rows).await.map(|row| (row, rows)) },
+                span.clone(),
+            )
+        }))
+    }
+
+    pub async fn fetch_all<'e, 'c: 'e, E>(self, executor: E) -> Result<Vec<O>, sqlx::Error>
+    where
+        'q: 'e,
+        E: 'e + sqlx::Executor<'c, Database = sqlx::Postgres>,
+        O: 'e,
+    {
+        tracing::Instrument::instrument(self.query.fetch_all(executor), self.span).await
+    }
+
+    pub async fn fetch_optional<'e, 'c: 'e, E>(self, executor: E) -> Result<Option<O>, sqlx::Error>
+    where
+        'q: 'e,
+        E: 'e + sqlx::Executor<'c, Database = sqlx::Postgres>,
+        O: 'e,
+    {
+        tracing::Instrument::instrument(self.query.fetch_optional(executor), self.span).await
+    }
 }
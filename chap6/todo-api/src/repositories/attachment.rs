@@ -0,0 +1,122 @@
+use super::*;
+
+#[axum::async_trait]
+pub trait AttachmentRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
+    async fn create(&self, todo_id: i32, payload: CreateAttachment) -> anyhow::Result<Attachment>;
+    async fn all(&self, todo_id: i32) -> anyhow::Result<Vec<Attachment>>;
+    async fn find(&self, todo_id: i32, id: i32) -> anyhow::Result<Attachment>;
+    async fn delete(&self, todo_id: i32, id: i32) -> anyhow::Result<Attachment>;
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, sqlx::FromRow, utoipa::ToSchema, ts_rs::TS)]
+pub struct Attachment {
+    pub id: i32,
+    pub todo_id: i32,
+    pub filename: String,
+    pub content_type: String,
+    #[ts(type = "number")]
+    pub size: i64,
+    #[serde(skip)]
+    pub storage_key: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateAttachment {
+    pub filename: String,
+    pub content_type: String,
+    pub size: i64,
+    pub storage_key: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct AttachmentRepositoryForDb {
+    pool: sqlx::PgPool,
+}
+
+impl AttachmentRepositoryForDb {
+    pub fn new(pool: sqlx::PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[axum::async_trait]
+impl AttachmentRepository for AttachmentRepositoryForDb {
+    async fn create(&self, todo_id: i32, payload: CreateAttachment) -> anyhow::Result<Attachment> {
+        sqlx::query("select id from todos where id=$1")
+            .bind(todo_id)
+            .traced()
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or(RepositoryError::NotFound(todo_id))?;
+
+        let attachment = sqlx::query_as::<_, Attachment>(
+            r#"
+insert into todo_attachments (todo_id, filename, content_type, size, storage_key)
+values ($1, $2, $3, $4, $5)
+returning *
+            "#
+        )
+        .bind(todo_id)
+        .bind(payload.filename)
+        .bind(payload.content_type)
+        .bind(payload.size)
+        .bind(payload.storage_key)
+        .traced()
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(attachment)
+    }
+
+    async fn all(&self, todo_id: i32) -> anyhow::Result<Vec<Attachment>> {
+        let attachments = sqlx::query_as::<_, Attachment>(
+            r#"
+select * from todo_attachments
+where todo_id=$1
+order by id asc;
+            "#
+        )
+        .bind(todo_id)
+        .traced()
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(attachments)
+    }
+
+    async fn find(&self, todo_id: i32, id: i32) -> anyhow::Result<Attachment> {
+        let attachment = sqlx::query_as::<_, Attachment>(
+            r#"
+select * from todo_attachments
+where todo_id=$1 and id=$2
+            "#
+        )
+        .bind(todo_id)
+        .bind(id)
+        .traced()
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(RepositoryError::NotFound(id))?;
+
+        Ok(attachment)
+    }
+
+    async fn delete(&self, todo_id: i32, id: i32) -> anyhow::Result<Attachment> {
+        let attachment = sqlx::query_as::<_, Attachment>(
+            r#"
+delete from todo_attachments
+where todo_id=$1 and id=$2
+returning *
+            "#
+        )
+        .bind(todo_id)
+        .bind(id)
+        .traced()
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(RepositoryError::NotFound(id))?;
+
+        Ok(attachment)
+    }
+}
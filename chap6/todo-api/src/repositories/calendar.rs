@@ -0,0 +1,128 @@
+use super::*;
+
+#[axum::async_trait]
+pub trait CalendarTokenRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
+    async fn create(&self, user_id: Option<i32>) -> anyhow::Result<NewCalendarToken>;
+    async fn find(&self, id: i32) -> anyhow::Result<CalendarToken>;
+    async fn all(&self) -> anyhow::Result<Vec<CalendarToken>>;
+    async fn delete(&self, id: i32) -> anyhow::Result<()>;
+    /// The token a feed URL carries, if it exists.
+    async fn verify(&self, token: &str) -> anyhow::Result<Option<CalendarToken>>;
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, sqlx::FromRow, utoipa::ToSchema)]
+pub struct CalendarToken {
+    pub id: i32,
+    /// Leading characters of the token, enough to tell tokens apart without revealing them.
+    pub prefix: String,
+    /// The user whose todos the feed shows.
+    pub user_id: Option<i32>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// A freshly created token; the secret is only ever returned here.
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, utoipa::ToSchema)]
+pub struct NewCalendarToken {
+    #[serde(flatten)]
+    pub calendar_token: CalendarToken,
+    pub token: String,
+    pub url: String,
+}
+
+fn hash_token(token: &str) -> String {
+    hex::encode(<sha2::Sha256 as sha2::Digest>::digest(token.as_bytes()))
+}
+
+#[derive(Debug, Clone)]
+pub struct CalendarTokenRepositoryForDb {
+    pool: sqlx::PgPool,
+}
+
+impl CalendarTokenRepositoryForDb {
+    pub fn new(pool: sqlx::PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[axum::async_trait]
+impl CalendarTokenRepository for CalendarTokenRepositoryForDb {
+    async fn create(&self, user_id: Option<i32>) -> anyhow::Result<NewCalendarToken> {
+        let token = hex::encode(rand::random::<[u8; 32]>());
+        let calendar_token = sqlx::query_as::<_, CalendarToken>(
+            r#"
+insert into calendar_tokens (token_hash, prefix, user_id)
+values ($1, $2, $3)
+returning id, prefix, user_id, created_at
+            "#
+        )
+        .bind(hash_token(&token))
+        .bind(&token[..8])
+        .bind(user_id)
+        .traced()
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(NewCalendarToken {
+            calendar_token,
+            url: format!("/calendar.ics?token={}", token),
+            token,
+        })
+    }
+
+    async fn find(&self, id: i32) -> anyhow::Result<CalendarToken> {
+        let token = sqlx::query_as::<_, CalendarToken>(
+            r#"
+select id, prefix, user_id, created_at from calendar_tokens
+where id=$1
+            "#
+        )
+        .bind(id)
+        .traced()
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(RepositoryError::NotFound(id))?;
+
+        Ok(token)
+    }
+
+    async fn all(&self) -> anyhow::Result<Vec<CalendarToken>> {
+        let tokens = sqlx::query_as::<_, CalendarToken>(
+            r#"
+select id, prefix, user_id, created_at from calendar_tokens
+order by id asc;
+            "#
+        )
+        .traced()
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(tokens)
+    }
+
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        let result = sqlx::query(
+            r#"
+delete from calendar_tokens where id=$1
+            "#
+        )
+        .bind(id)
+        .traced()
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+
+        Ok(())
+    }
+
+    async fn verify(&self, token: &str) -> anyhow::Result<Option<CalendarToken>> {
+        let found = sqlx::query_as::<_, CalendarToken>("select id, prefix, user_id, created_at from calendar_tokens where token_hash=$1")
+            .bind(hash_token(token))
+            .traced()
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(found)
+    }
+}
@@ -0,0 +1,147 @@
+use super::*;
+
+#[axum::async_trait]
+pub trait CommentRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
+    async fn create(&self, todo_id: i32, payload: CreateComment, author: String, author_user_id: Option<i32>) -> anyhow::Result<Comment>;
+    async fn find(&self, todo_id: i32, id: i32) -> anyhow::Result<Comment>;
+    async fn all(&self, todo_id: i32) -> anyhow::Result<Vec<Comment>>;
+    async fn update(&self, todo_id: i32, id: i32, payload: UpdateComment) -> anyhow::Result<Comment>;
+    async fn delete(&self, todo_id: i32, id: i32) -> anyhow::Result<()>;
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, sqlx::FromRow, utoipa::ToSchema, ts_rs::TS)]
+pub struct Comment {
+    pub id: i32,
+    pub todo_id: i32,
+    pub author: String,
+    // none for the admin token, tokens without an owner and open access
+    pub author_user_id: Option<i32>,
+    pub body: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, validator::Validate, utoipa::ToSchema, ts_rs::TS)]
+#[ts(rename = "NewCommentPayload")]
+pub struct CreateComment {
+    #[validate(length(min=1, message="cannot be empty"))]
+    #[validate(length(max=10000, message="over text length"))]
+    body: String,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, validator::Validate, utoipa::ToSchema)]
+pub struct UpdateComment {
+    #[validate(length(min=1, message="cannot be empty"))]
+    #[validate(length(max=10000, message="over text length"))]
+    body: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct CommentRepositoryForDb {
+    pool: sqlx::PgPool,
+}
+
+impl CommentRepositoryForDb {
+    pub fn new(pool: sqlx::PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[axum::async_trait]
+impl CommentRepository for CommentRepositoryForDb {
+    async fn create(&self, todo_id: i32, payload: CreateComment, author: String, author_user_id: Option<i32>) -> anyhow::Result<Comment> {
+        sqlx::query("select id from todos where id=$1")
+            .bind(todo_id)
+            .traced()
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or(RepositoryError::NotFound(todo_id))?;
+
+        let comment = sqlx::query_as::<_, Comment>(
+            r#"
+insert into todo_comments (todo_id, author, author_user_id, body)
+values ($1, $2, $3, $4)
+returning *
+            "#
+        )
+        .bind(todo_id)
+        .bind(author)
+        .bind(author_user_id)
+        .bind(payload.body)
+        .traced()
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(comment)
+    }
+
+    async fn find(&self, todo_id: i32, id: i32) -> anyhow::Result<Comment> {
+        let comment = sqlx::query_as::<_, Comment>(
+            r#"
+select * from todo_comments
+where todo_id=$1 and id=$2
+            "#
+        )
+        .bind(todo_id)
+        .bind(id)
+        .traced()
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(RepositoryError::NotFound(id))?;
+
+        Ok(comment)
+    }
+
+    async fn all(&self, todo_id: i32) -> anyhow::Result<Vec<Comment>> {
+        let comments = sqlx::query_as::<_, Comment>(
+            r#"
+select * from todo_comments
+where todo_id=$1
+order by created_at asc, id asc;
+            "#
+        )
+        .bind(todo_id)
+        .traced()
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(comments)
+    }
+
+    async fn update(&self, todo_id: i32, id: i32, payload: UpdateComment) -> anyhow::Result<Comment> {
+        let comment = sqlx::query_as::<_, Comment>(
+            r#"
+update todo_comments set body=$1, updated_at=now()
+where todo_id=$2 and id=$3
+returning *
+            "#
+        )
+        .bind(payload.body)
+        .bind(todo_id)
+        .bind(id)
+        .traced()
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(RepositoryError::NotFound(id))?;
+
+        Ok(comment)
+    }
+
+    async fn delete(&self, todo_id: i32, id: i32) -> anyhow::Result<()> {
+        let result = sqlx::query(
+            r#"
+delete from todo_comments where todo_id=$1 and id=$2
+            "#
+        )
+        .bind(todo_id)
+        .bind(id)
+        .traced()
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+
+        Ok(())
+    }
+}
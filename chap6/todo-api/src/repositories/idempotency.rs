@@ -0,0 +1,208 @@
+use super::*;
+
+#[axum::async_trait]
+pub trait IdempotencyRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
+    /// Claims `key` within `scope` for a request hashing to `request_hash`, unless an unexpired
+    /// claim exists, in which case that one is returned instead.
+    async fn reserve(&self, scope: &str, key: &str, request_hash: &str, ttl: chrono::Duration) -> anyhow::Result<Reservation>;
+    async fn complete(&self, scope: &str, key: &str, response: StoredResponse) -> anyhow::Result<()>;
+    /// Drops a claim so the key can be used again, e.g. after the request failed on our side.
+    async fn release(&self, scope: &str, key: &str) -> anyhow::Result<()>;
+    async fn purge_expired(&self) -> anyhow::Result<u64>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredResponse {
+    pub status: u16,
+    pub content_type: Option<String>,
+    pub body: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reservation {
+    Reserved,
+    /// `response` is `None` while the first request is still being handled.
+    Existing {
+        request_hash: String,
+        response: Option<StoredResponse>,
+    },
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct IdempotencyRow {
+    request_hash: String,
+    status: Option<i32>,
+    content_type: Option<String>,
+    body: Option<Vec<u8>>,
+}
+
+impl From<IdempotencyRow> for Reservation {
+    fn from(row: IdempotencyRow) -> Self {
+        let response = row.status.map(|status| StoredResponse {
+            status: status as u16,
+            content_type: row.content_type,
+            body: row.body.unwrap_or_default(),
+        });
+        Reservation::Existing { request_hash: row.request_hash, response }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IdempotencyRepositoryForDb {
+    pool: sqlx::PgPool,
+}
+
+impl IdempotencyRepositoryForDb {
+    pub fn new(pool: sqlx::PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[axum::async_trait]
+impl IdempotencyRepository for IdempotencyRepositoryForDb {
+    async fn reserve(&self, scope: &str, key: &str, request_hash: &str, ttl: chrono::Duration) -> anyhow::Result<Reservation> {
+        loop {
+            let reserved = sqlx::query(
+                r#"
+insert into idempotency_keys (scope, key, request_hash, expires_at)
+values ($1, $2, $3, now() + make_interval(secs => $4))
+on conflict (scope, key) do update
+set request_hash=excluded.request_hash, status=null, content_type=null, body=null,
+    created_at=now(), expires_at=excluded.expires_at
+where idempotency_keys.expires_at <= now()
+returning key
+                "#
+            )
+            .bind(scope)
+            .bind(key)
+            .bind(request_hash)
+            .bind(ttl.num_seconds() as f64)
+            .traced()
+            .fetch_optional(&self.pool)
+            .await?;
+            if reserved.is_some() {
+                return Ok(Reservation::Reserved);
+            }
+
+            let existing = sqlx::query_as::<_, IdempotencyRow>(
+                r#"
+select request_hash, status, content_type, body from idempotency_keys
+where scope=$1 and key=$2
+                "#
+            )
+            .bind(scope)
+            .bind(key)
+            .traced()
+            .fetch_optional(&self.pool)
+            .await?;
+            // released in between; try to claim it again
+            if let Some(row) = existing {
+                return Ok(row.into());
+            }
+        }
+    }
+
+    async fn complete(&self, scope: &str, key: &str, response: StoredResponse) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+update idempotency_keys set status=$3, content_type=$4, body=$5
+where scope=$1 and key=$2
+            "#
+        )
+        .bind(scope)
+        .bind(key)
+        .bind(response.status as i32)
+        .bind(response.content_type)
+        .bind(response.body)
+        .traced()
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn release(&self, scope: &str, key: &str) -> anyhow::Result<()> {
+        sqlx::query("delete from idempotency_keys where scope=$1 and key=$2")
+            .bind(scope)
+            .bind(key)
+            .traced()
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn purge_expired(&self) -> anyhow::Result<u64> {
+        let result = sqlx::query("delete from idempotency_keys where expires_at <= now()")
+            .traced()
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+
+    #[derive(Debug, Clone)]
+    struct StoredKey {
+        request_hash: String,
+        response: Option<StoredResponse>,
+        expires_at: chrono::DateTime<chrono::Utc>,
+    }
+
+    type IdempotencyDatas = std::collections::HashMap<(String, String), StoredKey>;
+
+    #[derive(Debug, Clone, Default)]
+    pub struct IdempotencyRepositoryForMemory {
+        store: std::sync::Arc<std::sync::RwLock<IdempotencyDatas>>,
+    }
+
+    impl IdempotencyRepositoryForMemory {
+        pub fn new() -> Self {
+            Self::default()
+        }
+    }
+
+    #[axum::async_trait]
+    impl IdempotencyRepository for IdempotencyRepositoryForMemory {
+        async fn reserve(&self, scope: &str, key: &str, request_hash: &str, ttl: chrono::Duration) -> anyhow::Result<Reservation> {
+            let mut store = self.store.write().unwrap();
+            let now = chrono::Utc::now();
+            let id = (scope.to_string(), key.to_string());
+            match store.get(&id) {
+                Some(stored) if stored.expires_at > now => Ok(Reservation::Existing {
+                    request_hash: stored.request_hash.clone(),
+                    response: stored.response.clone(),
+                }),
+                _ => {
+                    store.insert(id, StoredKey { request_hash: request_hash.to_string(), response: None, expires_at: now + ttl });
+                    Ok(Reservation::Reserved)
+                }
+            }
+        }
+
+        async fn complete(&self, scope: &str, key: &str, response: StoredResponse) -> anyhow::Result<()> {
+            let mut store = self.store.write().unwrap();
+            if let Some(stored) = store.get_mut(&(scope.to_string(), key.to_string())) {
+                stored.response = Some(response);
+            }
+            Ok(())
+        }
+
+        async fn release(&self, scope: &str, key: &str) -> anyhow::Result<()> {
+            self.store.write().unwrap().remove(&(scope.to_string(), key.to_string()));
+            Ok(())
+        }
+
+        async fn purge_expired(&self) -> anyhow::Result<u64> {
+            let mut store = self.store.write().unwrap();
+            let before = store.len();
+            let now = chrono::Utc::now();
+            store.retain(|_, stored| stored.expires_at > now);
+            Ok((before - store.len()) as u64)
+        }
+    }
+}
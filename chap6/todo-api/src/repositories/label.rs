@@ -2,21 +2,20 @@ use super::*;
 
 #[axum::async_trait]
 pub trait LabelRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
-    async fn create(&self, name: String) -> anyhow::Result<Label>;
+    /// Names are unique within a workspace, and among labels in none.
+    async fn create(&self, name: String, workspace_id: Option<i32>) -> anyhow::Result<Label>;
+    async fn find(&self, id: i32) -> anyhow::Result<Label>;
     async fn all(&self) -> anyhow::Result<Vec<Label>>;
     async fn delete(&self, id: i32) -> anyhow::Result<()>;
 }
 
-#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, sqlx::FromRow)]
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, sqlx::FromRow, utoipa::ToSchema, ts_rs::TS, async_graphql::SimpleObject)]
+#[graphql(complex)]
 pub struct Label {
     pub id: i32,
     pub name: String,
-}
-
-#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
-pub struct UpdateLabel {
-    id: i32,
-    name: String,
+    // `None` for labels visible to everyone
+    pub workspace_id: Option<i32>,
 }
 
 #[derive(Debug, Clone)]
@@ -32,13 +31,15 @@ impl LabelRepositoryForDb {
 
 #[axum::async_trait]
 impl LabelRepository for LabelRepositoryForDb {
-    async fn create(&self, name: String) -> anyhow::Result<Label> {
+    async fn create(&self, name: String, workspace_id: Option<i32>) -> anyhow::Result<Label> {
         let optional_label= sqlx::query_as::<_, Label>(
             r#"
-select * from labels where name=$1
+select * from labels where name=$1 and workspace_id is not distinct from $2
             "#
         )
         .bind(name.clone())
+        .bind(workspace_id)
+        .traced()
         .fetch_optional(&self.pool)
         .await?;
 
@@ -48,18 +49,30 @@ select * from labels where name=$1
 
         let label = sqlx::query_as::<_, Label>(
             r#"
-insert into labels (name)
-values ($1)
+insert into labels (name, workspace_id)
+values ($1, $2)
 returning *
             "#
         )
         .bind(name.clone())
+        .bind(workspace_id)
+        .traced()
         .fetch_one(&self.pool)
         .await?;
 
         Ok(label)
     }
 
+    async fn find(&self, id: i32) -> anyhow::Result<Label> {
+        let label = sqlx::query_as::<_, Label>("select * from labels where id=$1")
+            .bind(id)
+            .traced()
+            .fetch_optional(&self.pool)
+            .await?;
+
+        label.ok_or_else(|| RepositoryError::NotFound(id).into())
+    }
+
     async fn all(&self) -> anyhow::Result<Vec<Label>> {
         let labels = sqlx::query_as::<_, Label>(
             r#"
@@ -67,6 +80,7 @@ select * from labels
 order by labels.id asc;
             "#
         )
+        .traced()
         .fetch_all(&self.pool)
         .await?;
 
@@ -80,6 +94,7 @@ delete from labels where id=$1
             "#
         )
         .bind(id)
+        .traced()
         .execute(&self.pool)
         .await
         .map_err(|e| match e {
@@ -90,3 +105,51 @@ delete from labels where id=$1
         Ok(())
     }
 }
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+
+    type LabelDatas = std::collections::HashMap<i32, Label>;
+
+    #[derive(Debug, Clone, Default)]
+    pub struct LabelRepositoryForMemory {
+        store: std::sync::Arc<std::sync::RwLock<LabelDatas>>,
+    }
+
+    impl LabelRepositoryForMemory {
+        pub fn new() -> Self {
+            Self::default()
+        }
+    }
+
+    #[axum::async_trait]
+    impl LabelRepository for LabelRepositoryForMemory {
+        async fn create(&self, name: String, workspace_id: Option<i32>) -> anyhow::Result<Label> {
+            let mut store = self.store.write().unwrap();
+            if let Some(label) = store.values().find(|label| label.name == name && label.workspace_id == workspace_id) {
+                return Err(RepositoryError::Duplicate(label.id).into());
+            }
+            let id = store.keys().max().copied().unwrap_or(0) + 1;
+            let label = Label { id, name, workspace_id };
+            store.insert(id, label.clone());
+            Ok(label)
+        }
+
+        async fn find(&self, id: i32) -> anyhow::Result<Label> {
+            let label = self.store.read().unwrap().get(&id).cloned().ok_or(RepositoryError::NotFound(id))?;
+            Ok(label)
+        }
+
+        async fn all(&self) -> anyhow::Result<Vec<Label>> {
+            let mut labels: Vec<Label> = self.store.read().unwrap().values().cloned().collect();
+            labels.sort_by_key(|label| label.id);
+            Ok(labels)
+        }
+
+        async fn delete(&self, id: i32) -> anyhow::Result<()> {
+            self.store.write().unwrap().remove(&id).ok_or(RepositoryError::NotFound(id))?;
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,346 @@
+use super::*;
+
+#[axum::async_trait]
+pub trait ShareRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
+    /// `workspace_id` is the workspace of the shared todo or label, kept so shares can be listed
+    /// and revoked by its members.
+    async fn create(&self, payload: CreateShare, workspace_id: Option<i32>) -> anyhow::Result<NewShare>;
+    async fn all(&self) -> anyhow::Result<Vec<Share>>;
+    async fn find(&self, id: i32) -> anyhow::Result<Share>;
+    async fn delete(&self, id: i32) -> anyhow::Result<()>;
+    /// Looks up the unexpired share with this token and checks `password` against it.
+    async fn open(&self, token: &str, password: Option<&str>) -> anyhow::Result<ShareAccess>;
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, sqlx::FromRow, utoipa::ToSchema)]
+pub struct Share {
+    pub id: i32,
+    /// Leading characters of the token, enough to tell shares apart without revealing them.
+    pub prefix: String,
+    /// The shared todo, for a single todo.
+    pub todo_id: Option<i32>,
+    /// The label whose todos are shared, for a list.
+    pub label_id: Option<i32>,
+    pub workspace_id: Option<i32>,
+    pub has_password: bool,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// A freshly created share; the token is only ever returned here.
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, utoipa::ToSchema)]
+pub struct NewShare {
+    #[serde(flatten)]
+    pub share: Share,
+    pub token: String,
+    pub url: String,
+}
+
+/// Shares either one todo or the list of todos carrying a label.
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, validator::Validate, utoipa::ToSchema)]
+#[validate(schema(function = "validate_share_target"))]
+pub struct CreateShare {
+    pub todo_id: Option<i32>,
+    pub label_id: Option<i32>,
+    /// Asked for before the share is shown when set.
+    #[validate(length(min=8, message="must be at least 8 characters"))]
+    #[validate(length(max=128, message="over text length"))]
+    password: Option<String>,
+    /// Never expires when omitted.
+    #[validate(range(min=1, max=365, message="must be between 1 and 365 days"))]
+    expires_in_days: Option<i64>,
+}
+
+impl CreateShare {
+    #[cfg(test)]
+    pub fn new(todo_id: Option<i32>, label_id: Option<i32>, password: Option<String>, expires_in_days: Option<i64>) -> Self {
+        Self { todo_id, label_id, password, expires_in_days }
+    }
+
+    fn expires_at(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.expires_in_days.map(|days| chrono::Utc::now() + chrono::Duration::days(days))
+    }
+
+    fn password_hash(&self) -> anyhow::Result<Option<String>> {
+        self.password.as_deref().map(hash_password).transpose()
+    }
+}
+
+fn validate_share_target(payload: &CreateShare) -> Result<(), validator::ValidationError> {
+    match (payload.todo_id, payload.label_id) {
+        (Some(_), None) | (None, Some(_)) => Ok(()),
+        _ => Err(validator::ValidationError::new("specify exactly one of todo_id or label_id")),
+    }
+}
+
+/// What a share token gives access to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShareAccess {
+    /// Unknown, revoked or expired.
+    Missing,
+    /// The share has a password and it was missing or wrong.
+    Locked,
+    Open(Share),
+}
+
+fn new_token() -> String {
+    hex::encode(rand::random::<[u8; 32]>())
+}
+
+fn hash_token(token: &str) -> String {
+    hex::encode(<sha2::Sha256 as sha2::Digest>::digest(token.as_bytes()))
+}
+
+fn share_url(token: &str) -> String {
+    format!("/shared/{}/page", token)
+}
+
+fn hash_password(password: &str) -> anyhow::Result<String> {
+    let salt = argon2::password_hash::SaltString::generate(&mut argon2::password_hash::rand_core::OsRng);
+    let hash = argon2::PasswordHasher::hash_password(&argon2::Argon2::default(), password.as_bytes(), &salt)
+        .map_err(|e| anyhow::anyhow!("failed to hash password: {}", e))?;
+    Ok(hash.to_string())
+}
+
+fn unlock(share: Share, password_hash: Option<&str>, password: Option<&str>) -> ShareAccess {
+    let (password_hash, password) = match (password_hash, password) {
+        (None, _) => return ShareAccess::Open(share),
+        (Some(_), None) => return ShareAccess::Locked,
+        (Some(password_hash), Some(password)) => (password_hash, password),
+    };
+    let verified = argon2::password_hash::PasswordHash::new(password_hash)
+        .map(|hash| argon2::PasswordVerifier::verify_password(&argon2::Argon2::default(), password.as_bytes(), &hash).is_ok())
+        .unwrap_or(false);
+    if verified {
+        ShareAccess::Open(share)
+    } else {
+        ShareAccess::Locked
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct ShareRow {
+    id: i32,
+    prefix: String,
+    todo_id: Option<i32>,
+    label_id: Option<i32>,
+    workspace_id: Option<i32>,
+    password_hash: Option<String>,
+    created_at: chrono::DateTime<chrono::Utc>,
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl ShareRow {
+    fn into_access(self, password: Option<&str>) -> ShareAccess {
+        let share = Share {
+            id: self.id,
+            prefix: self.prefix,
+            todo_id: self.todo_id,
+            label_id: self.label_id,
+            workspace_id: self.workspace_id,
+            has_password: self.password_hash.is_some(),
+            created_at: self.created_at,
+            expires_at: self.expires_at,
+        };
+        unlock(share, self.password_hash.as_deref(), password)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ShareRepositoryForDb {
+    pool: sqlx::PgPool,
+}
+
+impl ShareRepositoryForDb {
+    pub fn new(pool: sqlx::PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[axum::async_trait]
+impl ShareRepository for ShareRepositoryForDb {
+    async fn create(&self, payload: CreateShare, workspace_id: Option<i32>) -> anyhow::Result<NewShare> {
+        let token = new_token();
+        let share = sqlx::query_as::<_, Share>(
+            r#"
+insert into shares (token_hash, prefix, todo_id, label_id, workspace_id, password_hash, expires_at)
+values ($1, $2, $3, $4, $5, $6, $7)
+returning id, prefix, todo_id, label_id, workspace_id, password_hash is not null as has_password, created_at, expires_at
+            "#
+        )
+        .bind(hash_token(&token))
+        .bind(&token[..8])
+        .bind(payload.todo_id)
+        .bind(payload.label_id)
+        .bind(workspace_id)
+        .bind(payload.password_hash()?)
+        .bind(payload.expires_at())
+        .traced()
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(NewShare { share, url: share_url(&token), token })
+    }
+
+    async fn all(&self) -> anyhow::Result<Vec<Share>> {
+        let shares = sqlx::query_as::<_, Share>(
+            r#"
+select id, prefix, todo_id, label_id, workspace_id, password_hash is not null as has_password, created_at, expires_at from shares
+order by id asc;
+            "#
+        )
+        .traced()
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(shares)
+    }
+
+    async fn find(&self, id: i32) -> anyhow::Result<Share> {
+        let share = sqlx::query_as::<_, Share>(
+            r#"
+select id, prefix, todo_id, label_id, workspace_id, password_hash is not null as has_password, created_at, expires_at from shares
+where id=$1
+            "#
+        )
+        .bind(id)
+        .traced()
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(RepositoryError::NotFound(id))?;
+
+        Ok(share)
+    }
+
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        let result = sqlx::query(
+            r#"
+delete from shares where id=$1
+            "#
+        )
+        .bind(id)
+        .traced()
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+
+        Ok(())
+    }
+
+    async fn open(&self, token: &str, password: Option<&str>) -> anyhow::Result<ShareAccess> {
+        let row = sqlx::query_as::<_, ShareRow>(
+            r#"
+select id, prefix, todo_id, label_id, workspace_id, password_hash, created_at, expires_at from shares
+where token_hash=$1 and (expires_at is null or expires_at > now())
+            "#
+        )
+        .bind(hash_token(token))
+        .traced()
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(match row {
+            Some(row) => row.into_access(password),
+            None => ShareAccess::Missing,
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+
+    /// `id -> (token hash, password hash, share)`
+    type ShareDatas = std::collections::HashMap<i32, (String, Option<String>, Share)>;
+
+    #[derive(Debug, Clone, Default)]
+    pub struct ShareRepositoryForMemory {
+        store: std::sync::Arc<std::sync::RwLock<ShareDatas>>,
+    }
+
+    impl ShareRepositoryForMemory {
+        pub fn new() -> Self {
+            Self::default()
+        }
+    }
+
+    #[axum::async_trait]
+    impl ShareRepository for ShareRepositoryForMemory {
+        async fn create(&self, payload: CreateShare, workspace_id: Option<i32>) -> anyhow::Result<NewShare> {
+            let password_hash = payload.password_hash()?;
+            let mut store = self.store.write().unwrap();
+            let id = store.keys().max().copied().unwrap_or(0) + 1;
+            let token = new_token();
+            let share = Share {
+                id,
+                prefix: token[..8].to_string(),
+                todo_id: payload.todo_id,
+                label_id: payload.label_id,
+                workspace_id,
+                has_password: password_hash.is_some(),
+                created_at: chrono::Utc::now(),
+                expires_at: payload.expires_at(),
+            };
+            store.insert(id, (hash_token(&token), password_hash, share.clone()));
+            Ok(NewShare { share, url: share_url(&token), token })
+        }
+
+        async fn all(&self) -> anyhow::Result<Vec<Share>> {
+            let store = self.store.read().unwrap();
+            let mut shares: Vec<Share> = store.values().map(|(_, _, share)| share.clone()).collect();
+            shares.sort_by_key(|share| share.id);
+            Ok(shares)
+        }
+
+        async fn find(&self, id: i32) -> anyhow::Result<Share> {
+            let store = self.store.read().unwrap();
+            let (_, _, share) = store.get(&id).ok_or(RepositoryError::NotFound(id))?;
+            Ok(share.clone())
+        }
+
+        async fn delete(&self, id: i32) -> anyhow::Result<()> {
+            let mut store = self.store.write().unwrap();
+            store.remove(&id).ok_or(RepositoryError::NotFound(id))?;
+            Ok(())
+        }
+
+        async fn open(&self, token: &str, password: Option<&str>) -> anyhow::Result<ShareAccess> {
+            let store = self.store.read().unwrap();
+            let now = chrono::Utc::now();
+            let hash = hash_token(token);
+            let found = store
+                .values()
+                .find(|(token_hash, _, share)| *token_hash == hash && share.expires_at.is_none_or(|expires_at| expires_at > now));
+            Ok(match found {
+                Some((_, password_hash, share)) => unlock(share.clone(), password_hash.as_deref(), password),
+                None => ShareAccess::Missing,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn passwords_unlock_shares() {
+        let share = Share {
+            id: 1,
+            prefix: "abcdef01".to_string(),
+            todo_id: Some(1),
+            label_id: None,
+            workspace_id: None,
+            has_password: true,
+            created_at: chrono::Utc::now(),
+            expires_at: None,
+        };
+        let hash = hash_password("correct horse").unwrap();
+        assert_eq!(ShareAccess::Open(share.clone()), unlock(share.clone(), Some(&hash), Some("correct horse")));
+        assert_eq!(ShareAccess::Locked, unlock(share.clone(), Some(&hash), Some("wrong horse")));
+        assert_eq!(ShareAccess::Locked, unlock(share.clone(), Some(&hash), None));
+        assert_eq!(ShareAccess::Open(share.clone()), unlock(share, None, Some("ignored")));
+    }
+}
@@ -3,27 +3,48 @@ use super::*;
 #[derive(Debug, Clone)]
 pub struct TodoRepositoryForDb {
     pool: sqlx::PgPool,
+    enforce_blockers: bool,
+    blob_store: Option<std::sync::Arc<dyn crate::blob_store::BlobStore>>,
 }
 
 impl TodoRepositoryForDb {
     pub fn new(pool: sqlx::PgPool) -> Self {
-        Self { pool }
+        Self { pool, enforce_blockers: true, blob_store: None }
+    }
+
+    /// Store holding attachment contents, cleaned up when a todo is deleted.
+    pub fn blob_store(mut self, blob_store: std::sync::Arc<dyn crate::blob_store::BlobStore>) -> Self {
+        self.blob_store = Some(blob_store);
+        self
+    }
+
+    /// Whether completing a todo is refused while any of its blockers is still open.
+    pub fn enforce_blockers(mut self, enforce_blockers: bool) -> Self {
+        self.enforce_blockers = enforce_blockers;
+        self
     }
 }
 
 #[axum::async_trait]
 impl TodoRepository for TodoRepositoryForDb {
+    #[tracing::instrument(name = "todo_repository.create", skip(self, payload), err(level = "warn"))]
     async fn create(&self, payload: CreateTodo) -> anyhow::Result<TodoEntity> {
-        let tx = self.pool.begin().await?;
+        let mut tx = self.pool.begin().await?;
         let row = sqlx::query_as::<_, TodoFromRow>(
             r#"
-insert into todos(text, completed)
-values ($1, false)
+insert into todos(text, completed, completed_at, position, priority, due, workspace_id)
+values ($1, $2, case when $2 then now() end, (select coalesce(min(position), 0) - $3 from todos), $4, $5, $6)
 returning *
             "#
         )
         .bind(payload.text.clone())
-        .fetch_one(&self.pool)
+        .bind(payload.completed)
+        .bind(POSITION_GAP)
+        .bind(payload.priority)
+        .bind(payload.due)
+        .bind(payload.workspace_id)
+        .traced()
+        .fetch_one(&mut tx)
         .await?;
 
         sqlx::query(
@@ -35,7 +56,8 @@ from unnest($2) as t(id);
         )
         .bind(row.id)
         .bind(payload.labels)
-        .execute(&self.pool)
+        .traced()
+        .execute(&mut tx)
         .await?;
 
         tx.commit().await?;
@@ -44,10 +66,17 @@ from unnest($2) as t(id);
         Ok(todo)
     }
 
+    #[tracing::instrument(name = "todo_repository.find", skip(self), err(level = "warn"))]
     async fn find(&self, id: i32) -> anyhow::Result<TodoEntity> {
         let items = sqlx::query_as::<_, TodoWithLabelFromRow>(
             r#"
-select todos.*, labels.id as label_id, labels.name as label_name
+select todos.*, labels.id as label_id, labels.name as label_name, labels.workspace_id as label_workspace_id,
+    exists(
+        select 1 from todo_dependencies td
+            inner join todos blocker on blocker.id = td.blocker_id
+        where td.todo_id = todos.id and not blocker.completed
+    ) as blocked,
+    (select count(*) from todo_comments tc where tc.todo_id = todos.id) as comment_count
 from todos
     left outer join todo_labels tl on todos.id = tl.todo_id
     left outer join labels on labels.id = tl.label_id
@@ -55,6 +84,7 @@ where todos.id=$1
             "#
         )
         .bind(id)
+        .traced()
         .fetch_all(&self.pool)
         .await
         .map_err(|e| match e {
@@ -67,37 +97,112 @@ where todos.id=$1
         Ok(todo.clone())
     }
 
+    #[tracing::instrument(name = "todo_repository.all", skip(self), err(level = "warn"))]
     async fn all(&self) -> anyhow::Result<Vec<TodoEntity>> {
         let items = sqlx::query_as::<_, TodoWithLabelFromRow>(
             r#"
-select todos.*, labels.id as label_id, labels.name as label_name
+select todos.*, labels.id as label_id, labels.name as label_name, labels.workspace_id as label_workspace_id,
+    exists(
+        select 1 from todo_dependencies td
+            inner join todos blocker on blocker.id = td.blocker_id
+        where td.todo_id = todos.id and not blocker.completed
+    ) as blocked,
+    (select count(*) from todo_comments tc where tc.todo_id = todos.id) as comment_count
 from todos
     left outer join todo_labels tl on todos.id = tl.todo_id
     left outer join labels on labels.id = tl.label_id
-order by todos.id desc;
+order by todos.position asc, todos.id desc;
             "#
         )
+        .traced()
         .fetch_all(&self.pool)
         .await?;
 
         Ok(fold_entities(items))
     }
 
+    #[tracing::instrument(name = "todo_repository.stream", skip(self))]
+    fn stream(&self) -> TodoStream {
+        // the query borrows the pool, so it runs in a task of its own that hands the todos over
+        let pool = self.pool.clone();
+        let (sender, receiver) = tokio::sync::mpsc::channel(64);
+        tokio::spawn(tracing::Instrument::in_current_span(async move {
+            let rows = sqlx::query_as::<_, TodoWithLabelFromRow>(
+            r#"
+select todos.*, labels.id as label_id, labels.name as label_name, labels.workspace_id as label_workspace_id,
+    exists(
+        select 1 from todo_dependencies td
+            inner join todos blocker on blocker.id = td.blocker_id
+        where td.todo_id = todos.id and not blocker.completed
+    ) as blocked,
+    (select count(*) from todo_comments tc where tc.todo_id = todos.id) as comment_count
+from todos
+    left outer join todo_labels tl on todos.id = tl.todo_id
+    left outer join labels on labels.id = tl.label_id
+order by todos.position asc, todos.id desc;
+            "#
+            )
+            .traced()
+            .fetch(&pool);
+            let mut todos = fold_entity_stream(rows);
+            while let Some(todo) = futures::StreamExt::next(&mut todos).await {
+                if sender.send(todo).await.is_err() {
+                    break;
+                }
+            }
+        }));
+
+        Box::pin(tokio_stream::wrappers::ReceiverStream::new(receiver))
+    }
+
+    #[tracing::instrument(name = "todo_repository.update", skip(self, payload), err(level = "warn"))]
     async fn update(&self, id: i32, payload: UpdateTodo) -> anyhow::Result<TodoEntity> {
-        let tx = self.pool.begin().await?;
+        let mut tx = self.pool.begin().await?;
 
-        let old_todo = self.find(id).await?;
+        // the row lock makes add_blocker wait for this update, and the blockers' share locks keep
+        // them from being reopened until it commits
+        let old_todo = sqlx::query_as::<_, TodoUpdateFromRow>(
+            r#"
+select text, completed, priority, due from todos where id=$1
+for update
+            "#
+        )
+        .bind(id)
+        .traced()
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or(RepositoryError::NotFound(id))?;
+        if self.enforce_blockers && payload.completed == Some(true) && !old_todo.completed {
+            let blockers = sqlx::query_as::<_, (bool,)>(
+                r#"
+select blocker.completed from todo_dependencies td
+    inner join todos blocker on blocker.id = td.blocker_id
+where td.todo_id=$1
+for share of blocker
+                "#
+            )
+            .bind(id)
+            .traced()
+            .fetch_all(&mut tx)
+            .await?;
+            if blockers.iter().any(|(completed,)| !completed) {
+                return Err(RepositoryError::Blocked(id).into());
+            }
+        }
         sqlx::query(
             r#"
-update todos set text=$1, completed=$2
-where id=$3
-returning *
+update todos set text=$1, completed=$2, priority=$3, due=$4,
+    completed_at=case when not $2 then null else coalesce(completed_at, now()) end
+where id=$5
             "#
         )
         .bind(payload.text.unwrap_or(old_todo.text))
         .bind(payload.completed.unwrap_or(old_todo.completed))
+        .bind(payload.priority.unwrap_or(old_todo.priority))
+        .bind(payload.due.unwrap_or(old_todo.due))
         .bind(id)
-        .fetch_one(&self.pool)
+        .traced()
+        .execute(&mut tx)
         .await?;
 
         if let Some(labels) = payload.labels {
@@ -107,7 +212,8 @@ delete from todo_labels where todo_id=$1
                 "#
             )
             .bind(id)
-            .execute(&self.pool)
+            .traced()
+            .execute(&mut tx)
             .await?;
 
             sqlx::query(
@@ -119,7 +225,8 @@ from unnest($2) as t(id)
             )
             .bind(id)
             .bind(labels)
-            .execute(&self.pool)
+            .traced()
+            .execute(&mut tx)
             .await?;
         };
 
@@ -128,36 +235,316 @@ from unnest($2) as t(id)
         Ok(todo)
     }
 
-    async fn delete(&self, id: i32) -> anyhow::Result<()> {
-        let tx = self.pool.begin().await?;
+    #[tracing::instrument(name = "todo_repository.move_to", skip(self, payload), err(level = "warn"))]
+    async fn move_to(&self, id: i32, payload: MoveTodo) -> anyhow::Result<TodoEntity> {
+        let (target_id, place_before) = match (payload.before, payload.after) {
+            (Some(target_id), None) => (target_id, true),
+            (None, Some(target_id)) => (target_id, false),
+            _ => return Err(RepositoryError::Unexpected("either before or after is required".to_string()).into()),
+        };
+        if target_id == id {
+            return Err(RepositoryError::Unexpected("cannot move a todo relative to itself".to_string()).into());
+        }
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("lock table todos in share row exclusive mode")
+            .traced()
+            .execute(&mut tx)
+            .await?;
+        sqlx::query("select id from todos where id=$1")
+            .bind(id)
+            .traced()
+            .fetch_optional(&mut tx)
+            .await?
+            .ok_or(RepositoryError::NotFound(id))?;
+
+        let mut rebalanced = false;
+        let position = loop {
+            let (target_position,): (i64,) = sqlx::query_as(
+                r#"
+select position from todos where id=$1
+                "#
+            )
+            .bind(target_id)
+            .traced()
+            .fetch_optional(&mut tx)
+            .await?
+            .ok_or(RepositoryError::NotFound(target_id))?;
+
+            let (neighbour,): (Option<i64>,) = sqlx::query_as(if place_before {
+                r#"
+select max(position) from todos where position < $1 and id <> $2
+                "#
+            } else {
+                r#"
+select min(position) from todos where position > $1 and id <> $2
+                "#
+            })
+            .bind(target_position)
+            .bind(id)
+            .traced()
+            .fetch_one(&mut tx)
+            .await?;
+
+            let position = if place_before {
+                position_between(neighbour, Some(target_position))
+            } else {
+                position_between(Some(target_position), neighbour)
+            };
+            match position {
+                Some(position) => break position,
+                None if !rebalanced => {
+                    rebalance_positions(&mut tx).await?;
+                    rebalanced = true;
+                }
+                None => return Err(RepositoryError::Unexpected("no room left to move todo".to_string()).into()),
+            }
+        };
+
         sqlx::query(
             r#"
-delete from todo_labels where todo_id=$1
+update todos set position=$1
+where id=$2
+            "#
+        )
+        .bind(position)
+        .bind(id)
+        .traced()
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+        let todo = self.find(id).await?;
+        Ok(todo)
+    }
+
+    #[tracing::instrument(name = "todo_repository.add_blocker", skip(self), err(level = "warn"))]
+    async fn add_blocker(&self, id: i32, blocker_id: i32) -> anyhow::Result<TodoEntity> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("lock table todo_dependencies in share row exclusive mode")
+            .traced()
+            .execute(&mut tx)
+            .await?;
+        // conflicts with the locks update takes, so a todo is not completed while it gets blocked
+        for todo_id in [id, blocker_id] {
+            sqlx::query("select id from todos where id=$1 for share")
+                .bind(todo_id)
+                .traced()
+                .fetch_optional(&mut tx)
+                .await?
+                .ok_or(RepositoryError::NotFound(todo_id))?;
+        }
+
+        let edges = sqlx::query_as::<_, (i32, i32)>(
+            r#"
+select todo_id, blocker_id from todo_dependencies
+            "#
+        )
+        .traced()
+        .fetch_all(&mut tx)
+        .await?;
+        if let Some(path) = find_dependency_cycle(&edges, id, blocker_id) {
+            return Err(RepositoryError::Cycle(path).into());
+        }
+
+        sqlx::query(
+            r#"
+insert into todo_dependencies (todo_id, blocker_id)
+values ($1, $2)
+on conflict do nothing
             "#
         )
         .bind(id)
+        .bind(blocker_id)
+        .traced()
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+        let todo = self.find(id).await?;
+        Ok(todo)
+    }
+
+    #[tracing::instrument(name = "todo_repository.remove_blocker", skip(self), err(level = "warn"))]
+    async fn remove_blocker(&self, id: i32, blocker_id: i32) -> anyhow::Result<TodoEntity> {
+        let result = sqlx::query(
+            r#"
+delete from todo_dependencies where todo_id=$1 and blocker_id=$2
+            "#
+        )
+        .bind(id)
+        .bind(blocker_id)
+        .traced()
         .execute(&self.pool)
-        .await
-        .map_err(|e| match e {
-            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
-            _ => RepositoryError::Unexpected(e.to_string()),
-        })?;
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(blocker_id).into());
+        }
+
+        let todo = self.find(id).await?;
+        Ok(todo)
+    }
+
+    #[tracing::instrument(name = "todo_repository.assign", skip(self), err(level = "warn"))]
+    async fn assign(&self, id: i32, assignee_id: Option<i32>) -> anyhow::Result<TodoEntity> {
+        let mut tx = self.pool.begin().await?;
+        let result = sqlx::query(
+            r#"
+update todos set assignee_id=$2 where id=$1
+            "#
+        )
+        .bind(id)
+        .bind(assignee_id)
+        .traced()
+        .execute(&mut tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(id).into());
+        }
 
+        if let Some(user_id) = assignee_id {
+            sqlx::query(
+                r#"
+insert into todo_watchers (todo_id, user_id) values ($1, $2)
+on conflict do nothing
+                "#
+            )
+            .bind(id)
+            .bind(user_id)
+            .traced()
+            .execute(&mut tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        let todo = self.find(id).await?;
+        Ok(todo)
+    }
+
+    #[tracing::instrument(name = "todo_repository.watchers", skip(self), err(level = "warn"))]
+    async fn watchers(&self, id: i32) -> anyhow::Result<Vec<i32>> {
+        self.find(id).await?;
+        let watchers = sqlx::query_as::<_, (i32,)>(
+            r#"
+select user_id from todo_watchers where todo_id=$1
+order by created_at asc, user_id asc
+            "#
+        )
+        .bind(id)
+        .traced()
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(watchers.into_iter().map(|(user_id,)| user_id).collect())
+    }
+
+    #[tracing::instrument(name = "todo_repository.watch", skip(self), err(level = "warn"))]
+    async fn watch(&self, id: i32, user_id: i32) -> anyhow::Result<()> {
+        self.find(id).await?;
         sqlx::query(
             r#"
-delete from todos where id=$1
+insert into todo_watchers (todo_id, user_id) values ($1, $2)
+on conflict do nothing
             "#
         )
         .bind(id)
+        .bind(user_id)
+        .traced()
         .execute(&self.pool)
-        .await
-        .map_err(|e| match e {
-            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
-            _ => RepositoryError::Unexpected(e.to_string()),
-        })?;
+        .await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "todo_repository.unwatch", skip(self), err(level = "warn"))]
+    async fn unwatch(&self, id: i32, user_id: i32) -> anyhow::Result<()> {
+        let result = sqlx::query(
+            r#"
+delete from todo_watchers where todo_id=$1 and user_id=$2
+            "#
+        )
+        .bind(id)
+        .bind(user_id)
+        .traced()
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(user_id).into());
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "todo_repository.delete", skip(self), err(level = "warn"))]
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        let storage_keys = sqlx::query_as::<_, (String,)>(
+            r#"
+delete from todo_attachments where todo_id=$1
+returning storage_key
+            "#
+        )
+        .bind(id)
+        .traced()
+        .fetch_all(&mut tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+delete from todo_comments where todo_id=$1
+            "#
+        )
+        .bind(id)
+        .traced()
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+delete from todo_dependencies where todo_id=$1 or blocker_id=$1
+            "#
+        )
+        .bind(id)
+        .traced()
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+delete from todo_labels where todo_id=$1
+            "#
+        )
+        .bind(id)
+        .traced()
+        .execute(&mut tx)
+        .await?;
+
+        let result = sqlx::query(
+            r#"
+delete from todos where id=$1
+            "#
+        )
+        .bind(id)
+        .traced()
+        .execute(&mut tx)
+        .await?;
+        // dropping the transaction rolls the other deletes back
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(id).into());
+        }
 
         tx.commit().await?;
 
+        // only once the rows are gone for good
+
+        if let Some(blob_store) = &self.blob_store {
+            for (storage_key,) in storage_keys {
+                if let Err(e) = blob_store.delete(&storage_key).await {
+                    tracing::warn!("failed to remove blob {}: {}", storage_key, e);
+                }
+            }
+        }
+
         Ok(())
     }
 }
@@ -168,10 +555,24 @@ pub trait TodoRepository: Clone + std::marker::Send + std::marker::Sync + 'stati
     async fn create(&self, payload: CreateTodo) -> anyhow::Result<TodoEntity>;
     async fn find(&self, id: i32) -> anyhow::Result<TodoEntity>;
     async fn all(&self) -> anyhow::Result<Vec<TodoEntity>>;
+    /// The todos of [`all`](TodoRepository::all), read as they are consumed rather than first
+    /// collected, for exports.
+    fn stream(&self) -> TodoStream;
     async fn update(&self, id: i32, payload: UpdateTodo) -> anyhow::Result<TodoEntity>;
+    async fn move_to(&self, id: i32, payload: MoveTodo) -> anyhow::Result<TodoEntity>;
+    async fn add_blocker(&self, id: i32, blocker_id: i32) -> anyhow::Result<TodoEntity>;
+    async fn remove_blocker(&self, id: i32, blocker_id: i32) -> anyhow::Result<TodoEntity>;
+    /// Assigns the todo, or unassigns it with `None`; an assignee also starts watching it.
+    async fn assign(&self, id: i32, assignee_id: Option<i32>) -> anyhow::Result<TodoEntity>;
+    /// Ids of the users watching the todo, in the order they started.
+    async fn watchers(&self, id: i32) -> anyhow::Result<Vec<i32>>;
+    async fn watch(&self, id: i32, user_id: i32) -> anyhow::Result<()>;
+    async fn unwatch(&self, id: i32, user_id: i32) -> anyhow::Result<()>;
     async fn delete(&self, id: i32) -> anyhow::Result<()>;
 }
 
+pub type TodoStream = std::pin::Pin<Box<dyn futures::Stream<Item = anyhow::Result<TodoEntity>> + Send>>;
+
 #[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
 struct TodoFromRow {
     id: i32,
@@ -179,73 +580,714 @@ struct TodoFromRow {
     completed: bool,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+struct TodoUpdateFromRow {
+    text: String,
+    completed: bool,
+    priority: Option<String>,
+    due: Option<chrono::NaiveDate>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
 struct TodoWithLabelFromRow {
     id: i32,
     text: String,
     completed: bool,
+    position: i64,
+    priority: Option<String>,
+    due: Option<chrono::NaiveDate>,
+    created_at: chrono::DateTime<chrono::Utc>,
+    completed_at: Option<chrono::DateTime<chrono::Utc>>,
+    workspace_id: Option<i32>,
+    assignee_id: Option<i32>,
+    blocked: bool,
+    comment_count: i64,
     label_id: Option<i32>,
     label_name: Option<String>,
+    label_workspace_id: Option<i32>,
 }
 
-#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq)]
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, utoipa::ToSchema, ts_rs::TS, async_graphql::SimpleObject)]
+#[ts(rename = "Todo")]
+#[graphql(name = "Todo")]
 pub struct TodoEntity {
     pub id: i32,
     pub text: String,
     pub completed: bool,
+    #[ts(type = "number")]
+    pub position: i64,
+    pub priority: Option<String>,
+    pub due: Option<chrono::NaiveDate>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub completed_at: Option<chrono::DateTime<chrono::Utc>>,
+    // `None` for todos visible to everyone
+    pub workspace_id: Option<i32>,
+    pub assignee_id: Option<i32>,
+    pub blocked: bool,
+    #[ts(type = "number")]
+    pub comment_count: i64,
+    #[graphql(guard = "crate::graphql::ScopeGuard(crate::auth::LABELS_READ)")]
     pub labels: Vec<crate::repositories::label::Label>,
 }
 
+/// Narrows a todo listing; `None` fields match everything.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TodoFilter {
+    pub label: Option<i32>,
+    pub completed: Option<bool>,
+    pub workspace: Option<i32>,
+    /// `me` or a user id.
+    #[param(value_type = Option<String>)]
+    pub assignee: Option<AssigneeFilter>,
+}
+
+impl TodoFilter {
+    pub fn matches(&self, todo: &TodoEntity) -> bool {
+        self.label.is_none_or(|label_id| todo.labels.iter().any(|label| label.id == label_id))
+            && self.completed.is_none_or(|completed| todo.completed == completed)
+            && self.workspace.is_none_or(|workspace_id| todo.workspace_id == Some(workspace_id))
+            && self.assignee.is_none_or(|assignee| matches!(assignee, AssigneeFilter::User(id) if todo.assignee_id == Some(id)))
+    }
+
+    /// Replaces `assignee=me` with the calling user, or gives `None` when the caller is not a user.
+    pub fn for_user(mut self, user_id: Option<i32>) -> Option<Self> {
+        if self.assignee == Some(AssigneeFilter::Me) {
+            self.assignee = Some(AssigneeFilter::User(user_id?));
+        }
+        Some(self)
+    }
+}
+
+/// `me` only matches once it has been resolved with [`TodoFilter::for_user`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssigneeFilter {
+    Me,
+    User(i32),
+}
+
+impl<'de> serde::Deserialize<'de> for AssigneeFilter {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        match value.as_str() {
+            "me" => Ok(AssigneeFilter::Me),
+            id => id
+                .parse()
+                .map(AssigneeFilter::User)
+                .map_err(|_| serde::de::Error::invalid_value(serde::de::Unexpected::Str(id), &"`me` or a user id")),
+        }
+    }
+}
+
 fn fold_entities(rows: Vec<TodoWithLabelFromRow>) -> Vec<TodoEntity> {
-    let mut rows = rows.iter();
     let mut accum: Vec<TodoEntity> = vec![];
-    'outer: while let Some(row) = rows.next() {
-        let mut todos = accum.iter_mut();
-        while let Some(todo) = todos.next() {
+    'outer: for row in rows.iter() {
+        for todo in accum.iter_mut() {
             if todo.id == row.id {
                 todo.labels.push(crate::repositories::label::Label {
                     id: row.label_id.unwrap(),
                     name: row.label_name.clone().unwrap(),
+                    workspace_id: row.label_workspace_id,
                 });
                 continue 'outer;
             }
         }
 
-        let labels = if row.label_id.is_some() {
-            vec![crate::repositories::label::Label {
-                id: row.label_id.unwrap(),
+        let labels = match row.label_id {
+            Some(label_id) => vec![crate::repositories::label::Label {
+                id: label_id,
                 name: row.label_name.clone().unwrap(),
-            }]
-        } else {
-            vec![]
+                workspace_id: row.label_workspace_id,
+            }],
+            None => vec![],
         };
 
         accum.push(TodoEntity {
             id: row.id,
             text: row.text.clone(),
             completed: row.completed,
+            position: row.position,
+            priority: row.priority.clone(),
+            due: row.due,
+            created_at: row.created_at,
+            completed_at: row.completed_at,
+            workspace_id: row.workspace_id,
+            assignee_id: row.assignee_id,
+            blocked: row.blocked,
+            comment_count: row.comment_count,
             labels,
         });
     }
     accum
 }
 
+/// Folds rows as [`fold_entities`] does while they are read; the rows of a todo have to be next
+/// to each other.
+fn fold_entity_stream<'a>(
+    rows: futures::stream::BoxStream<'a, Result<TodoWithLabelFromRow, sqlx::Error>>,
+) -> futures::stream::BoxStream<'a, anyhow::Result<TodoEntity>> {
+    let todos = futures::stream::unfold((rows, None), |(mut rows, pending)| async move {
+        let mut group: Vec<TodoWithLabelFromRow> = pending.into_iter().collect();
+        loop {
+            match futures::StreamExt::next(&mut rows).await {
+                Some(Ok(row)) if group.first().is_none_or(|first| first.id == row.id) => group.push(row),
+                Some(Ok(row)) => return Some((Ok(fold_entities(group).remove(0)), (rows, Some(row)))),
+                Some(Err(e)) => return Some((Err(e.into()), (rows, None))),
+                None if group.is_empty() => return None,
+                None => return Some((Ok(fold_entities(group).remove(0)), (rows, None))),
+            }
+        }
+    });
+    Box::pin(todos)
+}
+
+/// Spacing between neighbouring todos, leaving room for moves without renumbering.
+const POSITION_GAP: i64 = 1024;
+
+/// Picks a position strictly between two neighbours, or `None` when they are adjacent.
+fn position_between(lower: Option<i64>, upper: Option<i64>) -> Option<i64> {
+    match (lower, upper) {
+        (Some(lower), Some(upper)) if upper - lower > 1 => Some(lower + (upper - lower) / 2),
+        (Some(_), Some(_)) => None,
+        (Some(lower), None) => Some(lower + POSITION_GAP),
+        (None, Some(upper)) => Some(upper - POSITION_GAP),
+        (None, None) => Some(0),
+    }
+}
 
-#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, validator::Validate)]
+/// Returns the loop `todo_id -> blocker_id -> ... -> todo_id` that adding the edge would close.
+fn find_dependency_cycle(edges: &[(i32, i32)], todo_id: i32, blocker_id: i32) -> Option<Vec<i32>> {
+    let mut previous = std::collections::HashMap::new();
+    let mut queue = std::collections::VecDeque::from([blocker_id]);
+    previous.insert(blocker_id, todo_id);
+    while let Some(current) = queue.pop_front() {
+        if current == todo_id {
+            let mut path = vec![todo_id];
+            let mut step = previous[&todo_id];
+            while step != todo_id {
+                path.push(step);
+                step = previous[&step];
+            }
+            path[1..].reverse();
+            path.push(todo_id);
+            return Some(path);
+        }
+        for &(_, next) in edges.iter().filter(|(from, _)| *from == current) {
+            if let std::collections::hash_map::Entry::Vacant(entry) = previous.entry(next) {
+                entry.insert(current);
+                queue.push_back(next);
+            }
+        }
+    }
+    None
+}
+
+async fn rebalance_positions(tx: &mut sqlx::Transaction<'_, sqlx::Postgres>) -> anyhow::Result<()> {
+    sqlx::query(
+        r#"
+update todos set position = ranked.rn * $1
+from (select id, row_number() over (order by position asc, id desc) as rn from todos) as ranked
+where todos.id = ranked.id
+        "#
+    )
+    .bind(POSITION_GAP)
+    .traced()
+    .execute(tx)
+    .await?;
+    Ok(())
+}
+
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, validator::Validate, utoipa::ToSchema, ts_rs::TS, async_graphql::InputObject)]
+#[ts(rename = "NewTodoPayload")]
+#[graphql(name = "CreateTodoInput")]
 pub struct CreateTodo {
     #[validate(length(min=1, message="cannot be empty"))]
     #[validate(length(max=100, message="over text length"))]
     text: String,
     labels: Vec<i32>,
+    #[validate(custom = "validate_priority")]
+    #[ts(optional)]
+    priority: Option<String>,
+    #[ts(optional)]
+    due: Option<chrono::NaiveDate>,
+    // shares the todo with the members of a workspace
+    #[ts(optional)]
+    workspace_id: Option<i32>,
+    // only imports create todos that are already done
+    #[serde(skip)]
+    #[graphql(skip)]
+    completed: bool,
 }
 
-#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, validator::Validate)]
+impl CreateTodo {
+    pub fn new(text: String, labels: Vec<i32>, priority: Option<String>, due: Option<chrono::NaiveDate>) -> Self {
+        Self { text, labels, priority, due, workspace_id: None, completed: false }
+    }
+
+    /// Creates the todo completed, in the same write as the todo itself.
+    pub fn completed(mut self, completed: bool) -> Self {
+        self.completed = completed;
+        self
+    }
+
+    pub fn in_workspace(mut self, workspace_id: Option<i32>) -> Self {
+        self.workspace_id = workspace_id;
+        self
+    }
+
+    pub fn workspace_id(&self) -> Option<i32> {
+        self.workspace_id
+    }
+
+    pub fn labels(&self) -> &[i32] {
+        &self.labels
+    }
+}
+
+// GraphQL takes `crate::graphql::UpdateTodoInput` instead, as a GraphQL `null` would not be told
+// apart from a missing field here.
+/// Fields left out keep their value; `null` clears `priority` or `due`.
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, validator::Validate, utoipa::ToSchema, ts_rs::TS)]
 pub struct UpdateTodo {
     #[validate(length(min=1, message="cannot be empty"))]
     #[validate(length(max=100, message="over text length"))]
+    #[ts(optional)]
     text: Option<String>,
+    #[ts(optional)]
     completed: Option<bool>,
+    #[ts(optional)]
     labels: Option<Vec<i32>>,
+    #[validate(custom = "validate_priority")]
+    #[serde(default, with = "present")]
+    #[ts(optional, as = "Option<Option<String>>")]
+    priority: Option<Option<String>>,
+    #[serde(default, with = "present")]
+    #[ts(optional, as = "Option<Option<chrono::NaiveDate>>")]
+    due: Option<Option<chrono::NaiveDate>>,
+}
+
+/// `#[serde(with)]` for fields where `null` (`Some(None)`) is not the same as leaving the field
+/// out (`None`, through `#[serde(default)]`).
+mod present {
+    pub fn serialize<S: serde::Serializer, T: serde::Serialize>(value: &Option<Option<T>>, serializer: S) -> Result<S::Ok, S::Error> {
+        serde::Serialize::serialize(&value.as_ref().and_then(Option::as_ref), serializer)
+    }
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>, T: serde::Deserialize<'de>>(deserializer: D) -> Result<Option<Option<T>>, D::Error> {
+        <Option<T> as serde::Deserialize>::deserialize(deserializer).map(Some)
+    }
+}
+
+impl UpdateTodo {
+    pub fn new(text: Option<String>, completed: Option<bool>, labels: Option<Vec<i32>>) -> Self {
+        Self { text, completed, labels, priority: None, due: None }
+    }
+
+    /// The labels replacing the todo's, if any.
+    pub fn labels(&self) -> Option<&[i32]> {
+        self.labels.as_deref()
+    }
+
+    /// Sets the priority and due date; `None` keeps the todo's current value and `Some(None)`
+    /// clears it.
+    pub fn schedule(mut self, priority: Option<Option<String>>, due: Option<Option<chrono::NaiveDate>>) -> Self {
+        self.priority = priority;
+        self.due = due;
+        self
+    }
+}
+
+/// Priorities follow todo.txt: a single uppercase letter, `A` being the highest.
+fn validate_priority(priority: &str) -> Result<(), validator::ValidationError> {
+    match priority.as_bytes() {
+        [b'A'..=b'Z'] => Ok(()),
+        _ => Err(validator::ValidationError::new("priority must be a letter from A to Z")),
+    }
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, validator::Validate, utoipa::ToSchema, ts_rs::TS, async_graphql::InputObject)]
+#[validate(schema(function = "validate_move_target"))]
+#[graphql(name = "MoveTodoInput")]
+pub struct MoveTodo {
+    #[ts(optional)]
+    before: Option<i32>,
+    #[ts(optional)]
+    after: Option<i32>,
+}
+
+impl MoveTodo {
+    pub fn new(before: Option<i32>, after: Option<i32>) -> Self {
+        Self { before, after }
+    }
+
+    /// The todo to place the moved one next to.
+    pub fn target(&self) -> Option<i32> {
+        self.before.or(self.after)
+    }
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, validator::Validate, utoipa::ToSchema)]
+pub struct AddBlocker {
+    pub blocker_id: i32,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, validator::Validate, utoipa::ToSchema)]
+pub struct AssignTodo {
+    /// Unassigns the todo when `null`.
+    pub assignee_id: Option<i32>,
+}
+
+fn validate_move_target(payload: &MoveTodo) -> Result<(), validator::ValidationError> {
+    match (payload.before, payload.after) {
+        (Some(_), None) | (None, Some(_)) => Ok(()),
+        _ => Err(validator::ValidationError::new("specify exactly one of before or after")),
+    }
 }
 
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct TodoDatas {
+        todos: std::collections::HashMap<i32, TodoEntity>,
+        dependencies: Vec<(i32, i32)>,
+        /// `(todo_id, user_id)` in the order users started watching
+        watchers: Vec<(i32, i32)>,
+    }
+
+    impl TodoDatas {
+        fn entity(&self, id: i32) -> anyhow::Result<TodoEntity> {
+            let mut todo = self.todos.get(&id).cloned().ok_or(RepositoryError::NotFound(id))?;
+            todo.blocked = self
+                .dependencies
+                .iter()
+                .filter(|(todo_id, _)| *todo_id == id)
+                .any(|(_, blocker_id)| self.todos.get(blocker_id).is_some_and(|blocker| !blocker.completed));
+            Ok(todo)
+        }
+
+        fn ordered_ids(&self) -> Vec<i32> {
+            let mut todos: Vec<&TodoEntity> = self.todos.values().collect();
+            todos.sort_by_key(|todo| (todo.position, std::cmp::Reverse(todo.id)));
+            todos.into_iter().map(|todo| todo.id).collect()
+        }
+    }
+
+    /// Keeps todos in memory; label ids resolve against the labels it was created with.
+    #[derive(Debug, Clone)]
+    pub struct TodoRepositoryForMemory {
+        store: std::sync::Arc<std::sync::RwLock<TodoDatas>>,
+        labels: Vec<crate::repositories::label::Label>,
+        enforce_blockers: bool,
+    }
+
+    impl TodoRepositoryForMemory {
+        pub fn new(labels: Vec<crate::repositories::label::Label>) -> Self {
+            Self { store: std::sync::Arc::default(), labels, enforce_blockers: true }
+        }
+
+        pub fn enforce_blockers(mut self, enforce_blockers: bool) -> Self {
+            self.enforce_blockers = enforce_blockers;
+            self
+        }
+
+        fn resolve_labels(&self, ids: &[i32]) -> Vec<crate::repositories::label::Label> {
+            self.labels.iter().filter(|label| ids.contains(&label.id)).cloned().collect()
+        }
+    }
 
+    #[axum::async_trait]
+    impl TodoRepository for TodoRepositoryForMemory {
+        async fn create(&self, payload: CreateTodo) -> anyhow::Result<TodoEntity> {
+            let mut store = self.store.write().unwrap();
+            let id = store.todos.keys().max().copied().unwrap_or(0) + 1;
+            let position = store.todos.values().map(|todo| todo.position).min().unwrap_or(0) - POSITION_GAP;
+            let todo = TodoEntity {
+                id,
+                text: payload.text,
+                completed: payload.completed,
+                position,
+                priority: payload.priority,
+                due: payload.due,
+                created_at: chrono::Utc::now(),
+                completed_at: payload.completed.then(chrono::Utc::now),
+                workspace_id: payload.workspace_id,
+                assignee_id: None,
+                blocked: false,
+                comment_count: 0,
+                labels: self.resolve_labels(&payload.labels),
+            };
+            store.todos.insert(id, todo);
+            store.entity(id)
+        }
+
+        async fn find(&self, id: i32) -> anyhow::Result<TodoEntity> {
+            self.store.read().unwrap().entity(id)
+        }
+
+        async fn all(&self) -> anyhow::Result<Vec<TodoEntity>> {
+            let store = self.store.read().unwrap();
+            store.ordered_ids().into_iter().map(|id| store.entity(id)).collect()
+        }
+
+        fn stream(&self) -> TodoStream {
+            let store = self.store.read().unwrap();
+            let todos: Vec<_> = store.ordered_ids().into_iter().map(|id| store.entity(id)).collect();
+            Box::pin(futures::stream::iter(todos))
+        }
+
+        async fn update(&self, id: i32, payload: UpdateTodo) -> anyhow::Result<TodoEntity> {
+            let mut store = self.store.write().unwrap();
+            let old_todo = store.entity(id)?;
+            if self.enforce_blockers && payload.completed == Some(true) && !old_todo.completed && old_todo.blocked {
+                return Err(RepositoryError::Blocked(id).into());
+            }
+            let labels = payload.labels.map(|labels| self.resolve_labels(&labels));
+            let todo = store.todos.get_mut(&id).unwrap();
+            todo.text = payload.text.unwrap_or(old_todo.text);
+            todo.completed = payload.completed.unwrap_or(old_todo.completed);
+            todo.priority = payload.priority.unwrap_or(old_todo.priority);
+            todo.due = payload.due.unwrap_or(old_todo.due);
+            todo.completed_at = match todo.completed {
+                true => old_todo.completed_at.or_else(|| Some(chrono::Utc::now())),
+                false => None,
+            };
+            if let Some(labels) = labels {
+                todo.labels = labels;
+            }
+            store.entity(id)
+        }
+
+        async fn move_to(&self, id: i32, payload: MoveTodo) -> anyhow::Result<TodoEntity> {
+            let (target_id, place_before) = match (payload.before, payload.after) {
+                (Some(target_id), None) => (target_id, true),
+                (None, Some(target_id)) => (target_id, false),
+                _ => return Err(RepositoryError::Unexpected("either before or after is required".to_string()).into()),
+            };
+            let mut store = self.store.write().unwrap();
+            store.entity(id)?;
+            store.entity(target_id)?;
+            for rebalanced in [false, true] {
+                let others: Vec<i64> = store
+                    .ordered_ids()
+                    .into_iter()
+                    .filter(|other| *other != id)
+                    .map(|other| store.todos[&other].position)
+                    .collect();
+                let target_position = store.todos[&target_id].position;
+                let position = if place_before {
+                    position_between(others.iter().copied().filter(|p| *p < target_position).max(), Some(target_position))
+                } else {
+                    position_between(Some(target_position), others.iter().copied().filter(|p| *p > target_position).min())
+                };
+                match position {
+                    Some(position) => {
+                        store.todos.get_mut(&id).unwrap().position = position;
+                        return store.entity(id);
+                    }
+                    None if !rebalanced => {
+                        for (index, todo_id) in store.ordered_ids().into_iter().enumerate() {
+                            store.todos.get_mut(&todo_id).unwrap().position = (index as i64 + 1) * POSITION_GAP;
+                        }
+                    }
+                    None => {}
+                }
+            }
+            Err(RepositoryError::Unexpected("no room left to move todo".to_string()).into())
+        }
+
+        async fn add_blocker(&self, id: i32, blocker_id: i32) -> anyhow::Result<TodoEntity> {
+            let mut store = self.store.write().unwrap();
+            store.entity(id)?;
+            store.entity(blocker_id)?;
+            if let Some(path) = find_dependency_cycle(&store.dependencies, id, blocker_id) {
+                return Err(RepositoryError::Cycle(path).into());
+            }
+            if !store.dependencies.contains(&(id, blocker_id)) {
+                store.dependencies.push((id, blocker_id));
+            }
+            store.entity(id)
+        }
+
+        async fn remove_blocker(&self, id: i32, blocker_id: i32) -> anyhow::Result<TodoEntity> {
+            let mut store = self.store.write().unwrap();
+            let before = store.dependencies.len();
+            store.dependencies.retain(|edge| *edge != (id, blocker_id));
+            if store.dependencies.len() == before {
+                return Err(RepositoryError::NotFound(blocker_id).into());
+            }
+            store.entity(id)
+        }
+
+        async fn assign(&self, id: i32, assignee_id: Option<i32>) -> anyhow::Result<TodoEntity> {
+            let mut store = self.store.write().unwrap();
+            store.todos.get_mut(&id).ok_or(RepositoryError::NotFound(id))?.assignee_id = assignee_id;
+            if let Some(user_id) = assignee_id {
+                if !store.watchers.contains(&(id, user_id)) {
+                    store.watchers.push((id, user_id));
+                }
+            }
+            store.entity(id)
+        }
+
+        async fn watchers(&self, id: i32) -> anyhow::Result<Vec<i32>> {
+            let store = self.store.read().unwrap();
+            store.entity(id)?;
+            Ok(store.watchers.iter().filter(|(todo_id, _)| *todo_id == id).map(|(_, user_id)| *user_id).collect())
+        }
+
+        async fn watch(&self, id: i32, user_id: i32) -> anyhow::Result<()> {
+            let mut store = self.store.write().unwrap();
+            store.entity(id)?;
+            if !store.watchers.contains(&(id, user_id)) {
+                store.watchers.push((id, user_id));
+            }
+            Ok(())
+        }
+
+        async fn unwatch(&self, id: i32, user_id: i32) -> anyhow::Result<()> {
+            let mut store = self.store.write().unwrap();
+            let before = store.watchers.len();
+            store.watchers.retain(|watcher| *watcher != (id, user_id));
+            if store.watchers.len() == before {
+                return Err(RepositoryError::NotFound(user_id).into());
+            }
+            Ok(())
+        }
+
+        async fn delete(&self, id: i32) -> anyhow::Result<()> {
+            let mut store = self.store.write().unwrap();
+            store.todos.remove(&id).ok_or(RepositoryError::NotFound(id))?;
+            store.dependencies.retain(|(todo_id, blocker_id)| *todo_id != id && *blocker_id != id);
+            store.watchers.retain(|(todo_id, _)| *todo_id != id);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn position_between_neighbours() {
+        assert_eq!(Some(1536), position_between(Some(1024), Some(2048)));
+        assert_eq!(Some(0), position_between(None, Some(1024)));
+        assert_eq!(Some(3072), position_between(Some(2048), None));
+        assert_eq!(Some(0), position_between(None, None));
+    }
+
+    #[test]
+    fn position_between_adjacent_requires_rebalance() {
+        assert_eq!(None, position_between(Some(5), Some(6)));
+        assert_eq!(None, position_between(Some(5), Some(5)));
+        assert_eq!(Some(6), position_between(Some(5), Some(7)));
+    }
+
+    #[test]
+    fn dependency_cycle_path() {
+        let edges = vec![(2, 3), (3, 4), (5, 1)];
+        assert_eq!(Some(vec![4, 2, 3, 4]), find_dependency_cycle(&edges, 4, 2));
+        assert_eq!(Some(vec![1, 1]), find_dependency_cycle(&edges, 1, 1));
+        assert_eq!(None, find_dependency_cycle(&edges, 1, 2));
+        assert_eq!(None, find_dependency_cycle(&edges, 2, 4));
+    }
+
+    #[tokio::test]
+    async fn streamed_rows_fold_into_todos() {
+        let row = |id: i32, label: Option<(i32, &str)>| TodoWithLabelFromRow {
+            id,
+            text: format!("todo {}", id),
+            completed: false,
+            position: 0,
+            priority: None,
+            due: None,
+            created_at: chrono::Utc::now(),
+            completed_at: None,
+            workspace_id: None,
+            assignee_id: None,
+            blocked: false,
+            comment_count: 0,
+            label_id: label.map(|(id, _)| id),
+            label_name: label.map(|(_, name)| name.to_string()),
+            label_workspace_id: None,
+        };
+        let rows = vec![row(2, Some((1, "work"))), row(2, Some((2, "home"))), row(1, None), row(3, Some((1, "work")))];
+        let streamed: Vec<TodoEntity> = futures::TryStreamExt::try_collect(fold_entity_stream(Box::pin(futures::stream::iter(
+            rows.clone().into_iter().map(Ok),
+        ))))
+        .await
+        .unwrap();
+        assert_eq!(fold_entities(rows), streamed);
+        assert_eq!(vec![2, 1, 3], streamed.iter().map(|todo| todo.id).collect::<Vec<_>>());
+        assert_eq!(2, streamed[0].labels.len());
+    }
+
+    #[tokio::test]
+    async fn blocked_todos_cannot_be_completed() {
+        let complete = || UpdateTodo::new(None, Some(true), None);
+        let repository = test_utils::TodoRepositoryForMemory::new(vec![]);
+        let todo = repository.create(CreateTodo::new("ship".to_string(), vec![], None, None)).await.unwrap();
+        let blocker = repository.create(CreateTodo::new("review".to_string(), vec![], None, None)).await.unwrap();
+        assert!(repository.add_blocker(todo.id, blocker.id).await.unwrap().blocked);
+
+        let error = repository.update(todo.id, complete()).await.unwrap_err();
+        assert!(matches!(error.downcast_ref::<RepositoryError>(), Some(RepositoryError::Blocked(id)) if *id == todo.id));
+        assert!(!repository.find(todo.id).await.unwrap().completed);
+
+        repository.update(blocker.id, complete()).await.unwrap();
+        assert!(repository.update(todo.id, complete()).await.unwrap().completed);
+
+        let repository = test_utils::TodoRepositoryForMemory::new(vec![]).enforce_blockers(false);
+        let todo = repository.create(CreateTodo::new("ship".to_string(), vec![], None, None)).await.unwrap();
+        let blocker = repository.create(CreateTodo::new("review".to_string(), vec![], None, None)).await.unwrap();
+        repository.add_blocker(todo.id, blocker.id).await.unwrap();
+        assert!(repository.update(todo.id, complete()).await.unwrap().completed);
+    }
+
+    #[tokio::test]
+    async fn null_clears_what_leaving_out_keeps() {
+        let repository = test_utils::TodoRepositoryForMemory::new(vec![]);
+        let todo = repository
+            .create(CreateTodo::new("plan".to_string(), vec![], Some("A".to_string()), chrono::NaiveDate::from_ymd_opt(2026, 10, 20)))
+            .await
+            .unwrap();
+
+        let payload: UpdateTodo = serde_json::from_str(r#"{"text": "plan more"}"#).unwrap();
+        let kept = repository.update(todo.id, payload).await.unwrap();
+        assert_eq!((Some("A".to_string()), todo.due), (kept.priority, kept.due));
+
+        let payload: UpdateTodo = serde_json::from_str(r#"{"priority": null, "due": null}"#).unwrap();
+        validator::Validate::validate(&payload).unwrap();
+        let cleared = repository.update(todo.id, payload).await.unwrap();
+        assert_eq!((None, None), (cleared.priority, cleared.due));
+        assert_eq!("plan more", cleared.text);
+    }
+
+    #[tokio::test]
+    async fn moves_keep_order_through_rebalancing() {
+        let repository = test_utils::TodoRepositoryForMemory::new(vec![]);
+        let mut ids = vec![];
+        for text in ["a", "b", "c"] {
+            ids.push(repository.create(CreateTodo::new(text.to_string(), vec![], None, None)).await.unwrap().id);
+        }
+        let (a, b, c) = (ids[0], ids[1], ids[2]);
+        let order = || async { repository.all().await.unwrap().into_iter().map(|todo| todo.id).collect::<Vec<_>>() };
+        assert_eq!(vec![c, b, a], order().await);
+
+        repository.move_to(a, MoveTodo::new(Some(c), None)).await.unwrap();
+        assert_eq!(vec![a, c, b], order().await);
+        repository.move_to(a, MoveTodo::new(None, Some(b))).await.unwrap();
+        assert_eq!(vec![c, b, a], order().await);
+
+        // each move halves the gap after `c` until the positions have to be renumbered
+        for round in 0..20 {
+            let (moved, next) = if round % 2 == 0 { (a, b) } else { (b, a) };
+            repository.move_to(moved, MoveTodo::new(None, Some(c))).await.unwrap();
+            assert_eq!(vec![c, moved, next], order().await);
+        }
+        assert!(repository.move_to(a, MoveTodo::new(Some(404), None)).await.is_err());
+    }
+}
@@ -0,0 +1,261 @@
+use super::*;
+
+#[axum::async_trait]
+pub trait ApiTokenRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
+    /// `user_id` is the user the token acts as, none for tokens made with the admin token.
+    async fn create(&self, payload: CreateApiToken, user_id: Option<i32>) -> anyhow::Result<NewApiToken>;
+    async fn all(&self) -> anyhow::Result<Vec<ApiToken>>;
+    async fn find(&self, id: i32) -> anyhow::Result<ApiToken>;
+    async fn delete(&self, id: i32) -> anyhow::Result<()>;
+    /// Finds the unexpired token with this secret and records that it was used.
+    async fn verify(&self, token: &str) -> anyhow::Result<Option<ApiToken>>;
+}
+
+/// Marks our tokens so they are easy to recognise, e.g. by secret scanners.
+pub const TOKEN_PREFIX: &str = "todo_";
+
+/// `last_used_at` is only refreshed once this much time has passed, to avoid a write per request.
+const LAST_USED_PRECISION_SECONDS: i64 = 60;
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, sqlx::FromRow, utoipa::ToSchema)]
+pub struct ApiToken {
+    pub id: i32,
+    pub name: String,
+    /// Leading characters of the token, enough to tell tokens apart without revealing them.
+    pub prefix: String,
+    pub scopes: Vec<String>,
+    /// The user the token acts as.
+    pub user_id: Option<i32>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub last_used_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// A freshly created token; the secret is only ever returned here.
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, utoipa::ToSchema)]
+pub struct NewApiToken {
+    #[serde(flatten)]
+    pub api_token: ApiToken,
+    pub token: String,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, validator::Validate, utoipa::ToSchema)]
+pub struct CreateApiToken {
+    #[validate(length(min=1, message="Can not be empty"))]
+    #[validate(length(max=100, message="over text length"))]
+    name: String,
+    #[validate(length(min=1, message="grant at least one scope"))]
+    #[validate(custom = "validate_scopes")]
+    pub scopes: Vec<String>,
+    /// Never expires when omitted.
+    #[validate(range(min=1, max=365, message="must be between 1 and 365 days"))]
+    expires_in_days: Option<i64>,
+}
+
+impl CreateApiToken {
+    #[cfg(test)]
+    pub fn new(name: String, scopes: Vec<String>, expires_in_days: Option<i64>) -> Self {
+        Self { name, scopes, expires_in_days }
+    }
+
+    fn expires_at(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.expires_in_days.map(|days| chrono::Utc::now() + chrono::Duration::days(days))
+    }
+}
+
+fn validate_scopes(scopes: &[String]) -> Result<(), validator::ValidationError> {
+    if scopes.iter().all(|scope| crate::auth::SCOPES.contains(&scope.as_str())) {
+        Ok(())
+    } else {
+        Err(validator::ValidationError::new("unknown scope"))
+    }
+}
+
+fn new_token() -> String {
+    format!("{}{}", TOKEN_PREFIX, hex::encode(rand::random::<[u8; 32]>()))
+}
+
+fn token_prefix(token: &str) -> String {
+    token[..TOKEN_PREFIX.len() + 8].to_string()
+}
+
+fn hash_token(token: &str) -> String {
+    hex::encode(<sha2::Sha256 as sha2::Digest>::digest(token.as_bytes()))
+}
+
+#[derive(Debug, Clone)]
+pub struct ApiTokenRepositoryForDb {
+    pool: sqlx::PgPool,
+}
+
+impl ApiTokenRepositoryForDb {
+    pub fn new(pool: sqlx::PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[axum::async_trait]
+impl ApiTokenRepository for ApiTokenRepositoryForDb {
+    async fn create(&self, payload: CreateApiToken, user_id: Option<i32>) -> anyhow::Result<NewApiToken> {
+        let token = new_token();
+        let api_token = sqlx::query_as::<_, ApiToken>(
+            r#"
+insert into api_tokens (name, token_hash, prefix, scopes, user_id, expires_at)
+values ($1, $2, $3, $4, $5, $6)
+returning id, name, prefix, scopes, user_id, created_at, expires_at, last_used_at
+            "#
+        )
+        .bind(&payload.name)
+        .bind(hash_token(&token))
+        .bind(token_prefix(&token))
+        .bind(&payload.scopes)
+        .bind(user_id)
+        .bind(payload.expires_at())
+        .traced()
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(NewApiToken { api_token, token })
+    }
+
+    async fn all(&self) -> anyhow::Result<Vec<ApiToken>> {
+        let tokens = sqlx::query_as::<_, ApiToken>(
+            r#"
+select id, name, prefix, scopes, user_id, created_at, expires_at, last_used_at from api_tokens
+order by id asc;
+            "#
+        )
+        .traced()
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(tokens)
+    }
+
+    async fn find(&self, id: i32) -> anyhow::Result<ApiToken> {
+        let api_token = sqlx::query_as::<_, ApiToken>(
+            r#"
+select id, name, prefix, scopes, user_id, created_at, expires_at, last_used_at from api_tokens
+where id=$1
+            "#
+        )
+        .bind(id)
+        .traced()
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(RepositoryError::NotFound(id))?;
+
+        Ok(api_token)
+    }
+
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        let result = sqlx::query(
+            r#"
+delete from api_tokens where id=$1
+            "#
+        )
+        .bind(id)
+        .traced()
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+
+        Ok(())
+    }
+
+    async fn verify(&self, token: &str) -> anyhow::Result<Option<ApiToken>> {
+        let found = sqlx::query_as::<_, ApiToken>(
+            r#"
+with found as (
+    select id, name, prefix, scopes, user_id, created_at, expires_at, last_used_at from api_tokens
+    where token_hash=$1 and (expires_at is null or expires_at > now())
+), touched as (
+    update api_tokens set last_used_at=now()
+    where id in (select id from found)
+    and (last_used_at is null or last_used_at < now() - make_interval(secs => $2))
+)
+select * from found
+            "#
+        )
+        .bind(hash_token(token))
+        .bind(LAST_USED_PRECISION_SECONDS as f64)
+        .traced()
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(found)
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+
+    type ApiTokenDatas = std::collections::HashMap<i32, (String, ApiToken)>;
+
+    #[derive(Debug, Clone, Default)]
+    pub struct ApiTokenRepositoryForMemory {
+        store: std::sync::Arc<std::sync::RwLock<ApiTokenDatas>>,
+    }
+
+    impl ApiTokenRepositoryForMemory {
+        pub fn new() -> Self {
+            Self::default()
+        }
+    }
+
+    #[axum::async_trait]
+    impl ApiTokenRepository for ApiTokenRepositoryForMemory {
+        async fn create(&self, payload: CreateApiToken, user_id: Option<i32>) -> anyhow::Result<NewApiToken> {
+            let mut store = self.store.write().unwrap();
+            let id = store.keys().max().copied().unwrap_or(0) + 1;
+            let token = new_token();
+            let api_token = ApiToken {
+                id,
+                name: payload.name.clone(),
+                prefix: token_prefix(&token),
+                scopes: payload.scopes.clone(),
+                user_id,
+                created_at: chrono::Utc::now(),
+                expires_at: payload.expires_at(),
+                last_used_at: None,
+            };
+            store.insert(id, (hash_token(&token), api_token.clone()));
+            Ok(NewApiToken { api_token, token })
+        }
+
+        async fn all(&self) -> anyhow::Result<Vec<ApiToken>> {
+            let store = self.store.read().unwrap();
+            let mut tokens: Vec<ApiToken> = store.values().map(|(_, api_token)| api_token.clone()).collect();
+            tokens.sort_by_key(|api_token| api_token.id);
+            Ok(tokens)
+        }
+
+        async fn find(&self, id: i32) -> anyhow::Result<ApiToken> {
+            let store = self.store.read().unwrap();
+            let (_, api_token) = store.get(&id).ok_or(RepositoryError::NotFound(id))?;
+            Ok(api_token.clone())
+        }
+
+        async fn delete(&self, id: i32) -> anyhow::Result<()> {
+            let mut store = self.store.write().unwrap();
+            store.remove(&id).ok_or(RepositoryError::NotFound(id))?;
+            Ok(())
+        }
+
+        async fn verify(&self, token: &str) -> anyhow::Result<Option<ApiToken>> {
+            let mut store = self.store.write().unwrap();
+            let now = chrono::Utc::now();
+            let hash = hash_token(token);
+            let found = store
+                .values_mut()
+                .find(|(token_hash, api_token)| *token_hash == hash && api_token.expires_at.is_none_or(|expires_at| expires_at > now));
+            Ok(found.map(|(_, api_token)| {
+                api_token.last_used_at = Some(now);
+                api_token.clone()
+            }))
+        }
+    }
+}
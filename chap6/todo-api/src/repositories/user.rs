@@ -0,0 +1,290 @@
+use super::*;
+
+#[axum::async_trait]
+pub trait UserRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
+    /// Creates the user behind an identity on first login, and refreshes its details after that.
+    async fn upsert(&self, identity: Identity) -> anyhow::Result<User>;
+    async fn find(&self, id: i32) -> anyhow::Result<User>;
+    /// Keeps what the callback of a login needs, keyed by its `state`, until `ttl` runs out.
+    async fn begin_login(&self, state: &str, login: PendingLogin, ttl: chrono::Duration) -> anyhow::Result<()>;
+    /// Removes and returns an unexpired login, so each one can only be completed once.
+    async fn take_login(&self, state: &str) -> anyhow::Result<Option<PendingLogin>>;
+    /// Returns the secret for a new session cookie.
+    async fn create_session(&self, user_id: i32, ttl: chrono::Duration) -> anyhow::Result<String>;
+    async fn session_user(&self, token: &str) -> anyhow::Result<Option<User>>;
+    async fn delete_session(&self, token: &str) -> anyhow::Result<()>;
+    async fn purge_expired(&self) -> anyhow::Result<u64>;
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, sqlx::FromRow, utoipa::ToSchema)]
+pub struct User {
+    pub id: i32,
+    pub issuer: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub name: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub last_login_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Who the identity provider says logged in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    pub issuer: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct PendingLogin {
+    pub code_verifier: String,
+    pub nonce: String,
+    pub redirect_to: String,
+}
+
+fn new_session_token() -> String {
+    hex::encode(rand::random::<[u8; 32]>())
+}
+
+fn hash_token(token: &str) -> String {
+    hex::encode(<sha2::Sha256 as sha2::Digest>::digest(token.as_bytes()))
+}
+
+#[derive(Debug, Clone)]
+pub struct UserRepositoryForDb {
+    pool: sqlx::PgPool,
+}
+
+impl UserRepositoryForDb {
+    pub fn new(pool: sqlx::PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[axum::async_trait]
+impl UserRepository for UserRepositoryForDb {
+    async fn upsert(&self, identity: Identity) -> anyhow::Result<User> {
+        let user = sqlx::query_as::<_, User>(
+            r#"
+insert into users (issuer, subject, email, name)
+values ($1, $2, $3, $4)
+on conflict (issuer, subject) do update
+set email=excluded.email, name=excluded.name, last_login_at=now()
+returning *
+            "#
+        )
+        .bind(identity.issuer)
+        .bind(identity.subject)
+        .bind(identity.email)
+        .bind(identity.name)
+        .traced()
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+
+    async fn find(&self, id: i32) -> anyhow::Result<User> {
+        let user = sqlx::query_as::<_, User>("select * from users where id=$1")
+            .bind(id)
+            .traced()
+            .fetch_optional(&self.pool)
+            .await?;
+
+        user.ok_or_else(|| RepositoryError::NotFound(id).into())
+    }
+
+    async fn begin_login(&self, state: &str, login: PendingLogin, ttl: chrono::Duration) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+insert into oidc_logins (state, code_verifier, nonce, redirect_to, expires_at)
+values ($1, $2, $3, $4, now() + make_interval(secs => $5))
+            "#
+        )
+        .bind(state)
+        .bind(login.code_verifier)
+        .bind(login.nonce)
+        .bind(login.redirect_to)
+        .bind(ttl.num_seconds() as f64)
+        .traced()
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn take_login(&self, state: &str) -> anyhow::Result<Option<PendingLogin>> {
+        let login = sqlx::query_as::<_, PendingLogin>(
+            r#"
+delete from oidc_logins where state=$1 and expires_at > now()
+returning code_verifier, nonce, redirect_to
+            "#
+        )
+        .bind(state)
+        .traced()
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(login)
+    }
+
+    async fn create_session(&self, user_id: i32, ttl: chrono::Duration) -> anyhow::Result<String> {
+        let token = new_session_token();
+        sqlx::query(
+            r#"
+insert into sessions (token_hash, user_id, expires_at)
+values ($1, $2, now() + make_interval(secs => $3))
+            "#
+        )
+        .bind(hash_token(&token))
+        .bind(user_id)
+        .bind(ttl.num_seconds() as f64)
+        .traced()
+        .execute(&self.pool)
+        .await?;
+
+        Ok(token)
+    }
+
+    async fn session_user(&self, token: &str) -> anyhow::Result<Option<User>> {
+        let user = sqlx::query_as::<_, User>(
+            r#"
+select users.* from sessions
+inner join users on users.id = sessions.user_id
+where sessions.token_hash=$1 and sessions.expires_at > now()
+            "#
+        )
+        .bind(hash_token(token))
+        .traced()
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(user)
+    }
+
+    async fn delete_session(&self, token: &str) -> anyhow::Result<()> {
+        sqlx::query("delete from sessions where token_hash=$1")
+            .bind(hash_token(token))
+            .traced()
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn purge_expired(&self) -> anyhow::Result<u64> {
+        let sessions = sqlx::query("delete from sessions where expires_at <= now()")
+            .traced()
+            .execute(&self.pool)
+            .await?;
+        let logins = sqlx::query("delete from oidc_logins where expires_at <= now()")
+            .traced()
+            .execute(&self.pool)
+            .await?;
+
+        Ok(sessions.rows_affected() + logins.rows_affected())
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct UserDatas {
+        users: std::collections::HashMap<i32, User>,
+        logins: std::collections::HashMap<String, (PendingLogin, chrono::DateTime<chrono::Utc>)>,
+        sessions: std::collections::HashMap<String, (i32, chrono::DateTime<chrono::Utc>)>,
+    }
+
+    #[derive(Debug, Clone, Default)]
+    pub struct UserRepositoryForMemory {
+        store: std::sync::Arc<std::sync::RwLock<UserDatas>>,
+    }
+
+    impl UserRepositoryForMemory {
+        pub fn new() -> Self {
+            Self::default()
+        }
+    }
+
+    #[axum::async_trait]
+    impl UserRepository for UserRepositoryForMemory {
+        async fn upsert(&self, identity: Identity) -> anyhow::Result<User> {
+            let mut store = self.store.write().unwrap();
+            let now = chrono::Utc::now();
+            let existing = store
+                .users
+                .values_mut()
+                .find(|user| user.issuer == identity.issuer && user.subject == identity.subject);
+            if let Some(user) = existing {
+                user.email = identity.email;
+                user.name = identity.name;
+                user.last_login_at = now;
+                return Ok(user.clone());
+            }
+            let id = store.users.keys().max().copied().unwrap_or(0) + 1;
+            let user = User {
+                id,
+                issuer: identity.issuer,
+                subject: identity.subject,
+                email: identity.email,
+                name: identity.name,
+                created_at: now,
+                last_login_at: now,
+            };
+            store.users.insert(id, user.clone());
+            Ok(user)
+        }
+
+        async fn find(&self, id: i32) -> anyhow::Result<User> {
+            let store = self.store.read().unwrap();
+            let user = store.users.get(&id).cloned().ok_or(RepositoryError::NotFound(id))?;
+            Ok(user)
+        }
+
+        async fn begin_login(&self, state: &str, login: PendingLogin, ttl: chrono::Duration) -> anyhow::Result<()> {
+            let mut store = self.store.write().unwrap();
+            store.logins.insert(state.to_string(), (login, chrono::Utc::now() + ttl));
+            Ok(())
+        }
+
+        async fn take_login(&self, state: &str) -> anyhow::Result<Option<PendingLogin>> {
+            let mut store = self.store.write().unwrap();
+            let login = store.logins.remove(state);
+            Ok(login.filter(|(_, expires_at)| *expires_at > chrono::Utc::now()).map(|(login, _)| login))
+        }
+
+        async fn create_session(&self, user_id: i32, ttl: chrono::Duration) -> anyhow::Result<String> {
+            let mut store = self.store.write().unwrap();
+            let token = new_session_token();
+            store.sessions.insert(hash_token(&token), (user_id, chrono::Utc::now() + ttl));
+            Ok(token)
+        }
+
+        async fn session_user(&self, token: &str) -> anyhow::Result<Option<User>> {
+            let store = self.store.read().unwrap();
+            let user = store
+                .sessions
+                .get(&hash_token(token))
+                .filter(|(_, expires_at)| *expires_at > chrono::Utc::now())
+                .and_then(|(user_id, _)| store.users.get(user_id).cloned());
+            Ok(user)
+        }
+
+        async fn delete_session(&self, token: &str) -> anyhow::Result<()> {
+            self.store.write().unwrap().sessions.remove(&hash_token(token));
+            Ok(())
+        }
+
+        async fn purge_expired(&self) -> anyhow::Result<u64> {
+            let mut store = self.store.write().unwrap();
+            let now = chrono::Utc::now();
+            let before = store.sessions.len() + store.logins.len();
+            store.sessions.retain(|_, (_, expires_at)| *expires_at > now);
+            store.logins.retain(|_, (_, expires_at)| *expires_at > now);
+            Ok((before - store.sessions.len() - store.logins.len()) as u64)
+        }
+    }
+}
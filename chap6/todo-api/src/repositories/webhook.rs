@@ -0,0 +1,458 @@
+use super::*;
+
+#[axum::async_trait]
+pub trait WebhookRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
+    async fn create(&self, payload: CreateWebhook) -> anyhow::Result<NewWebhook>;
+    async fn all(&self) -> anyhow::Result<Vec<Webhook>>;
+    async fn delete(&self, id: i32) -> anyhow::Result<()>;
+    /// Queues `payload` for every webhook subscribed to `event`, returning how many were queued.
+    async fn enqueue(&self, event: &str, payload: serde_json::Value) -> anyhow::Result<usize>;
+    async fn deliveries(&self, filter: DeliveryFilter) -> anyhow::Result<Vec<Delivery>>;
+    /// Puts a delivery back in the queue with a fresh set of attempts, whatever its status.
+    async fn redeliver(&self, id: i32) -> anyhow::Result<Delivery>;
+    /// Takes up to `limit` due deliveries, counting the attempt and leasing them for `lease`
+    /// so another worker (or this one after a crash) only retries once the lease expires.
+    async fn claim_due(&self, limit: i64, lease: chrono::Duration) -> anyhow::Result<Vec<PendingDelivery>>;
+    async fn record(&self, id: i32, outcome: DeliveryOutcome) -> anyhow::Result<()>;
+}
+
+/// Most recent deliveries returned by [`WebhookRepository::deliveries`].
+pub const DELIVERY_PAGE_SIZE: i64 = 100;
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, sqlx::FromRow, utoipa::ToSchema)]
+pub struct Webhook {
+    pub id: i32,
+    pub url: String,
+    /// Event names this webhook receives, or `*` for all of them.
+    pub events: Vec<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// A freshly created webhook; the signing secret is only ever returned here.
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, utoipa::ToSchema)]
+pub struct NewWebhook {
+    #[serde(flatten)]
+    pub webhook: Webhook,
+    pub secret: String,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, validator::Validate, utoipa::ToSchema)]
+pub struct CreateWebhook {
+    #[validate(custom = "validate_url")]
+    url: String,
+    #[validate(length(min=1, message="subscribe to at least one event"))]
+    #[validate(custom = "validate_events")]
+    events: Vec<String>,
+    /// Generated when omitted.
+    #[validate(length(min=16, message="must be at least 16 characters"))]
+    #[validate(length(max=256, message="over text length"))]
+    secret: Option<String>,
+}
+
+impl CreateWebhook {
+    #[cfg(test)]
+    pub fn new(url: String, events: Vec<String>, secret: Option<String>) -> Self {
+        Self { url, events, secret }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn secret(&self) -> String {
+        self.secret.clone().unwrap_or_else(|| hex::encode(rand::random::<[u8; 32]>()))
+    }
+}
+
+fn validate_url(url: &str) -> Result<(), validator::ValidationError> {
+    match url.parse::<hyper::Uri>() {
+        Ok(uri) if matches!(uri.scheme_str(), Some("http" | "https")) && uri.host().is_some() => Ok(()),
+        _ => Err(validator::ValidationError::new("must be an absolute http or https url")),
+    }
+}
+
+fn validate_events(events: &[String]) -> Result<(), validator::ValidationError> {
+    let known = |event: &String| event == crate::webhooks::ALL_EVENTS || crate::webhooks::EVENT_NAMES.contains(&event.as_str());
+    if events.iter().all(known) {
+        Ok(())
+    } else {
+        Err(validator::ValidationError::new("unknown event name"))
+    }
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, sqlx::FromRow, utoipa::ToSchema)]
+pub struct Delivery {
+    pub id: i32,
+    pub webhook_id: i32,
+    pub event: String,
+    #[schema(value_type = Object)]
+    pub payload: serde_json::Value,
+    /// `pending`, `delivered`, or `dead` once every attempt failed.
+    pub status: String,
+    pub attempts: i32,
+    pub response_status: Option<i32>,
+    pub last_error: Option<String>,
+    pub next_attempt_at: chrono::DateTime<chrono::Utc>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub delivered_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// A claimed delivery together with where to send it and how to sign it.
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct PendingDelivery {
+    pub id: i32,
+    pub webhook_id: i32,
+    pub url: String,
+    pub secret: String,
+    pub event: String,
+    pub payload: serde_json::Value,
+    /// Attempts so far, including the one this claim is for.
+    pub attempts: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeliveryOutcome {
+    Delivered {
+        response_status: i32,
+    },
+    /// Retried at `retry_at`, or moved to the dead letters when there is none.
+    Failed {
+        response_status: Option<i32>,
+        error: String,
+        retry_at: Option<chrono::DateTime<chrono::Utc>>,
+    },
+}
+
+#[derive(Debug, Default, serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeliveryFilter {
+    pub webhook_id: Option<i32>,
+    /// `pending`, `delivered` or `dead`; `dead` lists the dead letters.
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct WebhookRepositoryForDb {
+    pool: sqlx::PgPool,
+}
+
+impl WebhookRepositoryForDb {
+    pub fn new(pool: sqlx::PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[axum::async_trait]
+impl WebhookRepository for WebhookRepositoryForDb {
+    async fn create(&self, payload: CreateWebhook) -> anyhow::Result<NewWebhook> {
+        let secret = payload.secret();
+        let webhook = sqlx::query_as::<_, Webhook>(
+            r#"
+insert into webhooks (url, events, secret)
+values ($1, $2, $3)
+returning id, url, events, created_at
+            "#
+        )
+        .bind(payload.url)
+        .bind(payload.events)
+        .bind(&secret)
+        .traced()
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(NewWebhook { webhook, secret })
+    }
+
+    async fn all(&self) -> anyhow::Result<Vec<Webhook>> {
+        let webhooks = sqlx::query_as::<_, Webhook>(
+            r#"
+select id, url, events, created_at from webhooks
+order by id asc;
+            "#
+        )
+        .traced()
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(webhooks)
+    }
+
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        let result = sqlx::query(
+            r#"
+delete from webhooks where id=$1
+            "#
+        )
+        .bind(id)
+        .traced()
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+
+        Ok(())
+    }
+
+    async fn enqueue(&self, event: &str, payload: serde_json::Value) -> anyhow::Result<usize> {
+        let result = sqlx::query(
+            r#"
+insert into webhook_deliveries (webhook_id, event, payload)
+select id, $1, $2 from webhooks
+where $1 = any(events) or $3 = any(events)
+            "#
+        )
+        .bind(event)
+        .bind(payload)
+        .bind(crate::webhooks::ALL_EVENTS)
+        .traced()
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() as usize)
+    }
+
+    async fn deliveries(&self, filter: DeliveryFilter) -> anyhow::Result<Vec<Delivery>> {
+        let deliveries = sqlx::query_as::<_, Delivery>(
+            r#"
+select * from webhook_deliveries
+where ($1::integer is null or webhook_id=$1) and ($2::text is null or status=$2)
+order by id desc
+limit $3
+            "#
+        )
+        .bind(filter.webhook_id)
+        .bind(filter.status)
+        .bind(DELIVERY_PAGE_SIZE)
+        .traced()
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(deliveries)
+    }
+
+    async fn redeliver(&self, id: i32) -> anyhow::Result<Delivery> {
+        let delivery = sqlx::query_as::<_, Delivery>(
+            r#"
+update webhook_deliveries set status='pending', attempts=0, next_attempt_at=now()
+where id=$1
+returning *
+            "#
+        )
+        .bind(id)
+        .traced()
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(RepositoryError::NotFound(id))?;
+
+        Ok(delivery)
+    }
+
+    async fn claim_due(&self, limit: i64, lease: chrono::Duration) -> anyhow::Result<Vec<PendingDelivery>> {
+        let deliveries = sqlx::query_as::<_, PendingDelivery>(
+            r#"
+update webhook_deliveries
+set attempts=webhook_deliveries.attempts + 1, next_attempt_at=now() + make_interval(secs => $2)
+from webhooks
+where webhooks.id=webhook_deliveries.webhook_id and webhook_deliveries.id in (
+    select id from webhook_deliveries
+    where status='pending' and next_attempt_at <= now()
+    order by next_attempt_at asc, id asc
+    limit $1
+    for update skip locked
+)
+returning webhook_deliveries.id, webhook_id, url, secret, event, payload, attempts
+            "#
+        )
+        .bind(limit)
+        .bind(lease.num_seconds() as f64)
+        .traced()
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(deliveries)
+    }
+
+    async fn record(&self, id: i32, outcome: DeliveryOutcome) -> anyhow::Result<()> {
+        match outcome {
+            DeliveryOutcome::Delivered { response_status } => {
+                sqlx::query(
+                    r#"
+update webhook_deliveries set status='delivered', response_status=$2, last_error=null, delivered_at=now()
+where id=$1
+                    "#
+                )
+                .bind(id)
+                .bind(response_status)
+                .traced()
+                .execute(&self.pool)
+                .await?;
+            }
+            DeliveryOutcome::Failed { response_status, error, retry_at } => {
+                sqlx::query(
+                    r#"
+update webhook_deliveries
+set status=case when $4::timestamptz is null then 'dead' else 'pending' end,
+    response_status=$2, last_error=$3, next_attempt_at=coalesce($4, next_attempt_at)
+where id=$1
+                    "#
+                )
+                .bind(id)
+                .bind(response_status)
+                .bind(error)
+                .bind(retry_at)
+                .traced()
+                .execute(&self.pool)
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct WebhookDatas {
+        webhooks: std::collections::BTreeMap<i32, NewWebhook>,
+        deliveries: std::collections::BTreeMap<i32, Delivery>,
+    }
+
+    #[derive(Debug, Clone, Default)]
+    pub struct WebhookRepositoryForMemory {
+        store: std::sync::Arc<std::sync::RwLock<WebhookDatas>>,
+    }
+
+    impl WebhookRepositoryForMemory {
+        pub fn new() -> Self {
+            Self::default()
+        }
+    }
+
+    #[axum::async_trait]
+    impl WebhookRepository for WebhookRepositoryForMemory {
+        async fn create(&self, payload: CreateWebhook) -> anyhow::Result<NewWebhook> {
+            let mut store = self.store.write().unwrap();
+            let id = store.webhooks.keys().max().copied().unwrap_or(0) + 1;
+            let webhook = NewWebhook {
+                secret: payload.secret(),
+                webhook: Webhook { id, url: payload.url, events: payload.events, created_at: chrono::Utc::now() },
+            };
+            store.webhooks.insert(id, webhook.clone());
+            Ok(webhook)
+        }
+
+        async fn all(&self) -> anyhow::Result<Vec<Webhook>> {
+            Ok(self.store.read().unwrap().webhooks.values().map(|new| new.webhook.clone()).collect())
+        }
+
+        async fn delete(&self, id: i32) -> anyhow::Result<()> {
+            let mut store = self.store.write().unwrap();
+            store.webhooks.remove(&id).ok_or(RepositoryError::NotFound(id))?;
+            store.deliveries.retain(|_, delivery| delivery.webhook_id != id);
+            Ok(())
+        }
+
+        async fn enqueue(&self, event: &str, payload: serde_json::Value) -> anyhow::Result<usize> {
+            let mut store = self.store.write().unwrap();
+            let webhook_ids: Vec<i32> = store
+                .webhooks
+                .values()
+                .filter(|new| new.webhook.events.iter().any(|name| name == event || name == crate::webhooks::ALL_EVENTS))
+                .map(|new| new.webhook.id)
+                .collect();
+            for webhook_id in webhook_ids.iter() {
+                let id = store.deliveries.keys().max().copied().unwrap_or(0) + 1;
+                let now = chrono::Utc::now();
+                store.deliveries.insert(id, Delivery {
+                    id,
+                    webhook_id: *webhook_id,
+                    event: event.to_string(),
+                    payload: payload.clone(),
+                    status: "pending".to_string(),
+                    attempts: 0,
+                    response_status: None,
+                    last_error: None,
+                    next_attempt_at: now,
+                    created_at: now,
+                    delivered_at: None,
+                });
+            }
+            Ok(webhook_ids.len())
+        }
+
+        async fn deliveries(&self, filter: DeliveryFilter) -> anyhow::Result<Vec<Delivery>> {
+            let store = self.store.read().unwrap();
+            Ok(store
+                .deliveries
+                .values()
+                .rev()
+                .filter(|delivery| filter.webhook_id.is_none_or(|webhook_id| delivery.webhook_id == webhook_id))
+                .filter(|delivery| filter.status.as_ref().is_none_or(|status| &delivery.status == status))
+                .take(DELIVERY_PAGE_SIZE as usize)
+                .cloned()
+                .collect())
+        }
+
+        async fn redeliver(&self, id: i32) -> anyhow::Result<Delivery> {
+            let mut store = self.store.write().unwrap();
+            let delivery = store.deliveries.get_mut(&id).ok_or(RepositoryError::NotFound(id))?;
+            delivery.status = "pending".to_string();
+            delivery.attempts = 0;
+            delivery.next_attempt_at = chrono::Utc::now();
+            Ok(delivery.clone())
+        }
+
+        async fn claim_due(&self, limit: i64, lease: chrono::Duration) -> anyhow::Result<Vec<PendingDelivery>> {
+            let mut store = self.store.write().unwrap();
+            let now = chrono::Utc::now();
+            let mut due: Vec<Delivery> = store
+                .deliveries
+                .values()
+                .filter(|delivery| delivery.status == "pending" && delivery.next_attempt_at <= now)
+                .cloned()
+                .collect();
+            due.sort_by_key(|delivery| (delivery.next_attempt_at, delivery.id));
+            let mut claimed = vec![];
+            for delivery in due.into_iter().take(limit as usize) {
+                let webhook = store.webhooks[&delivery.webhook_id].clone();
+                let stored = store.deliveries.get_mut(&delivery.id).unwrap();
+                stored.attempts += 1;
+                stored.next_attempt_at = now + lease;
+                claimed.push(PendingDelivery {
+                    id: delivery.id,
+                    webhook_id: delivery.webhook_id,
+                    url: webhook.webhook.url,
+                    secret: webhook.secret,
+                    event: delivery.event,
+                    payload: delivery.payload,
+                    attempts: stored.attempts,
+                });
+            }
+            Ok(claimed)
+        }
+
+        async fn record(&self, id: i32, outcome: DeliveryOutcome) -> anyhow::Result<()> {
+            let mut store = self.store.write().unwrap();
+            let delivery = store.deliveries.get_mut(&id).ok_or(RepositoryError::NotFound(id))?;
+            match outcome {
+                DeliveryOutcome::Delivered { response_status } => {
+                    delivery.status = "delivered".to_string();
+                    delivery.response_status = Some(response_status);
+                    delivery.last_error = None;
+                    delivery.delivered_at = Some(chrono::Utc::now());
+                }
+                DeliveryOutcome::Failed { response_status, error, retry_at } => {
+                    delivery.status = if retry_at.is_some() { "pending" } else { "dead" }.to_string();
+                    delivery.response_status = response_status;
+                    delivery.last_error = Some(error);
+                    delivery.next_attempt_at = retry_at.unwrap_or(delivery.next_attempt_at);
+                }
+            }
+            Ok(())
+        }
+    }
+}
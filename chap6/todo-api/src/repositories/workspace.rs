@@ -0,0 +1,484 @@
+use super::*;
+
+#[axum::async_trait]
+pub trait WorkspaceRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
+    /// Creates a workspace with `owner_id` as its first owner.
+    async fn create(&self, name: String, owner_id: i32) -> anyhow::Result<Workspace>;
+    async fn find(&self, id: i32) -> anyhow::Result<Workspace>;
+    async fn all(&self) -> anyhow::Result<Vec<Workspace>>;
+    /// The role `user_id` holds in each workspace they are a member of.
+    async fn roles(&self, user_id: i32) -> anyhow::Result<std::collections::HashMap<i32, Role>>;
+    async fn members(&self, workspace_id: i32) -> anyhow::Result<Vec<Member>>;
+    /// Refused with `RepositoryError::LastOwner` when it would leave the workspace without an owner.
+    async fn set_role(&self, workspace_id: i32, user_id: i32, role: Role) -> anyhow::Result<Member>;
+    /// Refused with `RepositoryError::LastOwner` when it would leave the workspace without an owner.
+    async fn remove_member(&self, workspace_id: i32, user_id: i32) -> anyhow::Result<()>;
+    async fn create_invite(&self, workspace_id: i32, payload: CreateInvite) -> anyhow::Result<NewInvite>;
+    /// Makes `user_id` a member with the invite's role and uses the invite up; existing members
+    /// keep their role. `None` for unknown, expired or already accepted invites.
+    async fn accept_invite(&self, token: &str, user_id: i32) -> anyhow::Result<Option<Member>>;
+}
+
+/// Roles in increasing order of what they allow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize, sqlx::Type, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+pub enum Role {
+    /// Reads the workspace's todos and labels.
+    Viewer,
+    /// Also creates, changes and deletes them.
+    Editor,
+    /// Also manages members and invites.
+    Owner,
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Role::Viewer => "viewer",
+            Role::Editor => "editor",
+            Role::Owner => "owner",
+        })
+    }
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, sqlx::FromRow, utoipa::ToSchema)]
+pub struct Workspace {
+    pub id: i32,
+    pub name: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, sqlx::FromRow, utoipa::ToSchema)]
+pub struct Member {
+    pub user_id: i32,
+    pub email: Option<String>,
+    pub name: Option<String>,
+    pub role: Role,
+    pub joined_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, sqlx::FromRow, utoipa::ToSchema)]
+pub struct Invite {
+    pub id: i32,
+    pub workspace_id: i32,
+    pub role: Role,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// A freshly created invite; the secret is only ever returned here.
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, utoipa::ToSchema)]
+pub struct NewInvite {
+    #[serde(flatten)]
+    pub invite: Invite,
+    pub token: String,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq, Eq, validator::Validate, utoipa::ToSchema)]
+pub struct CreateInvite {
+    pub role: Role,
+    /// 7 days when omitted.
+    #[validate(range(min=1, max=30, message="must be between 1 and 30 days"))]
+    expires_in_days: Option<i64>,
+}
+
+impl CreateInvite {
+    #[cfg(test)]
+    pub fn new(role: Role, expires_in_days: Option<i64>) -> Self {
+        Self { role, expires_in_days }
+    }
+
+    fn expires_at(&self) -> chrono::DateTime<chrono::Utc> {
+        chrono::Utc::now() + chrono::Duration::days(self.expires_in_days.unwrap_or(7))
+    }
+}
+
+fn new_invite_token() -> String {
+    hex::encode(rand::random::<[u8; 32]>())
+}
+
+fn hash_token(token: &str) -> String {
+    hex::encode(<sha2::Sha256 as sha2::Digest>::digest(token.as_bytes()))
+}
+
+/// Whether a workspace whose members hold `roles` would still have an owner after `user_id`
+/// changes to `new_role`, `None` meaning they leave.
+fn keeps_an_owner(roles: &[(i32, Role)], user_id: i32, new_role: Option<Role>) -> bool {
+    new_role == Some(Role::Owner)
+        || roles.iter().any(|(member, role)| *member != user_id && *role == Role::Owner)
+}
+
+#[derive(Debug, Clone)]
+pub struct WorkspaceRepositoryForDb {
+    pool: sqlx::PgPool,
+}
+
+impl WorkspaceRepositoryForDb {
+    pub fn new(pool: sqlx::PgPool) -> Self {
+        Self { pool }
+    }
+
+    async fn member(&self, workspace_id: i32, user_id: i32) -> anyhow::Result<Member> {
+        let member = sqlx::query_as::<_, Member>(
+            r#"
+select users.id as user_id, users.email, users.name, workspace_members.role, workspace_members.created_at as joined_at
+from workspace_members
+    inner join users on users.id = workspace_members.user_id
+where workspace_members.workspace_id=$1 and workspace_members.user_id=$2
+            "#
+        )
+        .bind(workspace_id)
+        .bind(user_id)
+        .traced()
+        .fetch_optional(&self.pool)
+        .await?;
+
+        member.ok_or_else(|| RepositoryError::NotFound(user_id).into())
+    }
+
+    /// Locks the members of a workspace until the transaction ends, so concurrent changes can not
+    /// both remove the last owner.
+    async fn lock_roles(tx: &mut sqlx::Transaction<'_, sqlx::Postgres>, workspace_id: i32) -> anyhow::Result<Vec<(i32, Role)>> {
+        let roles = sqlx::query_as::<_, (i32, Role)>(
+            r#"
+select user_id, role from workspace_members where workspace_id=$1
+for update
+            "#
+        )
+        .bind(workspace_id)
+        .traced()
+        .fetch_all(&mut *tx)
+        .await?;
+
+        Ok(roles)
+    }
+}
+
+#[axum::async_trait]
+impl WorkspaceRepository for WorkspaceRepositoryForDb {
+    async fn create(&self, name: String, owner_id: i32) -> anyhow::Result<Workspace> {
+        let mut tx = self.pool.begin().await?;
+        let workspace = sqlx::query_as::<_, Workspace>(
+            r#"
+insert into workspaces (name)
+values ($1)
+returning *
+            "#
+        )
+        .bind(name)
+        .traced()
+        .fetch_one(&mut tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+insert into workspace_members (workspace_id, user_id, role)
+values ($1, $2, $3)
+            "#
+        )
+        .bind(workspace.id)
+        .bind(owner_id)
+        .bind(Role::Owner)
+        .traced()
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(workspace)
+    }
+
+    async fn find(&self, id: i32) -> anyhow::Result<Workspace> {
+        let workspace = sqlx::query_as::<_, Workspace>("select * from workspaces where id=$1")
+            .bind(id)
+            .traced()
+            .fetch_optional(&self.pool)
+            .await?;
+
+        workspace.ok_or_else(|| RepositoryError::NotFound(id).into())
+    }
+
+    async fn all(&self) -> anyhow::Result<Vec<Workspace>> {
+        let workspaces = sqlx::query_as::<_, Workspace>("select * from workspaces order by id asc")
+            .traced()
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(workspaces)
+    }
+
+    async fn roles(&self, user_id: i32) -> anyhow::Result<std::collections::HashMap<i32, Role>> {
+        let roles = sqlx::query_as::<_, (i32, Role)>("select workspace_id, role from workspace_members where user_id=$1")
+            .bind(user_id)
+            .traced()
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(roles.into_iter().collect())
+    }
+
+    async fn members(&self, workspace_id: i32) -> anyhow::Result<Vec<Member>> {
+        let members = sqlx::query_as::<_, Member>(
+            r#"
+select users.id as user_id, users.email, users.name, workspace_members.role, workspace_members.created_at as joined_at
+from workspace_members
+    inner join users on users.id = workspace_members.user_id
+where workspace_members.workspace_id=$1
+order by workspace_members.created_at asc, users.id asc
+            "#
+        )
+        .bind(workspace_id)
+        .traced()
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(members)
+    }
+
+    async fn set_role(&self, workspace_id: i32, user_id: i32, role: Role) -> anyhow::Result<Member> {
+        let mut tx = self.pool.begin().await?;
+        let roles = Self::lock_roles(&mut tx, workspace_id).await?;
+        if !roles.iter().any(|(member, _)| *member == user_id) {
+            return Err(RepositoryError::NotFound(user_id).into());
+        }
+        if !keeps_an_owner(&roles, user_id, Some(role)) {
+            return Err(RepositoryError::LastOwner(workspace_id).into());
+        }
+        sqlx::query("update workspace_members set role=$3 where workspace_id=$1 and user_id=$2")
+            .bind(workspace_id)
+            .bind(user_id)
+            .bind(role)
+            .traced()
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+        self.member(workspace_id, user_id).await
+    }
+
+    async fn remove_member(&self, workspace_id: i32, user_id: i32) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        let roles = Self::lock_roles(&mut tx, workspace_id).await?;
+        if !roles.iter().any(|(member, _)| *member == user_id) {
+            return Err(RepositoryError::NotFound(user_id).into());
+        }
+        if !keeps_an_owner(&roles, user_id, None) {
+            return Err(RepositoryError::LastOwner(workspace_id).into());
+        }
+        sqlx::query("delete from workspace_members where workspace_id=$1 and user_id=$2")
+            .bind(workspace_id)
+            .bind(user_id)
+            .traced()
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn create_invite(&self, workspace_id: i32, payload: CreateInvite) -> anyhow::Result<NewInvite> {
+        let token = new_invite_token();
+        let invite = sqlx::query_as::<_, Invite>(
+            r#"
+insert into workspace_invites (workspace_id, token_hash, role, expires_at)
+values ($1, $2, $3, $4)
+returning id, workspace_id, role, created_at, expires_at
+            "#
+        )
+        .bind(workspace_id)
+        .bind(hash_token(&token))
+        .bind(payload.role)
+        .bind(payload.expires_at())
+        .traced()
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(NewInvite { invite, token })
+    }
+
+    async fn accept_invite(&self, token: &str, user_id: i32) -> anyhow::Result<Option<Member>> {
+        let mut tx = self.pool.begin().await?;
+        let accepted = sqlx::query_as::<_, (i32, Role)>(
+            r#"
+update workspace_invites set accepted_by=$2, accepted_at=now()
+where token_hash=$1 and accepted_at is null and expires_at > now()
+returning workspace_id, role
+            "#
+        )
+        .bind(hash_token(token))
+        .bind(user_id)
+        .traced()
+        .fetch_optional(&mut tx)
+        .await?;
+        let (workspace_id, role) = match accepted {
+            Some(accepted) => accepted,
+            None => return Ok(None),
+        };
+
+        sqlx::query(
+            r#"
+insert into workspace_members (workspace_id, user_id, role)
+values ($1, $2, $3)
+on conflict (workspace_id, user_id) do nothing
+            "#
+        )
+        .bind(workspace_id)
+        .bind(user_id)
+        .bind(role)
+        .traced()
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(Some(self.member(workspace_id, user_id).await?))
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct WorkspaceDatas {
+        workspaces: std::collections::HashMap<i32, Workspace>,
+        /// `(workspace_id, user_id) -> (role, joined_at)`
+        members: std::collections::BTreeMap<(i32, i32), (Role, chrono::DateTime<chrono::Utc>)>,
+        /// `token hash -> (invite, accepted)`
+        invites: std::collections::HashMap<String, (Invite, bool)>,
+    }
+
+    impl WorkspaceDatas {
+        fn roles(&self, workspace_id: i32) -> Vec<(i32, Role)> {
+            self.members
+                .iter()
+                .filter(|((workspace, _), _)| *workspace == workspace_id)
+                .map(|((_, user_id), (role, _))| (*user_id, *role))
+                .collect()
+        }
+
+        /// Members only know their user id here; there are no users to join with.
+        fn member(&self, workspace_id: i32, user_id: i32) -> anyhow::Result<Member> {
+            let (role, joined_at) = self.members.get(&(workspace_id, user_id)).ok_or(RepositoryError::NotFound(user_id))?;
+            Ok(Member { user_id, email: None, name: None, role: *role, joined_at: *joined_at })
+        }
+    }
+
+    #[derive(Debug, Clone, Default)]
+    pub struct WorkspaceRepositoryForMemory {
+        store: std::sync::Arc<std::sync::RwLock<WorkspaceDatas>>,
+    }
+
+    impl WorkspaceRepositoryForMemory {
+        pub fn new() -> Self {
+            Self::default()
+        }
+    }
+
+    #[axum::async_trait]
+    impl WorkspaceRepository for WorkspaceRepositoryForMemory {
+        async fn create(&self, name: String, owner_id: i32) -> anyhow::Result<Workspace> {
+            let mut store = self.store.write().unwrap();
+            let id = store.workspaces.keys().max().copied().unwrap_or(0) + 1;
+            let workspace = Workspace { id, name, created_at: chrono::Utc::now() };
+            store.workspaces.insert(id, workspace.clone());
+            store.members.insert((id, owner_id), (Role::Owner, workspace.created_at));
+            Ok(workspace)
+        }
+
+        async fn find(&self, id: i32) -> anyhow::Result<Workspace> {
+            let store = self.store.read().unwrap();
+            let workspace = store.workspaces.get(&id).cloned().ok_or(RepositoryError::NotFound(id))?;
+            Ok(workspace)
+        }
+
+        async fn all(&self) -> anyhow::Result<Vec<Workspace>> {
+            let mut workspaces: Vec<Workspace> = self.store.read().unwrap().workspaces.values().cloned().collect();
+            workspaces.sort_by_key(|workspace| workspace.id);
+            Ok(workspaces)
+        }
+
+        async fn roles(&self, user_id: i32) -> anyhow::Result<std::collections::HashMap<i32, Role>> {
+            let store = self.store.read().unwrap();
+            Ok(store
+                .members
+                .iter()
+                .filter(|((_, member), _)| *member == user_id)
+                .map(|((workspace_id, _), (role, _))| (*workspace_id, *role))
+                .collect())
+        }
+
+        async fn members(&self, workspace_id: i32) -> anyhow::Result<Vec<Member>> {
+            let store = self.store.read().unwrap();
+            let mut members = store
+                .roles(workspace_id)
+                .into_iter()
+                .map(|(user_id, _)| store.member(workspace_id, user_id))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            members.sort_by_key(|member| (member.joined_at, member.user_id));
+            Ok(members)
+        }
+
+        async fn set_role(&self, workspace_id: i32, user_id: i32, role: Role) -> anyhow::Result<Member> {
+            let mut store = self.store.write().unwrap();
+            store.member(workspace_id, user_id)?;
+            if !keeps_an_owner(&store.roles(workspace_id), user_id, Some(role)) {
+                return Err(RepositoryError::LastOwner(workspace_id).into());
+            }
+            store.members.get_mut(&(workspace_id, user_id)).unwrap().0 = role;
+            store.member(workspace_id, user_id)
+        }
+
+        async fn remove_member(&self, workspace_id: i32, user_id: i32) -> anyhow::Result<()> {
+            let mut store = self.store.write().unwrap();
+            store.member(workspace_id, user_id)?;
+            if !keeps_an_owner(&store.roles(workspace_id), user_id, None) {
+                return Err(RepositoryError::LastOwner(workspace_id).into());
+            }
+            store.members.remove(&(workspace_id, user_id));
+            Ok(())
+        }
+
+        async fn create_invite(&self, workspace_id: i32, payload: CreateInvite) -> anyhow::Result<NewInvite> {
+            let mut store = self.store.write().unwrap();
+            let token = new_invite_token();
+            let invite = Invite {
+                id: store.invites.len() as i32 + 1,
+                workspace_id,
+                role: payload.role,
+                created_at: chrono::Utc::now(),
+                expires_at: payload.expires_at(),
+            };
+            store.invites.insert(hash_token(&token), (invite.clone(), false));
+            Ok(NewInvite { invite, token })
+        }
+
+        async fn accept_invite(&self, token: &str, user_id: i32) -> anyhow::Result<Option<Member>> {
+            let mut store = self.store.write().unwrap();
+            let (invite, accepted) = match store.invites.get_mut(&hash_token(token)) {
+                Some(entry) if !entry.1 && entry.0.expires_at > chrono::Utc::now() => entry,
+                _ => return Ok(None),
+            };
+            *accepted = true;
+            let (workspace_id, role) = (invite.workspace_id, invite.role);
+            store.members.entry((workspace_id, user_id)).or_insert((role, chrono::Utc::now()));
+            store.member(workspace_id, user_id).map(Some)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn a_workspace_keeps_an_owner() {
+        let roles = vec![(1, Role::Owner), (2, Role::Editor)];
+        assert!(!keeps_an_owner(&roles, 1, Some(Role::Editor)));
+        assert!(!keeps_an_owner(&roles, 1, None));
+        assert!(keeps_an_owner(&roles, 1, Some(Role::Owner)));
+        assert!(keeps_an_owner(&roles, 2, None));
+
+        let roles = vec![(1, Role::Owner), (2, Role::Owner)];
+        assert!(keeps_an_owner(&roles, 1, None));
+    }
+}
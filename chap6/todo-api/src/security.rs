@@ -0,0 +1,279 @@
+//! Protections for browsers talking to the API.
+//!
+//! [`SecurityConfig::cors`] lets the origins in `CORS_ALLOWED_ORIGINS` call the API with
+//! credentials, so a frontend served elsewhere can send the session cookie. A browser attaches
+//! that cookie to requests started by any site though, so a write authenticated by it also has
+//! to send the session's CSRF token back in the `x-csrf-token` header. The token is handed out
+//! in the `csrf` cookie at login and by `/auth/me` (double submit); it is derived from the
+//! session, so another site can neither read nor plant one. `crate::auth::authenticate` refuses
+//! writes by a logged in session without it. [`headers`] adds the standard security headers to
+//! every response; HSTS only goes out over https, and `x-forwarded-proto` only says so when
+//! `TRUST_PROXY_HEADERS=true` (the API is only reachable through a proxy that sets it).
+
+/// Name of the cookie holding the CSRF token of a session.
+pub const CSRF_COOKIE: &str = "csrf";
+/// Header writes authenticated by a session cookie have to repeat the CSRF token in.
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+/// Served pages may only load what this server serves; Swagger UI sets inline styles.
+const PAGE_CSP: &str = "default-src 'self'; img-src 'self' data:; style-src 'self' 'unsafe-inline'; object-src 'none'; base-uri 'none'; form-action 'self'; frame-ancestors 'none'";
+const DEFAULT_ALLOWED_ORIGINS: &str = "http://localhost:3001";
+const DEFAULT_HSTS_MAX_AGE: u64 = 365 * 24 * 60 * 60;
+
+#[derive(Debug, Clone)]
+pub struct SecurityConfig {
+    allowed_origins: Vec<axum::http::HeaderValue>,
+    allowed_methods: Vec<axum::http::Method>,
+    allowed_headers: Vec<axum::http::HeaderName>,
+    hsts_max_age: Option<u64>,
+    trust_proxy_headers: bool,
+}
+
+impl SecurityConfig {
+    /// Allows the usual methods and the headers the API reads, and sends HSTS for a year.
+    pub fn new(allowed_origins: Vec<axum::http::HeaderValue>) -> Self {
+        Self {
+            allowed_origins,
+            allowed_methods: vec![
+                axum::http::Method::GET,
+                axum::http::Method::POST,
+                axum::http::Method::PUT,
+                axum::http::Method::PATCH,
+                axum::http::Method::DELETE,
+            ],
+            allowed_headers: vec![
+                axum::http::header::CONTENT_TYPE,
+                axum::http::header::AUTHORIZATION,
+                axum::http::HeaderName::from_static(crate::idempotency::IDEMPOTENCY_KEY),
+                axum::http::HeaderName::from_static(crate::handlers::share::SHARE_PASSWORD),
+                axum::http::HeaderName::from_static(CSRF_HEADER),
+                axum::http::HeaderName::from_static("traceparent"),
+                axum::http::HeaderName::from_static("tracestate"),
+            ],
+            hsts_max_age: Some(DEFAULT_HSTS_MAX_AGE),
+            trust_proxy_headers: false,
+        }
+    }
+
+    /// Whether to believe `x-forwarded-proto`, which any client can send unless a proxy in front
+    /// of the API overwrites it.
+    pub fn trust_proxy_headers(mut self, trust_proxy_headers: bool) -> Self {
+        self.trust_proxy_headers = trust_proxy_headers;
+        self
+    }
+
+    /// Reads the comma separated `CORS_ALLOWED_ORIGINS` (exact origins, no `*`),
+    /// `CORS_ALLOWED_METHODS` and `CORS_ALLOWED_HEADERS` (added to the headers the API reads),
+    /// `HSTS_MAX_AGE` in seconds, `0` turning HSTS off, and `TRUST_PROXY_HEADERS`.
+    pub fn from_env() -> Self {
+        let list = |name: &str| -> Option<Vec<String>> {
+            let value = std::env::var(name).ok()?;
+            Some(value.split(',').map(str::trim).filter(|item| !item.is_empty()).map(str::to_string).collect())
+        };
+        let origins = list("CORS_ALLOWED_ORIGINS").unwrap_or(vec![DEFAULT_ALLOWED_ORIGINS.to_string()]);
+        let mut config = Self::new(
+            origins
+                .iter()
+                .map(|origin| {
+                    assert!(origin != "*", "CORS_ALLOWED_ORIGINS can not be * while credentials are allowed");
+                    origin.parse().expect("invalid origin in CORS_ALLOWED_ORIGINS")
+                })
+                .collect(),
+        );
+        if let Some(methods) = list("CORS_ALLOWED_METHODS") {
+            config.allowed_methods = methods
+                .iter()
+                .map(|method| method.to_uppercase().parse().expect("invalid method in CORS_ALLOWED_METHODS"))
+                .collect();
+        }
+        if let Some(headers) = list("CORS_ALLOWED_HEADERS") {
+            config
+                .allowed_headers
+                .extend(headers.iter().map(|header| header.parse::<axum::http::HeaderName>().expect("invalid header in CORS_ALLOWED_HEADERS")));
+        }
+        if let Some(max_age) = std::env::var("HSTS_MAX_AGE").ok().and_then(|value| value.parse().ok()) {
+            config.hsts_max_age = (max_age > 0).then_some(max_age);
+        }
+        config.trust_proxy_headers(std::env::var("TRUST_PROXY_HEADERS").map(|value| value == "true").unwrap_or(false))
+    }
+
+    /// Whether pages from `origin` may use the API with the session cookie.
+    pub fn allows_origin(&self, origin: &axum::http::HeaderValue) -> bool {
+        self.allowed_origins.contains(origin)
+    }
+
+    pub fn cors(&self) -> tower_http::cors::CorsLayer {
+        tower_http::cors::CorsLayer::new()
+            .allow_origin(tower_http::cors::Origin::list(self.allowed_origins.clone()))
+            .allow_methods(self.allowed_methods.clone())
+            .allow_headers(self.allowed_headers.clone())
+            .allow_credentials(true)
+            .expose_headers(vec![
+                axum::http::HeaderName::from_static(crate::idempotency::REPLAYED),
+                axum::http::HeaderName::from_static(CSRF_HEADER),
+                axum::http::HeaderName::from_static("x-request-id"),
+                axum::http::header::RETRY_AFTER,
+            ])
+    }
+}
+
+/// The CSRF token belonging to a session secret.
+pub fn csrf_token(session: &str) -> String {
+    hex::encode(<sha2::Sha256 as sha2::Digest>::digest(format!("csrf:{}", session).as_bytes()))
+}
+
+/// Whether a request may use the session `session`: reads always may, writes only with its
+/// CSRF token in [`CSRF_HEADER`].
+pub fn csrf_verified(method: &axum::http::Method, headers: &axum::http::HeaderMap, session: &str) -> bool {
+    use sha2::Digest;
+    if method.is_safe() {
+        return true;
+    }
+    let sent = match headers.get(CSRF_HEADER).and_then(|value| value.to_str().ok()) {
+        Some(sent) => sent,
+        None => return false,
+    };
+    // compare digests so the comparison time does not depend on the token
+    sha2::Sha256::digest(sent.as_bytes()) == sha2::Sha256::digest(csrf_token(session).as_bytes())
+}
+
+/// Reached over https, directly or, with `trust_proxy_headers`, through a proxy terminating TLS
+/// for us.
+fn is_https(request: &axum::http::Request<axum::body::Body>, trust_proxy_headers: bool) -> bool {
+    let forwarded = || {
+        request
+            .headers()
+            .get("x-forwarded-proto")
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|proto| proto.eq_ignore_ascii_case("https"))
+    };
+    request.uri().scheme() == Some(&axum::http::uri::Scheme::HTTPS) || (trust_proxy_headers && forwarded())
+}
+
+fn is_html(headers: &axum::http::HeaderMap) -> bool {
+    headers
+        .get(axum::http::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with(mime::TEXT_HTML.as_ref()))
+}
+
+/// Adds the security headers a handler did not set itself.
+pub async fn headers(
+    request: axum::http::Request<axum::body::Body>,
+    next: axum::middleware::Next<axum::body::Body>,
+) -> axum::response::Response {
+    let config = request
+        .extensions()
+        .get::<SecurityConfig>()
+        .cloned()
+        .expect("security config extension is missing");
+    let https = is_https(&request, config.trust_proxy_headers);
+    let mut response = next.run(request).await;
+    let html = is_html(response.headers());
+    let headers = response.headers_mut();
+    headers
+        .entry(axum::http::header::X_CONTENT_TYPE_OPTIONS)
+        .or_insert(axum::http::HeaderValue::from_static("nosniff"));
+    headers
+        .entry(axum::http::header::REFERRER_POLICY)
+        .or_insert(axum::http::HeaderValue::from_static("no-referrer"));
+    if html {
+        headers
+            .entry(axum::http::header::CONTENT_SECURITY_POLICY)
+            .or_insert(axum::http::HeaderValue::from_static(PAGE_CSP));
+    }
+    if let (true, Some(max_age)) = (https, config.hsts_max_age) {
+        headers.insert(
+            axum::http::header::STRICT_TRANSPORT_SECURITY,
+            axum::http::HeaderValue::from_str(&format!("max-age={}; includeSubDomains", max_age)).unwrap(),
+        );
+    }
+    response
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn app() -> axum::Router {
+        app_with(SecurityConfig::new(vec!["http://localhost:3001".parse().unwrap()]).trust_proxy_headers(true))
+    }
+
+    fn app_with(config: SecurityConfig) -> axum::Router {
+        axum::Router::new()
+            .route("/", axum::routing::get(|| async { "hello world" }))
+            .route("/page", axum::routing::get(|| async { axum::response::Html("<p>hello</p>") }))
+            .layer(axum::middleware::from_fn(headers))
+            .layer(axum::extract::Extension(config.clone()))
+            .layer(config.cors())
+    }
+
+    async fn send(app: &axum::Router, request: axum::http::Request<axum::body::Body>) -> axum::response::Response {
+        tower::ServiceExt::oneshot(app.clone(), request).await.unwrap()
+    }
+
+    fn header<'a>(response: &'a axum::response::Response, name: &str) -> Option<&'a str> {
+        response.headers().get(name).map(|value| value.to_str().unwrap())
+    }
+
+    #[tokio::test]
+    async fn pages_get_security_headers() {
+        let app = app();
+        let json = send(&app, axum::http::Request::get("/").body(axum::body::Body::empty()).unwrap()).await;
+        assert_eq!(Some("nosniff"), header(&json, "x-content-type-options"));
+        assert_eq!(Some("no-referrer"), header(&json, "referrer-policy"));
+        assert_eq!(None, header(&json, "content-security-policy"));
+        assert_eq!(None, header(&json, "strict-transport-security"));
+
+        let page = axum::http::Request::get("/page")
+            .header("x-forwarded-proto", "https")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let page = send(&app, page).await;
+        assert_eq!(Some(PAGE_CSP), header(&page, "content-security-policy"));
+        assert_eq!(Some("max-age=31536000; includeSubDomains"), header(&page, "strict-transport-security"));
+    }
+
+    #[tokio::test]
+    async fn forwarded_proto_needs_a_trusted_proxy() {
+        let app = app_with(SecurityConfig::new(vec![]));
+        let request = axum::http::Request::get("/")
+            .header("x-forwarded-proto", "https")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        assert_eq!(None, header(&send(&app, request).await, "strict-transport-security"));
+    }
+
+    #[tokio::test]
+    async fn only_allowed_origins_get_credentialed_cors() {
+        let app = app();
+        let preflight = |origin: &str| {
+            axum::http::Request::builder()
+                .method(axum::http::Method::OPTIONS)
+                .uri("/")
+                .header("origin", origin)
+                .header("access-control-request-method", "POST")
+                .header("access-control-request-headers", CSRF_HEADER)
+                .body(axum::body::Body::empty())
+                .unwrap()
+        };
+        let allowed = send(&app, preflight("http://localhost:3001")).await;
+        assert_eq!(Some("http://localhost:3001"), header(&allowed, "access-control-allow-origin"));
+        assert_eq!(Some("true"), header(&allowed, "access-control-allow-credentials"));
+        assert!(header(&allowed, "access-control-allow-headers").unwrap().contains(CSRF_HEADER));
+
+        let refused = send(&app, preflight("https://evil.example")).await;
+        assert_eq!(None, header(&refused, "access-control-allow-origin"));
+    }
+
+    #[test]
+    fn writes_need_the_session_csrf_token() {
+        let mut headers = axum::http::HeaderMap::new();
+        assert!(csrf_verified(&axum::http::Method::GET, &headers, "secret"));
+        assert!(!csrf_verified(&axum::http::Method::POST, &headers, "secret"));
+        headers.insert(CSRF_HEADER, csrf_token("other").parse().unwrap());
+        assert!(!csrf_verified(&axum::http::Method::POST, &headers, "secret"));
+        headers.insert(CSRF_HEADER, csrf_token("secret").parse().unwrap());
+        assert!(csrf_verified(&axum::http::Method::DELETE, &headers, "secret"));
+    }
+}
@@ -0,0 +1,286 @@
+//! Logging setup and per-request tracing.
+//!
+//! Every request gets an `X-Request-Id` (the client's if it is a short id of letters, digits and
+//! dashes, see [`drop_invalid_request_id`], or else a generated one) that is echoed on the
+//! response and recorded on a `request` span together with the method, path, route template,
+//! status and latency. Everything logged while handling the request, including the repository
+//! spans and the SQL statements below them, is nested in that span. Secrets that travel in the
+//! URL never reach the logs: share link tokens are masked in the path, and the query string
+//! (which carries calendar feed tokens) is not recorded at all.
+//!
+//! With `OTEL_EXPORTER_OTLP_ENDPOINT` set the same spans are also exported over OTLP/gRPC, and a
+//! W3C `traceparent` header on the request makes the `request` span part of the caller's trace.
+
+/// `LOG_FORMAT=json` switches to one JSON object per line, carrying the enclosing spans.
+pub fn init() {
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::util::SubscriberInitExt;
+    use tracing_subscriber::Layer;
+
+    let fmt = match std::env::var("LOG_FORMAT").as_deref() {
+        Ok("json") => tracing_subscriber::fmt::layer().json().with_current_span(true).with_span_list(true).boxed(),
+        _ => tracing_subscriber::fmt::layer().boxed(),
+    };
+    // logged once the subscriber is in place, as there is nothing to log to before
+    let mut otel_error = None;
+    let otel = std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT").ok().and_then(|endpoint| match otlp_provider(&endpoint) {
+        Ok(provider) => {
+            let tracer = opentelemetry::trace::TracerProvider::tracer(&provider, "todo-api");
+            opentelemetry::global::set_tracer_provider(provider);
+            Some(tracing_opentelemetry::layer().with_tracer(tracer))
+        }
+        Err(e) => {
+            otel_error = Some(e);
+            None
+        }
+    });
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::EnvFilter::from_default_env())
+        .with(fmt)
+        .with(otel)
+        .init();
+    if let Some(e) = otel_error {
+        tracing::error!("failed to set up the OTLP exporter, spans are not exported: {}", e);
+    }
+}
+
+/// Exports spans in batches to an OTLP/gRPC collector such as `http://localhost:4317`.
+/// The service is named by `OTEL_SERVICE_NAME`, `todo-api` by default.
+pub fn otlp_provider(endpoint: &str) -> Result<opentelemetry_sdk::trace::TracerProvider, opentelemetry::trace::TraceError> {
+    use opentelemetry_otlp::WithExportConfig;
+    let exporter = opentelemetry_otlp::new_exporter().tonic().with_endpoint(endpoint).build_span_exporter()?;
+    let service_name = std::env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| "todo-api".to_string());
+    let resource = opentelemetry_sdk::Resource::new(vec![opentelemetry::KeyValue::new("service.name", service_name)]);
+    Ok(opentelemetry_sdk::trace::TracerProvider::builder()
+        .with_batch_exporter(exporter, opentelemetry_sdk::runtime::Tokio)
+        .with_config(opentelemetry_sdk::trace::config().with_resource(resource))
+        .build())
+}
+
+/// Flushes spans that are still buffered; call before exiting.
+pub fn shutdown() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
+
+struct HeaderExtractor<'a>(&'a axum::http::HeaderMap);
+
+impl<'a> opentelemetry::propagation::Extractor for HeaderExtractor<'a> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
+
+/// Longest `X-Request-Id` taken from a client.
+const MAX_REQUEST_ID_LEN: usize = 64;
+
+/// Removes an `X-Request-Id` that is too long or has characters other than ASCII letters, digits
+/// and `-`, so [`MakeRandomRequestId`] replaces it instead of it reaching the logs and the response.
+pub fn drop_invalid_request_id<B>(mut request: axum::http::Request<B>) -> axum::http::Request<B> {
+    let valid = |value: &axum::http::HeaderValue| {
+        let value = value.as_bytes();
+        !value.is_empty() && value.len() <= MAX_REQUEST_ID_LEN && value.iter().all(|byte| byte.is_ascii_alphanumeric() || *byte == b'-')
+    };
+    if !request.headers().get_all("x-request-id").iter().all(valid) {
+        request.headers_mut().remove("x-request-id");
+    }
+    request
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MakeRandomRequestId;
+
+impl tower_http::request_id::MakeRequestId for MakeRandomRequestId {
+    fn make_request_id<B>(&mut self, _: &axum::http::Request<B>) -> Option<tower_http::request_id::RequestId> {
+        let id = hex::encode(rand::random::<[u8; 16]>());
+        Some(tower_http::request_id::RequestId::new(id.parse().unwrap()))
+    }
+}
+
+/// Path prefixes whose next segment is a secret.
+const SECRET_SEGMENTS: [&str; 1] = ["/shared/"];
+
+/// The path of a request as it may be logged, e.g. `/shared/:token/page` for a share link.
+fn loggable_path(path: &str) -> std::borrow::Cow<'_, str> {
+    for prefix in SECRET_SEGMENTS {
+        if let Some(rest) = path.strip_prefix(prefix) {
+            let tail = rest.find('/').map_or("", |slash| &rest[slash..]);
+            return format!("{}:token{}", prefix, tail).into();
+        }
+    }
+    path.into()
+}
+
+pub fn request_span<B>(request: &axum::http::Request<B>) -> tracing::Span {
+    let request_id = request
+        .headers()
+        .get("x-request-id")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let span = tracing::info_span!(
+        "request",
+        method = %request.method(),
+        path = %loggable_path(request.uri().path()),
+        request_id,
+        route = tracing::field::Empty,
+        status = tracing::field::Empty,
+        latency_ms = tracing::field::Empty,
+    );
+    let parent = opentelemetry::propagation::TextMapPropagator::extract(
+        &opentelemetry_sdk::propagation::TraceContextPropagator::new(),
+        &HeaderExtractor(request.headers()),
+    );
+    tracing_opentelemetry::OpenTelemetrySpanExt::set_parent(&span, parent);
+    span
+}
+
+pub fn on_response<B>(response: &axum::http::Response<B>, latency: std::time::Duration, span: &tracing::Span) {
+    span.record("status", response.status().as_u16());
+    span.record("latency_ms", latency.as_millis() as u64);
+    tracing::info!("finished processing request");
+}
+
+/// Route layer recording which route template matched, e.g. `/todos/:id` rather than `/todos/42`.
+pub async fn record_route(
+    request: axum::http::Request<axum::body::Body>,
+    next: axum::middleware::Next<axum::body::Body>,
+) -> axum::response::Response {
+    if let Some(route) = request.extensions().get::<axum::extract::MatchedPath>() {
+        tracing::Span::current().record("route", route.as_str());
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tower::ServiceExt;
+
+    fn app() -> axum::Router {
+        axum::Router::new()
+            .route("/todos/:id", axum::routing::get(|| async { "todo" }))
+            .route_layer(axum::middleware::from_fn(record_route))
+            .layer(
+                tower::ServiceBuilder::new()
+                    .map_request(drop_invalid_request_id)
+                    .layer(tower_http::request_id::SetRequestIdLayer::x_request_id(MakeRandomRequestId))
+                    .layer(tower_http::trace::TraceLayer::new_for_http().make_span_with(request_span).on_response(on_response))
+                    .layer(tower_http::request_id::PropagateRequestIdLayer::x_request_id()),
+            )
+    }
+
+    #[tokio::test]
+    async fn request_ids_are_generated_or_kept() {
+        let request = axum::http::Request::get("/todos/1").body(axum::body::Body::empty()).unwrap();
+        let response = app().oneshot(request).await.unwrap();
+        let generated = response.headers()["x-request-id"].to_str().unwrap();
+        assert_eq!(32, generated.len());
+        assert!(generated.bytes().all(|byte| byte.is_ascii_hexdigit()));
+
+        let request = axum::http::Request::get("/todos/1")
+            .header("x-request-id", "from-the-client")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let response = app().oneshot(request).await.unwrap();
+        assert_eq!("from-the-client", response.headers()["x-request-id"]);
+
+        for invalid in ["has spaces", "line\tbreak", "x".repeat(MAX_REQUEST_ID_LEN + 1).as_str(), ""] {
+            let request = axum::http::Request::get("/todos/1")
+                .header("x-request-id", invalid)
+                .body(axum::body::Body::empty())
+                .unwrap();
+            let response = app().oneshot(request).await.unwrap();
+            assert_eq!(32, response.headers()["x-request-id"].len(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn share_tokens_are_masked() {
+        assert_eq!("/todos/42", loggable_path("/todos/42"));
+        assert_eq!("/shared/:token", loggable_path("/shared/0123456789abcdef"));
+        assert_eq!("/shared/:token/page", loggable_path("/shared/0123456789abcdef/page"));
+    }
+
+    /// Stands in for an OpenTelemetry collector, keeping every span it is sent.
+    #[derive(Clone, Default)]
+    struct Collector {
+        spans: std::sync::Arc<std::sync::Mutex<Vec<opentelemetry_proto::tonic::trace::v1::Span>>>,
+    }
+
+    #[tonic::async_trait]
+    impl opentelemetry_proto::tonic::collector::trace::v1::trace_service_server::TraceService for Collector {
+        async fn export(
+            &self,
+            request: tonic::Request<opentelemetry_proto::tonic::collector::trace::v1::ExportTraceServiceRequest>,
+        ) -> Result<tonic::Response<opentelemetry_proto::tonic::collector::trace::v1::ExportTraceServiceResponse>, tonic::Status> {
+            let mut spans = self.spans.lock().unwrap();
+            for resource in request.into_inner().resource_spans {
+                for scope in resource.scope_spans {
+                    spans.extend(scope.spans);
+                }
+            }
+            Ok(tonic::Response::new(Default::default()))
+        }
+    }
+
+    fn attribute<'a>(span: &'a opentelemetry_proto::tonic::trace::v1::Span, key: &str) -> Option<&'a opentelemetry_proto::tonic::common::v1::any_value::Value> {
+        span.attributes
+            .iter()
+            .find(|attribute| attribute.key == key)
+            .and_then(|attribute| attribute.value.as_ref())
+            .and_then(|value| value.value.as_ref())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn spans_are_exported_under_the_callers_trace() {
+        use crate::repositories::TraceQuery;
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let collector = Collector::default();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(opentelemetry_proto::tonic::collector::trace::v1::trace_service_server::TraceServiceServer::new(collector.clone()))
+                .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener)),
+        );
+
+        let provider = otlp_provider(&endpoint).unwrap();
+        let tracer = opentelemetry::trace::TracerProvider::tracer(&provider, "test");
+        let subscriber = tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(tracer));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let app = axum::Router::new()
+            .route(
+                "/todos",
+                axum::routing::get(|| async {
+                    let _query = sqlx::query("select * from todos").traced();
+                    "todos"
+                }),
+            )
+            .layer(tower_http::trace::TraceLayer::new_for_http().make_span_with(request_span));
+        let request = axum::http::Request::get("/todos")
+            .header("traceparent", "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        app.oneshot(request).await.unwrap();
+        assert!(provider.force_flush().iter().all(Result::is_ok));
+
+        let spans = collector.spans.lock().unwrap();
+        let trace_id = hex::decode("0af7651916cd43dd8448eb211c80319c").unwrap();
+        let request_span = spans.iter().find(|span| span.name == "request").unwrap();
+        assert_eq!(trace_id, request_span.trace_id);
+        assert_eq!(hex::decode("b7ad6b7169203331").unwrap(), request_span.parent_span_id);
+
+        let query_span = spans.iter().find(|span| span.name == "db.query").unwrap();
+        assert_eq!(trace_id, query_span.trace_id);
+        assert_eq!(request_span.span_id, query_span.parent_span_id);
+        assert_eq!(
+            Some(&opentelemetry_proto::tonic::common::v1::any_value::Value::StringValue("select * from todos".to_string())),
+            attribute(query_span, "db.statement"),
+        );
+    }
+}
@@ -0,0 +1,413 @@
+pub mod ical;
+pub mod markdown;
+pub mod todotxt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferFormat {
+    #[default]
+    Json,
+    Csv,
+    Ndjson,
+    Todotxt,
+    Ics,
+}
+
+impl TransferFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            TransferFormat::Json => "application/json",
+            TransferFormat::Csv => "text/csv; charset=utf-8",
+            TransferFormat::Ndjson => "application/x-ndjson",
+            TransferFormat::Todotxt => "text/plain; charset=utf-8",
+            TransferFormat::Ics => "text/calendar; charset=utf-8",
+        }
+    }
+}
+
+/// A todo as it travels through export and import, with labels referenced by name.
+///
+/// `created_at` and `completed_at` are exported for reference; imported todos get fresh timestamps.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct TodoRecord {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<i32>,
+    pub text: String,
+    #[serde(default)]
+    pub completed: bool,
+    #[serde(default)]
+    pub priority: Option<String>,
+    #[serde(default)]
+    pub due: Option<chrono::NaiveDate>,
+    #[serde(default)]
+    pub created_at: Option<chrono::NaiveDate>,
+    #[serde(default)]
+    pub completed_at: Option<chrono::NaiveDate>,
+    #[serde(default)]
+    pub labels: Vec<String>,
+}
+
+impl From<crate::repositories::todo::TodoEntity> for TodoRecord {
+    fn from(todo: crate::repositories::todo::TodoEntity) -> Self {
+        Self {
+            id: Some(todo.id),
+            text: todo.text,
+            completed: todo.completed,
+            priority: todo.priority,
+            due: todo.due,
+            created_at: Some(todo.created_at.date_naive()),
+            completed_at: todo.completed_at.map(|completed_at| completed_at.date_naive()),
+            labels: todo.labels.into_iter().map(|label| label.name).collect(),
+        }
+    }
+}
+
+/// Labels are joined into a single CSV column with this separator; a separator or `\` in a
+/// label's name is escaped with a `\`.
+const CSV_LABEL_SEPARATOR: char = ';';
+
+fn join_labels(labels: &[String]) -> String {
+    labels
+        .iter()
+        .map(|name| name.replace('\\', "\\\\").replace(CSV_LABEL_SEPARATOR, &format!("\\{}", CSV_LABEL_SEPARATOR)))
+        .collect::<Vec<_>>()
+        .join(&CSV_LABEL_SEPARATOR.to_string())
+}
+
+fn split_labels(labels: &str) -> Vec<String> {
+    let mut names = vec![String::new()];
+    let mut chars = labels.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => names.last_mut().unwrap().extend(chars.next()),
+            CSV_LABEL_SEPARATOR => names.push(String::new()),
+            c => names.last_mut().unwrap().push(c),
+        }
+    }
+    names
+        .iter()
+        .map(|name| name.trim())
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct CsvRecord {
+    id: Option<i32>,
+    text: String,
+    completed: bool,
+    priority: Option<String>,
+    due: Option<chrono::NaiveDate>,
+    created_at: Option<chrono::NaiveDate>,
+    completed_at: Option<chrono::NaiveDate>,
+    labels: String,
+}
+
+impl From<TodoRecord> for CsvRecord {
+    fn from(record: TodoRecord) -> Self {
+        Self {
+            id: record.id,
+            text: record.text,
+            completed: record.completed,
+            priority: record.priority,
+            due: record.due,
+            created_at: record.created_at,
+            completed_at: record.completed_at,
+            labels: join_labels(&record.labels),
+        }
+    }
+}
+
+impl From<CsvRecord> for TodoRecord {
+    fn from(record: CsvRecord) -> Self {
+        Self {
+            id: record.id,
+            text: record.text,
+            completed: record.completed,
+            priority: record.priority,
+            due: record.due,
+            created_at: record.created_at,
+            completed_at: record.completed_at,
+            labels: split_labels(&record.labels),
+        }
+    }
+}
+
+/// Encodes records one chunk at a time as they arrive, so the export can be streamed.
+pub fn encode(
+    format: TransferFormat,
+    records: impl futures::Stream<Item = anyhow::Result<TodoRecord>> + Send,
+) -> impl futures::Stream<Item = anyhow::Result<bytes::Bytes>> + Send {
+    let (header, footer): (&'static str, &'static str) = match format {
+        TransferFormat::Json => ("[", "]\n"),
+        TransferFormat::Csv => ("id,text,completed,priority,due,created_at,completed_at,labels\n", ""),
+        TransferFormat::Ndjson | TransferFormat::Todotxt => ("", ""),
+        TransferFormat::Ics => (ical::CALENDAR_HEADER, ical::CALENDAR_FOOTER),
+    };
+    let body = futures::StreamExt::map(futures::StreamExt::enumerate(records), move |(index, record)| {
+        record.and_then(|record| encode_record(format, index, record))
+    });
+    let header = futures::stream::once(futures::future::ready(Ok(bytes::Bytes::from_static(header.as_bytes()))));
+    let footer = futures::stream::once(futures::future::ready(Ok(bytes::Bytes::from_static(footer.as_bytes()))));
+    futures::StreamExt::chain(futures::StreamExt::chain(header, body), footer)
+}
+
+fn encode_record(format: TransferFormat, index: usize, record: TodoRecord) -> anyhow::Result<bytes::Bytes> {
+    let mut buf = vec![];
+    match format {
+        TransferFormat::Json => {
+            if index > 0 {
+                buf.push(b',');
+            }
+            serde_json::to_writer(&mut buf, &record)?;
+        }
+        TransferFormat::Ndjson => {
+            serde_json::to_writer(&mut buf, &record)?;
+            buf.push(b'\n');
+        }
+        TransferFormat::Csv => {
+            let mut writer = csv::WriterBuilder::new().has_headers(false).from_writer(buf);
+            writer.serialize(CsvRecord::from(record))?;
+            buf = writer.into_inner()?;
+        }
+        TransferFormat::Todotxt => {
+            buf.extend(todotxt::to_line(&record).into_bytes());
+            buf.push(b'\n');
+        }
+        TransferFormat::Ics => buf.extend(ical::to_vtodo(&record, chrono::Utc::now()).into_bytes()),
+    }
+    Ok(buf.into())
+}
+
+/// Parsed import rows, numbered from 1, each either a record or the reason it was rejected.
+/// Line based formats number rows by their line, counting blank lines they skip.
+pub type DecodedRows = Vec<(usize, Result<TodoRecord, String>)>;
+
+fn decode_lines(body: &[u8], parse: impl Fn(&str) -> Result<TodoRecord, String>) -> anyhow::Result<DecodedRows> {
+    Ok(std::str::from_utf8(body)?
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| (index + 1, parse(line)))
+        .collect())
+}
+
+pub fn decode(format: TransferFormat, body: &[u8]) -> anyhow::Result<DecodedRows> {
+    let rows = match format {
+        TransferFormat::Json => serde_json::from_slice::<Vec<serde_json::Value>>(body)?
+            .into_iter()
+            .map(|value| serde_json::from_value::<TodoRecord>(value).map_err(|e| e.to_string()))
+            .collect::<Vec<_>>(),
+        TransferFormat::Ndjson => {
+            return decode_lines(body, |line| serde_json::from_str::<TodoRecord>(line).map_err(|e| e.to_string()))
+        }
+        TransferFormat::Csv => csv::Reader::from_reader(body)
+            .deserialize::<CsvRecord>()
+            .map(|record| record.map(TodoRecord::from).map_err(|e| e.to_string()))
+            .collect(),
+        TransferFormat::Todotxt => return decode_lines(body, todotxt::from_line),
+        TransferFormat::Ics => ical::from_calendar(std::str::from_utf8(body)?),
+    };
+    Ok(rows.into_iter().enumerate().map(|(index, row)| (index + 1, row)).collect())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, utoipa::ToSchema)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    /// Todos created, or that would be created on a dry run.
+    pub todos: usize,
+    /// Labels created by name, or that would be created on a dry run.
+    pub labels: Vec<String>,
+    pub errors: Vec<ImportRowError>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, utoipa::ToSchema)]
+pub struct ImportRowError {
+    pub row: usize,
+    pub message: String,
+}
+
+fn validation_message(e: validator::ValidationErrors) -> String {
+    format!("validation error: {}", e).replace('\n', ", ")
+}
+
+/// What to report for a row the repository did not create, without passing on database errors.
+fn row_error_message(e: anyhow::Error) -> String {
+    match e.downcast_ref::<crate::repositories::RepositoryError>() {
+        Some(crate::repositories::RepositoryError::Unexpected(_)) | None => {
+            tracing::warn!("failed to import a row: {}", e);
+            "could not be created".to_string()
+        }
+        Some(e) => e.to_string(),
+    }
+}
+
+/// Creates the todos in `rows`, adding any labels missing by name; rows that fail are reported, not fatal.
+///
+/// The import is not one transaction: each row is created on its own, so the rows before a failing
+/// one stay, and the labels are created first and stay even when no row naming them is created.
+pub async fn import_records<T, L>(
+    todo_repository: &T,
+    label_repository: &L,
+    rows: DecodedRows,
+    dry_run: bool,
+) -> anyhow::Result<ImportReport>
+where
+    T: crate::repositories::todo::TodoRepository,
+    L: crate::repositories::label::LabelRepository,
+{
+    let mut errors = vec![];
+    let mut records = vec![];
+    for (row, record) in rows {
+        let record = record.and_then(|record| {
+            validator::Validate::validate(&crate::repositories::todo::CreateTodo::new(
+                record.text.clone(),
+                vec![],
+                record.priority.clone(),
+                record.due,
+            ))
+            .map_err(validation_message)?;
+            for name in record.labels.iter() {
+                validator::Validate::validate(&crate::handlers::label::CreateLabel::new(name.clone()))
+                    .map_err(validation_message)?;
+            }
+            Ok(record)
+        });
+        match record {
+            Ok(record) => records.push((row, record)),
+            Err(message) => errors.push(ImportRowError { row, message }),
+        }
+    }
+
+    // imported todos belong to no workspace, so neither do their labels
+    let mut label_ids: std::collections::HashMap<String, i32> = label_repository
+        .all()
+        .await?
+        .into_iter()
+        .filter(|label| label.workspace_id.is_none())
+        .map(|label| (label.name, label.id))
+        .collect();
+    let mut missing_labels: Vec<String> = vec![];
+    for (_, record) in records.iter() {
+        for name in record.labels.iter() {
+            if !label_ids.contains_key(name) && !missing_labels.contains(name) {
+                missing_labels.push(name.clone());
+            }
+        }
+    }
+
+    if dry_run {
+        return Ok(ImportReport { dry_run, todos: records.len(), labels: missing_labels, errors });
+    }
+
+    for name in missing_labels.iter() {
+        let id = match label_repository.create(name.clone(), None).await {
+            Ok(label) => label.id,
+            Err(e) => match e.downcast_ref::<crate::repositories::RepositoryError>() {
+                Some(crate::repositories::RepositoryError::Duplicate(id)) => *id,
+                _ => return Err(e),
+            },
+        };
+        label_ids.insert(name.clone(), id);
+    }
+
+    // new todos go to the top of the list, so create from the bottom up to keep the file's order
+    let mut todos = 0;
+    for (row, record) in records.into_iter().rev() {
+        let labels = record.labels.iter().map(|name| label_ids[name]).collect();
+        // one write per row, so a row fails as a whole instead of leaving an open todo behind
+        let payload = crate::repositories::todo::CreateTodo::new(record.text, labels, record.priority, record.due).completed(record.completed);
+        match todo_repository.create(payload).await {
+            Ok(_) => todos += 1,
+            Err(e) => errors.push(ImportRowError { row, message: row_error_message(e) }),
+        }
+    }
+    errors.sort_by_key(|error| error.row);
+
+    Ok(ImportReport { dry_run, todos, labels: missing_labels, errors })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn records() -> Vec<TodoRecord> {
+        vec![
+            TodoRecord {
+                id: Some(2),
+                text: "write, \"quoted\" report".to_string(),
+                completed: true,
+                priority: Some("A".to_string()),
+                due: chrono::NaiveDate::from_ymd_opt(2026, 10, 31),
+                created_at: chrono::NaiveDate::from_ymd_opt(2026, 10, 1),
+                completed_at: chrono::NaiveDate::from_ymd_opt(2026, 10, 19),
+                labels: vec!["work".to_string(), "urgent; really".to_string(), "a\\b".to_string()],
+            },
+            TodoRecord {
+                id: Some(1),
+                text: "buy milk".to_string(),
+                completed: false,
+                priority: None,
+                due: None,
+                created_at: None,
+                completed_at: None,
+                labels: vec![],
+            },
+        ]
+    }
+
+    fn round_trip(format: TransferFormat) -> DecodedRows {
+        let chunks = encode(format, futures::stream::iter(records().into_iter().map(Ok)));
+        let body: Vec<u8> = futures::executor::block_on(futures::TryStreamExt::try_collect::<Vec<_>>(chunks))
+            .unwrap()
+            .concat();
+        decode(format, &body).unwrap()
+    }
+
+    #[test]
+    fn round_trip_all_formats() {
+        let expected: DecodedRows = records().into_iter().enumerate().map(|(i, r)| (i + 1, Ok(r))).collect();
+        for format in [TransferFormat::Json, TransferFormat::Csv, TransferFormat::Ndjson] {
+            assert_eq!(expected, round_trip(format), "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn decode_reports_row_errors() {
+        let rows = decode(TransferFormat::Ndjson, b"{\"text\":\"ok\"}\n\n{\"completed\":true}\n").unwrap();
+        assert_eq!(2, rows.len());
+        assert!(rows[0].1.is_ok());
+        // the blank line still counts
+        assert_eq!(3, rows[1].0);
+        assert!(rows[1].1.is_err());
+
+        let rows = decode(TransferFormat::Csv, b"id,text,completed,labels\n,a,maybe,\n,b,false,x; y\n").unwrap();
+        assert!(rows[0].1.is_err());
+        assert_eq!(vec!["x".to_string(), "y".to_string()], rows[1].1.clone().unwrap().labels);
+    }
+
+    #[tokio::test]
+    async fn completed_rows_are_created_completed() {
+        use crate::repositories::todo::TodoRepository;
+
+        let todos = crate::repositories::todo::test_utils::TodoRepositoryForMemory::new(vec![]);
+        let labels = crate::repositories::label::test_utils::LabelRepositoryForMemory::new();
+        let rows = records().into_iter().enumerate().map(|(i, r)| (i + 1, Ok(r))).collect();
+        let report = import_records(&todos, &labels, rows, false).await.unwrap();
+        assert_eq!((2, vec![]), (report.todos, report.errors));
+
+        let imported = todos.all().await.unwrap();
+        let done = imported.iter().find(|todo| todo.text == records()[0].text).unwrap();
+        assert!(done.completed && done.completed_at.is_some());
+        assert!(imported.iter().any(|todo| todo.text == "buy milk" && !todo.completed && todo.completed_at.is_none()));
+    }
+
+    #[test]
+    fn row_errors_do_not_leak_database_errors() {
+        let e = anyhow::Error::new(sqlx::Error::Protocol("relation \"todos\" is locked".to_string()));
+        assert_eq!("could not be created", row_error_message(e));
+        let e = crate::repositories::RepositoryError::NotFound(3).into();
+        assert_eq!("NotFound, 3", row_error_message(e));
+    }
+}
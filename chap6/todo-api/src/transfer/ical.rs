@@ -0,0 +1,225 @@
+//! Conversion between [`TodoRecord`] and iCalendar (RFC 5545) `VTODO` components.
+//!
+//! Priorities `A`..`I` map onto iCalendar's `1`..`9`; later letters collapse to `9`.
+
+use super::TodoRecord;
+
+pub const CALENDAR_HEADER: &str = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//todo-api//todos//EN\r\n";
+pub const CALENDAR_FOOTER: &str = "END:VCALENDAR\r\n";
+
+/// Content lines longer than this many octets are folded.
+const MAX_LINE_OCTETS: usize = 75;
+
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape_text(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => unescaped.push('\n'),
+            Some(c) => unescaped.push(c),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Splits on commas that are not escaped, as used by multi-valued properties like `CATEGORIES`.
+fn split_values(value: &str) -> Vec<String> {
+    let mut values = vec![];
+    let mut current = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                current.push(c);
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+            }
+            ',' => values.push(std::mem::take(&mut current)),
+            c => current.push(c),
+        }
+    }
+    values.push(current);
+    values.iter().map(|value| unescape_text(value)).filter(|value| !value.is_empty()).collect()
+}
+
+fn fold_line(line: &str, out: &mut String) {
+    let mut octets = 0;
+    for c in line.chars() {
+        if octets + c.len_utf8() > MAX_LINE_OCTETS {
+            out.push_str("\r\n ");
+            octets = 1;
+        }
+        out.push(c);
+        octets += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+fn format_date_time(date: chrono::NaiveDate) -> String {
+    format!("{}T000000Z", date.format("%Y%m%d"))
+}
+
+fn parse_date(value: &str) -> Option<chrono::NaiveDate> {
+    chrono::NaiveDate::parse_from_str(value.get(..8)?, "%Y%m%d").ok()
+}
+
+pub fn to_vtodo(record: &TodoRecord, stamp: chrono::DateTime<chrono::Utc>) -> String {
+    let mut lines = vec!["BEGIN:VTODO".to_string()];
+    match record.id {
+        Some(id) => lines.push(format!("UID:todo-{}@todo-api", id)),
+        None => lines.push(format!("UID:{:032x}@todo-api", rand::random::<u128>())),
+    }
+    lines.push(format!("DTSTAMP:{}", stamp.format("%Y%m%dT%H%M%SZ")));
+    lines.push(format!("SUMMARY:{}", escape_text(&record.text)));
+    lines.push(format!("STATUS:{}", if record.completed { "COMPLETED" } else { "NEEDS-ACTION" }));
+    if !record.labels.is_empty() {
+        let categories: Vec<String> = record.labels.iter().map(|label| escape_text(label)).collect();
+        lines.push(format!("CATEGORIES:{}", categories.join(",")));
+    }
+    if let Some(priority) = record.priority.as_deref().and_then(|priority| priority.bytes().next()) {
+        lines.push(format!("PRIORITY:{}", (priority - b'A' + 1).min(9)));
+    }
+    if let Some(due) = record.due {
+        lines.push(format!("DUE;VALUE=DATE:{}", due.format("%Y%m%d")));
+    }
+    if let Some(created_at) = record.created_at {
+        lines.push(format!("CREATED:{}", format_date_time(created_at)));
+    }
+    if let Some(completed_at) = record.completed_at {
+        lines.push(format!("COMPLETED:{}", format_date_time(completed_at)));
+    }
+    lines.push("END:VTODO".to_string());
+
+    let mut out = String::new();
+    for line in lines {
+        fold_line(&line, &mut out);
+    }
+    out
+}
+
+/// Unfolds content lines and yields `(name, value)` pairs, dropping property parameters.
+fn content_lines(body: &str) -> Vec<(String, String)> {
+    let mut unfolded: Vec<String> = vec![];
+    for line in body.split('\n').map(|line| line.strip_suffix('\r').unwrap_or(line)) {
+        match (line.strip_prefix(' ').or_else(|| line.strip_prefix('\t')), unfolded.last_mut()) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ if line.is_empty() => {}
+            _ => unfolded.push(line.to_string()),
+        }
+    }
+    unfolded
+        .into_iter()
+        .filter_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            let name = name.split(';').next().unwrap_or_default().to_ascii_uppercase();
+            Some((name, value.to_string()))
+        })
+        .collect()
+}
+
+/// Reads every `VTODO` in a calendar; components without a summary are reported as errors.
+pub fn from_calendar(body: &str) -> Vec<Result<TodoRecord, String>> {
+    let mut records = vec![];
+    let mut current: Option<TodoRecord> = None;
+    for (name, value) in content_lines(body) {
+        match (name.as_str(), current.as_mut()) {
+            ("BEGIN", None) if value.eq_ignore_ascii_case("VTODO") => {
+                current = Some(TodoRecord {
+                    id: None,
+                    text: String::new(),
+                    completed: false,
+                    priority: None,
+                    due: None,
+                    created_at: None,
+                    completed_at: None,
+                    labels: vec![],
+                });
+            }
+            ("END", Some(_)) if value.eq_ignore_ascii_case("VTODO") => {
+                let record = current.take().unwrap();
+                records.push(if record.text.trim().is_empty() {
+                    Err("VTODO without SUMMARY".to_string())
+                } else {
+                    Ok(record)
+                });
+            }
+            ("SUMMARY", Some(record)) => record.text = unescape_text(&value),
+            ("STATUS", Some(record)) => record.completed = value.eq_ignore_ascii_case("COMPLETED"),
+            ("CATEGORIES", Some(record)) => record.labels.extend(split_values(&value)),
+            ("PRIORITY", Some(record)) => {
+                record.priority = match value.trim().parse::<u8>() {
+                    Ok(priority @ 1..=9) => Some(((b'A' + priority - 1) as char).to_string()),
+                    _ => None,
+                }
+            }
+            ("DUE", Some(record)) => record.due = parse_date(&value),
+            ("CREATED", Some(record)) => record.created_at = parse_date(&value),
+            ("COMPLETED", Some(record)) => {
+                record.completed_at = parse_date(&value);
+                record.completed = true;
+            }
+            _ => {}
+        }
+    }
+    records
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn vtodo_round_trip() {
+        let record = TodoRecord {
+            id: Some(3),
+            text: "review; the \\ long, long proposal\nwith a second line and enough words to need folding".to_string(),
+            completed: true,
+            priority: Some("B".to_string()),
+            due: chrono::NaiveDate::from_ymd_opt(2026, 11, 1),
+            created_at: chrono::NaiveDate::from_ymd_opt(2026, 10, 1),
+            completed_at: chrono::NaiveDate::from_ymd_opt(2026, 10, 19),
+            labels: vec!["work".to_string(), "a,b".to_string()],
+        };
+        let vtodo = to_vtodo(&record, chrono::Utc::now());
+        assert!(vtodo.split("\r\n").all(|line| line.len() <= MAX_LINE_OCTETS));
+        assert!(vtodo.contains("STATUS:COMPLETED\r\n"));
+        assert!(vtodo.contains("DUE;VALUE=DATE:20261101\r\n"));
+
+        let calendar = format!("{}{}{}", CALENDAR_HEADER, vtodo, CALENDAR_FOOTER);
+        assert_eq!(vec![Ok(TodoRecord { id: None, ..record })], from_calendar(&calendar));
+    }
+
+    #[test]
+    fn reads_foreign_vtodos() {
+        let calendar = "BEGIN:VCALENDAR\nBEGIN:VEVENT\nSUMMARY:not a todo\nEND:VEVENT\nBEGIN:VTODO\nSUMMARY:Buy\n  milk\nCATEGORIES:home\nCATEGORIES:errands\nDUE:20261020T170000Z\nEND:VTODO\nBEGIN:VTODO\nSTATUS:NEEDS-ACTION\nEND:VTODO\nEND:VCALENDAR\n";
+        let records = from_calendar(calendar);
+        assert_eq!(2, records.len());
+        let record = records[0].clone().unwrap();
+        assert_eq!("Buy milk", record.text);
+        assert!(!record.completed);
+        assert_eq!(vec!["home".to_string(), "errands".to_string()], record.labels);
+        assert_eq!(chrono::NaiveDate::from_ymd_opt(2026, 10, 20), record.due);
+        assert!(records[1].is_err());
+    }
+}
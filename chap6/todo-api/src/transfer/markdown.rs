@@ -0,0 +1,135 @@
+//! GitHub-style markdown checklists (`- [ ] task`).
+//!
+//! On import, headings above an item become its labels and nested items are prefixed with
+//! their parents' text, e.g. `Release / Write notes`. Plain bullets only act as such prefixes.
+
+use super::{DecodedRows, TodoRecord};
+
+/// Joins a nested item's text onto its parents'.
+const NESTING_SEPARATOR: &str = " / ";
+
+pub fn render(heading: Option<&str>, records: &[TodoRecord]) -> String {
+    let mut out = String::new();
+    if let Some(heading) = heading {
+        out.push_str(&format!("# {}\n\n", heading));
+    }
+    for record in records {
+        let mark = if record.completed { 'x' } else { ' ' };
+        out.push_str(&format!("- [{}] {}\n", mark, record.text.replace('\n', " ")));
+    }
+    out
+}
+
+fn parse_heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    let title = line[level..].strip_prefix(' ')?.trim().trim_end_matches('#').trim();
+    if (1..=6).contains(&level) && !title.is_empty() {
+        Some((level, title))
+    } else {
+        None
+    }
+}
+
+/// Returns the indent width, whether the item is checked (`None` for plain bullets) and its text.
+fn parse_item(line: &str) -> Option<(usize, Option<bool>, &str)> {
+    let trimmed = line.trim_start();
+    let indent: usize = line[..line.len() - trimmed.len()]
+        .chars()
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum();
+    let rest = trimmed
+        .strip_prefix("- ")
+        .or_else(|| trimmed.strip_prefix("* "))
+        .or_else(|| trimmed.strip_prefix("+ "))?
+        .trim_start();
+    let (checked, text) = match rest.get(..3) {
+        Some("[ ]") => (Some(false), &rest[3..]),
+        Some("[x]") | Some("[X]") => (Some(true), &rest[3..]),
+        _ => (None, rest),
+    };
+    Some((indent, checked, text.trim()))
+}
+
+pub fn parse(body: &str) -> DecodedRows {
+    let mut rows = vec![];
+    let mut headings: Vec<String> = vec![];
+    let mut parents: Vec<(usize, String)> = vec![];
+    for (index, line) in body.lines().enumerate() {
+        if let Some((level, title)) = parse_heading(line) {
+            headings.truncate(level - 1);
+            headings.resize(level - 1, String::new());
+            headings.push(title.to_string());
+            parents.clear();
+            continue;
+        }
+        let (indent, checked, text) = match parse_item(line) {
+            Some(item) => item,
+            None => continue,
+        };
+        while parents.last().is_some_and(|(parent_indent, _)| *parent_indent >= indent) {
+            parents.pop();
+        }
+        parents.push((indent, text.to_string()));
+        let path: Vec<&str> = parents.iter().map(|(_, parent)| parent.as_str()).collect();
+
+        if let Some(completed) = checked {
+            let row = if text.is_empty() {
+                Err("empty checklist item".to_string())
+            } else {
+                Ok(TodoRecord {
+                    id: None,
+                    text: path.join(NESTING_SEPARATOR),
+                    completed,
+                    priority: None,
+                    due: None,
+                    created_at: None,
+                    completed_at: None,
+                    labels: headings.iter().filter(|heading| !heading.is_empty()).cloned().collect(),
+                })
+            };
+            rows.push((index + 1, row));
+        }
+    }
+    rows
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_nested_checklist() {
+        let body = "# Work\nsome notes\n- [ ] plain task\n- Release\n  - [x] Write notes\n    - [ ] Proofread\n  - [ ] Tag\n## Ops\n* [X] Deploy\n- [ ]\n";
+        let rows = parse(body);
+        let records: Vec<(String, bool, Vec<String>)> = rows
+            .iter()
+            .filter_map(|(_, row)| row.clone().ok())
+            .map(|record| (record.text, record.completed, record.labels))
+            .collect();
+        let work = vec!["Work".to_string()];
+        assert_eq!(
+            vec![
+                ("plain task".to_string(), false, work.clone()),
+                ("Release / Write notes".to_string(), true, work.clone()),
+                ("Release / Write notes / Proofread".to_string(), false, work.clone()),
+                ("Release / Tag".to_string(), false, work),
+                ("Deploy".to_string(), true, vec!["Work".to_string(), "Ops".to_string()]),
+            ],
+            records
+        );
+        assert_eq!((10, Err("empty checklist item".to_string())), rows[5]);
+    }
+
+    #[test]
+    fn render_round_trips_completed_state() {
+        let records: Vec<TodoRecord> = parse("- [x] done\n- [ ] open\n").into_iter().map(|(_, row)| row.unwrap()).collect();
+        let rendered = render(Some("home"), &records);
+        assert_eq!("# home\n\n- [x] done\n- [ ] open\n", rendered);
+        let reparsed: Vec<(String, bool)> = parse(&rendered)
+            .into_iter()
+            .map(|(_, row)| row.unwrap())
+            .map(|record| (record.text, record.completed))
+            .collect();
+        assert_eq!(vec![("done".to_string(), true), ("open".to_string(), false)], reparsed);
+    }
+}
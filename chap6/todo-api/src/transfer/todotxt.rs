@@ -0,0 +1,216 @@
+//! Conversion between [`TodoRecord`] and [todo.txt](https://github.com/todotxt/todo.txt) lines.
+//!
+//! Labels starting with `@` are written as contexts, every other label as a `+project`.
+//! A completed todo keeps its priority as a `pri:` key, as the todo.txt CLI does.
+//! Words of the text that would read as metadata (`+project`, `@context`, `due:`/`pri:` keys, or
+//! a leading `x`, `(A)` or date) are written with a leading `\`, which is dropped again on import.
+
+use super::TodoRecord;
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+fn parse_date(token: &str) -> Option<chrono::NaiveDate> {
+    if token.len() != 10 {
+        return None;
+    }
+    chrono::NaiveDate::parse_from_str(token, DATE_FORMAT).ok()
+}
+
+fn parse_priority(token: &str) -> Option<String> {
+    match token.as_bytes() {
+        [b'(', priority @ b'A'..=b'Z', b')'] => Some((*priority as char).to_string()),
+        _ => None,
+    }
+}
+
+/// Whether a word anywhere in a line reads as a label or key rather than text.
+fn is_metadata(token: &str) -> bool {
+    token.strip_prefix('+').is_some_and(|project| !project.is_empty())
+        || (token.len() > 1 && token.starts_with('@'))
+        || token.strip_prefix("due:").and_then(parse_date).is_some()
+        || token.strip_prefix("pri:").and_then(|priority| parse_priority(&format!("({})", priority))).is_some()
+}
+
+/// Escapes the words of a todo's text; the first one also must not look like the completion
+/// marker, a priority or a date.
+fn escape_text(text: &str) -> String {
+    text.split_whitespace()
+        .enumerate()
+        .map(|(i, word)| {
+            let leading = i == 0 && (word == "x" || parse_priority(word).is_some() || parse_date(word).is_some());
+            if leading || word.starts_with('\\') || is_metadata(word) {
+                format!("\\{}", word)
+            } else {
+                word.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn to_line(record: &TodoRecord) -> String {
+    let mut tokens: Vec<String> = vec![];
+    if record.completed {
+        tokens.push("x".to_string());
+        // a lone date after `x` reads as the completion date, so the creation date needs both
+        if let Some(completed_at) = record.completed_at {
+            tokens.push(completed_at.format(DATE_FORMAT).to_string());
+            if let Some(created_at) = record.created_at {
+                tokens.push(created_at.format(DATE_FORMAT).to_string());
+            }
+        }
+    } else {
+        if let Some(priority) = &record.priority {
+            tokens.push(format!("({})", priority));
+        }
+        if let Some(created_at) = record.created_at {
+            tokens.push(created_at.format(DATE_FORMAT).to_string());
+        }
+    }
+
+    tokens.push(escape_text(&record.text));
+    for label in record.labels.iter() {
+        let label = label.split_whitespace().collect::<Vec<_>>().join("_");
+        if label.starts_with('@') {
+            tokens.push(label);
+        } else {
+            tokens.push(format!("+{}", label));
+        }
+    }
+    if record.completed {
+        if let Some(priority) = &record.priority {
+            tokens.push(format!("pri:{}", priority));
+        }
+    }
+    if let Some(due) = record.due {
+        tokens.push(format!("due:{}", due.format(DATE_FORMAT)));
+    }
+    tokens.join(" ")
+}
+
+pub fn from_line(line: &str) -> Result<TodoRecord, String> {
+    let mut tokens = line.split_whitespace().peekable();
+    let mut record = TodoRecord {
+        id: None,
+        text: String::new(),
+        completed: false,
+        priority: None,
+        due: None,
+        created_at: None,
+        completed_at: None,
+        labels: vec![],
+    };
+
+    if tokens.peek() == Some(&"x") {
+        record.completed = true;
+        tokens.next();
+    }
+    if let Some(priority) = tokens.peek().and_then(|token| parse_priority(token)) {
+        record.priority = Some(priority);
+        tokens.next();
+    }
+    let mut dates = vec![];
+    while dates.len() < 2 {
+        match tokens.peek().and_then(|token| parse_date(token)) {
+            Some(date) => {
+                dates.push(date);
+                tokens.next();
+            }
+            None => break,
+        }
+    }
+    match (record.completed, dates.as_slice()) {
+        (true, [completed_at, created_at]) => {
+            record.completed_at = Some(*completed_at);
+            record.created_at = Some(*created_at);
+        }
+        (true, [completed_at]) => record.completed_at = Some(*completed_at),
+        (false, [created_at, ..]) => record.created_at = Some(*created_at),
+        _ => {}
+    }
+
+    let mut words = vec![];
+    for token in tokens {
+        if let Some(word) = token.strip_prefix('\\') {
+            words.push(word);
+        } else if let Some(project) = token.strip_prefix('+').filter(|project| !project.is_empty()) {
+            record.labels.push(project.to_string());
+        } else if token.len() > 1 && token.starts_with('@') {
+            record.labels.push(token.to_string());
+        } else if let Some(due) = token.strip_prefix("due:").and_then(parse_date) {
+            record.due = Some(due);
+        } else if let Some(priority) = token.strip_prefix("pri:").and_then(|priority| parse_priority(&format!("({})", priority))) {
+            record.priority = Some(priority);
+        } else {
+            words.push(token);
+        }
+    }
+    if words.is_empty() {
+        return Err(format!("missing text in todo.txt line: {}", line));
+    }
+    record.text = words.join(" ");
+    Ok(record)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn parse_full_line() {
+        let record = from_line("x 2026-10-19 2026-10-01 call mom +family @phone pri:B due:2026-10-20").unwrap();
+        assert!(record.completed);
+        assert_eq!(Some("B".to_string()), record.priority);
+        assert_eq!(chrono::NaiveDate::from_ymd_opt(2026, 10, 19), record.completed_at);
+        assert_eq!(chrono::NaiveDate::from_ymd_opt(2026, 10, 1), record.created_at);
+        assert_eq!(chrono::NaiveDate::from_ymd_opt(2026, 10, 20), record.due);
+        assert_eq!("call mom", record.text);
+        assert_eq!(vec!["family".to_string(), "@phone".to_string()], record.labels);
+
+        let record = from_line("(A) 2026-10-01 see http://example.com due:someday").unwrap();
+        assert!(!record.completed);
+        assert_eq!(Some("A".to_string()), record.priority);
+        assert_eq!("see http://example.com due:someday", record.text);
+        assert!(from_line("x +only @labels").is_err());
+
+        let record = from_line(r"\(B) \+1 for \@team \\o/").unwrap();
+        assert_eq!((None, vec![]), (record.priority, record.labels));
+        assert_eq!(r"(B) +1 for @team \o/", record.text);
+    }
+
+    fn date() -> impl Strategy<Value = chrono::NaiveDate> {
+        (2000i32..2100, 1u32..=12, 1u32..=28).prop_map(|(y, m, d)| chrono::NaiveDate::from_ymd_opt(y, m, d).unwrap())
+    }
+
+    prop_compose! {
+        fn record()(
+            // mixes in words that have to be escaped to stay text
+            text in prop::collection::vec(prop_oneof![
+                "[a-z]{2,8}",
+                "[+@\\\\]{1,2}[a-z]{0,8}",
+                "x|\\([A-Z]\\)|(due|pri):[A-Z0-9-]{1,10}",
+                date().prop_map(|date| date.to_string()),
+                date().prop_map(|date| format!("due:{}", date)),
+            ], 1..5),
+            completed in any::<bool>(),
+            priority in prop::option::of("[A-Z]"),
+            due in prop::option::of(date()),
+            created_at in prop::option::of(date()),
+            completed_at in prop::option::of(date()),
+            labels in prop::collection::vec("[+@]?[a-z]{1,8}", 0..4),
+        ) -> TodoRecord {
+            // only keep the date combinations todo.txt can express
+            let completed_at = if completed { completed_at } else { None };
+            let created_at = if completed && completed_at.is_none() { None } else { created_at };
+            TodoRecord { id: None, text: text.join(" "), completed, priority, due, created_at, completed_at, labels }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn round_trip(record in record()) {
+            prop_assert_eq!(Ok(record.clone()), from_line(&to_line(&record)));
+        }
+    }
+}
@@ -0,0 +1,89 @@
+//! TypeScript definitions for `todo-front/src/types/todo.d.ts`, derived from the API types.
+//!
+//! The test fails when the checked-in file is stale; run it with `UPDATE_TS_TYPES=1` to rewrite it.
+
+use ts_rs::TS;
+
+const HEADER: &str = "// Generated from the todo-api types by `cargo test typescript`. Do not edit by hand;\n// run `UPDATE_TS_TYPES=1 cargo test typescript` in todo-api to regenerate.\n";
+
+/// Payloads the frontend sends along with the id of the todo they apply to.
+const PATH_PAYLOADS: &str = "export type UpdateTodoPayload = UpdateTodo & {\n  id: number\n}\n\nexport type MoveTodoPayload = MoveTodo & {\n  id: number\n}\n";
+
+/// Turns ts-rs' one-line `type X = { a: A, b: B, };` into the multi-line layout the frontend uses.
+fn format_declaration(declaration: &str) -> String {
+    let (name, body) = declaration.split_once(" = ").unwrap();
+    let body = body.trim_end_matches(';').trim();
+    let fields = body.strip_prefix('{').and_then(|body| body.strip_suffix('}')).unwrap();
+
+    let mut out = format!("export {} = {{\n", name);
+    let mut depth = 0;
+    let mut field = String::new();
+    for c in fields.chars().chain(std::iter::once(',')) {
+        match c {
+            '<' | '{' | '(' | '[' => depth += 1,
+            '>' | '}' | ')' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                if !field.trim().is_empty() {
+                    out.push_str(&format!("  {}\n", field.trim()));
+                }
+                field.clear();
+                continue;
+            }
+            _ => {}
+        }
+        field.push(c);
+    }
+    out.push_str("}\n");
+    out
+}
+
+pub fn declarations() -> String {
+    let declarations = [
+        crate::repositories::todo::TodoEntity::decl(),
+        crate::repositories::todo::CreateTodo::decl(),
+        crate::repositories::label::Label::decl(),
+        crate::handlers::label::CreateLabel::decl(),
+        crate::repositories::todo::UpdateTodo::decl(),
+        crate::repositories::todo::MoveTodo::decl(),
+        crate::repositories::comment::Comment::decl(),
+        crate::repositories::comment::CreateComment::decl(),
+        crate::repositories::attachment::Attachment::decl(),
+    ];
+    let mut out = HEADER.to_string();
+    for declaration in declarations.iter() {
+        out.push('\n');
+        out.push_str(&format_declaration(declaration));
+    }
+    out.push('\n');
+    out.push_str(PATH_PAYLOADS);
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn frontend_types_are_current() {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../todo-front/src/types/todo.d.ts");
+        let generated = declarations();
+        if std::env::var("UPDATE_TS_TYPES").is_ok() {
+            std::fs::write(&path, &generated).unwrap();
+            return;
+        }
+        let current = std::fs::read_to_string(&path).unwrap_or_default();
+        assert!(
+            current == generated,
+            "{} is out of date; run `UPDATE_TS_TYPES=1 cargo test typescript` to regenerate it",
+            path.display()
+        );
+    }
+
+    #[test]
+    fn format_nested_fields() {
+        assert_eq!(
+            "export type A = {\n  a: Array<Record<string, number>>\n  b?: string\n}\n",
+            format_declaration("type A = { a: Array<Record<string, number>>, b?: string, };")
+        );
+    }
+}
@@ -0,0 +1,482 @@
+//! Outgoing webhooks.
+//!
+//! [`TodoRepositoryWithWebhooks`] and [`LabelRepositoryWithWebhooks`] queue a delivery for every
+//! subscribed webhook after each successful write. The queue lives in the database, so changes
+//! are not lost when a receiver is down or the server restarts; [`run_worker`] drains it, signing
+//! each request and retrying failures with exponential backoff until they end up as dead letters.
+//!
+//! Every request is a JSON `POST` of `{"event", "occurred_at", "data"}` with these headers:
+//!
+//! - `X-Webhook-Event`: the event name, e.g. `todo.updated`
+//! - `X-Webhook-Delivery`: the delivery id, stable across retries
+//! - `X-Webhook-Timestamp`: unix seconds when the request was signed
+//! - `X-Webhook-Signature`: `sha256=` followed by the hex HMAC-SHA256 of `{timestamp}.{body}`,
+//!   keyed with the webhook's secret
+//!
+//! `todo.assigned` is sent when a todo's assignee changes, with the previous assignee and the
+//! watchers to notify, so a receiver can tell the people involved.
+//!
+//! Webhooks are only sent to public addresses unless their host is allowlisted, see
+//! [`destination`].
+
+pub mod destination;
+
+pub const EVENT_NAMES: [&str; 6] = ["todo.created", "todo.updated", "todo.deleted", "todo.assigned", "label.created", "label.deleted"];
+
+/// Event filter entry subscribing to every event.
+pub const ALL_EVENTS: &str = "*";
+
+/// Attempts before a delivery becomes a dead letter.
+pub const MAX_ATTEMPTS: i32 = 8;
+
+const FIRST_RETRY: std::time::Duration = std::time::Duration::from_secs(30);
+const MAX_RETRY: std::time::Duration = std::time::Duration::from_secs(6 * 60 * 60);
+
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+const BATCH_SIZE: i64 = 20;
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+/// Longer than a request can take, so a claimed delivery is only picked up again if the worker died.
+const CLAIM_LEASE: std::time::Duration = std::time::Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WebhookEvent {
+    TodoCreated(crate::repositories::todo::TodoEntity),
+    TodoUpdated(crate::repositories::todo::TodoEntity),
+    TodoDeleted(i32),
+    TodoAssigned {
+        todo: crate::repositories::todo::TodoEntity,
+        previous_assignee_id: Option<i32>,
+        watcher_ids: Vec<i32>,
+    },
+    LabelCreated(crate::repositories::label::Label),
+    LabelDeleted(i32),
+}
+
+impl WebhookEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            WebhookEvent::TodoCreated(_) => "todo.created",
+            WebhookEvent::TodoUpdated(_) => "todo.updated",
+            WebhookEvent::TodoDeleted(_) => "todo.deleted",
+            WebhookEvent::TodoAssigned { .. } => "todo.assigned",
+            WebhookEvent::LabelCreated(_) => "label.created",
+            WebhookEvent::LabelDeleted(_) => "label.deleted",
+        }
+    }
+
+    pub fn payload(&self) -> serde_json::Value {
+        let data = match self {
+            WebhookEvent::TodoCreated(todo) | WebhookEvent::TodoUpdated(todo) => serde_json::json!(todo),
+            WebhookEvent::TodoAssigned { todo, previous_assignee_id, watcher_ids } => serde_json::json!({
+                "todo": todo,
+                "previous_assignee_id": previous_assignee_id,
+                "watcher_ids": watcher_ids,
+            }),
+            WebhookEvent::LabelCreated(label) => serde_json::json!(label),
+            WebhookEvent::TodoDeleted(id) | WebhookEvent::LabelDeleted(id) => serde_json::json!({ "id": id }),
+        };
+        serde_json::json!({ "event": self.name(), "occurred_at": chrono::Utc::now(), "data": data })
+    }
+}
+
+/// Queues an event, logging instead of failing: the write it describes has already happened.
+async fn notify<W: crate::repositories::webhook::WebhookRepository>(webhooks: &W, event: WebhookEvent) {
+    if let Err(e) = webhooks.enqueue(event.name(), event.payload()).await {
+        tracing::error!("failed to queue webhook {}: {}", event.name(), e);
+    }
+}
+
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    use hmac::Mac;
+    let mut mac = hmac::Hmac::<sha2::Sha256>::new_from_slice(secret.as_bytes()).expect("hmac accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// When to retry after `attempts` failed attempts, or `None` once they are used up.
+pub fn backoff(attempts: i32) -> Option<std::time::Duration> {
+    if attempts >= MAX_ATTEMPTS {
+        return None;
+    }
+    let exponent = attempts.clamp(1, 16) as u32 - 1;
+    Some(FIRST_RETRY.saturating_mul(2u32.pow(exponent)).min(MAX_RETRY))
+}
+
+pub type Client = hyper::Client<hyper_rustls::HttpsConnector<hyper::client::HttpConnector>>;
+
+pub fn client() -> Client {
+    let connector = hyper_rustls::HttpsConnectorBuilder::new()
+        .with_webpki_roots()
+        .https_or_http()
+        .enable_http1()
+        .build();
+    hyper::Client::builder().build(connector)
+}
+
+/// Client for deliveries, connecting only to destinations the [`destination::DestinationPolicy`] allows.
+#[derive(Clone)]
+pub struct WebhookClient {
+    http: hyper::Client<hyper_rustls::HttpsConnector<hyper::client::HttpConnector<destination::GuardedResolver>>>,
+    policy: destination::DestinationPolicy,
+}
+
+impl WebhookClient {
+    pub fn new(policy: destination::DestinationPolicy) -> Self {
+        let mut http = hyper::client::HttpConnector::new_with_resolver(destination::GuardedResolver::new(policy.clone()));
+        http.enforce_http(false);
+        let connector = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_webpki_roots()
+            .https_or_http()
+            .enable_http1()
+            .wrap_connector(http);
+        Self { http: hyper::Client::builder().build(connector), policy }
+    }
+}
+
+/// Sends one delivery and decides what happens to it next.
+pub async fn deliver(client: &WebhookClient, delivery: &crate::repositories::webhook::PendingDelivery) -> crate::repositories::webhook::DeliveryOutcome {
+    // IP addresses in the url are connected to without going through the resolver
+    if let Err(e) = delivery.url.parse().map_err(|_| destination::DestinationError::InvalidUrl(delivery.url.clone())).and_then(|uri| client.policy.check_literal(&uri)) {
+        return failed(delivery, None, e.to_string());
+    }
+    let body = serde_json::to_vec(&delivery.payload).unwrap();
+    let timestamp = chrono::Utc::now().timestamp();
+    let request = hyper::Request::post(&delivery.url)
+        .header(hyper::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+        .header(hyper::header::USER_AGENT, concat!("todo-api-webhooks/", env!("CARGO_PKG_VERSION")))
+        .header("X-Webhook-Event", &delivery.event)
+        .header("X-Webhook-Delivery", delivery.id)
+        .header("X-Webhook-Timestamp", timestamp)
+        .header("X-Webhook-Signature", sign(&delivery.secret, timestamp, &body))
+        .body(hyper::Body::from(body));
+    let request = match request {
+        Ok(request) => request,
+        Err(e) => return failed(delivery, None, e.to_string()),
+    };
+
+    match tokio::time::timeout(REQUEST_TIMEOUT, client.http.request(request)).await {
+        Ok(Ok(response)) if response.status().is_success() => crate::repositories::webhook::DeliveryOutcome::Delivered {
+            response_status: response.status().as_u16() as i32,
+        },
+        Ok(Ok(response)) => failed(delivery, Some(response.status().as_u16() as i32), format!("receiver responded {}", response.status())),
+        Ok(Err(e)) => failed(delivery, None, e.to_string()),
+        Err(_) => failed(delivery, None, format!("no response within {}s", REQUEST_TIMEOUT.as_secs())),
+    }
+}
+
+fn failed(delivery: &crate::repositories::webhook::PendingDelivery, response_status: Option<i32>, error: String) -> crate::repositories::webhook::DeliveryOutcome {
+    let retry_at = backoff(delivery.attempts).map(|delay| chrono::Utc::now() + chrono::Duration::from_std(delay).unwrap());
+    crate::repositories::webhook::DeliveryOutcome::Failed { response_status, error, retry_at }
+}
+
+/// Delivers one batch of due deliveries, returning how many were attempted.
+pub async fn deliver_due<W: crate::repositories::webhook::WebhookRepository>(repository: &W, client: &WebhookClient) -> anyhow::Result<usize> {
+    let deliveries = repository
+        .claim_due(BATCH_SIZE, chrono::Duration::from_std(CLAIM_LEASE).unwrap())
+        .await?;
+    let outcomes = futures::future::join_all(deliveries.iter().map(|delivery| deliver(client, delivery))).await;
+    for (delivery, outcome) in deliveries.iter().zip(outcomes) {
+        if let crate::repositories::webhook::DeliveryOutcome::Failed { error, retry_at: None, .. } = &outcome {
+            tracing::warn!("webhook delivery {} to {} is dead after {} attempts: {}", delivery.id, delivery.url, delivery.attempts, error);
+        }
+        repository.record(delivery.id, outcome).await?;
+    }
+    Ok(deliveries.len())
+}
+
+pub async fn run_worker<W: crate::repositories::webhook::WebhookRepository>(repository: W, policy: destination::DestinationPolicy) {
+    let client = WebhookClient::new(policy);
+    loop {
+        match deliver_due(&repository, &client).await {
+            // keep going while there is a backlog
+            Ok(count) if count as i64 == BATCH_SIZE => continue,
+            Ok(_) => {}
+            Err(e) => tracing::error!("webhook worker: {}", e),
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TodoRepositoryWithWebhooks<T, W> {
+    inner: T,
+    webhooks: W,
+}
+
+impl<T: crate::repositories::todo::TodoRepository, W: crate::repositories::webhook::WebhookRepository> TodoRepositoryWithWebhooks<T, W> {
+    pub fn new(inner: T, webhooks: W) -> Self {
+        Self { inner, webhooks }
+    }
+
+    async fn updated(&self, todo: crate::repositories::todo::TodoEntity) -> crate::repositories::todo::TodoEntity {
+        notify(&self.webhooks, WebhookEvent::TodoUpdated(todo.clone())).await;
+        todo
+    }
+}
+
+#[axum::async_trait]
+impl<T: crate::repositories::todo::TodoRepository, W: crate::repositories::webhook::WebhookRepository> crate::repositories::todo::TodoRepository
+    for TodoRepositoryWithWebhooks<T, W>
+{
+    async fn create(&self, payload: crate::repositories::todo::CreateTodo) -> anyhow::Result<crate::repositories::todo::TodoEntity> {
+        let todo = self.inner.create(payload).await?;
+        notify(&self.webhooks, WebhookEvent::TodoCreated(todo.clone())).await;
+        Ok(todo)
+    }
+
+    async fn find(&self, id: i32) -> anyhow::Result<crate::repositories::todo::TodoEntity> {
+        self.inner.find(id).await
+    }
+
+    async fn all(&self) -> anyhow::Result<Vec<crate::repositories::todo::TodoEntity>> {
+        self.inner.all().await
+    }
+
+    fn stream(&self) -> crate::repositories::todo::TodoStream {
+        self.inner.stream()
+    }
+
+    async fn update(&self, id: i32, payload: crate::repositories::todo::UpdateTodo) -> anyhow::Result<crate::repositories::todo::TodoEntity> {
+        Ok(self.updated(self.inner.update(id, payload).await?).await)
+    }
+
+    async fn move_to(&self, id: i32, payload: crate::repositories::todo::MoveTodo) -> anyhow::Result<crate::repositories::todo::TodoEntity> {
+        Ok(self.updated(self.inner.move_to(id, payload).await?).await)
+    }
+
+    async fn add_blocker(&self, id: i32, blocker_id: i32) -> anyhow::Result<crate::repositories::todo::TodoEntity> {
+        Ok(self.updated(self.inner.add_blocker(id, blocker_id).await?).await)
+    }
+
+    async fn remove_blocker(&self, id: i32, blocker_id: i32) -> anyhow::Result<crate::repositories::todo::TodoEntity> {
+        Ok(self.updated(self.inner.remove_blocker(id, blocker_id).await?).await)
+    }
+
+    async fn assign(&self, id: i32, assignee_id: Option<i32>) -> anyhow::Result<crate::repositories::todo::TodoEntity> {
+        let previous_assignee_id = self.inner.find(id).await?.assignee_id;
+        let todo = self.inner.assign(id, assignee_id).await?;
+        if previous_assignee_id != todo.assignee_id {
+            let watcher_ids = self.inner.watchers(id).await?;
+            notify(&self.webhooks, WebhookEvent::TodoAssigned { todo: todo.clone(), previous_assignee_id, watcher_ids }).await;
+        }
+        Ok(todo)
+    }
+
+    async fn watchers(&self, id: i32) -> anyhow::Result<Vec<i32>> {
+        self.inner.watchers(id).await
+    }
+
+    async fn watch(&self, id: i32, user_id: i32) -> anyhow::Result<()> {
+        self.inner.watch(id, user_id).await
+    }
+
+    async fn unwatch(&self, id: i32, user_id: i32) -> anyhow::Result<()> {
+        self.inner.unwatch(id, user_id).await
+    }
+
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        self.inner.delete(id).await?;
+        notify(&self.webhooks, WebhookEvent::TodoDeleted(id)).await;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LabelRepositoryWithWebhooks<L, W> {
+    inner: L,
+    webhooks: W,
+}
+
+impl<L: crate::repositories::label::LabelRepository, W: crate::repositories::webhook::WebhookRepository> LabelRepositoryWithWebhooks<L, W> {
+    pub fn new(inner: L, webhooks: W) -> Self {
+        Self { inner, webhooks }
+    }
+}
+
+#[axum::async_trait]
+impl<L: crate::repositories::label::LabelRepository, W: crate::repositories::webhook::WebhookRepository> crate::repositories::label::LabelRepository
+    for LabelRepositoryWithWebhooks<L, W>
+{
+    async fn create(&self, name: String, workspace_id: Option<i32>) -> anyhow::Result<crate::repositories::label::Label> {
+        let label = self.inner.create(name, workspace_id).await?;
+        notify(&self.webhooks, WebhookEvent::LabelCreated(label.clone())).await;
+        Ok(label)
+    }
+
+    async fn find(&self, id: i32) -> anyhow::Result<crate::repositories::label::Label> {
+        self.inner.find(id).await
+    }
+
+    async fn all(&self) -> anyhow::Result<Vec<crate::repositories::label::Label>> {
+        self.inner.all().await
+    }
+
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        self.inner.delete(id).await?;
+        notify(&self.webhooks, WebhookEvent::LabelDeleted(id)).await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::label::LabelRepository;
+    use crate::repositories::todo::TodoRepository;
+    use crate::repositories::webhook::WebhookRepository;
+
+    type Received = std::sync::Arc<std::sync::Mutex<Vec<(axum::http::HeaderMap, bytes::Bytes)>>>;
+
+    /// Starts a receiver answering with `statuses` in turn (the last one repeats) and recording every request.
+    async fn receiver(statuses: Vec<u16>) -> (String, Received) {
+        let received = Received::default();
+        let recorded = received.clone();
+        let app = axum::Router::new().route(
+            "/hook",
+            axum::routing::post(move |headers: axum::http::HeaderMap, body: bytes::Bytes| {
+                let recorded = recorded.clone();
+                let statuses = statuses.clone();
+                async move {
+                    let mut recorded = recorded.lock().unwrap();
+                    recorded.push((headers, body));
+                    let status = statuses[(recorded.len() - 1).min(statuses.len() - 1)];
+                    axum::http::StatusCode::from_u16(status).unwrap()
+                }
+            }),
+        );
+        let server = axum::Server::bind(&std::net::SocketAddr::from(([127, 0, 0, 1], 0))).serve(app.into_make_service());
+        let url = format!("http://{}/hook", server.local_addr());
+        tokio::spawn(server);
+        (url, received)
+    }
+
+    fn header<'a>(headers: &'a axum::http::HeaderMap, name: &str) -> &'a str {
+        headers.get(name).unwrap().to_str().unwrap()
+    }
+
+    #[test]
+    fn backoff_doubles_until_attempts_run_out() {
+        assert_eq!(Some(FIRST_RETRY), backoff(1));
+        assert_eq!(Some(FIRST_RETRY * 4), backoff(3));
+        assert!(backoff(MAX_ATTEMPTS - 1).unwrap() <= MAX_RETRY);
+        assert_eq!(None, backoff(MAX_ATTEMPTS));
+    }
+
+    #[tokio::test]
+    async fn signed_deliveries_are_retried() {
+        let (url, received) = receiver(vec![500, 204]).await;
+        let webhooks = crate::repositories::webhook::test_utils::WebhookRepositoryForMemory::new();
+        let webhook = webhooks
+            .create(crate::repositories::webhook::CreateWebhook::new(url, vec!["todo.created".to_string(), "label.deleted".to_string()], None))
+            .await
+            .unwrap();
+        let todos = TodoRepositoryWithWebhooks::new(crate::repositories::todo::test_utils::TodoRepositoryForMemory::new(vec![]), webhooks.clone());
+        let labels = LabelRepositoryWithWebhooks::new(crate::repositories::label::test_utils::LabelRepositoryForMemory::new(), webhooks.clone());
+
+        let todo = todos.create(crate::repositories::todo::CreateTodo::new("ship it".to_string(), vec![], None, None)).await.unwrap();
+        todos.delete(todo.id).await.unwrap();
+        let label = labels.create("ci".to_string(), None).await.unwrap();
+        labels.delete(label.id).await.unwrap();
+        let queued = webhooks.deliveries(Default::default()).await.unwrap();
+        assert_eq!(vec!["label.deleted", "todo.created"], queued.iter().map(|delivery| delivery.event.as_str()).collect::<Vec<_>>());
+
+        let client = WebhookClient::new(destination::DestinationPolicy::new(vec!["127.0.0.1".to_string()]));
+        assert_eq!(2, deliver_due(&webhooks, &client).await.unwrap());
+        let pending = webhooks.deliveries(Default::default()).await.unwrap();
+        assert!(pending.iter().any(|delivery| delivery.status == "pending" && delivery.response_status == Some(500)));
+        assert!(pending.iter().any(|delivery| delivery.status == "delivered"));
+        // the failed one waits for its backoff
+        assert_eq!(0, deliver_due(&webhooks, &client).await.unwrap());
+
+        let failed = pending.iter().find(|delivery| delivery.status == "pending").unwrap();
+        webhooks.redeliver(failed.id).await.unwrap();
+        assert_eq!(1, deliver_due(&webhooks, &client).await.unwrap());
+        let delivered = webhooks.deliveries(Default::default()).await.unwrap();
+        assert!(delivered.iter().all(|delivery| delivery.status == "delivered"));
+
+        let received = received.lock().unwrap();
+        assert_eq!(3, received.len());
+        for (headers, body) in received.iter() {
+            let timestamp = header(headers, "x-webhook-timestamp").parse().unwrap();
+            assert_eq!(sign(&webhook.secret, timestamp, body), header(headers, "x-webhook-signature"));
+        }
+        let retried = received.iter().filter(|(headers, _)| header(headers, "x-webhook-delivery") == failed.id.to_string()).count();
+        assert_eq!(2, retried);
+        let payload: serde_json::Value = serde_json::from_slice(&received[0].1).unwrap();
+        assert!(EVENT_NAMES.contains(&payload["event"].as_str().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn assignment_changes_are_announced() {
+        let webhooks = crate::repositories::webhook::test_utils::WebhookRepositoryForMemory::new();
+        webhooks
+            .create(crate::repositories::webhook::CreateWebhook::new("http://localhost/hook".to_string(), vec!["todo.assigned".to_string()], None))
+            .await
+            .unwrap();
+        let todos = TodoRepositoryWithWebhooks::new(crate::repositories::todo::test_utils::TodoRepositoryForMemory::new(vec![]), webhooks.clone());
+        let todo = todos.create(crate::repositories::todo::CreateTodo::new("review".to_string(), vec![], None, None)).await.unwrap();
+        todos.watch(todo.id, 7).await.unwrap();
+
+        assert_eq!(Some(3), todos.assign(todo.id, Some(3)).await.unwrap().assignee_id);
+        // assigning the same user again changes nothing
+        todos.assign(todo.id, Some(3)).await.unwrap();
+        assert_eq!(vec![7, 3], todos.watchers(todo.id).await.unwrap());
+        todos.assign(todo.id, None).await.unwrap();
+
+        let queued = webhooks.deliveries(Default::default()).await.unwrap();
+        assert_eq!(vec!["todo.assigned", "todo.assigned"], queued.iter().map(|delivery| delivery.event.as_str()).collect::<Vec<_>>());
+        let event = WebhookEvent::TodoAssigned { todo: todos.find(todo.id).await.unwrap(), previous_assignee_id: Some(3), watcher_ids: vec![7, 3] };
+        let payload = event.payload();
+        assert_eq!(serde_json::json!(3), payload["data"]["previous_assignee_id"]);
+        assert_eq!(serde_json::json!([7, 3]), payload["data"]["watcher_ids"]);
+    }
+
+    #[tokio::test]
+    async fn exhausted_deliveries_become_dead_letters() {
+        let (url, _) = receiver(vec![503]).await;
+        let delivery = crate::repositories::webhook::PendingDelivery {
+            id: 1,
+            webhook_id: 1,
+            url,
+            secret: "secret".to_string(),
+            event: "todo.deleted".to_string(),
+            payload: WebhookEvent::TodoDeleted(1).payload(),
+            attempts: 1,
+        };
+        let client = WebhookClient::new(destination::DestinationPolicy::new(vec!["127.0.0.1".to_string()]));
+        assert!(matches!(
+            deliver(&client, &delivery).await,
+            crate::repositories::webhook::DeliveryOutcome::Failed { response_status: Some(503), retry_at: Some(_), .. }
+        ));
+        let last = crate::repositories::webhook::PendingDelivery { attempts: MAX_ATTEMPTS, ..delivery };
+        assert!(matches!(
+            deliver(&client, &last).await,
+            crate::repositories::webhook::DeliveryOutcome::Failed { response_status: Some(503), retry_at: None, .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn internal_receivers_need_to_be_allowed() {
+        let (url, received) = receiver(vec![204]).await;
+        let delivery = crate::repositories::webhook::PendingDelivery {
+            id: 1,
+            webhook_id: 1,
+            url: url.replace("127.0.0.1", "localhost"),
+            secret: "secret".to_string(),
+            event: "todo.deleted".to_string(),
+            payload: WebhookEvent::TodoDeleted(1).payload(),
+            attempts: 1,
+        };
+        let client = WebhookClient::new(destination::DestinationPolicy::default());
+        for url in [delivery.url.clone(), url.clone(), "http://169.254.169.254/latest/meta-data".to_string()] {
+            let delivery = crate::repositories::webhook::PendingDelivery { url, ..delivery.clone() };
+            assert!(matches!(deliver(&client, &delivery).await, crate::repositories::webhook::DeliveryOutcome::Failed { response_status: None, .. }));
+        }
+        assert!(received.lock().unwrap().is_empty());
+
+        let client = WebhookClient::new(destination::DestinationPolicy::new(vec!["localhost".to_string()]));
+        assert!(matches!(deliver(&client, &delivery).await, crate::repositories::webhook::DeliveryOutcome::Delivered { response_status: 204 }));
+    }
+}
@@ -0,0 +1,161 @@
+//! Where webhooks may be sent.
+//!
+//! Anyone who can create a webhook chooses the URL the server will `POST` to, so without a check
+//! a webhook could reach services that are only meant to be reachable from inside the network
+//! (loopback, RFC 1918, link-local such as cloud metadata endpoints). [`DestinationPolicy`]
+//! rejects those addresses when a webhook is created, and again for the addresses its host
+//! resolves to at every delivery, so a DNS record changed after creation does not get around it.
+//! Hosts listed in `WEBHOOK_ALLOWED_HOSTS` are exempt, for receivers that really are internal.
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum DestinationError {
+    #[error("{0} is not a valid webhook url")]
+    InvalidUrl(String),
+    #[error("{0} could not be resolved")]
+    Unresolved(String),
+    #[error("{host} resolves to the non-public address {address}")]
+    NonPublic { host: String, address: std::net::IpAddr },
+}
+
+/// Whether `address` is reachable from outside a network, i.e. none of loopback, private (RFC 1918
+/// and IPv6 unique local), link-local, shared (carrier-grade NAT), unspecified or broadcast.
+pub fn is_public(address: std::net::IpAddr) -> bool {
+    match address {
+        std::net::IpAddr::V4(v4) => {
+            let [first, second, ..] = v4.octets();
+            let shared = first == 100 && (64..128).contains(&second);
+            !(v4.is_loopback() || v4.is_private() || v4.is_link_local() || v4.is_unspecified() || v4.is_broadcast() || shared || first == 0)
+        }
+        std::net::IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_public(v4.into()),
+            None => !(v6.is_loopback() || v6.is_unspecified() || v6.is_unique_local() || v6.is_unicast_link_local()),
+        },
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DestinationPolicy {
+    allowed_hosts: std::sync::Arc<Vec<String>>,
+}
+
+impl DestinationPolicy {
+    /// Only public addresses, except for `allowed_hosts` (names or IP addresses as they appear in
+    /// the URL).
+    pub fn new(allowed_hosts: Vec<String>) -> Self {
+        let allowed_hosts = allowed_hosts.into_iter().map(|host| host.to_ascii_lowercase()).collect();
+        Self { allowed_hosts: std::sync::Arc::new(allowed_hosts) }
+    }
+
+    /// Reads the comma separated `WEBHOOK_ALLOWED_HOSTS`.
+    pub fn from_env() -> Self {
+        let hosts = std::env::var("WEBHOOK_ALLOWED_HOSTS").unwrap_or_default();
+        Self::new(hosts.split(',').map(str::trim).filter(|host| !host.is_empty()).map(str::to_string).collect())
+    }
+
+    fn allows_host(&self, host: &str) -> bool {
+        self.allowed_hosts.iter().any(|allowed| allowed.eq_ignore_ascii_case(host))
+    }
+
+    /// Checks the addresses `host` resolved to; all of them have to be allowed, as the
+    /// connection may use any.
+    pub fn check(&self, host: &str, addresses: impl IntoIterator<Item = std::net::IpAddr>) -> Result<(), DestinationError> {
+        if self.allows_host(host) {
+            return Ok(());
+        }
+        match addresses.into_iter().find(|address| !is_public(*address)) {
+            Some(address) => Err(DestinationError::NonPublic { host: host.to_string(), address }),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks an IP address written into the URL itself, which is connected to without a lookup.
+    /// Host names are left to [`GuardedResolver`].
+    pub fn check_literal(&self, uri: &hyper::Uri) -> Result<(), DestinationError> {
+        let host = uri.host().ok_or_else(|| DestinationError::InvalidUrl(uri.to_string()))?;
+        match host.trim_start_matches('[').trim_end_matches(']').parse::<std::net::IpAddr>() {
+            Ok(address) => self.check(host, [address]),
+            Err(_) => Ok(()),
+        }
+    }
+
+    /// Checks the URL of a new webhook, resolving its host.
+    pub async fn check_url(&self, url: &str) -> Result<(), DestinationError> {
+        let uri = url.parse::<hyper::Uri>().map_err(|_| DestinationError::InvalidUrl(url.to_string()))?;
+        let host = uri.host().ok_or_else(|| DestinationError::InvalidUrl(url.to_string()))?;
+        if self.allows_host(host) {
+            return Ok(());
+        }
+        self.check_literal(&uri)?;
+        let port = uri.port_u16().unwrap_or(if uri.scheme_str() == Some("https") { 443 } else { 80 });
+        let addresses = tokio::net::lookup_host((host.trim_start_matches('[').trim_end_matches(']'), port))
+            .await
+            .map_err(|_| DestinationError::Unresolved(host.to_string()))?;
+        self.check(host, addresses.map(|address| address.ip()))
+    }
+}
+
+/// Resolver for the webhook client refusing hosts that resolve to addresses the
+/// [`DestinationPolicy`] does not allow.
+#[derive(Debug, Clone)]
+pub struct GuardedResolver {
+    inner: hyper::client::connect::dns::GaiResolver,
+    policy: DestinationPolicy,
+}
+
+impl GuardedResolver {
+    pub fn new(policy: DestinationPolicy) -> Self {
+        Self { inner: hyper::client::connect::dns::GaiResolver::new(), policy }
+    }
+}
+
+impl tower::Service<hyper::client::connect::dns::Name> for GuardedResolver {
+    type Response = std::vec::IntoIter<std::net::SocketAddr>;
+    type Error = std::io::Error;
+    type Future = futures::future::BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut std::task::Context<'_>) -> std::task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, name: hyper::client::connect::dns::Name) -> Self::Future {
+        let resolving = self.inner.call(name.clone());
+        let policy = self.policy.clone();
+        Box::pin(async move {
+            let addresses: Vec<std::net::SocketAddr> = resolving.await?.collect();
+            policy
+                .check(name.as_str(), addresses.iter().map(|address| address.ip()))
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::PermissionDenied, e))?;
+            Ok(addresses.into_iter())
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn internal_addresses_are_not_public() {
+        for address in ["127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.1.1", "169.254.169.254", "100.64.0.1", "0.0.0.0", "::1", "fe80::1", "fd00::1", "::ffff:127.0.0.1"] {
+            assert!(!is_public(address.parse().unwrap()), "{}", address);
+        }
+        for address in ["93.184.216.34", "8.8.8.8", "2606:4700:4700::1111"] {
+            assert!(is_public(address.parse().unwrap()), "{}", address);
+        }
+    }
+
+    #[tokio::test]
+    async fn only_allowed_hosts_may_be_internal() {
+        let policy = DestinationPolicy::default();
+        assert!(matches!(policy.check_url("http://127.0.0.1:8080/hook").await, Err(DestinationError::NonPublic { .. })));
+        assert!(matches!(policy.check_url("http://[::1]/hook").await, Err(DestinationError::NonPublic { .. })));
+        assert!(matches!(policy.check_url("http://localhost/hook").await, Err(DestinationError::NonPublic { .. })));
+        assert!(policy.check("hooks.example", ["93.184.216.34".parse().unwrap()]).is_ok());
+        assert!(policy.check("hooks.example", ["93.184.216.34".parse().unwrap(), "10.0.0.1".parse().unwrap()]).is_err());
+
+        let policy = DestinationPolicy::new(vec!["LOCALHOST".to_string(), "127.0.0.1".to_string()]);
+        assert_eq!(Ok(()), policy.check_url("http://localhost/hook").await);
+        assert_eq!(Ok(()), policy.check_url("http://127.0.0.1:8080/hook").await);
+        assert!(policy.check_url("http://10.0.0.1/hook").await.is_err());
+    }
+}
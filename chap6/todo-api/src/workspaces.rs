@@ -0,0 +1,443 @@
+//! Workspaces share todos and labels between the users who are members of them.
+//!
+//! A todo or label belongs to at most one workspace; those in none stay visible to every
+//! caller, as before. For users logged in through `crate::oidc` ([`Caller::User`]) and the API
+//! tokens they made, [`Memberships`] decides what they may do in a workspace: viewers read its
+//! todos and labels, editors also change them, and owners also manage members and invites.
+//! Non-members are told the workspace does not exist. Open access and the tokens the admin token
+//! made are members of no workspace; only the admin token itself reaches every workspace.
+//!
+//! [`resolve`] loads the caller's roles once per request; [`require_todo`] checks them on routes
+//! under `/todos/:id`, and the handlers that create or list todos and labels check them directly.
+
+use crate::auth::Caller;
+use crate::repositories::workspace::Role;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    View,
+    Edit,
+    Manage,
+}
+
+impl Permission {
+    /// Reads (`GET`/`HEAD`) need to view, everything else to edit.
+    pub fn of(method: &axum::http::Method) -> Self {
+        match *method {
+            axum::http::Method::GET | axum::http::Method::HEAD => Permission::View,
+            _ => Permission::Edit,
+        }
+    }
+
+    fn required_role(self) -> Role {
+        match self {
+            Permission::View => Role::Viewer,
+            Permission::Edit => Role::Editor,
+            Permission::Manage => Role::Owner,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum WorkspaceError {
+    #[error("workspace {0} not found")]
+    NotFound(i32),
+    #[error("needs the {required} role in workspace {workspace_id}")]
+    Forbidden { workspace_id: i32, required: Role },
+    #[error("only users and their tokens can {0}")]
+    UserRequired(&'static str),
+    #[error("label {0} is not in the todo's workspace")]
+    ForeignLabel(i32),
+}
+
+impl axum::response::IntoResponse for WorkspaceError {
+    fn into_response(self) -> axum::response::Response {
+        let status = match self {
+            WorkspaceError::NotFound(_) => axum::http::StatusCode::NOT_FOUND,
+            WorkspaceError::Forbidden { .. } | WorkspaceError::UserRequired(_) => axum::http::StatusCode::FORBIDDEN,
+            WorkspaceError::ForeignLabel(_) => axum::http::StatusCode::BAD_REQUEST,
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+/// Answers non-members as if the todo or label they asked for did not exist, without naming its
+/// workspace.
+pub fn concealed_response(error: WorkspaceError) -> axum::response::Response {
+    match error {
+        WorkspaceError::NotFound(_) => {
+            axum::response::IntoResponse::into_response((axum::http::StatusCode::NOT_FOUND, "Not Found".to_string()))
+        }
+        e => axum::response::IntoResponse::into_response(e),
+    }
+}
+
+/// The caller's role in each workspace, or nothing for callers that are not subject to roles.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Memberships(Option<std::collections::HashMap<i32, Role>>);
+
+impl Memberships {
+    pub fn new(roles: std::collections::HashMap<i32, Role>) -> Self {
+        Self(Some(roles))
+    }
+
+    pub fn unrestricted() -> Self {
+        Self(None)
+    }
+
+    pub fn role(&self, workspace_id: i32) -> Option<Role> {
+        self.0.as_ref().and_then(|roles| roles.get(&workspace_id).copied())
+    }
+
+    /// Whether the caller may act on something in `workspace_id`; `None` is no workspace.
+    pub fn check(&self, workspace_id: Option<i32>, permission: Permission) -> Result<(), WorkspaceError> {
+        let (roles, workspace_id) = match (&self.0, workspace_id) {
+            (Some(roles), Some(workspace_id)) => (roles, workspace_id),
+            _ => return Ok(()),
+        };
+        let required = permission.required_role();
+        match roles.get(&workspace_id) {
+            None => Err(WorkspaceError::NotFound(workspace_id)),
+            Some(role) if *role >= required => Ok(()),
+            Some(_) => Err(WorkspaceError::Forbidden { workspace_id, required }),
+        }
+    }
+
+    pub fn can_view(&self, workspace_id: Option<i32>) -> bool {
+        self.check(workspace_id, Permission::View).is_ok()
+    }
+}
+
+/// Checks that the labels `ids` exist among `labels` and are in the todo's workspace, so a member
+/// can not attach, or learn the names of, another workspace's labels.
+pub fn check_labels(labels: &[crate::repositories::label::Label], ids: &[i32], workspace_id: Option<i32>) -> Result<(), WorkspaceError> {
+    match ids
+        .iter()
+        .find(|id| !labels.iter().any(|label| label.id == **id && label.workspace_id == workspace_id))
+    {
+        Some(id) => Err(WorkspaceError::ForeignLabel(*id)),
+        None => Ok(()),
+    }
+}
+
+/// Puts the [`Memberships`] of the request's [`Caller`] into its extensions; layered inside
+/// `crate::auth::authenticate`.
+pub async fn resolve<W: crate::repositories::workspace::WorkspaceRepository>(
+    mut request: axum::http::Request<axum::body::Body>,
+    next: axum::middleware::Next<axum::body::Body>,
+) -> axum::response::Response {
+    let caller = request.extensions().get::<Caller>().cloned().unwrap_or(Caller::Unauthenticated);
+    let repository = request
+        .extensions()
+        .get::<std::sync::Arc<W>>()
+        .cloned()
+        .expect("workspace repository extension is missing");
+    let memberships = match memberships(repository.as_ref(), &caller).await {
+        Ok(memberships) => memberships,
+        Err(e) => {
+            tracing::error!("failed to load workspace roles: {}", e);
+            return axum::response::IntoResponse::into_response(axum::http::StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    request.extensions_mut().insert(memberships);
+    next.run(request).await
+}
+
+/// The roles of `caller`: a user's own, also for their tokens, every workspace for the admin token,
+/// and none for open access or tokens without an owner.
+pub async fn memberships<W: crate::repositories::workspace::WorkspaceRepository>(
+    repository: &W,
+    caller: &Caller,
+) -> anyhow::Result<Memberships> {
+    Ok(match caller {
+        Caller::Admin => Memberships::unrestricted(),
+        Caller::User { id } | Caller::Token { user_id: Some(id), .. } => Memberships::new(repository.roles(*id).await?),
+        Caller::Open | Caller::Unauthenticated | Caller::Token { user_id: None, .. } => Memberships::new(Default::default()),
+    })
+}
+
+/// Extractor checking the caller's role in the workspace of the todo in the `:id` path segment,
+/// viewing for reads and editing for everything else, except that acting for themselves (a
+/// `:user_id` segment naming the caller) only needs viewing; applied with [`require_todo`].
+pub struct RequireTodo<T>(std::marker::PhantomData<fn() -> T>);
+
+#[axum::async_trait]
+impl<T: crate::repositories::todo::TodoRepository, B: Send> axum::extract::FromRequest<B> for RequireTodo<T> {
+    type Rejection = axum::response::Response;
+
+    async fn from_request(req: &mut axum::extract::RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let axum::extract::Path(params) = axum::extract::Path::<std::collections::HashMap<String, String>>::from_request(req)
+            .await
+            .map_err(axum::response::IntoResponse::into_response)?;
+        let id = params
+            .get("id")
+            .and_then(|value| value.parse::<i32>().ok())
+            .ok_or_else(|| axum::response::IntoResponse::into_response(axum::http::StatusCode::NOT_FOUND))?;
+        let extensions = req.extensions().expect("extensions were taken");
+        let memberships = extensions.get::<Memberships>().expect("memberships extension is missing");
+        if memberships.0.is_none() {
+            return Ok(RequireTodo(std::marker::PhantomData));
+        }
+        let caller_id = extensions.get::<Caller>().and_then(Caller::user_id);
+        let permission = match params.get("user_id").and_then(|value| value.parse::<i32>().ok()) {
+            Some(user_id) if Some(user_id) == caller_id => Permission::View,
+            _ => Permission::of(req.method()),
+        };
+        let repository = extensions
+            .get::<std::sync::Arc<T>>()
+            .expect("todo repository extension is missing");
+        // a todo that does not exist is left to the handler to report
+        if let Ok(todo) = repository.find(id).await {
+            memberships.check(todo.workspace_id, permission).map_err(concealed_response)?;
+        }
+        Ok(RequireTodo(std::marker::PhantomData))
+    }
+}
+
+/// Route layer running [`RequireTodo`], e.g. `.route_layer(require_todo::<Todo>())`.
+pub fn require_todo<T: crate::repositories::todo::TodoRepository>(
+) -> axum::extract::extractor_middleware::ExtractorMiddlewareLayer<RequireTodo<T>> {
+    axum::extract::extractor_middleware::<RequireTodo<T>>()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::todo::TodoRepository;
+    use crate::repositories::workspace::WorkspaceRepository;
+    use tower::ServiceExt;
+
+    type Todos = crate::repositories::todo::test_utils::TodoRepositoryForMemory;
+    type Workspaces = crate::repositories::workspace::test_utils::WorkspaceRepositoryForMemory;
+    type Users = crate::repositories::user::test_utils::UserRepositoryForMemory;
+    type Labels = crate::repositories::label::test_utils::LabelRepositoryForMemory;
+
+    fn app(todos: Todos, workspaces: Workspaces, user_id: i32) -> axum::Router {
+        app_with_labels(todos, workspaces, Labels::new(), user_id)
+    }
+
+    fn app_with_labels(todos: Todos, workspaces: Workspaces, labels: Labels, user_id: i32) -> axum::Router {
+        app_for(todos, workspaces, labels, Caller::User { id: user_id })
+    }
+
+    fn app_for(todos: Todos, workspaces: Workspaces, labels: Labels, caller: Caller) -> axum::Router {
+        axum::Router::new()
+            .route(
+                "/todos",
+                axum::routing::get(crate::handlers::todo::all_todo::<Todos>).post(crate::handlers::todo::create_todo::<Todos, Labels>),
+            )
+            .route(
+                "/todos/:id",
+                axum::routing::get(crate::handlers::todo::find_todo::<Todos>)
+                    .patch(crate::handlers::todo::update_todo::<Todos, Labels>)
+                    .route_layer(require_todo::<Todos>()),
+            )
+            .route(
+                "/workspaces/:id/members/:user_id",
+                axum::routing::patch(crate::handlers::workspace::update_member::<Workspaces>),
+            )
+            .route("/invites/accept", axum::routing::post(crate::handlers::workspace::accept_invite::<Workspaces>))
+            .route(
+                "/todos/:id/assign",
+                axum::routing::post(crate::handlers::todo::assign_todo::<Todos, Workspaces, Users>).route_layer(require_todo::<Todos>()),
+            )
+            .route(
+                "/todos/:id/watchers/:user_id",
+                axum::routing::put(crate::handlers::todo::add_watcher::<Todos, Workspaces, Users>)
+                    .delete(crate::handlers::todo::delete_watcher::<Todos>)
+                    .route_layer(require_todo::<Todos>()),
+            )
+            .layer(axum::middleware::from_fn(resolve::<Workspaces>))
+            .layer(axum::extract::Extension(caller))
+            .layer(axum::extract::Extension(std::sync::Arc::new(Users::new())))
+            .layer(axum::extract::Extension(std::sync::Arc::new(labels)))
+            .layer(axum::extract::Extension(std::sync::Arc::new(todos)))
+            .layer(axum::extract::Extension(std::sync::Arc::new(workspaces)))
+    }
+
+    async fn send(app: &axum::Router, method: &str, uri: &str, body: Option<String>) -> (axum::http::StatusCode, String) {
+        let request = axum::http::Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(axum::http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+            .body(body.map(axum::body::Body::from).unwrap_or_else(axum::body::Body::empty))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, String::from_utf8(bytes.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn roles_decide_what_members_may_do() {
+        let (owner, viewer, editor, stranger) = (1, 2, 3, 4);
+        let todos = Todos::new(vec![]);
+        let workspaces = Workspaces::new();
+        let workspace = workspaces.create("team".to_string(), owner).await.unwrap();
+        let shared = todos
+            .create(crate::repositories::todo::CreateTodo::new("shared".to_string(), vec![], None, None).in_workspace(Some(workspace.id)))
+            .await
+            .unwrap();
+        let personal = todos
+            .create(crate::repositories::todo::CreateTodo::new("personal".to_string(), vec![], None, None))
+            .await
+            .unwrap();
+        for (user_id, role) in [(viewer, Role::Viewer), (editor, Role::Editor)] {
+            let invite = workspaces
+                .create_invite(workspace.id, crate::repositories::workspace::CreateInvite::new(role, None))
+                .await
+                .unwrap();
+            let body = format!(r#"{{"token": "{}"}}"#, invite.token);
+            let app = app(todos.clone(), workspaces.clone(), user_id);
+            assert_eq!(axum::http::StatusCode::OK, send(&app, "POST", "/invites/accept", Some(body.clone())).await.0);
+            // an invite is only good once
+            assert_eq!(axum::http::StatusCode::NOT_FOUND, send(&app, "POST", "/invites/accept", Some(body)).await.0);
+        }
+
+        let uri = format!("/todos/{}", shared.id);
+        let patch = Some(r#"{"completed": true}"#.to_string());
+        let viewer_app = app(todos.clone(), workspaces.clone(), viewer);
+        assert_eq!(axum::http::StatusCode::OK, send(&viewer_app, "GET", &uri, None).await.0);
+        assert_eq!(axum::http::StatusCode::FORBIDDEN, send(&viewer_app, "PATCH", &uri, patch.clone()).await.0);
+        let editor_app = app(todos.clone(), workspaces.clone(), editor);
+        assert_eq!(axum::http::StatusCode::CREATED, send(&editor_app, "PATCH", &uri, patch).await.0);
+        let members_uri = format!("/workspaces/{}/members/{}", workspace.id, viewer);
+        let promote = Some(r#"{"role": "editor"}"#.to_string());
+        assert_eq!(axum::http::StatusCode::FORBIDDEN, send(&editor_app, "PATCH", &members_uri, promote).await.0);
+
+        let stranger_app = app(todos.clone(), workspaces.clone(), stranger);
+        assert_eq!(axum::http::StatusCode::NOT_FOUND, send(&stranger_app, "GET", &uri, None).await.0);
+        let (status, body) = send(&stranger_app, "GET", "/todos", None).await;
+        assert_eq!(axum::http::StatusCode::OK, status);
+        let visible: Vec<crate::repositories::todo::TodoEntity> = serde_json::from_str(&body).unwrap();
+        assert_eq!(vec![personal.id], visible.iter().map(|todo| todo.id).collect::<Vec<_>>());
+
+        let owner_app = app(todos, workspaces, owner);
+        let demote = Some(r#"{"role": "viewer"}"#.to_string());
+        let own_uri = format!("/workspaces/{}/members/{}", workspace.id, owner);
+        assert_eq!(axum::http::StatusCode::CONFLICT, send(&owner_app, "PATCH", &own_uri, demote).await.0);
+    }
+
+    #[tokio::test]
+    async fn tokens_without_an_owner_see_no_workspace() {
+        let todos = Todos::new(vec![]);
+        let workspaces = Workspaces::new();
+        let workspace = workspaces.create("team".to_string(), 1).await.unwrap();
+        let shared = todos
+            .create(crate::repositories::todo::CreateTodo::new("shared".to_string(), vec![], None, None).in_workspace(Some(workspace.id)))
+            .await
+            .unwrap();
+        let caller = Caller::Token { id: 1, user_id: None, scopes: vec![crate::auth::TODOS_READ.to_string()] };
+        let app = app_for(todos, workspaces, Labels::new(), caller);
+
+        assert_eq!(axum::http::StatusCode::NOT_FOUND, send(&app, "GET", &format!("/todos/{}", shared.id), None).await.0);
+        let (status, body) = send(&app, "GET", "/todos", None).await;
+        assert_eq!(axum::http::StatusCode::OK, status);
+        let visible: Vec<crate::repositories::todo::TodoEntity> = serde_json::from_str(&body).unwrap();
+        assert!(visible.is_empty());
+    }
+
+    #[tokio::test]
+    async fn assignees_are_members() {
+        let (owner, editor, stranger) = (1, 2, 3);
+        let todos = Todos::new(vec![]);
+        let workspaces = Workspaces::new();
+        let workspace = workspaces.create("team".to_string(), owner).await.unwrap();
+        let invite = workspaces
+            .create_invite(workspace.id, crate::repositories::workspace::CreateInvite::new(Role::Editor, None))
+            .await
+            .unwrap();
+        workspaces.accept_invite(&invite.token, editor).await.unwrap();
+        let todo = todos
+            .create(crate::repositories::todo::CreateTodo::new("shared".to_string(), vec![], None, None).in_workspace(Some(workspace.id)))
+            .await
+            .unwrap();
+        let owner_app = app(todos.clone(), workspaces.clone(), owner);
+        let uri = format!("/todos/{}/assign", todo.id);
+
+        let (status, body) = send(&owner_app, "POST", &uri, Some(format!(r#"{{"assignee_id": {}}}"#, stranger))).await;
+        assert_eq!(axum::http::StatusCode::BAD_REQUEST, status);
+        assert!(body.contains("not a member"));
+        assert_eq!(axum::http::StatusCode::OK, send(&owner_app, "POST", &uri, Some(format!(r#"{{"assignee_id": {}}}"#, editor))).await.0);
+        assert_eq!(vec![editor], todos.watchers(todo.id).await.unwrap());
+
+        let editor_app = app(todos.clone(), workspaces.clone(), editor);
+        let (_, body) = send(&editor_app, "GET", "/todos?assignee=me", None).await;
+        let assigned: Vec<crate::repositories::todo::TodoEntity> = serde_json::from_str(&body).unwrap();
+        assert_eq!(vec![todo.id], assigned.iter().map(|todo| todo.id).collect::<Vec<_>>());
+        let (_, body) = send(&owner_app, "GET", "/todos?assignee=me", None).await;
+        assert_eq!("[]", body);
+
+        assert_eq!(axum::http::StatusCode::OK, send(&owner_app, "POST", &uri, Some(r#"{"assignee_id": null}"#.to_string())).await.0);
+        assert_eq!(None, todos.find(todo.id).await.unwrap().assignee_id);
+    }
+
+    #[tokio::test]
+    async fn members_watch_for_themselves() {
+        let (owner, viewer, stranger) = (1, 2, 3);
+        let todos = Todos::new(vec![]);
+        let workspaces = Workspaces::new();
+        let workspace = workspaces.create("team".to_string(), owner).await.unwrap();
+        let invite = workspaces
+            .create_invite(workspace.id, crate::repositories::workspace::CreateInvite::new(Role::Viewer, None))
+            .await
+            .unwrap();
+        workspaces.accept_invite(&invite.token, viewer).await.unwrap();
+        let todo = todos
+            .create(crate::repositories::todo::CreateTodo::new("shared".to_string(), vec![], None, None).in_workspace(Some(workspace.id)))
+            .await
+            .unwrap();
+        let watcher = |user_id: i32| format!("/todos/{}/watchers/{}", todo.id, user_id);
+
+        let viewer_app = app(todos.clone(), workspaces.clone(), viewer);
+        assert_eq!(axum::http::StatusCode::NO_CONTENT, send(&viewer_app, "PUT", &watcher(viewer), None).await.0);
+        assert_eq!(axum::http::StatusCode::FORBIDDEN, send(&viewer_app, "PUT", &watcher(owner), None).await.0);
+        let stranger_app = app(todos.clone(), workspaces.clone(), stranger);
+        assert_eq!(axum::http::StatusCode::NOT_FOUND, send(&stranger_app, "PUT", &watcher(stranger), None).await.0);
+        let owner_app = app(todos.clone(), workspaces, owner);
+        assert_eq!(axum::http::StatusCode::NO_CONTENT, send(&owner_app, "DELETE", &watcher(viewer), None).await.0);
+        assert!(todos.watchers(todo.id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn tokens_act_for_their_user() {
+        let workspaces = Workspaces::new();
+        let workspace = workspaces.create("team".to_string(), 1).await.unwrap();
+        let users_token = Caller::Token { id: 1, user_id: Some(1), scopes: vec![] };
+        let strangers_token = Caller::Token { id: 2, user_id: Some(2), scopes: vec![] };
+
+        assert_eq!(Some(Role::Owner), memberships(&workspaces, &users_token).await.unwrap().role(workspace.id));
+        assert!(!memberships(&workspaces, &strangers_token).await.unwrap().can_view(Some(workspace.id)));
+        let open = memberships(&workspaces, &Caller::Open).await.unwrap();
+        assert!(open.can_view(None));
+        assert!(!open.can_view(Some(workspace.id)));
+        assert!(memberships(&workspaces, &Caller::Admin).await.unwrap().can_view(Some(workspace.id)));
+    }
+
+    #[tokio::test]
+    async fn labels_stay_in_their_workspace() {
+        use crate::repositories::label::LabelRepository;
+        let owner = 1;
+        let workspaces = Workspaces::new();
+        let team = workspaces.create("team".to_string(), owner).await.unwrap();
+        let other = workspaces.create("other".to_string(), owner).await.unwrap();
+        let labels = Labels::new();
+        let ours = labels.create("ours".to_string(), Some(team.id)).await.unwrap();
+        let theirs = labels.create("theirs".to_string(), Some(other.id)).await.unwrap();
+        let todos = Todos::new(vec![ours.clone(), theirs.clone()]);
+        let app = app_with_labels(todos.clone(), workspaces, labels, owner);
+
+        let create = |label_id: i32| Some(format!(r#"{{"text": "shared", "labels": [{}], "workspace_id": {}}}"#, label_id, team.id));
+        let (status, body) = send(&app, "POST", "/todos", create(theirs.id)).await;
+        assert_eq!(axum::http::StatusCode::BAD_REQUEST, status);
+        assert!(body.contains("not in the todo's workspace"));
+        let (status, body) = send(&app, "POST", "/todos", create(ours.id)).await;
+        assert_eq!(axum::http::StatusCode::CREATED, status);
+        let todo: crate::repositories::todo::TodoEntity = serde_json::from_str(&body).unwrap();
+
+        let uri = format!("/todos/{}", todo.id);
+        let update = |label_id: i32| Some(format!(r#"{{"labels": [{}]}}"#, label_id));
+        assert_eq!(axum::http::StatusCode::BAD_REQUEST, send(&app, "PATCH", &uri, update(theirs.id)).await.0);
+        assert_eq!(vec![ours.id], todos.find(todo.id).await.unwrap().labels.iter().map(|label| label.id).collect::<Vec<_>>());
+    }
+}
@@ -9,11 +9,15 @@ export const getLabelItems = async () => {
   return json
 }
 
-export const addLabelItem = async (payload: NewLabelPayload) => {
+export const addLabelItem = async (
+  payload: NewLabelPayload,
+  idempotencyKey: string = crypto.randomUUID()
+) => {
   const res = await fetch('http://localhost:3000/labels', {
     method: 'POST',
     headers: {
       'Content-Type': 'application/json',
+      'Idempotency-Key': idempotencyKey,
     },
     body: JSON.stringify(payload),
   })
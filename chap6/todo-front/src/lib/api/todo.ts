@@ -1,10 +1,20 @@
-import type { NewTodoPayload, Todo, UpdateTodoPayload } from '../../types/todo'
+import type {
+  MoveTodoPayload,
+  NewTodoPayload,
+  Todo,
+  UpdateTodoPayload,
+} from '../../types/todo'
 
-export const addTodoItem = async (payload: NewTodoPayload) => {
+// retries must pass the same key so the server does not create a second item
+export const addTodoItem = async (
+  payload: NewTodoPayload,
+  idempotencyKey: string = crypto.randomUUID()
+) => {
   const res = await fetch('http://localhost:3000/todos', {
     method: 'POST',
     headers: {
       'Content-Type': 'application/json',
+      'Idempotency-Key': idempotencyKey,
     },
     body: JSON.stringify(payload),
   })
@@ -40,6 +50,22 @@ export const updateTodoItem = async (todo: UpdateTodoPayload) => {
   return json
 }
 
+export const moveTodoItem = async (payload: MoveTodoPayload) => {
+  const { id, ...target } = payload
+  const res = await fetch(`http://localhost:3000/todos/${id}/move`, {
+    method: 'POST',
+    headers: {
+      'Content-Type': 'application/json',
+    },
+    body: JSON.stringify(target),
+  })
+  if (!res.ok) {
+    throw new Error('move todo request failed')
+  }
+  const json: Todo = await res.json()
+  return json
+}
+
 export const deleteTodoItem = async (id: number) => {
   const res = await fetch(`http://localhost:3000/todos/${id}`, {
     method: 'DELETE',
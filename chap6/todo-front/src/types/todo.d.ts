@@ -1,27 +1,81 @@
+// Generated from the todo-api types by `cargo test typescript`. Do not edit by hand;
+// run `UPDATE_TS_TYPES=1 cargo test typescript` in todo-api to regenerate.
+
 export type Todo = {
   id: number
   text: string
   completed: boolean
-  labels: Label[]
+  position: number
+  priority: string | null
+  due: string | null
+  created_at: string
+  completed_at: string | null
+  workspace_id: number | null
+  assignee_id: number | null
+  blocked: boolean
+  comment_count: number
+  labels: Array<Label>
 }
 
 export type NewTodoPayload = {
   text: string
-  labels: number[]
+  labels: Array<number>
+  priority?: string
+  due?: string
+  workspace_id?: number
 }
 
 export type Label = {
   id: number
   name: string
+  workspace_id: number | null
 }
 
 export type NewLabelPayload = {
   name: string
+  workspace_id?: number
 }
 
-export type UpdateTodoPayload = {
-  id: number
+export type UpdateTodo = {
   text?: string
   completed?: boolean
-  labels?: number[]
+  labels?: Array<number>
+  priority?: string | null
+  due?: string | null
+}
+
+export type MoveTodo = {
+  before?: number
+  after?: number
+}
+
+export type Comment = {
+  id: number
+  todo_id: number
+  author: string
+  author_user_id: number | null
+  body: string
+  created_at: string
+  updated_at: string
+}
+
+export type NewCommentPayload = {
+  body: string
+}
+
+export type Attachment = {
+  id: number
+  todo_id: number
+  filename: string
+  content_type: string
+  size: number
+  created_at: string
+}
+
+export type UpdateTodoPayload = UpdateTodo & {
+  id: number
+}
+
+export type MoveTodoPayload = MoveTodo & {
+  id: number
 }